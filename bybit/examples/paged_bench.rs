@@ -0,0 +1,101 @@
+//! Compare `BybitClient::get_kline` against page by page processing with
+//! `BybitClient::get_kline_paged`, reporting wall time, per-page latency (including the pacing
+//! delay between pages) and the most klines held in memory at once by each.
+//!
+//! ```sh
+//! cargo run --release --example paged_bench -- --symbol BTCUSDT --interval 1 --days 7
+//! ```
+
+use bybit_kline::{
+    category::Category, interval::Interval, kline::KlineType, window, BybitClient, KlineQuery,
+};
+use chrono::Utc;
+use clap::Parser;
+use futures_util::TryStreamExt;
+use std::{error::Error, pin::pin, time::Instant};
+
+#[derive(Parser, Debug)]
+struct Args {
+    #[arg(long, default_value = "BTCUSDT")]
+    symbol: String,
+
+    #[arg(long, default_value = "linear")]
+    category: Category,
+
+    #[arg(long, default_value = "1")]
+    interval: Interval,
+
+    /// Days of history to fetch, ending now
+    #[arg(long, default_value = "3")]
+    days: u64,
+
+    #[arg(long, default_value = "1000")]
+    limit_per_request: u32,
+
+    #[arg(long)]
+    testnet: bool,
+
+    /// Serve requests from this base url instead, eg/ a local mock
+    #[arg(long)]
+    base_url: Option<String>,
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    let args = Args::parse();
+    let mut client = BybitClient::new(args.testnet, 1)?.with_preflight(false);
+    if let Some(base_url) = &args.base_url {
+        client = client.with_base_url(base_url);
+    }
+
+    let end = Utc::now().timestamp_millis() as u64;
+    let query = KlineQuery {
+        symbol: args.symbol.clone(),
+        category: args.category,
+        interval: args.interval,
+        kline_type: KlineType::Trade,
+        start: end.saturating_sub(args.days * 86_400_000),
+        end,
+        max_records: window::UNLIMITED_RECORDS,
+        limit_per_request: args.limit_per_request,
+    };
+
+    let started = Instant::now();
+    let klines = client.get_kline(&query, |_| {}).await?;
+    println!(
+        "get_kline:       {} klines in {:.2?}, peak {} klines held",
+        klines.len(),
+        started.elapsed(),
+        klines.len()
+    );
+    drop(klines);
+
+    let started = Instant::now();
+    let mut pages = pin!(client.get_kline_paged(&query));
+    let (mut total, mut peak, mut latencies) = (0, 0, Vec::new());
+    let mut requested = Instant::now();
+    while let Some(page) = pages.try_next().await? {
+        latencies.push(requested.elapsed());
+        total += page.len();
+        peak = peak.max(page.len());
+        requested = Instant::now();
+    }
+    println!(
+        "get_kline_paged: {} klines in {:.2?}, peak {} klines held",
+        total,
+        started.elapsed(),
+        peak
+    );
+
+    latencies.sort();
+    if let (Some(fastest), Some(slowest)) = (latencies.first(), latencies.last()) {
+        println!(
+            "{} pages, latency min {:.2?} median {:.2?} max {:.2?}",
+            latencies.len(),
+            fastest,
+            latencies[latencies.len() / 2],
+            slowest
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,130 @@
+use crate::{
+    category::Category,
+    kline::{Kline, KlineType},
+};
+use std::collections::{BTreeMap, HashSet};
+
+/// Default `--anomaly-threshold`: a price more than 20% off the rolling median is flagged.
+pub const DEFAULT_THRESHOLD: f64 = 0.2;
+
+/// Bars either side of a kline whose closes make up its rolling median.
+const NEIGHBOURS: usize = 5;
+
+/// Bar whose price strays too far from the closes around it, eg/ a fat-finger wick or a stale
+/// print.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Anomaly {
+    pub start_time: u64,
+    pub kline_type: KlineType,
+    pub category: Category,
+    /// Which of open, high, low and close deviates the most.
+    pub field: &'static str,
+    pub price: f64,
+    /// Median close of the neighbouring bars.
+    pub median: f64,
+    /// Relative deviation of `price` from `median`, eg/ 0.5 for 50% off.
+    pub deviation: f64,
+}
+
+/// Flag every kline whose open, high, low or close deviates from the median close of the
+/// [`NEIGHBOURS`] bars either side of it by more than `threshold`, as a fraction of the median.
+///
+/// The median leaves out the kline itself, so a lone spike can't drag its own reference, and
+/// merged series (eg/ trade and mark price klines) are screened independently. Each series
+/// must be time ordered, and bars with fewer than 2 neighbours aren't screened.
+pub fn detect_anomalies(klines: &[Kline], threshold: f64) -> Vec<Anomaly> {
+    let mut series = BTreeMap::<(KlineType, Category), Vec<&Kline>>::new();
+    for kline in klines {
+        series
+            .entry((kline.kline_type, kline.category))
+            .or_default()
+            .push(kline);
+    }
+
+    let mut anomalies = Vec::new();
+    for klines in series.values() {
+        for (i, kline) in klines.iter().enumerate() {
+            let neighbours = klines[i.saturating_sub(NEIGHBOURS)..(i + NEIGHBOURS + 1).min(klines.len())]
+                .iter()
+                .filter(|neighbour| neighbour.start_time != kline.start_time)
+                .map(|neighbour| neighbour.close_price)
+                .collect::<Vec<_>>();
+            let Some(median) = median(neighbours).filter(|median| *median > 0.0) else {
+                continue;
+            };
+
+            let (field, price) = [
+                ("open", kline.open_price),
+                ("high", kline.high_price),
+                ("low", kline.low_price),
+                ("close", kline.close_price),
+            ]
+            .into_iter()
+            .max_by(|(_, a), (_, b)| (a - median).abs().total_cmp(&(b - median).abs()))
+            .expect("four prices");
+            let deviation = (price - median).abs() / median;
+            if deviation > threshold {
+                anomalies.push(Anomaly {
+                    start_time: kline.start_time,
+                    kline_type: kline.kline_type,
+                    category: kline.category,
+                    field,
+                    price,
+                    median,
+                    deviation,
+                });
+            }
+        }
+    }
+    anomalies.sort_by_key(|anomaly| anomaly.start_time);
+    anomalies
+}
+
+/// Remove the klines flagged in `anomalies`.
+pub fn drop_anomalies(klines: &mut Vec<Kline>, anomalies: &[Anomaly]) {
+    let flagged = anomalies
+        .iter()
+        .map(|anomaly| (anomaly.start_time, anomaly.kline_type, anomaly.category))
+        .collect::<HashSet<_>>();
+    klines.retain(|kline| !flagged.contains(&(kline.start_time, kline.kline_type, kline.category)));
+}
+
+fn median(mut values: Vec<f64>) -> Option<f64> {
+    if values.len() < 2 {
+        return None;
+    }
+    values.sort_by(f64::total_cmp);
+    let mid = values.len() / 2;
+    Some(match values.len() % 2 {
+        0 => (values[mid - 1] + values[mid]) / 2.0,
+        _ => values[mid],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kline::test_kline;
+
+    #[test]
+    fn test_detect_anomalies() {
+        let mut klines = (0..20)
+            .map(|i| test_kline(i * 60_000, 100.0 + i as f64 * 0.1))
+            .collect::<Vec<_>>();
+        // A fat-finger wick 50% above the market, and a mark price series which isn't off
+        klines[7].high_price = 151.0;
+        klines.extend((0..20).map(|i| Kline { kline_type: KlineType::Mark, ..test_kline(i * 60_000, 200.0) }));
+
+        let anomalies = detect_anomalies(&klines, DEFAULT_THRESHOLD);
+        assert_eq!(anomalies.len(), 1);
+        let anomaly = &anomalies[0];
+        assert_eq!((anomaly.start_time, anomaly.kline_type, anomaly.field), (7 * 60_000, KlineType::Trade, "high"));
+        assert!((anomaly.deviation - 0.5).abs() < 0.01);
+
+        // A looser threshold lets the wick through, and the flagged bar is the only one dropped
+        assert!(detect_anomalies(&klines, 0.6).is_empty());
+        drop_anomalies(&mut klines, &anomalies);
+        assert_eq!(klines.len(), 39);
+        assert!(klines.iter().all(|kline| kline.high_price != 151.0));
+    }
+}
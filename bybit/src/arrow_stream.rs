@@ -0,0 +1,415 @@
+use crate::{kline::Kline, writer::OutputWriter, BybitError};
+use std::io::Write;
+
+/// Klines per Arrow record batch.
+pub const BATCH_SIZE: usize = 10_000;
+
+/// Marks the start of every encapsulated IPC message.
+const CONTINUATION: [u8; 4] = [0xff; 4];
+/// `MetadataVersion::V5`.
+const METADATA_VERSION: i16 = 4;
+/// `MessageHeader` union members.
+const HEADER_SCHEMA: u8 = 1;
+const HEADER_RECORD_BATCH: u8 = 3;
+/// `Type` union members.
+const TYPE_FLOATING_POINT: u8 = 3;
+const TYPE_UTF8: u8 = 5;
+const TYPE_TIMESTAMP: u8 = 10;
+const PRECISION_DOUBLE: i16 = 2;
+const TIME_UNIT_MILLISECOND: i16 = 1;
+
+/// Value of a float64 column.
+type Column = fn(&Kline) -> f64;
+
+/// Float64 columns following `start_time`, the same as Parquet output.
+const PRICE_COLUMNS: [(&str, Column); 6] = [
+    ("open", |kline| kline.open_price),
+    ("high", |kline| kline.high_price),
+    ("low", |kline| kline.low_price),
+    ("close", |kline| kline.close_price),
+    ("base_volume", Kline::base_volume),
+    ("quote_volume", Kline::quote_volume),
+];
+
+/// Arrow IPC stream of klines, eg/ for `pyarrow.ipc.open_stream`: the schema, a record batch
+/// every [`BATCH_SIZE`] klines and the end of stream marker. Columns match Parquet output:
+/// `start_time` (timestamp in millis, UTC), the prices and volumes as float64 and `kline_type`
+/// as utf8, none of them nullable.
+#[derive(Debug, Default)]
+pub struct ArrowStreamWriter {
+    batch: Vec<Kline>,
+}
+
+impl OutputWriter for ArrowStreamWriter {
+    fn write_header(&mut self, out: &mut dyn Write) -> Result<(), BybitError> {
+        Ok(write_message(out, HEADER_SCHEMA, schema(), &[])?)
+    }
+
+    fn write_kline(&mut self, out: &mut dyn Write, kline: &Kline) -> Result<(), BybitError> {
+        self.batch.push(kline.clone());
+        if self.batch.len() >= BATCH_SIZE {
+            self.write_batch(out)?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self, out: &mut dyn Write) -> Result<(), BybitError> {
+        self.write_batch(out)?;
+        out.write_all(&CONTINUATION)?;
+        Ok(out.write_all(&0i32.to_le_bytes())?)
+    }
+}
+
+impl ArrowStreamWriter {
+    fn write_batch(&mut self, out: &mut dyn Write) -> Result<(), BybitError> {
+        if self.batch.is_empty() {
+            return Ok(());
+        }
+        let mut body = Body::default();
+        let start_times = self.batch.iter().flat_map(|kline| (kline.start_time as i64).to_le_bytes());
+        body.column(&start_times.collect::<Vec<_>>());
+        for (_, value) in PRICE_COLUMNS {
+            let values = self.batch.iter().flat_map(|kline| value(kline).to_le_bytes());
+            body.column(&values.collect::<Vec<_>>());
+        }
+        let mut offsets = 0i32.to_le_bytes().to_vec();
+        let mut data = Vec::new();
+        for kline in &self.batch {
+            data.extend_from_slice(kline.kline_type.as_str().as_bytes());
+            offsets.extend_from_slice(&(data.len() as i32).to_le_bytes());
+        }
+        body.buffer(&[]);
+        body.buffer(&offsets);
+        body.buffer(&data);
+
+        let rows = self.batch.len() as i64;
+        let record_batch = Table::default()
+            .with(0, Slot::Long(rows))
+            .with(1, Slot::Structs(vec![[rows, 0]; PRICE_COLUMNS.len() + 2]))
+            .with(2, Slot::Structs(body.buffers));
+        write_message(out, HEADER_RECORD_BATCH, record_batch, &body.bytes)?;
+        self.batch.clear();
+        Ok(())
+    }
+}
+
+fn schema() -> Table {
+    let field = |name: &str, type_type: u8, type_table: Table| {
+        Table::default()
+            .with(0, Slot::String(name.to_string()))
+            .with(1, Slot::Bool(false))
+            .with(2, Slot::Byte(type_type))
+            .with(3, Slot::Table(type_table))
+            .with(5, Slot::Tables(Vec::new()))
+    };
+    let timestamp = Table::default()
+        .with(0, Slot::Short(TIME_UNIT_MILLISECOND))
+        .with(1, Slot::String("UTC".to_string()));
+    let mut fields = vec![field("start_time", TYPE_TIMESTAMP, timestamp)];
+    for (name, _) in PRICE_COLUMNS {
+        let double = Table::default().with(0, Slot::Short(PRECISION_DOUBLE));
+        fields.push(field(name, TYPE_FLOATING_POINT, double));
+    }
+    fields.push(field("kline_type", TYPE_UTF8, Table::default()));
+    Table::default().with(1, Slot::Tables(fields))
+}
+
+/// Write an encapsulated message: the continuation marker, the length of the `Message`
+/// flatbuffer, padded to 8 bytes, the flatbuffer and the body.
+fn write_message(out: &mut dyn Write, header_type: u8, header: Table, body: &[u8]) -> std::io::Result<()> {
+    let message = Table::default()
+        .with(0, Slot::Short(METADATA_VERSION))
+        .with(1, Slot::Byte(header_type))
+        .with(2, Slot::Table(header))
+        .with(3, Slot::Long(body.len() as i64));
+    let metadata = message.finish();
+    out.write_all(&CONTINUATION)?;
+    out.write_all(&(metadata.len() as i32).to_le_bytes())?;
+    out.write_all(&metadata)?;
+    out.write_all(body)
+}
+
+/// Record batch body: every column's buffers, each padded to 8 bytes, and the `Buffer`
+/// (offset, length) of each.
+#[derive(Default)]
+struct Body {
+    bytes: Vec<u8>,
+    buffers: Vec<[i64; 2]>,
+}
+
+impl Body {
+    /// A fixed width column without nulls: an empty validity bitmap and its values.
+    fn column(&mut self, values: &[u8]) {
+        self.buffer(&[]);
+        self.buffer(values);
+    }
+
+    fn buffer(&mut self, bytes: &[u8]) {
+        self.buffers.push([self.bytes.len() as i64, bytes.len() as i64]);
+        self.bytes.extend_from_slice(bytes);
+        pad(&mut self.bytes, 8);
+    }
+}
+
+/// A flatbuffer table, its fields by id. Only what Arrow's metadata needs is supported.
+#[derive(Default)]
+struct Table {
+    fields: Vec<(u16, Slot)>,
+}
+
+enum Slot {
+    Bool(bool),
+    Byte(u8),
+    Short(i16),
+    Long(i64),
+    String(String),
+    Table(Table),
+    Tables(Vec<Table>),
+    /// Structs of two longs, Arrow's `FieldNode` and `Buffer`.
+    Structs(Vec<[i64; 2]>),
+}
+
+impl Slot {
+    /// Size inline in a table, offsets to strings, tables and vectors taking 4 bytes.
+    fn size(&self) -> usize {
+        match self {
+            Slot::Bool(_) | Slot::Byte(_) => 1,
+            Slot::Short(_) => 2,
+            Slot::Long(_) => 8,
+            _ => 4,
+        }
+    }
+}
+
+impl Table {
+    fn with(mut self, id: u16, slot: Slot) -> Self {
+        self.fields.push((id, slot));
+        self
+    }
+
+    /// Serialize as a flatbuffer with this table as its root, padded to 8 bytes.
+    fn finish(&self) -> Vec<u8> {
+        let mut buffer = vec![0; 4];
+        let root = self.write(&mut buffer);
+        buffer[..4].copy_from_slice(&(root as u32).to_le_bytes());
+        pad(&mut buffer, 8);
+        buffer
+    }
+
+    /// Append the vtable, then the table, then everything it references, which flatbuffer
+    /// offsets require to follow it. Returns the position of the table.
+    fn write(&self, buffer: &mut Vec<u8>) -> usize {
+        // Largest fields first after the vtable offset, each aligned to its size
+        let mut fields = self.fields.iter().collect::<Vec<_>>();
+        fields.sort_by_key(|(_, slot)| std::cmp::Reverse(slot.size()));
+        let mut size = 4usize;
+        let mut layout = Vec::new();
+        for (id, slot) in fields {
+            let at = size.next_multiple_of(slot.size());
+            layout.push((*id, slot, at));
+            size = at + slot.size();
+        }
+
+        let slots = self.fields.iter().map(|(id, _)| *id as usize + 1).max().unwrap_or(0);
+        let mut vtable = vec![0u16; 2 + slots];
+        vtable[0] = (vtable.len() * 2) as u16;
+        vtable[1] = size as u16;
+        for (id, _, at) in &layout {
+            vtable[2 + *id as usize] = *at as u16;
+        }
+        pad(buffer, 2);
+        let vtable_at = buffer.len();
+        buffer.extend(vtable.iter().flat_map(|entry| entry.to_le_bytes()));
+
+        pad(buffer, 8);
+        let table_at = buffer.len();
+        buffer.resize(table_at + size, 0);
+        put(buffer, table_at, &((table_at - vtable_at) as i32).to_le_bytes());
+        let mut references = Vec::new();
+        for (_, slot, at) in layout {
+            let at = table_at + at;
+            match slot {
+                Slot::Bool(value) => put(buffer, at, &[*value as u8]),
+                Slot::Byte(value) => put(buffer, at, &[*value]),
+                Slot::Short(value) => put(buffer, at, &value.to_le_bytes()),
+                Slot::Long(value) => put(buffer, at, &value.to_le_bytes()),
+                reference => references.push((at, reference)),
+            }
+        }
+        for (at, reference) in references {
+            let target = match reference {
+                Slot::String(value) => {
+                    pad(buffer, 4);
+                    let target = buffer.len();
+                    buffer.extend_from_slice(&(value.len() as u32).to_le_bytes());
+                    buffer.extend_from_slice(value.as_bytes());
+                    buffer.push(0);
+                    target
+                }
+                Slot::Table(table) => table.write(buffer),
+                Slot::Tables(tables) => {
+                    pad(buffer, 4);
+                    let target = buffer.len();
+                    buffer.extend_from_slice(&(tables.len() as u32).to_le_bytes());
+                    buffer.resize(target + 4 + tables.len() * 4, 0);
+                    for (index, table) in tables.iter().enumerate() {
+                        let entry = target + 4 + index * 4;
+                        let table = table.write(buffer);
+                        put(buffer, entry, &((table - entry) as u32).to_le_bytes());
+                    }
+                    target
+                }
+                Slot::Structs(structs) => {
+                    // The length precedes the structs, which are aligned to 8 bytes
+                    pad(buffer, 4);
+                    if buffer.len().is_multiple_of(8) {
+                        buffer.extend_from_slice(&[0; 4]);
+                    }
+                    let target = buffer.len();
+                    buffer.extend_from_slice(&(structs.len() as u32).to_le_bytes());
+                    buffer.extend(structs.iter().flatten().flat_map(|value| value.to_le_bytes()));
+                    target
+                }
+                _ => unreachable!("scalars are written inline"),
+            };
+            put(buffer, at, &((target - at) as u32).to_le_bytes());
+        }
+        table_at
+    }
+}
+
+fn put(buffer: &mut [u8], at: usize, bytes: &[u8]) {
+    buffer[at..at + bytes.len()].copy_from_slice(bytes);
+}
+
+fn pad(buffer: &mut Vec<u8>, align: usize) {
+    buffer.resize(buffer.len().next_multiple_of(align), 0);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kline::{test_kline, KlineType};
+    use crate::writer::write_klines;
+
+    /// Reads flatbuffer tables by following their vtables.
+    struct Reader<'a> {
+        buffer: &'a [u8],
+    }
+
+    impl Reader<'_> {
+        fn u32(&self, at: usize) -> usize {
+            u32::from_le_bytes(self.buffer[at..at + 4].try_into().unwrap()) as usize
+        }
+
+        fn i64(&self, at: usize) -> i64 {
+            i64::from_le_bytes(self.buffer[at..at + 8].try_into().unwrap())
+        }
+
+        /// Position of field `id` of the table at `table`, if set.
+        fn field(&self, table: usize, id: usize) -> Option<usize> {
+            let vtable = table - i32::from_le_bytes(self.buffer[table..table + 4].try_into().unwrap()) as usize;
+            let vtable_size = u16::from_le_bytes([self.buffer[vtable], self.buffer[vtable + 1]]) as usize;
+            if 4 + id * 2 >= vtable_size {
+                return None;
+            }
+            let at = vtable + 4 + id * 2;
+            match u16::from_le_bytes([self.buffer[at], self.buffer[at + 1]]) as usize {
+                0 => None,
+                offset => Some(table + offset),
+            }
+        }
+
+        fn reference(&self, table: usize, id: usize) -> usize {
+            let at = self.field(table, id).unwrap();
+            at + self.u32(at)
+        }
+
+        fn string(&self, table: usize, id: usize) -> &str {
+            let at = self.reference(table, id);
+            std::str::from_utf8(&self.buffer[at + 4..at + 4 + self.u32(at)]).unwrap()
+        }
+
+        /// Positions of the tables of a vector.
+        fn tables(&self, table: usize, id: usize) -> Vec<usize> {
+            let at = self.reference(table, id);
+            (0..self.u32(at)).map(|index| at + 4 + index * 4 + self.u32(at + 4 + index * 4)).collect()
+        }
+
+        fn structs(&self, table: usize, id: usize) -> Vec<[i64; 2]> {
+            let at = self.reference(table, id);
+            assert_eq!((at + 4) % 8, 0);
+            (0..self.u32(at)).map(|index| [self.i64(at + 4 + index * 16), self.i64(at + 12 + index * 16)]).collect()
+        }
+    }
+
+    /// Split a stream into its messages' metadata and bodies, checking the end of stream marker.
+    fn messages(stream: &[u8]) -> Vec<(&[u8], &[u8])> {
+        let mut messages = Vec::new();
+        let mut at = 0;
+        loop {
+            assert_eq!(stream[at..at + 4], CONTINUATION);
+            let length = u32::from_le_bytes(stream[at + 4..at + 8].try_into().unwrap()) as usize;
+            if length == 0 {
+                assert_eq!(at + 8, stream.len());
+                return messages;
+            }
+            assert_eq!(length % 8, 0);
+            let metadata = &stream[at + 8..at + 8 + length];
+            let reader = Reader { buffer: metadata };
+            let body_length = reader.i64(reader.field(reader.u32(0), 3).unwrap()) as usize;
+            let body = &stream[at + 8 + length..at + 8 + length + body_length];
+            messages.push((metadata, body));
+            at += 8 + length + body_length;
+        }
+    }
+
+    #[test]
+    fn test_arrow_stream() {
+        let klines = (0..BATCH_SIZE as u64 + 2)
+            .map(|n| Kline {
+                kline_type: if n % 2 == 0 { KlineType::Trade } else { KlineType::Mark },
+                ..test_kline(1_704_067_200_000 + n * 60_000, 100.0 + n as f64)
+            })
+            .collect::<Vec<_>>();
+        let mut stream = Vec::new();
+        write_klines(&mut ArrowStreamWriter::default(), &mut stream, &klines).unwrap();
+
+        // The schema, a full batch and the remainder
+        let parts = messages(&stream);
+        assert_eq!(parts.len(), 3);
+        let schema = Reader { buffer: parts[0].0 };
+        let message = schema.u32(0);
+        assert_eq!(schema.buffer[schema.field(message, 1).unwrap()], HEADER_SCHEMA);
+        let fields = schema.tables(schema.reference(message, 2), 1);
+        let names = fields.iter().map(|&field| schema.string(field, 0)).collect::<Vec<_>>();
+        assert_eq!(names, ["start_time", "open", "high", "low", "close", "base_volume", "quote_volume", "kline_type"]);
+        let types = fields.iter().map(|&field| schema.buffer[schema.field(field, 2).unwrap()]).collect::<Vec<_>>();
+        assert_eq!(types, [TYPE_TIMESTAMP, 3, 3, 3, 3, 3, 3, TYPE_UTF8]);
+        assert_eq!(schema.string(schema.reference(fields[0], 3), 1), "UTC");
+        assert!(fields.iter().all(|&field| schema.tables(field, 5).is_empty()));
+
+        let (metadata, body) = parts[2];
+        let batch = Reader { buffer: metadata };
+        let message = batch.u32(0);
+        assert_eq!(batch.buffer[batch.field(message, 1).unwrap()], HEADER_RECORD_BATCH);
+        let record_batch = batch.reference(message, 2);
+        assert_eq!(batch.i64(batch.field(record_batch, 0).unwrap()), 2);
+        assert_eq!(batch.structs(record_batch, 1), vec![[2, 0]; 8]);
+        let buffers = batch.structs(record_batch, 2);
+        assert_eq!(buffers.len(), 17);
+        assert!(buffers.iter().all(|[offset, _]| offset % 8 == 0));
+        let buffer = |index: usize| &body[buffers[index][0] as usize..(buffers[index][0] + buffers[index][1]) as usize];
+        let start_time = i64::from_le_bytes(buffer(1)[8..].try_into().unwrap());
+        assert_eq!(start_time as u64, klines[BATCH_SIZE + 1].start_time);
+        let close = f64::from_le_bytes(buffer(9)[..8].try_into().unwrap());
+        assert_eq!(close, klines[BATCH_SIZE].close_price);
+        assert_eq!(buffer(15), [0, 0, 0, 0, 5, 0, 0, 0, 9, 0, 0, 0]);
+        assert_eq!(buffer(16), b"trademark");
+
+        // An empty stream is still well formed, the schema and end of stream marker
+        let mut stream = Vec::new();
+        write_klines(&mut ArrowStreamWriter::default(), &mut stream, &[]).unwrap();
+        assert_eq!(messages(&stream).len(), 1);
+    }
+}
@@ -0,0 +1,959 @@
+use crate::{category::Category, gaps::GapFill, interval::Interval, kline::Kline, trades::TradeSummary};
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::{cmp::Reverse, collections::BinaryHeap, fmt};
+
+// Barter-compatible data structures
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BarterCandle {
+    pub close_time: DateTime<Utc>,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub volume: f64,
+    pub trade_count: u64,
+    /// Volume in the quote coin, only populated with `--barter-quote-volume`.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub quote_volume: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BarterPublicTrade {
+    pub id: String,
+    pub price: f64,
+    pub amount: f64,
+    pub side: BarterSide,
+}
+
+/// Order flow of a bar aggregated from Bybit's public trades, emitted with `--data-kind
+/// trade-summary` as `{"TradeSummary":{"close_time":..,"buy_volume":..,"sell_volume":..,
+/// "trade_count":..,"vwap":..}}`. Volumes are in the base coin (contracts for inverse), by the
+/// taker's side.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BarterTradeSummary {
+    pub close_time: DateTime<Utc>,
+    pub buy_volume: f64,
+    pub sell_volume: f64,
+    pub trade_count: u64,
+    /// Volume weighted average price, absent for a bar without trades.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub vwap: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BarterSide {
+    Buy,
+    Sell,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BarterMarketEvent {
+    pub time_exchange: DateTime<Utc>,
+    pub time_received: DateTime<Utc>,
+    pub exchange: String,
+    pub instrument: usize,
+    /// Canonical `base/quote[:settle]` symbol, only populated with `--normalize-symbol`.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub symbol: Option<String>,
+    /// Bybit's raw symbol (e.g. BTCUSDT), only populated with `--normalize-symbol`.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub raw_symbol: Option<String>,
+    /// Whether a live candle has closed, only populated with `--live --include-unconfirmed`.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub confirmed: Option<bool>,
+    /// Fill strategy of a synthetic bar inserted by `--fill-gaps`, absent for received bars.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub filled: Option<GapFill>,
+    /// Bybit interval of the source kline (eg/ `60`, `D`), only populated when several
+    /// intervals are fetched in one run.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub interval: Option<String>,
+    /// Native timezone of the source exchange, timestamps are always UTC. Only populated with
+    /// `--annotate-timezone`, for merges across exchanges.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub timezone: Option<String>,
+    pub kind: BarterDataKind,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub enum BarterDataKind {
+    Candle(BarterCandle),
+    Trade(BarterPublicTrade),
+    TradeSummary(BarterTradeSummary),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BarterMarketStreamEvent {
+    #[serde(rename = "Item")]
+    pub item: BarterMarketEventResult,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BarterMarketEventResult {
+    #[serde(rename = "Ok")]
+    pub ok: BarterMarketEvent,
+}
+
+impl From<BarterMarketEvent> for BarterMarketStreamEvent {
+    fn from(market_event: BarterMarketEvent) -> Self {
+        BarterMarketStreamEvent {
+            item: BarterMarketEventResult { ok: market_event },
+        }
+    }
+}
+
+impl BarterMarketStreamEvent {
+    /// Serialise as a single ndjson line, flattened to a [`CompactBarterEvent`] if `compact`.
+    pub fn to_json(&self, compact: bool) -> Result<String, serde_json::Error> {
+        match compact {
+            true => serde_json::to_string(&CompactBarterEvent::from(&self.item.ok)),
+            false => serde_json::to_string(self),
+        }
+    }
+}
+
+/// Flattened, short keyed form of a [`BarterMarketEvent`] written by `--compact-barter`, eg/
+/// `{"t":1704067200000,"x":"bybit_perpetuals_usd","i":0,"k":"c","ct":1704068100000,"o":1.0,"h":2.0,"l":0.5,"c":1.5,"v":10.0,"n":0}`.
+///
+/// Timestamps are unix millis. `tr` (time received) is only written when it differs from `t`
+/// (time exchange), and optional fields are omitted like in the full form.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct CompactBarterEvent {
+    #[serde(rename = "t")]
+    pub time_exchange: i64,
+    #[serde(rename = "tr", skip_serializing_if = "Option::is_none", default)]
+    pub time_received: Option<i64>,
+    #[serde(rename = "x")]
+    pub exchange: String,
+    #[serde(rename = "i")]
+    pub instrument: usize,
+    #[serde(rename = "sy", skip_serializing_if = "Option::is_none", default)]
+    pub symbol: Option<String>,
+    #[serde(rename = "rs", skip_serializing_if = "Option::is_none", default)]
+    pub raw_symbol: Option<String>,
+    #[serde(rename = "cf", skip_serializing_if = "Option::is_none", default)]
+    pub confirmed: Option<bool>,
+    #[serde(rename = "f", skip_serializing_if = "Option::is_none", default)]
+    pub filled: Option<GapFill>,
+    #[serde(rename = "iv", skip_serializing_if = "Option::is_none", default)]
+    pub interval: Option<String>,
+    #[serde(rename = "tz", skip_serializing_if = "Option::is_none", default)]
+    pub timezone: Option<String>,
+    #[serde(flatten)]
+    pub kind: CompactBarterKind,
+}
+
+/// Payload of a [`CompactBarterEvent`], tagged `"k":"c"` for candles, `"k":"t"` for trades and
+/// `"k":"s"` for trade summaries.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "k")]
+pub enum CompactBarterKind {
+    #[serde(rename = "c")]
+    Candle {
+        #[serde(rename = "ct")]
+        close_time: i64,
+        #[serde(rename = "o")]
+        open: f64,
+        #[serde(rename = "h")]
+        high: f64,
+        #[serde(rename = "l")]
+        low: f64,
+        #[serde(rename = "c")]
+        close: f64,
+        #[serde(rename = "v")]
+        volume: f64,
+        #[serde(rename = "n")]
+        trade_count: u64,
+        #[serde(rename = "q", skip_serializing_if = "Option::is_none", default)]
+        quote_volume: Option<f64>,
+    },
+    #[serde(rename = "t")]
+    Trade {
+        id: String,
+        #[serde(rename = "p")]
+        price: f64,
+        #[serde(rename = "a")]
+        amount: f64,
+        #[serde(rename = "s")]
+        side: BarterSide,
+    },
+    #[serde(rename = "s")]
+    TradeSummary {
+        #[serde(rename = "ct")]
+        close_time: i64,
+        #[serde(rename = "bv")]
+        buy_volume: f64,
+        #[serde(rename = "sv")]
+        sell_volume: f64,
+        #[serde(rename = "n")]
+        trade_count: u64,
+        #[serde(rename = "vw", skip_serializing_if = "Option::is_none", default)]
+        vwap: Option<f64>,
+    },
+}
+
+impl From<&BarterMarketEvent> for CompactBarterEvent {
+    fn from(event: &BarterMarketEvent) -> Self {
+        let time_exchange = event.time_exchange.timestamp_millis();
+        let time_received = event.time_received.timestamp_millis();
+        CompactBarterEvent {
+            time_exchange,
+            time_received: (time_received != time_exchange).then_some(time_received),
+            exchange: event.exchange.clone(),
+            instrument: event.instrument,
+            symbol: event.symbol.clone(),
+            raw_symbol: event.raw_symbol.clone(),
+            confirmed: event.confirmed,
+            filled: event.filled,
+            interval: event.interval.clone(),
+            timezone: event.timezone.clone(),
+            kind: match &event.kind {
+                BarterDataKind::Candle(candle) => CompactBarterKind::Candle {
+                    close_time: candle.close_time.timestamp_millis(),
+                    open: candle.open,
+                    high: candle.high,
+                    low: candle.low,
+                    close: candle.close,
+                    volume: candle.volume,
+                    trade_count: candle.trade_count,
+                    quote_volume: candle.quote_volume,
+                },
+                BarterDataKind::Trade(trade) => CompactBarterKind::Trade {
+                    id: trade.id.clone(),
+                    price: trade.price,
+                    amount: trade.amount,
+                    side: trade.side,
+                },
+                BarterDataKind::TradeSummary(summary) => CompactBarterKind::TradeSummary {
+                    close_time: summary.close_time.timestamp_millis(),
+                    buy_volume: summary.buy_volume,
+                    sell_volume: summary.sell_volume,
+                    trade_count: summary.trade_count,
+                    vwap: summary.vwap,
+                },
+            },
+        }
+    }
+}
+
+/// Path through a kline's OHLC prices used to approximate its intra-bar trades.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum TradeWalk {
+    /// Bullish bars visit the low before the high, bearish bars the high before the low.
+    Auto,
+    /// Always open -> high -> low -> close.
+    Ohlc,
+    /// Always open -> low -> high -> close.
+    Olhc,
+}
+
+impl fmt::Display for TradeWalk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            TradeWalk::Auto => "auto",
+            TradeWalk::Ohlc => "ohlc",
+            TradeWalk::Olhc => "olhc",
+        })
+    }
+}
+
+
+/// Source of the `time_received` stamped on barter events.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum TimeReceived {
+    /// Wall clock time at serialisation, which differs between runs.
+    #[default]
+    Now,
+    /// Copy `time_exchange`, so repeated runs produce byte identical output.
+    Exchange,
+    /// Unix epoch.
+    Epoch,
+}
+
+impl TimeReceived {
+    fn resolve(&self, time_exchange: DateTime<Utc>) -> DateTime<Utc> {
+        match self {
+            TimeReceived::Now => Utc::now(),
+            TimeReceived::Exchange => time_exchange,
+            TimeReceived::Epoch => DateTime::UNIX_EPOCH,
+        }
+    }
+}
+
+impl fmt::Display for TimeReceived {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            TimeReceived::Now => "now",
+            TimeReceived::Exchange => "exchange",
+            TimeReceived::Epoch => "epoch",
+        })
+    }
+}
+
+
+/// Which end of a bar labels barter candles, via their `close_time`.
+///
+/// `time_exchange` is always the bar's open time, as reported by Bybit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum TimestampConvention {
+    /// Label bars by their open time (`close_time` = start time), eg/ for platforms indexing
+    /// candles by open time.
+    Open,
+    /// Label bars by their close time (`close_time` = start time + interval), as barter does.
+    #[default]
+    Close,
+}
+
+impl fmt::Display for TimestampConvention {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            TimestampConvention::Open => "open",
+            TimestampConvention::Close => "close",
+        })
+    }
+}
+
+
+/// Order in which the barter events of several symbols fetched concurrently are emitted, so
+/// the stream is deterministic regardless of which fetch finishes first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum MergeOrder {
+    /// Every event of the first symbol, then every event of the next, in the order given.
+    #[default]
+    PerSymbol,
+    /// A single stream ordered by `time_exchange` across symbols, ties in symbol order.
+    Time,
+}
+
+impl fmt::Display for MergeOrder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            MergeOrder::PerSymbol => "per-symbol",
+            MergeOrder::Time => "time",
+        })
+    }
+}
+
+
+/// K-way merge of several barter event streams, each already ordered by `time_exchange` (eg/
+/// one per instrument), into a single globally time ordered stream as barter's replay loop
+/// expects.
+///
+/// Events sharing a timestamp are emitted by ascending instrument index, then in stream order,
+/// so the merged stream is deterministic. Only one pending event per stream is held at a time.
+pub fn merge_by_time<I>(streams: impl IntoIterator<Item = I>) -> MergeByTime<I::IntoIter>
+where
+    I: IntoIterator<Item = BarterMarketStreamEvent>,
+{
+    let mut merge = MergeByTime {
+        streams: Vec::new(),
+        heads: Vec::new(),
+        queue: BinaryHeap::new(),
+    };
+    for stream in streams {
+        merge.streams.push(stream.into_iter());
+        merge.heads.push(None);
+        merge.advance(merge.streams.len() - 1);
+    }
+    merge
+}
+
+/// Iterator returned by [`merge_by_time`].
+pub struct MergeByTime<I> {
+    streams: Vec<I>,
+    /// Next event of each stream, queued by its merge key.
+    heads: Vec<Option<BarterMarketStreamEvent>>,
+    /// Min-heap of (time_exchange, instrument, stream) for every stream with a pending event.
+    queue: BinaryHeap<Reverse<(DateTime<Utc>, usize, usize)>>,
+}
+
+impl<I: Iterator<Item = BarterMarketStreamEvent>> MergeByTime<I> {
+    /// Queue the next event of `stream`, if it has one.
+    fn advance(&mut self, stream: usize) {
+        if let Some(event) = self.streams[stream].next() {
+            let key = (
+                event.item.ok.time_exchange,
+                event.item.ok.instrument,
+                stream,
+            );
+            self.heads[stream] = Some(event);
+            self.queue.push(Reverse(key));
+        }
+    }
+}
+
+impl<I: Iterator<Item = BarterMarketStreamEvent>> Iterator for MergeByTime<I> {
+    type Item = BarterMarketStreamEvent;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let Reverse((_, _, stream)) = self.queue.pop()?;
+        let event = self.heads[stream].take();
+        self.advance(stream);
+        event
+    }
+}
+
+/// Settings controlling how klines are rendered as barter events.
+#[derive(Debug, Clone, Default)]
+pub struct BarterOptions {
+    pub instrument_index: usize,
+    /// Include the quote coin volume in barter candles.
+    pub quote_volume: bool,
+    /// Canonical symbol to emit alongside Bybit's raw symbol.
+    pub symbol: Option<String>,
+    pub raw_symbol: Option<String>,
+    pub time_received: TimeReceived,
+    /// Interval to tag events with, so multi-timeframe streams are unambiguous.
+    pub interval: Option<Interval>,
+    /// Record the exchange's native timezone on each event.
+    pub annotate_timezone: bool,
+    /// Timestamp populating candles' `close_time`.
+    pub timestamp_convention: TimestampConvention,
+    /// Exchange names to emit in place of the defaults.
+    pub exchange_names: ExchangeNames,
+}
+
+/// Barter exchange names overriding [`exchange_name`] per Bybit category, so events match an
+/// existing barter instrument registry (eg/ `bybit-linear`).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExchangeNames {
+    pub spot: Option<String>,
+    pub linear: Option<String>,
+    pub inverse: Option<String>,
+    pub option: Option<String>,
+}
+
+impl ExchangeNames {
+    /// Exchange name of a Bybit category, the override if one is set.
+    pub fn name(&self, category: Category) -> &str {
+        let custom = match category {
+            Category::Spot => self.spot.as_deref(),
+            Category::Linear => self.linear.as_deref(),
+            Category::Inverse => self.inverse.as_deref(),
+            Category::Option => self.option.as_deref(),
+        };
+        custom.unwrap_or_else(|| exchange_name(category))
+    }
+}
+
+/// Native timezone of Bybit's timestamps.
+pub const EXCHANGE_TIMEZONE: &str = "UTC";
+
+/// Sample events with every optional field populated, one per [`BarterDataKind`], documenting
+/// the exact shape of the emitted ndjson (the `Item.Ok.kind.Candle` nesting, serde renames and
+/// field types) for `--dump-schema`.
+pub fn sample_events() -> Vec<BarterMarketStreamEvent> {
+    let time_exchange = DateTime::from_timestamp_millis(1_704_067_200_000)
+        .unwrap_or_else(|| DateTime::from_timestamp(0, 0).unwrap());
+    let event = |kind| {
+        BarterMarketEvent {
+            time_exchange,
+            time_received: time_exchange,
+            exchange: exchange_name(Category::Linear).to_string(),
+            instrument: 0,
+            symbol: Some("BTC/USDT:USDT".to_string()),
+            raw_symbol: Some("BTCUSDT".to_string()),
+            confirmed: Some(true),
+            filled: Some(GapFill::Forward),
+            interval: Some(Interval::Min15.as_str().to_string()),
+            timezone: Some(EXCHANGE_TIMEZONE.to_string()),
+            kind,
+        }
+        .into()
+    };
+
+    vec![
+        event(BarterDataKind::Candle(BarterCandle {
+            close_time: time_exchange + chrono::Duration::minutes(15),
+            open: 42000.5,
+            high: 42100.0,
+            low: 41900.0,
+            close: 42050.0,
+            volume: 12.5,
+            trade_count: 0,
+            quote_volume: Some(525000.0),
+        })),
+        event(BarterDataKind::Trade(BarterPublicTrade {
+            id: "synthetic-1704067200000-0".to_string(),
+            price: 42000.5,
+            amount: 3.125,
+            side: BarterSide::Buy,
+        })),
+        event(BarterDataKind::TradeSummary(BarterTradeSummary {
+            close_time: time_exchange + chrono::Duration::minutes(15),
+            buy_volume: 7.25,
+            sell_volume: 5.25,
+            trade_count: 311,
+            vwap: Some(42031.2),
+        })),
+    ]
+}
+
+/// Map a Bybit category to the barter exchange name.
+pub fn exchange_name(category: Category) -> &'static str {
+    match category {
+        Category::Spot => "bybit_spot",
+        Category::Linear => "bybit_perpetuals_usd",
+        Category::Inverse => "bybit_perpetuals_usd", // Using same as linear for now
+        Category::Option => "bybit_options",
+    }
+}
+
+impl Kline {
+    fn to_market_event(
+        &self,
+        time_exchange: DateTime<Utc>,
+        kind: BarterDataKind,
+        options: &BarterOptions,
+    ) -> BarterMarketStreamEvent {
+        BarterMarketEvent {
+            time_exchange,
+            time_received: options.time_received.resolve(time_exchange),
+            exchange: options.exchange_names.name(self.category).to_string(),
+            instrument: options.instrument_index,
+            symbol: options.symbol.clone(),
+            raw_symbol: options.raw_symbol.clone(),
+            confirmed: None,
+            filled: self.filled,
+            interval: options
+                .interval
+                .map(|interval| interval.as_str().to_string()),
+            timezone: options
+                .annotate_timezone
+                .then(|| EXCHANGE_TIMEZONE.to_string()),
+            kind,
+        }
+        .into()
+    }
+
+    /// Bar open time, and the `close_time` labelling it under the options' convention.
+    fn event_times(
+        &self,
+        interval: Interval,
+        options: &BarterOptions,
+    ) -> (DateTime<Utc>, DateTime<Utc>) {
+        let start_time = DateTime::from_timestamp_millis(self.start_time as i64)
+            .unwrap_or_else(|| DateTime::from_timestamp(0, 0).unwrap());
+
+        let close_time = match options.timestamp_convention {
+            TimestampConvention::Open => start_time,
+            // Start of the next bar, following calendar months for the M interval
+            TimestampConvention::Close => {
+                DateTime::from_timestamp_millis(self.close_time(interval) as i64)
+                    .unwrap_or(start_time)
+            }
+        };
+        (start_time, close_time)
+    }
+
+    pub fn to_barter_event(
+        &self,
+        interval: Interval,
+        options: &BarterOptions,
+    ) -> BarterMarketStreamEvent {
+        let (start_time, close_time) = self.event_times(interval, options);
+
+        let candle = BarterCandle {
+            close_time,
+            open: self.open_price,
+            high: self.high_price,
+            low: self.low_price,
+            close: self.close_price,
+            volume: self.base_volume(),
+            trade_count: 0, // Bybit doesn't provide trade count in kline data
+            quote_volume: options.quote_volume.then(|| self.quote_volume()),
+        };
+
+        self.to_market_event(start_time, BarterDataKind::Candle(candle), options)
+    }
+
+    /// Barter trade summary of this kline's bar, timed like its candle.
+    pub fn to_trade_summary_event(
+        &self,
+        interval: Interval,
+        summary: &TradeSummary,
+        options: &BarterOptions,
+    ) -> BarterMarketStreamEvent {
+        let (start_time, close_time) = self.event_times(interval, options);
+        let summary = BarterTradeSummary {
+            close_time,
+            buy_volume: summary.buy_volume,
+            sell_volume: summary.sell_volume,
+            trade_count: summary.trade_count,
+            vwap: summary.vwap,
+        };
+        self.to_market_event(start_time, BarterDataKind::TradeSummary(summary), options)
+    }
+
+    /// Approximate this kline as four synthetic barter public trades walking its OHLC path,
+    /// spread evenly across the bar with the volume split equally between them.
+    ///
+    /// Synthetic trades are labelled via their id: `synthetic-<start_time>-<n>`.
+    pub fn to_synthetic_trades(
+        &self,
+        interval: Interval,
+        walk: TradeWalk,
+        options: &BarterOptions,
+    ) -> Vec<BarterMarketStreamEvent> {
+        let low_first = match walk {
+            TradeWalk::Auto => self.close_price >= self.open_price,
+            TradeWalk::Ohlc => false,
+            TradeWalk::Olhc => true,
+        };
+        let path = if low_first {
+            [
+                self.open_price,
+                self.low_price,
+                self.high_price,
+                self.close_price,
+            ]
+        } else {
+            [
+                self.open_price,
+                self.high_price,
+                self.low_price,
+                self.close_price,
+            ]
+        };
+
+        let step_ms = interval.duration_ms() / path.len() as u64;
+        let amount = self.base_volume() / path.len() as f64;
+
+        let mut previous_price = None;
+        path.iter()
+            .enumerate()
+            .map(|(n, &price)| {
+                let side = match previous_price {
+                    Some(previous) if price < previous => BarterSide::Sell,
+                    Some(_) => BarterSide::Buy,
+                    None if self.close_price < self.open_price => BarterSide::Sell,
+                    None => BarterSide::Buy,
+                };
+                previous_price = Some(price);
+
+                let time_exchange =
+                    DateTime::from_timestamp_millis((self.start_time + n as u64 * step_ms) as i64)
+                        .unwrap_or_else(|| DateTime::from_timestamp(0, 0).unwrap());
+
+                let trade = BarterPublicTrade {
+                    id: format!("synthetic-{}-{}", self.start_time, n),
+                    price,
+                    amount,
+                    side,
+                };
+                self.to_market_event(time_exchange, BarterDataKind::Trade(trade), options)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kline::test_kline;
+
+    #[test]
+    fn test_to_synthetic_trades_walks_ohlc_path() {
+        let kline = Kline {
+            open_price: 100.0,
+            high_price: 110.0,
+            low_price: 95.0,
+            volume: 8.0,
+            turnover: 820.0,
+            ..test_kline(1_704_067_200_000, 105.0)
+        };
+
+        let trades =
+            kline.to_synthetic_trades(Interval::Hour1, TradeWalk::Auto, &BarterOptions::default());
+
+        let fills = trades
+            .iter()
+            .map(|event| match &event.item.ok.kind {
+                BarterDataKind::Trade(trade) => (trade.price, trade.amount, trade.side),
+                _ => panic!("expected trade"),
+            })
+            .collect::<Vec<_>>();
+
+        // Bullish bar visits the low before the high
+        assert_eq!(
+            fills,
+            vec![
+                (100.0, 2.0, BarterSide::Buy),
+                (95.0, 2.0, BarterSide::Sell),
+                (110.0, 2.0, BarterSide::Buy),
+                (105.0, 2.0, BarterSide::Sell),
+            ]
+        );
+
+        let last = &trades[3].item.ok;
+        assert_eq!(
+            last.time_exchange.timestamp_millis() as u64,
+            kline.start_time + 45 * 60_000
+        );
+        assert!(
+            matches!(&last.kind, BarterDataKind::Trade(trade) if trade.id.starts_with("synthetic-"))
+        );
+    }
+
+    #[test]
+    fn test_to_barter_event_reproducible_with_exchange_time_received() {
+        let kline = Kline {
+            open_price: 100.0,
+            high_price: 110.0,
+            low_price: 95.0,
+            volume: 8.0,
+            turnover: 820.0,
+            ..test_kline(1_704_067_200_000, 105.0)
+        };
+        let options = BarterOptions {
+            time_received: TimeReceived::Exchange,
+            ..Default::default()
+        };
+
+        let first = serde_json::to_string(&kline.to_barter_event(Interval::Min15, &options)).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        let second = serde_json::to_string(&kline.to_barter_event(Interval::Min15, &options)).unwrap();
+        assert_eq!(first, second);
+
+        let event = kline.to_barter_event(Interval::Min15, &options).item.ok;
+        assert_eq!(event.time_received, event.time_exchange);
+
+        let epoch = BarterOptions {
+            time_received: TimeReceived::Epoch,
+            ..Default::default()
+        };
+        let event = kline.to_barter_event(Interval::Min15, &epoch).item.ok;
+        assert_eq!(event.time_received.timestamp_millis(), 0);
+    }
+
+    #[test]
+    fn test_to_barter_event_optional_tags() {
+        let kline = Kline {
+            open_price: 100.0,
+            high_price: 110.0,
+            low_price: 95.0,
+            volume: 8.0,
+            turnover: 820.0,
+            ..test_kline(1_704_067_200_000, 105.0)
+        };
+
+        // Untagged unless several intervals are fetched
+        let untagged = serde_json::to_string(&kline.to_barter_event(Interval::Hour1, &BarterOptions::default()));
+        assert!(!untagged.unwrap().contains("\"interval\""));
+
+        let options = BarterOptions {
+            interval: Some(Interval::Hour1),
+            ..Default::default()
+        };
+        let event = kline.to_barter_event(Interval::Hour1, &options).item.ok;
+        assert_eq!(event.interval.as_deref(), Some("60"));
+
+        let annotated = BarterOptions {
+            annotate_timezone: true,
+            ..Default::default()
+        };
+        let event = kline.to_barter_event(Interval::Hour1, &annotated).item.ok;
+        assert_eq!(event.timezone.as_deref(), Some("UTC"));
+        assert!(kline
+            .to_barter_event(Interval::Hour1, &options)
+            .item
+            .ok
+            .timezone
+            .is_none());
+    }
+
+    #[test]
+    fn test_to_barter_event_timestamp_conventions() {
+        let kline = Kline {
+            open_price: 100.0,
+            high_price: 110.0,
+            low_price: 95.0,
+            volume: 8.0,
+            turnover: 820.0,
+            ..test_kline(1_704_067_200_000, 105.0)
+        };
+        let close_time = |convention| {
+            let options = BarterOptions {
+                timestamp_convention: convention,
+                ..Default::default()
+            };
+            let event = kline.to_barter_event(Interval::Min15, &options).item.ok;
+            assert_eq!(
+                event.time_exchange.timestamp_millis() as u64,
+                kline.start_time
+            );
+            match event.kind {
+                BarterDataKind::Candle(candle) => candle.close_time.timestamp_millis() as u64,
+                _ => panic!("expected candle"),
+            }
+        };
+
+        assert_eq!(
+            close_time(TimestampConvention::Close),
+            kline.start_time + 15 * 60_000
+        );
+        assert_eq!(close_time(TimestampConvention::Open), kline.start_time);
+        assert_eq!(TimestampConvention::default(), TimestampConvention::Close);
+        assert_eq!(
+            TimestampConvention::from_str("open", false).unwrap(),
+            TimestampConvention::Open
+        );
+        assert!(TimestampConvention::from_str("mid", false).is_err());
+    }
+
+    #[test]
+    fn test_to_barter_event_close_time_spans_interval() {
+        let kline = Kline {
+            open_price: 100.0,
+            high_price: 110.0,
+            low_price: 95.0,
+            volume: 8.0,
+            turnover: 820.0,
+            ..test_kline(1_704_067_200_000, 105.0)
+        };
+        let span = |interval| {
+            let event = kline.to_barter_event(interval, &BarterOptions::default()).item.ok;
+            match event.kind {
+                BarterDataKind::Candle(candle) => candle.close_time - event.time_exchange,
+                _ => panic!("expected candle"),
+            }
+        };
+
+        assert_eq!(span(Interval::Day), chrono::Duration::hours(24));
+        assert_eq!(span(Interval::Hour1), chrono::Duration::hours(1));
+        assert_eq!(span(Interval::Week), chrono::Duration::days(7));
+        // January 2024 has 31 days
+        assert_eq!(span(Interval::Month), chrono::Duration::days(31));
+    }
+
+    #[test]
+    fn test_merge_by_time_orders_across_instruments() {
+        let event = |start_time: u64, instrument_index: usize| {
+            let kline = Kline {
+                open_price: 100.0,
+                high_price: 110.0,
+                low_price: 95.0,
+                volume: 8.0,
+                turnover: 820.0,
+                ..test_kline(start_time, 105.0)
+            };
+            let options = BarterOptions {
+                instrument_index,
+                ..Default::default()
+            };
+            kline.to_barter_event(Interval::Min1, &options)
+        };
+
+        // Streams given out of instrument order, with ties at 60s and 180s
+        let streams = vec![
+            vec![event(60_000, 2), event(180_000, 2)],
+            vec![event(0, 0), event(60_000, 0), event(120_000, 0)],
+            vec![event(180_000, 1)],
+            vec![],
+        ];
+
+        let merged = merge_by_time(streams)
+            .map(|event| {
+                let event = event.item.ok;
+                (event.time_exchange.timestamp_millis(), event.instrument)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            merged,
+            vec![
+                (0, 0),
+                (60_000, 0),
+                (60_000, 2),
+                (120_000, 0),
+                (180_000, 1),
+                (180_000, 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sample_events_populate_every_field() {
+        let samples = serde_json::to_value(sample_events()).unwrap();
+
+        let candle = &samples[0]["Item"]["Ok"];
+        for field in [
+            "symbol",
+            "raw_symbol",
+            "confirmed",
+            "filled",
+            "interval",
+            "timezone",
+        ] {
+            assert!(!candle[field].is_null(), "missing {}", field);
+        }
+        assert!(candle["kind"]["Candle"]["quote_volume"].is_number());
+        assert_eq!(samples[1]["Item"]["Ok"]["kind"]["Trade"]["side"], "Buy");
+        assert!(samples[2]["Item"]["Ok"]["kind"]["TradeSummary"]["vwap"].is_number());
+
+        // Samples round trip through the published types
+        let events: Vec<BarterMarketStreamEvent> = serde_json::from_value(samples).unwrap();
+        assert_eq!(events.len(), 3);
+    }
+
+    #[test]
+    fn test_exchange_name_overrides() {
+        let names = ExchangeNames {
+            linear: Some("bybit-linear".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(names.name(Category::Linear), "bybit-linear");
+        assert_eq!(names.name(Category::Inverse), "bybit_perpetuals_usd");
+
+        let mut kline = Kline {
+            open_price: 1.0,
+            high_price: 2.0,
+            low_price: 0.5,
+            ..test_kline(1_704_067_200_000, 1.5)
+        };
+        let options = BarterOptions {
+            exchange_names: names,
+            ..Default::default()
+        };
+        assert_eq!(kline.to_barter_event(Interval::Min1, &options).item.ok.exchange, "bybit-linear");
+        kline.category = Category::Spot;
+        assert_eq!(kline.to_barter_event(Interval::Min1, &options).item.ok.exchange, "bybit_spot");
+    }
+
+    #[test]
+    fn test_compact_barter_event() {
+        let kline = Kline {
+            open_price: 1.0,
+            high_price: 2.0,
+            low_price: 0.5,
+            ..test_kline(1_704_067_200_000, 1.5)
+        };
+        let options = BarterOptions {
+            time_received: TimeReceived::Exchange,
+            ..Default::default()
+        };
+        let event = kline.to_barter_event(Interval::Min15, &options);
+        let compact = event.to_json(true).unwrap();
+        assert_eq!(
+            compact,
+            r#"{"t":1704067200000,"x":"bybit_perpetuals_usd","i":0,"k":"c","ct":1704068100000,"o":1.0,"h":2.0,"l":0.5,"c":1.5,"v":10.0,"n":0}"#
+        );
+        assert!(compact.len() * 2 < event.to_json(false).unwrap().len());
+
+        // Every field of the full form survives, trades included
+        for sample in sample_events() {
+            let compact = serde_json::from_str::<CompactBarterEvent>(&sample.to_json(true).unwrap()).unwrap();
+            assert_eq!(compact, CompactBarterEvent::from(&sample.item.ok));
+        }
+        let trade = serde_json::from_str::<serde_json::Value>(&sample_events()[1].to_json(true).unwrap()).unwrap();
+        assert_eq!((trade["k"].as_str(), trade["s"].as_str()), (Some("t"), Some("Buy")));
+
+        // Trade summaries share the candle's timing, and omit the vwap of a bar without trades
+        let summary = kline.to_trade_summary_event(Interval::Min15, &TradeSummary::default(), &options);
+        assert_eq!(
+            summary.to_json(true).unwrap(),
+            r#"{"t":1704067200000,"x":"bybit_perpetuals_usd","i":0,"k":"s","ct":1704068100000,"bv":0.0,"sv":0.0,"n":0}"#
+        );
+    }
+}
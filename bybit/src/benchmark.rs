@@ -0,0 +1,117 @@
+use crate::size_estimate;
+use std::{
+    fmt,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+/// Bars in the window `--benchmark` fetches when no date range is given.
+pub const DEFAULT_BARS: u64 = 10_000;
+
+/// HTTP requests made by a client, with the bytes they received and the time they took,
+/// counted across every concurrent fetch.
+#[derive(Debug, Default)]
+pub struct TransferStats {
+    requests: AtomicU64,
+    bytes: AtomicU64,
+    latency_micros: AtomicU64,
+}
+
+impl TransferStats {
+    /// Count a completed request, `latency` from sending it to its body being read.
+    pub fn record(&self, bytes: usize, latency: Duration) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        self.bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+        self.latency_micros
+            .fetch_add(latency.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    pub fn requests(&self) -> u64 {
+        self.requests.load(Ordering::Relaxed)
+    }
+
+    /// Response body bytes received, as sent over the wire.
+    pub fn bytes(&self) -> u64 {
+        self.bytes.load(Ordering::Relaxed)
+    }
+
+    /// Mean time per request, zero before the first one completes.
+    pub fn average_latency(&self) -> Duration {
+        match self.requests() {
+            0 => Duration::ZERO,
+            requests => Duration::from_micros(self.latency_micros.load(Ordering::Relaxed) / requests),
+        }
+    }
+}
+
+/// Throughput of a `--benchmark` fetch.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BenchmarkReport {
+    pub wall_time: Duration,
+    pub bars: u64,
+    pub requests: u64,
+    pub bytes: u64,
+    pub average_latency: Duration,
+}
+
+impl BenchmarkReport {
+    pub fn new(wall_time: Duration, bars: u64, stats: &TransferStats) -> Self {
+        Self {
+            wall_time,
+            bars,
+            requests: stats.requests(),
+            bytes: stats.bytes(),
+            average_latency: stats.average_latency(),
+        }
+    }
+
+    /// `count` per second of wall time.
+    fn rate(&self, count: u64) -> f64 {
+        let secs = self.wall_time.as_secs_f64();
+        if secs > 0.0 {
+            count as f64 / secs
+        } else {
+            0.0
+        }
+    }
+}
+
+impl fmt::Display for BenchmarkReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Wall time:    {:.2} s", self.wall_time.as_secs_f64())?;
+        writeln!(f, "Bars:         {} ({:.1} bars/s)", self.bars, self.rate(self.bars))?;
+        writeln!(f, "Requests:     {} ({:.2} requests/s)", self.requests, self.rate(self.requests))?;
+        writeln!(f, "Avg latency:  {} ms per request", self.average_latency.as_millis())?;
+        write!(
+            f,
+            "Transferred:  {} ({}/s)",
+            size_estimate::format_bytes(self.bytes),
+            size_estimate::format_bytes(self.rate(self.bytes) as u64)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_benchmark_report() {
+        let stats = TransferStats::default();
+        assert_eq!(stats.average_latency(), Duration::ZERO);
+        stats.record(2048, Duration::from_millis(100));
+        stats.record(1024, Duration::from_millis(300));
+
+        let report = BenchmarkReport::new(Duration::from_secs(2), 2000, &stats);
+        assert_eq!((report.requests, report.bytes), (2, 3072));
+        assert_eq!(report.average_latency, Duration::from_millis(200));
+        assert_eq!(
+            report.to_string(),
+            "Wall time:    2.00 s\n\
+             Bars:         2000 (1000.0 bars/s)\n\
+             Requests:     2 (1.00 requests/s)\n\
+             Avg latency:  200 ms per request\n\
+             Transferred:  3.00 KiB (1.50 KiB/s)"
+        );
+    }
+}
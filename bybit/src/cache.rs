@@ -0,0 +1,140 @@
+use crate::{kline::Kline, BybitError};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    fs,
+    path::PathBuf,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Identity of a single kline request, used to address its cached response.
+#[derive(Debug, Clone, Serialize)]
+pub struct CacheKey<'a> {
+    pub base_url: &'a str,
+    pub endpoint: &'a str,
+    pub category: &'a str,
+    pub symbol: &'a str,
+    pub interval: &'a str,
+    pub start: u64,
+    pub end: u64,
+    pub limit: u32,
+}
+
+impl CacheKey<'_> {
+    /// Hex encoded SHA-256 digest of the key, used as the cache file name.
+    pub fn digest(&self) -> String {
+        let key = serde_json::to_vec(self).expect("CacheKey is always serialisable");
+        format!("{:x}", Sha256::digest(key))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheEntry {
+    /// Unix time in seconds the klines were fetched.
+    fetched_at: u64,
+    klines: Vec<Kline>,
+}
+
+/// Content-addressable on-disk cache of parsed kline responses.
+#[derive(Debug, Clone)]
+pub struct KlineCache {
+    dir: PathBuf,
+    ttl: Duration,
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|now| now.as_secs())
+        .unwrap_or_default()
+}
+
+impl KlineCache {
+    pub fn new(dir: PathBuf, ttl: Duration) -> Self {
+        Self { dir, ttl }
+    }
+
+    fn path(&self, key: &CacheKey<'_>) -> PathBuf {
+        self.dir.join(format!("{}.json", key.digest()))
+    }
+
+    /// Cached klines for the request, if present and younger than the TTL.
+    ///
+    /// Unreadable or corrupt entries are treated as a miss.
+    pub fn get(&self, key: &CacheKey<'_>) -> Option<Vec<Kline>> {
+        let contents = fs::read(self.path(key)).ok()?;
+        let entry = serde_json::from_slice::<CacheEntry>(&contents).ok()?;
+
+        let age = unix_now().saturating_sub(entry.fetched_at);
+        (age <= self.ttl.as_secs()).then_some(entry.klines)
+    }
+
+    /// Store the klines returned for a request.
+    ///
+    /// Writes to a temporary file first so concurrent readers never observe a partial entry.
+    pub fn put(&self, key: &CacheKey<'_>, klines: &[Kline]) -> Result<(), BybitError> {
+        fs::create_dir_all(&self.dir)?;
+
+        let entry = CacheEntry {
+            fetched_at: unix_now(),
+            klines: klines.to_vec(),
+        };
+        let path = self.path(key);
+        let temp_path = path.with_extension(format!("json.{}.tmp", std::process::id()));
+        fs::write(&temp_path, serde_json::to_vec(&entry)?)?;
+        fs::rename(&temp_path, &path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kline::test_kline;
+
+    fn key(start: u64) -> CacheKey<'static> {
+        CacheKey {
+            base_url: "https://api.bybit.com",
+            endpoint: "/v5/market/kline",
+            category: "linear",
+            symbol: "BTCUSDT",
+            interval: "15",
+            start,
+            end: start + 900_000,
+            limit: 1,
+        }
+    }
+
+    #[test]
+    fn test_kline_cache_round_trip_and_ttl() {
+        let dir = std::env::temp_dir().join(format!("bybit-kline-cache-test-{}", std::process::id()));
+        let kline = Kline {
+            open_price: 1.0,
+            high_price: 2.0,
+            low_price: 0.5,
+            ..test_kline(0, 1.5)
+        };
+
+        let cache = KlineCache::new(dir.clone(), Duration::from_secs(60));
+        assert!(cache.get(&key(0)).is_none());
+
+        cache.put(&key(0), std::slice::from_ref(&kline)).unwrap();
+        let cached = cache.get(&key(0)).unwrap();
+        assert_eq!(cached.len(), 1);
+        assert_eq!(cached[0].close_price, 1.5);
+
+        // Distinct requests are addressed separately
+        assert!(cache.get(&key(900_000)).is_none());
+
+        // Entries older than the TTL are a miss, with a zero TTL only same-second hits remain
+        let expired = KlineCache::new(dir.clone(), Duration::ZERO);
+        let entry = CacheEntry {
+            fetched_at: unix_now() - 10,
+            klines: vec![kline],
+        };
+        fs::write(expired.path(&key(0)), serde_json::to_vec(&entry).unwrap()).unwrap();
+        assert!(expired.get(&key(0)).is_none());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,178 @@
+use crate::{
+    category::Category, kline::Kline, BybitClient, BybitError, FetchProgress, KlineQuery,
+};
+
+/// Outcome of fetching a single category for `--category all`.
+#[derive(Debug)]
+pub struct CategoryReport {
+    pub category: Category,
+    /// Number of klines received, or the error the category failed with.
+    pub result: Result<usize, BybitError>,
+}
+
+impl BybitClient {
+    /// Fetch `query` once per category, isolating each category's errors so one endpoint being
+    /// down (or the symbol not being listed there) doesn't discard the others' klines.
+    ///
+    /// Returns the klines of every category that succeeded, alongside a report per category in
+    /// the order requested. `progress` receives the category each update belongs to.
+    pub async fn get_kline_categories(
+        &self,
+        query: &KlineQuery,
+        categories: &[Category],
+        mut progress: impl FnMut(Category, FetchProgress),
+    ) -> (Vec<Kline>, Vec<CategoryReport>) {
+        let mut klines = Vec::new();
+        let mut reports = Vec::with_capacity(categories.len());
+
+        for &category in categories {
+            let query = KlineQuery {
+                category,
+                ..query.clone()
+            };
+            let result = self
+                .get_kline(&query, |update| progress(category, update))
+                .await
+                .map(|fetched| {
+                    let count = fetched.len();
+                    klines.extend(fetched);
+                    count
+                });
+
+            reports.push(CategoryReport { category, result });
+        }
+
+        (klines, reports)
+    }
+
+    /// Try `categories` in order with a single kline request over the query's window, returning
+    /// the first one with data, for `--category-fallback`. A category whose request fails (eg/
+    /// the symbol isn't listed there) is skipped like an empty one.
+    ///
+    /// Also returns a report per category tried, `Ok(0)` for an empty window.
+    pub async fn first_category_with_data(
+        &self,
+        query: &KlineQuery,
+        categories: &[Category],
+    ) -> (Option<Category>, Vec<CategoryReport>) {
+        let mut reports = Vec::with_capacity(categories.len());
+        for &category in categories {
+            let query = KlineQuery {
+                category,
+                ..query.clone()
+            };
+            let result = self
+                .get_kline_single(&query, query.start, query.end.saturating_sub(1), 1)
+                .await
+                .map(|klines| klines.len());
+            let found = matches!(result, Ok(count) if count > 0);
+            reports.push(CategoryReport { category, result });
+            if found {
+                return (Some(category), reports);
+            }
+        }
+        (None, reports)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        interval::Interval,
+        kline::KlineType,
+        mock_server::{self, MockResponse},
+    };
+
+    /// Minimal Bybit kline API, failing every request for the inverse category.
+    async fn mock_server() -> String {
+        mock_server::serve(
+            |request| match mock_server::query_param(request, "category") {
+                Some("inverse") => MockResponse::status("503 Service Unavailable"),
+                Some(category) => MockResponse::klines(category, &[1704067200000]),
+                None => MockResponse::status("400 Bad Request"),
+            },
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_failed_category_is_isolated() {
+        let client = BybitClient::new(false, 1)
+            .unwrap()
+            .with_base_url(mock_server().await)
+            .with_preflight(false);
+        let query = KlineQuery {
+            symbol: "BTCUSDT".to_string(),
+            category: Category::Linear,
+            interval: Interval::Min15,
+            kline_type: KlineType::Trade,
+            start: 1704067200000,
+            end: 1704067200000 + Interval::Min15.duration_ms(),
+            max_records: 1,
+            limit_per_request: 1,
+        };
+
+        let (klines, reports) = client
+            .get_kline_categories(
+                &query,
+                &[Category::Spot, Category::Inverse, Category::Linear],
+                |_, _| {},
+            )
+            .await;
+
+        let categories = klines
+            .iter()
+            .map(|kline| kline.category.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(categories, vec!["spot", "linear"]);
+
+        assert_eq!(reports.len(), 3);
+        assert_eq!(reports[0].result.as_ref().unwrap(), &1);
+        assert!(matches!(
+            reports[1].result,
+            Err(BybitError::RequestError(_))
+        ));
+        assert_eq!(reports[2].category, Category::Linear);
+        assert_eq!(reports[2].result.as_ref().unwrap(), &1);
+    }
+
+    #[tokio::test]
+    async fn test_first_category_with_data() {
+        // Not listed as a linear perpetual, only as an inverse one
+        let base_url = mock_server::serve(|request| match mock_server::query_param(request, "category") {
+            Some("linear") => MockResponse::klines("linear", &[]),
+            Some(category) => MockResponse::klines(category, &[1704067200000]),
+            None => MockResponse::status("400 Bad Request"),
+        })
+        .await;
+        let client = BybitClient::new(false, 1)
+            .unwrap()
+            .with_base_url(base_url)
+            .with_preflight(false);
+        let query = KlineQuery {
+            symbol: "BTCUSD".to_string(),
+            category: Category::Linear,
+            interval: Interval::Min15,
+            kline_type: KlineType::Trade,
+            start: 1704067200000,
+            end: 1704067200000 + Interval::Min15.duration_ms(),
+            max_records: 1,
+            limit_per_request: 1,
+        };
+
+        let (category, reports) = client
+            .first_category_with_data(
+                &query,
+                &[Category::Linear, Category::Inverse, Category::Spot],
+            )
+            .await;
+        assert_eq!(category, Some(Category::Inverse));
+        // Spot is never tried
+        let tried = reports
+            .iter()
+            .map(|report| (report.category, *report.result.as_ref().unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(tried, vec![(Category::Linear, 0), (Category::Inverse, 1)]);
+    }
+}
@@ -0,0 +1,176 @@
+use crate::BybitError;
+use clap::{builder::PossibleValue, ValueEnum};
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+
+/// Coin a kline volume field is denominated in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VolumeUnit {
+    /// Base coin, eg/ BTC for BTCUSDT.
+    Base,
+    /// Quote coin, eg/ USDT for BTCUSDT. Inverse contracts are worth 1 USD each, so their
+    /// contract count is quote volume.
+    Quote,
+}
+
+/// Units of the raw `volume` and `turnover` fields Bybit reports for a category's klines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VolumeUnits {
+    pub volume: VolumeUnit,
+    pub turnover: VolumeUnit,
+}
+
+impl VolumeUnits {
+    /// Map raw `(volume, turnover)` onto `(base_volume, quote_volume)`.
+    pub fn normalize(&self, volume: f64, turnover: f64) -> (f64, f64) {
+        match self.volume {
+            VolumeUnit::Base => (volume, turnover),
+            VolumeUnit::Quote => (turnover, volume),
+        }
+    }
+}
+
+/// Bybit product category a symbol is listed in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Category {
+    Spot,
+    Linear,
+    Inverse,
+    Option,
+}
+
+impl Category {
+    /// Categories fetched for `--category all`, in order. Options are left out, an option
+    /// symbol names a single contract which isn't listed in any other category.
+    pub fn all() -> &'static [Category] {
+        &[Category::Spot, Category::Linear, Category::Inverse]
+    }
+
+    /// Every category, including options.
+    pub fn variants() -> &'static [Category] {
+        &[
+            Category::Spot,
+            Category::Linear,
+            Category::Inverse,
+            Category::Option,
+        ]
+    }
+
+    /// Category identifier as expected by the Bybit API (e.g. "linear").
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Category::Spot => "spot",
+            Category::Linear => "linear",
+            Category::Inverse => "inverse",
+            Category::Option => "option",
+        }
+    }
+
+    /// Units of the raw kline volume fields of this category:
+    ///
+    /// | category | `volume`                  | `turnover`  |
+    /// |----------|---------------------------|-------------|
+    /// | spot     | base coin                 | quote coin  |
+    /// | linear   | base coin                 | quote coin  |
+    /// | inverse  | contracts (USD, quote)    | base coin   |
+    /// | option   | contracts (base coin)     | quote coin  |
+    pub fn volume_units(&self) -> VolumeUnits {
+        match self {
+            Category::Spot | Category::Linear | Category::Option => VolumeUnits {
+                volume: VolumeUnit::Base,
+                turnover: VolumeUnit::Quote,
+            },
+            Category::Inverse => VolumeUnits {
+                volume: VolumeUnit::Quote,
+                turnover: VolumeUnit::Base,
+            },
+        }
+    }
+
+    /// Human readable description of the category.
+    pub fn description(&self) -> &'static str {
+        match self {
+            Category::Spot => "spot pairs",
+            Category::Linear => "USDT and USDC settled perpetuals and futures",
+            Category::Inverse => "coin settled perpetuals and futures",
+            Category::Option => "USDC and USDT settled options",
+        }
+    }
+}
+
+/// Whether the raw kline volume fields of `categories` are in different units, so merging them
+/// without normalising to base and quote volume would mix units.
+pub fn mixes_volume_units(categories: &[Category]) -> bool {
+    categories
+        .windows(2)
+        .any(|pair| pair[0].volume_units() != pair[1].volume_units())
+}
+
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.as_str())
+    }
+}
+
+impl FromStr for Category {
+    type Err = BybitError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Category::variants()
+            .iter()
+            .copied()
+            .find(|category| category.as_str() == s)
+            .ok_or_else(|| {
+                BybitError::ArgumentError(format!(
+                    "Unsupported category: {} (expected spot, linear, inverse or option)",
+                    s
+                ))
+            })
+    }
+}
+
+impl ValueEnum for Category {
+    fn value_variants<'a>() -> &'a [Self] {
+        Category::variants()
+    }
+
+    fn to_possible_value(&self) -> Option<PossibleValue> {
+        Some(PossibleValue::new(self.as_str()).help(self.description()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_category_round_trip() {
+        for &category in Category::variants() {
+            assert_eq!(category.as_str().parse::<Category>().unwrap(), category);
+            assert_eq!(
+                serde_json::to_string(&category).unwrap(),
+                format!("\"{}\"", category)
+            );
+            assert_eq!(
+                <Category as ValueEnum>::from_str(category.as_str(), false).unwrap(),
+                category
+            );
+        }
+        assert!("futures".parse::<Category>().is_err());
+        assert!(<Category as ValueEnum>::from_str("all", false).is_err());
+    }
+
+    #[test]
+    fn test_volume_units_per_category() {
+        // Raw volume 8 and turnover 820, normalised to (base, quote)
+        assert_eq!(Category::Spot.volume_units().normalize(8.0, 820.0), (8.0, 820.0));
+        assert_eq!(Category::Linear.volume_units().normalize(8.0, 820.0), (8.0, 820.0));
+        assert_eq!(Category::Inverse.volume_units().normalize(820.0, 8.0), (8.0, 820.0));
+
+        assert_eq!(Category::Spot.volume_units(), Category::Linear.volume_units());
+        assert_ne!(Category::Linear.volume_units(), Category::Inverse.volume_units());
+        assert!(!mixes_volume_units(&[Category::Spot, Category::Linear]));
+        assert!(mixes_volume_units(Category::all()));
+    }
+}
@@ -0,0 +1,64 @@
+use crate::kline::Kline;
+use sha2::{Digest, Sha256};
+
+/// Decimal places prices and volumes are rounded to before hashing, so float formatting noise
+/// doesn't change the checksum.
+const PRECISION: usize = 8;
+
+/// Canonical form of a single kline, one line of the hashed input.
+pub(crate) fn canonical_row(kline: &Kline) -> String {
+    format!(
+        "{},{},{},{:.p$},{:.p$},{:.p$},{:.p$},{:.p$},{:.p$},{}\n",
+        kline.start_time,
+        kline.category,
+        kline.kline_type,
+        kline.open_price,
+        kline.high_price,
+        kline.low_price,
+        kline.close_price,
+        kline.volume,
+        kline.turnover,
+        kline
+            .filled
+            .map(|fill| fill.to_string())
+            .unwrap_or_default(),
+        p = PRECISION
+    )
+}
+
+/// Hex encoded SHA-256 digest of a kline dataset, stable across runs: klines are hashed in
+/// canonical (time, series) order with prices and volumes rounded to a fixed precision.
+///
+/// Identical data always hashes the same regardless of fetch order, so comparing against a
+/// stored checksum detects silent revisions of historical bars.
+pub fn checksum(klines: &[Kline]) -> String {
+    let mut sorted = klines.iter().collect::<Vec<_>>();
+    sorted.sort_by(|a, b| a.dedup_key().cmp(&b.dedup_key()));
+
+    let mut hasher = Sha256::new();
+    for kline in sorted {
+        hasher.update(canonical_row(kline));
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kline::test_kline;
+
+    #[test]
+    fn test_checksum_is_canonical() {
+        let klines = vec![test_kline(0, 1.5), test_kline(60_000, 1.75)];
+        let expected = checksum(&klines);
+        assert_eq!(expected.len(), 64);
+
+        // Order and sub-precision float noise don't matter
+        let reordered = vec![test_kline(60_000, 1.75 + 1e-12), test_kline(0, 1.5)];
+        assert_eq!(checksum(&reordered), expected);
+
+        // A revised bar does
+        let revised = vec![test_kline(0, 1.5), test_kline(60_000, 1.76)];
+        assert_ne!(checksum(&revised), expected);
+    }
+}
@@ -0,0 +1,82 @@
+use std::time::Duration;
+
+/// Most klines Bybit serves per request.
+pub const MAX_CHUNK_LIMIT: u32 = 1000;
+
+/// Fewest klines requested per chunk by [`ChunkController`].
+pub const MIN_CHUNK_LIMIT: u32 = 50;
+
+/// Per-request latency [`ChunkController`] aims to stay under.
+pub const DEFAULT_TARGET_LATENCY: Duration = Duration::from_secs(2);
+
+/// Klines added to the limit after each chunk which met the latency target.
+const ADDITIVE_STEP: u32 = 50;
+
+/// AIMD controller of the klines requested per chunk for `--adaptive-chunking`.
+///
+/// Starts at the configured limit, grows it additively while requests complete within the
+/// target latency, and halves it whenever a request is slow or needed retries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkController {
+    limit: u32,
+    target_latency: Duration,
+}
+
+impl ChunkController {
+    pub fn new(initial_limit: u32, target_latency: Duration) -> Self {
+        Self {
+            limit: initial_limit.clamp(MIN_CHUNK_LIMIT.min(initial_limit), MAX_CHUNK_LIMIT),
+            target_latency,
+        }
+    }
+
+    /// Klines to request for the next chunk.
+    pub fn limit(&self) -> u32 {
+        self.limit
+    }
+
+    /// Adjust the limit after a chunk completed in `latency`, having needed `retries` retries.
+    pub fn observe(&mut self, latency: Duration, retries: u32) {
+        if retries > 0 || latency > self.target_latency {
+            self.limit = (self.limit / 2).max(MIN_CHUNK_LIMIT.min(self.limit));
+        } else {
+            self.limit = (self.limit + ADDITIVE_STEP).min(MAX_CHUNK_LIMIT);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunk_controller_aimd() {
+        let fast = Duration::from_millis(200);
+        let slow = Duration::from_secs(5);
+        let mut controller = ChunkController::new(400, DEFAULT_TARGET_LATENCY);
+        assert_eq!(controller.limit(), 400);
+
+        // Additive increase up to Bybit's limit
+        controller.observe(fast, 0);
+        assert_eq!(controller.limit(), 450);
+        for _ in 0..20 {
+            controller.observe(fast, 0);
+        }
+        assert_eq!(controller.limit(), MAX_CHUNK_LIMIT);
+
+        // Multiplicative decrease on slow or retried requests, down to the floor
+        controller.observe(slow, 0);
+        assert_eq!(controller.limit(), 500);
+        controller.observe(fast, 1);
+        assert_eq!(controller.limit(), 250);
+        for _ in 0..10 {
+            controller.observe(slow, 0);
+        }
+        assert_eq!(controller.limit(), MIN_CHUNK_LIMIT);
+
+        // A configured limit below the floor is kept
+        let mut small = ChunkController::new(10, DEFAULT_TARGET_LATENCY);
+        small.observe(slow, 0);
+        assert_eq!(small.limit(), 10);
+    }
+}
@@ -0,0 +1,226 @@
+use crate::{kline::Kline, BybitClient, BybitError, KlineQuery};
+use chrono::Utc;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::{collections::BTreeMap, fmt};
+
+/// Default `--verify-samples`: windows re-requested by `--verify-continuity-against-exchange`.
+pub const DEFAULT_SAMPLES: usize = 5;
+
+/// Bars in each re-requested window.
+const WINDOW_BARS: u64 = 50;
+
+/// Relative difference within which a re-fetched price or volume matches the stored one, so
+/// only float formatting noise is tolerated.
+pub const TOLERANCE: f64 = 1e-9;
+
+/// Difference between a stored bar and what Bybit returns for it now.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Discrepancy {
+    /// Bybit returned a bar the stored series lacks, eg/ a page dropped by pagination.
+    Missing { start_time: u64 },
+    /// The stored series has a bar Bybit didn't return.
+    Unexpected { start_time: u64 },
+    /// The stored series holds the bar more than once.
+    Duplicate { start_time: u64, count: usize },
+    /// A stored price or volume differs from Bybit's by more than [`TOLERANCE`].
+    Mismatch {
+        start_time: u64,
+        field: &'static str,
+        stored: f64,
+        fetched: f64,
+    },
+}
+
+impl fmt::Display for Discrepancy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Discrepancy::Missing { start_time } => write!(f, "{}: missing, Bybit returned it", start_time),
+            Discrepancy::Unexpected { start_time } => write!(f, "{}: stored, but Bybit didn't return it", start_time),
+            Discrepancy::Duplicate { start_time, count } => write!(f, "{}: stored {} times", start_time, count),
+            Discrepancy::Mismatch { start_time, field, stored, fetched } => {
+                write!(f, "{}: {} stored {}, Bybit returned {}", start_time, field, stored, fetched)
+            }
+        }
+    }
+}
+
+/// Outcome of re-requesting sampled windows of a fetched series.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ContinuityReport {
+    pub windows: usize,
+    /// Settled bars Bybit returned across the windows.
+    pub bars_checked: usize,
+    pub discrepancies: Vec<Discrepancy>,
+}
+
+impl BybitClient {
+    /// Re-request `samples` random windows of up to 50 bars within the query's range and compare
+    /// them with `klines`, the series already fetched for `query`, to catch bars a large pull
+    /// silently dropped, duplicated or garbled.
+    ///
+    /// Windows are aligned to the interval from the range start and a range of 50 bars or fewer
+    /// is checked whole. Bars which haven't closed yet are skipped, Bybit still revises them.
+    /// `seed` makes the windows reproducible.
+    pub async fn verify_continuity(
+        &self,
+        query: &KlineQuery,
+        klines: &[Kline],
+        samples: usize,
+        seed: Option<u64>,
+    ) -> Result<ContinuityReport, BybitError> {
+        let mut rng = match seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_os_rng(),
+        };
+        let now = Utc::now().timestamp_millis() as u64;
+        let step = query.interval.duration_ms();
+        let total_bars = query.end.saturating_sub(query.start).div_ceil(step);
+        let windows = match total_bars {
+            0 => 0,
+            bars if bars <= WINDOW_BARS => 1,
+            _ => samples,
+        };
+
+        let mut stored = BTreeMap::<u64, Vec<&Kline>>::new();
+        for kline in klines {
+            stored.entry(kline.start_time).or_default().push(kline);
+        }
+
+        let mut report = ContinuityReport {
+            windows,
+            ..Default::default()
+        };
+        for _ in 0..windows {
+            let offset = rng.random_range(0..=total_bars.saturating_sub(WINDOW_BARS));
+            let start = query.start + offset * step;
+            let end = (start + WINDOW_BARS * step).min(query.end);
+            let fetched = self
+                .get_kline_single(query, start, end - 1, WINDOW_BARS as u32)
+                .await?;
+
+            let settled = |start_time: u64| query.interval.next_start(start_time) <= now;
+            let fetched = fetched
+                .into_iter()
+                .filter(|kline| (start..end).contains(&kline.start_time) && settled(kline.start_time))
+                .map(|kline| (kline.start_time, kline))
+                .collect::<BTreeMap<_, _>>();
+            report.bars_checked += fetched.len();
+
+            for (&start_time, kline) in &fetched {
+                match stored.get(&start_time).map(Vec::as_slice) {
+                    None | Some([]) => report.discrepancies.push(Discrepancy::Missing { start_time }),
+                    Some([stored]) => report.discrepancies.extend(mismatch(stored, kline)),
+                    Some(duplicates) => report.discrepancies.push(Discrepancy::Duplicate {
+                        start_time,
+                        count: duplicates.len(),
+                    }),
+                }
+            }
+            for &start_time in stored.range(start..end).map(|(start_time, _)| start_time) {
+                if settled(start_time) && !fetched.contains_key(&start_time) {
+                    report.discrepancies.push(Discrepancy::Unexpected { start_time });
+                }
+            }
+        }
+        report.discrepancies.sort_by_key(|discrepancy| match discrepancy {
+            Discrepancy::Missing { start_time }
+            | Discrepancy::Unexpected { start_time }
+            | Discrepancy::Duplicate { start_time, .. }
+            | Discrepancy::Mismatch { start_time, .. } => *start_time,
+        });
+        report.discrepancies.dedup();
+        Ok(report)
+    }
+}
+
+/// First field of `stored` differing from `fetched` beyond [`TOLERANCE`].
+fn mismatch(stored: &Kline, fetched: &Kline) -> Option<Discrepancy> {
+    [
+        ("open", stored.open_price, fetched.open_price),
+        ("high", stored.high_price, fetched.high_price),
+        ("low", stored.low_price, fetched.low_price),
+        ("close", stored.close_price, fetched.close_price),
+        ("volume", stored.volume, fetched.volume),
+        ("turnover", stored.turnover, fetched.turnover),
+    ]
+    .into_iter()
+    .find(|(_, stored, fetched)| (stored - fetched).abs() > TOLERANCE * stored.abs().max(fetched.abs()))
+    .map(|(field, stored_value, fetched)| Discrepancy::Mismatch {
+        start_time: stored.start_time,
+        field,
+        stored: stored_value,
+        fetched,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kline::test_kline;
+    use crate::{
+        category::Category,
+        interval::Interval,
+        kline::KlineType,
+        mock_server::{self, MockResponse},
+    };
+
+    #[tokio::test]
+    async fn test_verify_continuity_reports_dropped_and_changed_bars() {
+        let start = 1704067200000;
+        let step = Interval::Min1.duration_ms();
+
+        // Every minute bar from the requested start to end, newest first
+        let base_url = mock_server::serve(move |request| {
+            let param = |name| {
+                mock_server::query_param(request, name)
+                    .and_then(|value| value.parse::<u64>().ok())
+                    .unwrap()
+            };
+            let bars = (param("start").div_ceil(step)..=param("end") / step)
+                .rev()
+                .map(|bar| bar * step)
+                .take(param("limit") as usize)
+                .collect::<Vec<_>>();
+            MockResponse::klines("linear", &bars)
+        })
+        .await;
+        let client = BybitClient::new(false, 1)
+            .unwrap()
+            .with_base_url(base_url)
+            .with_preflight(false);
+        let query = KlineQuery {
+            symbol: "BTCUSDT".to_string(),
+            category: Category::Linear,
+            interval: Interval::Min1,
+            kline_type: KlineType::Trade,
+            start,
+            end: start + 40 * step,
+            max_records: 0,
+            limit_per_request: 1000,
+        };
+
+        let mut klines = (0..40)
+            .map(|i| Kline {
+                open_price: 1.0,
+                high_price: 2.0,
+                low_price: 0.5,
+                ..test_kline(start + i * step, 1.5)
+            })
+            .collect::<Vec<_>>();
+        let matching = client.verify_continuity(&query, &klines, DEFAULT_SAMPLES, Some(7)).await.unwrap();
+        assert_eq!((matching.windows, matching.bars_checked), (1, 40));
+        assert!(matching.discrepancies.is_empty());
+
+        // A bar lost mid-range and another with a different close
+        klines.remove(20);
+        klines[5].close_price = 1.6;
+        let report = client.verify_continuity(&query, &klines, DEFAULT_SAMPLES, Some(7)).await.unwrap();
+        assert_eq!(
+            report.discrepancies,
+            vec![
+                Discrepancy::Mismatch { start_time: start + 5 * step, field: "close", stored: 1.6, fetched: 1.5 },
+                Discrepancy::Missing { start_time: start + 20 * step },
+            ]
+        );
+    }
+}
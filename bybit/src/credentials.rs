@@ -0,0 +1,171 @@
+use crate::BybitError;
+use std::{
+    fmt, fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+/// Bybit API key pair for signed requests.
+///
+/// `Debug` masks the key and omits the secret, so credentials can't leak into logs.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Credentials {
+    pub api_key: String,
+    pub api_secret: String,
+}
+
+impl Credentials {
+    /// The API key with all but its first 4 characters masked, for reporting which key is in use.
+    pub fn masked_key(&self) -> String {
+        let shown: String = self.api_key.chars().take(4).collect();
+        format!("{}****", shown)
+    }
+}
+
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Credentials")
+            .field("api_key", &self.masked_key())
+            .field("api_secret", &"<redacted>")
+            .finish()
+    }
+}
+
+/// `~/.config/bybit-fetch/credentials.toml`, `None` when `HOME` isn't set.
+pub fn default_path() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .filter(|home| !home.is_empty())
+        .map(|home| PathBuf::from(home).join(".config/bybit-fetch/credentials.toml"))
+}
+
+/// `api_key` and `api_secret` from a credentials file, either of which may be absent.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct CredentialsFile {
+    api_key: Option<String>,
+    api_secret: Option<String>,
+}
+
+/// Parse the flat `key = "value"` subset of TOML a credentials file needs. Comments, blank
+/// lines, table headers and unknown keys are ignored; values are basic or literal strings.
+/// Errors name the line but never echo its contents, since it may hold the secret.
+fn parse_file(contents: &str) -> Result<CredentialsFile, String> {
+    let mut file = CredentialsFile::default();
+    for (number, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with('[') {
+            continue;
+        }
+        let invalid = || format!("line {}: expected key = \"value\"", number + 1);
+        let (key, value) = line.split_once('=').ok_or_else(invalid)?;
+        let value = value.trim();
+        let value = match value.chars().next() {
+            Some(quote @ ('"' | '\'')) => value[1..]
+                .split_once(quote)
+                .filter(|(_, rest)| rest.trim().is_empty() || rest.trim().starts_with('#'))
+                .map(|(value, _)| value.to_string())
+                .ok_or_else(invalid)?,
+            _ => return Err(invalid()),
+        };
+        match key.trim() {
+            "api_key" => file.api_key = Some(value),
+            "api_secret" => file.api_secret = Some(value),
+            _ => {}
+        }
+    }
+    Ok(file)
+}
+
+/// Resolve credentials, each of the key and secret taken from the first source setting it:
+/// the CLI flags, then the `BYBIT_API_KEY`/`BYBIT_API_SECRET` environment variables (which clap
+/// merges into the flags), then the credentials file.
+///
+/// `path` is `--credentials-file`, which must exist; otherwise [`default_path`] is read if
+/// present. Returns `None` when no source sets either value, and an error when only one is set.
+pub fn resolve(
+    api_key: Option<String>,
+    api_secret: Option<String>,
+    path: Option<&Path>,
+) -> Result<Option<Credentials>, BybitError> {
+    let file = if api_key.is_some() && api_secret.is_some() {
+        CredentialsFile::default()
+    } else {
+        match path {
+            Some(path) => read_file(path, true)?,
+            None => match default_path() {
+                Some(path) => read_file(&path, false)?,
+                None => CredentialsFile::default(),
+            },
+        }
+    };
+
+    match (api_key.or(file.api_key), api_secret.or(file.api_secret)) {
+        (Some(api_key), Some(api_secret)) => Ok(Some(Credentials { api_key, api_secret })),
+        (None, None) => Ok(None),
+        (Some(_), None) => Err(BybitError::ArgumentError(
+            "API key set without an API secret".to_string(),
+        )),
+        (None, Some(_)) => Err(BybitError::ArgumentError(
+            "API secret set without an API key".to_string(),
+        )),
+    }
+}
+
+/// Read a credentials file, treating a missing file as empty unless `required`.
+fn read_file(path: &Path, required: bool) -> Result<CredentialsFile, BybitError> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == ErrorKind::NotFound && !required => {
+            return Ok(CredentialsFile::default())
+        }
+        Err(err) => {
+            return Err(BybitError::ArgumentError(format!(
+                "Cannot read credentials file {}: {}",
+                path.display(),
+                err
+            )))
+        }
+    };
+    parse_file(&contents).map_err(|err| {
+        BybitError::ArgumentError(format!("Invalid credentials file {}: {}", path.display(), err))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_credentials_file() {
+        let file = parse_file(
+            "# bybit-fetch\n[default]\napi_key = \"KEY123\"  # read only\napi_secret='s3cr=t'\nregion = \"eu\"\n",
+        )
+        .unwrap();
+        assert_eq!(file.api_key.as_deref(), Some("KEY123"));
+        assert_eq!(file.api_secret.as_deref(), Some("s3cr=t"));
+
+        let err = parse_file("api_key = \"KEY\"\napi_secret = hunter2\n").unwrap_err();
+        assert_eq!(err, "line 2: expected key = \"value\"");
+    }
+
+    #[test]
+    fn test_resolve_prefers_flags_over_file() {
+        let path = std::env::temp_dir()
+            .join(format!("bybit-kline-credentials-{}.toml", std::process::id()));
+        fs::write(&path, "api_key = \"FILEKEY\"\napi_secret = \"filesecret\"\n").unwrap();
+
+        let credentials = resolve(Some("FLAGKEY".to_string()), None, Some(&path))
+            .unwrap()
+            .unwrap();
+        assert_eq!(credentials.api_key, "FLAGKEY");
+        assert_eq!(credentials.api_secret, "filesecret");
+
+        // The secret never appears in debug output
+        let debug = format!("{:?}", credentials);
+        assert!(!debug.contains("filesecret") && !debug.contains("FLAGKEY"));
+        assert!(debug.contains("FLAG****"));
+
+        assert!(resolve(None, None, Some(&path.with_extension("missing"))).is_err());
+        fs::remove_file(&path).unwrap();
+        assert!(resolve(Some("KEY".to_string()), None, Some(&path)).is_err());
+    }
+}
@@ -0,0 +1,152 @@
+use crate::{category::Category, output::Delimited, BybitClient, BybitError};
+use chrono::DateTime;
+use serde::{Deserialize, Serialize};
+use std::io::{self, Write};
+
+/// Most entries Bybit returns per `/v5/market/delivery-price` page.
+const PAGE_LIMIT: &str = "200";
+
+/// Entry of `/v5/market/delivery-price`, prices and times as strings.
+#[derive(Debug, Deserialize)]
+struct RawDeliveryPrice {
+    #[serde(rename = "deliveryPrice")]
+    delivery_price: String,
+    #[serde(rename = "deliveryTime")]
+    delivery_time: String,
+}
+
+/// Price an expiring futures or options contract settled at.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct DeliveryPrice {
+    /// Unix millis.
+    pub delivery_time: u64,
+    pub delivery_price: f64,
+}
+
+impl TryFrom<RawDeliveryPrice> for DeliveryPrice {
+    type Error = BybitError;
+
+    fn try_from(raw: RawDeliveryPrice) -> Result<Self, Self::Error> {
+        let invalid = || BybitError::ApiError {
+            msg: format!(
+                "Invalid delivery price: {} at {}",
+                raw.delivery_price, raw.delivery_time
+            ),
+        };
+        Ok(Self {
+            delivery_time: raw.delivery_time.parse().map_err(|_| invalid())?,
+            delivery_price: raw.delivery_price.parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+impl BybitClient {
+    /// Delivery price history of an expiring contract, oldest first, paging through every
+    /// cursor of `/v5/market/delivery-price`. Only linear and inverse futures and options
+    /// deliver, spot is rejected.
+    pub async fn get_delivery_price(
+        &self,
+        symbol: &str,
+        category: Category,
+    ) -> Result<Vec<DeliveryPrice>, BybitError> {
+        if category == Category::Spot {
+            return Err(BybitError::ArgumentError(
+                "Delivery prices are only published for linear, inverse and option contracts"
+                    .to_string(),
+            ));
+        }
+        let mut prices = self
+            .get_all_pages::<RawDeliveryPrice>(
+                "/v5/market/delivery-price",
+                &[
+                    ("category", category.as_str()),
+                    ("symbol", symbol),
+                    ("limit", PAGE_LIMIT),
+                ],
+            )
+            .await?
+            .into_iter()
+            .map(DeliveryPrice::try_from)
+            .collect::<Result<Vec<_>, _>>()?;
+        prices.sort_by_key(|price| price.delivery_time);
+        Ok(prices)
+    }
+}
+
+/// Write delivery prices as a table, or as csv or tsv with `delivery_time` in unix millis.
+pub fn write_delivery_prices<W: Write>(
+    mut writer: W,
+    symbol: &str,
+    prices: &[DeliveryPrice],
+    format: Option<Delimited>,
+) -> io::Result<()> {
+    match format {
+        Some(format) => {
+            writeln!(writer, "{}", format.format_row(&["delivery_time", "delivery_price"]))?;
+            for price in prices {
+                let fields = [price.delivery_time.to_string(), price.delivery_price.to_string()];
+                writeln!(writer, "{}", format.format_row(&fields))?;
+            }
+        }
+        None => {
+            writeln!(writer, "\nDelivery prices of {}:\n", symbol)?;
+            writeln!(writer, "{:<24} | {:>16}", "Delivery Time (UTC)", "Delivery Price")?;
+            writeln!(writer, "{:-<24}-+-{:->16}", "", "")?;
+            for price in prices {
+                let time = DateTime::from_timestamp_millis(price.delivery_time as i64)
+                    .map(|time| time.format("%Y-%m-%d %H:%M:%S").to_string())
+                    .unwrap_or_else(|| price.delivery_time.to_string());
+                writeln!(writer, "{:<24} | {:>16}", time, price.delivery_price)?;
+            }
+            writeln!(writer, "\nTotal records: {}", prices.len())?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_server::{self, MockResponse};
+
+    #[tokio::test]
+    async fn test_get_delivery_price_follows_cursor() {
+        let base_url = mock_server::serve(|request| {
+            let entry = |time: u64, price: &str| {
+                format!(r#"{{"symbol":"BTCUSDT-27DEC24","deliveryPrice":"{}","deliveryTime":"{}"}}"#, price, time)
+            };
+            // Newest first, like Bybit, across two pages
+            match mock_server::query_param(request, "cursor") {
+                None => MockResponse::ok(&format!(
+                    r#"{{"category":"linear","list":[{},{}],"nextPageCursor":"page2"}}"#,
+                    entry(3_000, "97000.5"),
+                    entry(2_000, "96000")
+                )),
+                Some("page2") => MockResponse::ok(&format!(
+                    r#"{{"category":"linear","list":[{}],"nextPageCursor":""}}"#,
+                    entry(1_000, "95000")
+                )),
+                Some(_) => MockResponse::status("400 Bad Request"),
+            }
+        })
+        .await;
+        let client = BybitClient::new(false, 1)
+            .unwrap()
+            .with_base_url(base_url)
+            .with_preflight(false);
+
+        let prices = client
+            .get_delivery_price("BTCUSDT-27DEC24", Category::Linear)
+            .await
+            .unwrap();
+        let times = prices.iter().map(|price| price.delivery_time).collect::<Vec<_>>();
+        assert_eq!(times, vec![1_000, 2_000, 3_000]);
+        assert_eq!(prices[2].delivery_price, 97000.5);
+
+        let mut csv = Vec::new();
+        write_delivery_prices(&mut csv, "BTCUSDT-27DEC24", &prices[..1], Some(Delimited::Csv)).unwrap();
+        assert_eq!(String::from_utf8(csv).unwrap(), "delivery_time,delivery_price\n1000,95000\n");
+
+        assert!(client.get_delivery_price("BTCUSDT", Category::Spot).await.is_err());
+    }
+}
@@ -0,0 +1,271 @@
+use crate::{
+    category::Category,
+    interval::Interval,
+    kline::{self, Kline, KlineType},
+};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt};
+
+/// Run of consecutive bars missing from a kline series.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Gap {
+    /// Start time of the first missing bar.
+    pub start: u64,
+    /// Start time of the bar that ends the gap (exclusive).
+    pub end: u64,
+}
+
+impl Gap {
+    /// Number of missing bars, following calendar months for monthly klines.
+    pub fn bars(&self, interval: Interval) -> u64 {
+        let mut bars = 0;
+        let mut start_time = self.start;
+        while start_time < self.end {
+            bars += 1;
+            start_time = interval.next_start(start_time);
+        }
+        bars
+    }
+}
+
+/// Detect missing bars between consecutive klines of a single, time ordered series.
+pub fn detect_gaps(klines: &[Kline], interval: Interval) -> Vec<Gap> {
+    klines
+        .windows(2)
+        .filter_map(|pair| {
+            let expected = interval.next_start(pair[0].start_time);
+            (pair[1].start_time > expected).then_some(Gap {
+                start: expected,
+                end: pair[1].start_time,
+            })
+        })
+        .collect()
+}
+
+/// Split a single, time ordered series into its contiguous segments, breaking at every gap
+/// found by [`detect_gaps`].
+pub fn split_on_gaps(klines: &[Kline], interval: Interval) -> Vec<&[Kline]> {
+    klines
+        .chunk_by(|a, b| b.start_time <= interval.next_start(a.start_time))
+        .collect()
+}
+
+/// Line emitted ahead of each segment of ndjson output split with `--split-on-gaps`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct SegmentMarker {
+    pub segment: usize,
+    /// Start times of the segment's first and last bars, in unix millis.
+    pub first_start_time: u64,
+    pub last_start_time: u64,
+    pub records: usize,
+}
+
+impl SegmentMarker {
+    pub fn new(segment: usize, klines: &[Kline]) -> Self {
+        Self {
+            segment,
+            first_start_time: klines.first().map_or(0, |kline| kline.start_time),
+            last_start_time: klines.last().map_or(0, |kline| kline.start_time),
+            records: klines.len(),
+        }
+    }
+}
+
+/// Strategy used to synthesize klines for missing bars.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum GapFill {
+    /// Carry the prior close forward as a flat OHLC bar with zero volume.
+    Forward,
+    /// Flat bar at the prior close with zero volume, marked as a zero volume fill.
+    ZeroVolume,
+}
+
+impl fmt::Display for GapFill {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            GapFill::Forward => "forward",
+            GapFill::ZeroVolume => "zero-volume",
+        })
+    }
+}
+
+
+impl GapFill {
+    /// Synthetic kline for the missing bar starting at `start_time`, following `prior`.
+    fn synthesize(&self, prior: &Kline, start_time: u64) -> Kline {
+        Kline {
+            start_time,
+            open_price: prior.close_price,
+            high_price: prior.close_price,
+            low_price: prior.close_price,
+            close_price: prior.close_price,
+            volume: 0.0,
+            turnover: 0.0,
+            kline_type: prior.kline_type,
+            category: prior.category,
+            filled: Some(*self),
+        }
+    }
+}
+
+/// Insert synthetic klines for every bar missing between the first and last bar of each
+/// series, so that each series is contiguous.
+///
+/// Merged series (eg/ trade and mark price klines) are filled independently. Bars before the
+/// first or after the last received kline are not synthesized, there's no prior close to use.
+///
+/// With `max_bars`, a gap of more than that many bars (eg/ a multi-day outage) is left as a
+/// real gap rather than fabricating a long flat stretch; a gap of exactly `max_bars` is still
+/// filled. Returns the filled klines and the gaps left unfilled.
+pub fn fill_gaps(
+    klines: Vec<Kline>,
+    interval: Interval,
+    mode: GapFill,
+    max_bars: Option<u64>,
+) -> (Vec<Kline>, Vec<Gap>) {
+    let mut series = BTreeMap::<(KlineType, Category), Vec<Kline>>::new();
+    for kline in klines {
+        series
+            .entry((kline.kline_type, kline.category))
+            .or_default()
+            .push(kline);
+    }
+
+    let mut filled = Vec::new();
+    let mut unfilled = Vec::new();
+    for (_, klines) in series {
+        for gap in detect_gaps(&klines, interval) {
+            if max_bars.is_some_and(|max_bars| gap.bars(interval) > max_bars) {
+                unfilled.push(gap);
+                continue;
+            }
+            let prior = klines
+                .iter()
+                .rev()
+                .find(|kline| kline.start_time < gap.start)
+                .expect("a gap always follows a received kline");
+
+            let mut start_time = gap.start;
+            while start_time < gap.end {
+                filled.push(mode.synthesize(prior, start_time));
+                start_time = interval.next_start(start_time);
+            }
+        }
+        filled.extend(klines);
+    }
+
+    kline::sort_and_dedup(&mut filled);
+    unfilled.sort_by_key(|gap| gap.start);
+    (filled, unfilled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kline::test_kline;
+
+    #[test]
+    fn test_fill_gaps_is_contiguous() {
+        let step = Interval::Min15.duration_ms();
+        let klines = vec![
+            test_kline(0, 1.0),
+            test_kline(step, 2.0),
+            test_kline(4 * step, 3.0),
+            Kline { kline_type: KlineType::Mark, ..test_kline(0, 10.0) },
+            Kline { kline_type: KlineType::Mark, ..test_kline(2 * step, 11.0) },
+        ];
+        assert_eq!(
+            detect_gaps(&klines[..3], Interval::Min15),
+            vec![Gap {
+                start: 2 * step,
+                end: 4 * step
+            }]
+        );
+
+        let (filled, unfilled) = fill_gaps(klines, Interval::Min15, GapFill::Forward, None);
+        assert!(unfilled.is_empty());
+        for kline_type in [KlineType::Trade, KlineType::Mark] {
+            let series = filled
+                .iter()
+                .filter(|kline| kline.kline_type == kline_type)
+                .cloned()
+                .collect::<Vec<_>>();
+            assert!(detect_gaps(&series, Interval::Min15).is_empty());
+        }
+
+        let trades = filled
+            .iter()
+            .filter(|kline| kline.kline_type == KlineType::Trade)
+            .map(|kline| (kline.start_time, kline.close_price, kline.volume, kline.filled))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            trades,
+            vec![
+                (0, 1.0, 10.0, None),
+                (step, 2.0, 10.0, None),
+                (2 * step, 2.0, 0.0, Some(GapFill::Forward)),
+                (3 * step, 2.0, 0.0, Some(GapFill::Forward)),
+                (4 * step, 3.0, 10.0, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_fill_gaps_monthly_calendar() {
+        // 2024-01-01, 2024-04-01 UTC
+        let klines = vec![
+            test_kline(1704067200000, 1.0),
+            test_kline(1711929600000, 2.0),
+        ];
+        let (filled, _) = fill_gaps(klines, Interval::Month, GapFill::ZeroVolume, None);
+
+        // 2024-02-01 and 2024-03-01 UTC
+        let starts = filled.iter().map(|kline| kline.start_time).collect::<Vec<_>>();
+        assert_eq!(
+            starts,
+            vec![1704067200000, 1706745600000, 1709251200000, 1711929600000]
+        );
+        assert!(filled[1..3]
+            .iter()
+            .all(|kline| kline.filled == Some(GapFill::ZeroVolume)));
+    }
+
+    #[test]
+    fn test_fill_gaps_max_bars_boundary() {
+        let step = Interval::Min15.duration_ms();
+        // A 2 bar gap then a 3 bar gap
+        let klines = [0, 3, 7].map(|bar| test_kline(bar * step, 1.0)).to_vec();
+
+        let (filled, unfilled) = fill_gaps(klines.clone(), Interval::Min15, GapFill::Forward, Some(2));
+        let starts = filled.iter().map(|kline| kline.start_time / step).collect::<Vec<_>>();
+        assert_eq!(starts, vec![0, 1, 2, 3, 7]);
+        assert_eq!(unfilled, vec![Gap { start: 4 * step, end: 7 * step }]);
+        assert_eq!(unfilled[0].bars(Interval::Min15), 3);
+
+        // Raising the limit to the larger gap's size fills it too
+        let (filled, unfilled) = fill_gaps(klines, Interval::Min15, GapFill::Forward, Some(3));
+        assert_eq!(filled.len(), 8);
+        assert!(unfilled.is_empty());
+    }
+
+    #[test]
+    fn test_split_on_gaps() {
+        let step = Interval::Min15.duration_ms();
+        let klines = [0, 1, 2, 5, 7, 8]
+            .map(|bar| test_kline(bar * step, 1.0))
+            .to_vec();
+
+        let segments = split_on_gaps(&klines, Interval::Min15)
+            .iter()
+            .map(|segment| segment.iter().map(|kline| kline.start_time / step).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        assert_eq!(segments, vec![vec![0, 1, 2], vec![5], vec![7, 8]]);
+        assert_eq!(
+            SegmentMarker::new(2, &klines[4..]),
+            SegmentMarker { segment: 2, first_start_time: 7 * step, last_start_time: 8 * step, records: 2 }
+        );
+        assert!(split_on_gaps(&[], Interval::Min15).is_empty());
+    }
+}
@@ -0,0 +1,532 @@
+use std::io::{self, Write};
+
+/// Gzip member header: magic, deflate, no flags, no mtime, no extra flags, unknown OS.
+const HEADER: [u8; 10] = [0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 0xff];
+
+/// Input buffered and compressed as one DEFLATE block. Matches never reach back past the start
+/// of their block, so blocks compress independently.
+const BLOCK_SIZE: usize = 64 * 1024;
+/// Most bytes a stored block can hold.
+const MAX_STORED: usize = 65535;
+/// Farthest back DEFLATE lets a match start.
+const WINDOW_SIZE: usize = 32 * 1024;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+/// Earlier positions tried per match, trading compression ratio for speed.
+const MAX_CHAIN: usize = 64;
+const HASH_BITS: u32 = 15;
+const NO_POSITION: usize = usize::MAX;
+
+/// Base match length of each length symbol from 257, and its extra bits.
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+/// Base distance of each distance symbol, and its extra bits.
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+const CRC_TABLE: [u32; 256] = crc_table();
+
+const fn crc_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut n = 0;
+    while n < 256 {
+        let mut crc = n as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 { 0xedb8_8320 ^ (crc >> 1) } else { crc >> 1 };
+            bit += 1;
+        }
+        table[n] = crc;
+        n += 1;
+    }
+    table
+}
+
+/// CRC-32 (IEEE) of `data`, continuing from the `crc` of the bytes before it (0 to start).
+pub fn crc32(crc: u32, data: &[u8]) -> u32 {
+    !data.iter().fold(!crc, |crc, &byte| {
+        CRC_TABLE[((crc ^ byte as u32) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+/// Gzip encoder of everything written to it, eg/ for `--compress gzip`.
+///
+/// Compression is LZ77 with DEFLATE's fixed Huffman codes, which gets most of the gain on
+/// repetitive text like csv or ndjson rows without building per-block code tables, and blocks
+/// which wouldn't shrink are stored as they are. Each flush ends the current block on a byte
+/// boundary, so the stream written so far decodes up to there, and [`GzipEncoder::finish`]
+/// completes it with the gzip trailer.
+pub struct GzipEncoder<W: Write> {
+    inner: W,
+    pending: Vec<u8>,
+    bits: BitWriter,
+    crc: u32,
+    size: u32,
+    started: bool,
+    finished: bool,
+}
+
+impl<W: Write> GzipEncoder<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            pending: Vec::with_capacity(BLOCK_SIZE),
+            bits: BitWriter::default(),
+            crc: 0,
+            size: 0,
+            started: false,
+            finished: false,
+        }
+    }
+
+    /// The underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Give back the underlying writer, without writing anything still buffered.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    /// Compress whatever is still buffered, then write the final block and the trailer (CRC-32
+    /// and length of the input). Further calls do nothing.
+    pub fn finish(&mut self) -> io::Result<()> {
+        if self.finished {
+            return Ok(());
+        }
+        self.compress_pending()?;
+        // An empty final block with fixed codes: BFINAL, BTYPE 01 and the end of block symbol
+        self.bits.put(1, 1);
+        self.bits.put(1, 2);
+        put_symbol(&mut self.bits, 256);
+        self.bits.align();
+        self.bits.out.extend_from_slice(&self.crc.to_le_bytes());
+        self.bits.out.extend_from_slice(&self.size.to_le_bytes());
+        self.write_out()?;
+        self.finished = true;
+        self.inner.flush()
+    }
+
+    fn compress_pending(&mut self) -> io::Result<()> {
+        if !self.pending.is_empty() {
+            compress_block(&mut self.bits, &self.pending);
+            self.pending.clear();
+        }
+        self.write_out()
+    }
+
+    fn write_out(&mut self) -> io::Result<()> {
+        if !self.started {
+            self.inner.write_all(&HEADER)?;
+            self.started = true;
+        }
+        self.inner.write_all(&self.bits.out)?;
+        self.bits.out.clear();
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for GzipEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.crc = crc32(self.crc, buf);
+        // ISIZE is the input length modulo 2^32
+        self.size = self.size.wrapping_add(buf.len() as u32);
+        for chunk in buf.chunks(BLOCK_SIZE) {
+            let take = chunk.len().min(BLOCK_SIZE - self.pending.len());
+            self.pending.extend_from_slice(&chunk[..take]);
+            if self.pending.len() == BLOCK_SIZE {
+                self.compress_pending()?;
+                self.pending.extend_from_slice(&chunk[take..]);
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if !self.finished && (!self.pending.is_empty() || self.bits.count > 0) {
+            self.compress_pending()?;
+            // An empty stored block brings the stream to a byte boundary (a zlib sync flush)
+            self.bits.put(0, 3);
+            self.bits.align();
+            self.bits.out.extend_from_slice(&[0x00, 0x00, 0xff, 0xff]);
+            self.write_out()?;
+        }
+        self.inner.flush()
+    }
+}
+
+/// DEFLATE's bit packing: values least significant bit first, filling each byte from its low
+/// bit.
+#[derive(Default)]
+struct BitWriter {
+    out: Vec<u8>,
+    buffer: u64,
+    count: u32,
+}
+
+impl BitWriter {
+    fn put(&mut self, value: u32, bits: u32) {
+        self.buffer |= (value as u64) << self.count;
+        self.count += bits;
+        while self.count >= 8 {
+            self.out.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    /// Huffman codes are packed most significant bit first.
+    fn put_code(&mut self, code: u32, bits: u32) {
+        self.put(code.reverse_bits() >> (32 - bits), bits);
+    }
+
+    fn align(&mut self) {
+        if self.count > 0 {
+            self.out.push(self.buffer as u8);
+            self.buffer = 0;
+            self.count = 0;
+        }
+    }
+}
+
+/// Fixed Huffman code of a literal/length symbol.
+fn put_symbol(bits: &mut BitWriter, symbol: u16) {
+    let symbol = symbol as u32;
+    match symbol {
+        0..=143 => bits.put_code(0x30 + symbol, 8),
+        144..=255 => bits.put_code(0x190 + symbol - 144, 9),
+        256..=279 => bits.put_code(symbol - 256, 7),
+        _ => bits.put_code(0xc0 + symbol - 280, 8),
+    }
+}
+
+/// A literal byte, or a match of `length` bytes `distance` back.
+#[derive(Debug, Clone, Copy)]
+enum Token {
+    Literal(u8),
+    Match { length: usize, distance: usize },
+}
+
+fn length_index(length: usize) -> usize {
+    LENGTH_BASE.iter().rposition(|&base| base as usize <= length).unwrap_or(0)
+}
+
+fn distance_index(distance: usize) -> usize {
+    DISTANCE_BASE.iter().rposition(|&base| base as usize <= distance).unwrap_or(0)
+}
+
+/// Bits of a literal/length symbol's fixed Huffman code.
+fn symbol_bits(symbol: usize) -> usize {
+    match symbol {
+        0..=143 => 8,
+        144..=255 => 9,
+        256..=279 => 7,
+        _ => 8,
+    }
+}
+
+impl Token {
+    /// Bits this token takes with the fixed Huffman codes.
+    fn bits(&self) -> usize {
+        match *self {
+            Token::Literal(byte) => symbol_bits(byte as usize),
+            Token::Match { length, distance } => {
+                let (length, distance) = (length_index(length), distance_index(distance));
+                symbol_bits(257 + length) + LENGTH_EXTRA[length] as usize + 5 + DISTANCE_EXTRA[distance] as usize
+            }
+        }
+    }
+
+    fn put(&self, bits: &mut BitWriter) {
+        match *self {
+            Token::Literal(byte) => put_symbol(bits, byte as u16),
+            Token::Match { length, distance } => {
+                let index = length_index(length);
+                put_symbol(bits, 257 + index as u16);
+                bits.put((length - LENGTH_BASE[index] as usize) as u32, LENGTH_EXTRA[index] as u32);
+                let index = distance_index(distance);
+                bits.put_code(index as u32, 5);
+                bits.put((distance - DISTANCE_BASE[index] as usize) as u32, DISTANCE_EXTRA[index] as u32);
+            }
+        }
+    }
+}
+
+/// Compress `data` as non-final blocks: a fixed Huffman block of the longest recent earlier
+/// matches, found through hash chains of 3 byte prefixes, or stored blocks if that would be
+/// smaller, so input that doesn't compress, eg/ already compressed data, barely grows.
+fn compress_block(bits: &mut BitWriter, data: &[u8]) {
+    let tokens = tokenize(data);
+    // Block header and end of block symbol, against each stored block's header, padding to a
+    // byte boundary and length fields
+    let fixed = 3 + tokens.iter().map(Token::bits).sum::<usize>() + 7;
+    let stored = data.len().div_ceil(MAX_STORED).max(1) * (3 + 7 + 32) + data.len() * 8;
+    if stored < fixed {
+        for chunk in data.chunks(MAX_STORED) {
+            bits.put(0, 3);
+            bits.align();
+            bits.out.extend_from_slice(&(chunk.len() as u16).to_le_bytes());
+            bits.out.extend_from_slice(&(!(chunk.len() as u16)).to_le_bytes());
+            bits.out.extend_from_slice(chunk);
+        }
+        return;
+    }
+    bits.put(0, 1);
+    bits.put(1, 2);
+    for token in &tokens {
+        token.put(bits);
+    }
+    put_symbol(bits, 256);
+}
+
+fn tokenize(data: &[u8]) -> Vec<Token> {
+    let hash = |at: usize| {
+        let key = u32::from_le_bytes([data[at], data[at + 1], data[at + 2], 0]);
+        (key.wrapping_mul(0x9e37_79b1) >> (32 - HASH_BITS)) as usize
+    };
+    // Most recent position of each hash, and the one before each position with the same hash
+    let mut head = vec![NO_POSITION; 1 << HASH_BITS];
+    let mut previous = vec![NO_POSITION; data.len()];
+    let insert = |at: usize, head: &mut [usize], previous: &mut [usize]| {
+        if at + MIN_MATCH <= data.len() {
+            let key = hash(at);
+            previous[at] = head[key];
+            head[key] = at;
+        }
+    };
+
+    let mut tokens = Vec::new();
+    let mut at = 0;
+    while at < data.len() {
+        let (mut length, mut distance) = (0, 0);
+        if at + MIN_MATCH <= data.len() {
+            let longest = MAX_MATCH.min(data.len() - at);
+            let mut candidate = head[hash(at)];
+            let mut chain = MAX_CHAIN;
+            while candidate != NO_POSITION && at - candidate <= WINDOW_SIZE && chain > 0 {
+                let matched = (0..longest)
+                    .take_while(|&offset| data[candidate + offset] == data[at + offset])
+                    .count();
+                if matched > length {
+                    (length, distance) = (matched, at - candidate);
+                    if matched == longest {
+                        break;
+                    }
+                }
+                candidate = previous[candidate];
+                chain -= 1;
+            }
+        }
+
+        if length >= MIN_MATCH {
+            tokens.push(Token::Match { length, distance });
+            for position in at..at + length {
+                insert(position, &mut head, &mut previous);
+            }
+            at += length;
+        } else {
+            tokens.push(Token::Literal(data[at]));
+            insert(at, &mut head, &mut previous);
+            at += 1;
+        }
+    }
+    tokens
+}
+
+/// Decode a gzip stream of stored and fixed Huffman blocks, as [`GzipEncoder`] writes, checking
+/// its trailer.
+#[cfg(test)]
+pub(crate) fn gunzip(data: &[u8]) -> Vec<u8> {
+    assert_eq!(data[..10], HEADER);
+    let mut reader = BitReader { data: &data[10..], position: 0 };
+    let mut out = Vec::new();
+    loop {
+        let last = reader.bits(1) == 1;
+        match reader.bits(2) {
+            0 => {
+                reader.position = reader.position.div_ceil(8) * 8;
+                let length = reader.bits(16) as usize;
+                assert_eq!(reader.bits(16) as usize, !length & 0xffff);
+                for _ in 0..length {
+                    out.push(reader.bits(8) as u8);
+                }
+            }
+            1 => loop {
+                let symbol = reader.symbol();
+                match symbol {
+                    0..=255 => out.push(symbol as u8),
+                    256 => break,
+                    _ => {
+                        let index = symbol as usize - 257;
+                        let length = LENGTH_BASE[index] as usize + reader.bits(LENGTH_EXTRA[index] as u32) as usize;
+                        let index = reader.code(5) as usize;
+                        let distance = DISTANCE_BASE[index] as usize + reader.bits(DISTANCE_EXTRA[index] as u32) as usize;
+                        for _ in 0..length {
+                            out.push(out[out.len() - distance]);
+                        }
+                    }
+                }
+            },
+            block_type => panic!("unexpected block type {}", block_type),
+        }
+        if last {
+            break;
+        }
+    }
+    let trailer = 10 + reader.position.div_ceil(8);
+    assert_eq!(data[trailer..trailer + 4], crc32(0, &out).to_le_bytes());
+    assert_eq!(data[trailer + 4..], (out.len() as u32).to_le_bytes());
+    out
+}
+
+#[cfg(test)]
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+#[cfg(test)]
+impl BitReader<'_> {
+    fn bits(&mut self, count: u32) -> u32 {
+        (0..count).fold(0, |value, bit| {
+            let set = self.data[self.position / 8] >> (self.position % 8) & 1;
+            self.position += 1;
+            value | (set as u32) << bit
+        })
+    }
+
+    fn code(&mut self, count: u32) -> u32 {
+        (0..count).fold(0, |code, _| code << 1 | self.bits(1))
+    }
+
+    fn symbol(&mut self) -> u16 {
+        let code = self.code(7);
+        if code <= 0x17 {
+            return 256 + code as u16;
+        }
+        let code = code << 1 | self.bits(1);
+        match code {
+            0x30..=0xbf => (code - 0x30) as u16,
+            0xc0..=0xc7 => (code - 0xc0 + 280) as u16,
+            _ => ((code << 1 | self.bits(1)) - 0x190 + 144) as u16,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(0, b"123456789"), 0xcbf4_3926);
+        assert_eq!(crc32(crc32(0, b"12345"), b"6789"), 0xcbf4_3926);
+        assert_eq!(crc32(0, b""), 0);
+    }
+
+    #[test]
+    fn test_gzip_round_trip() {
+        // Several blocks of repetitive csv, flushed mid-block and written in odd sized pieces
+        let csv = (0..6000)
+            .map(|row| format!("{},42000.5,42100.25,41950.0,{}.5,12.{}\n", 1_704_067_200_000u64 + row * 60_000, 42000 + row % 97, row % 10))
+            .collect::<String>();
+        let mut encoder = GzipEncoder::new(Vec::new());
+        for (index, piece) in csv.as_bytes().chunks(7919).enumerate() {
+            encoder.write_all(piece).unwrap();
+            if index % 5 == 0 {
+                encoder.flush().unwrap();
+            }
+        }
+        encoder.finish().unwrap();
+        encoder.finish().unwrap();
+        let compressed = encoder.get_ref().clone();
+        assert_eq!(gunzip(&compressed), csv.as_bytes());
+        assert!(compressed.len() < csv.len() / 3, "{} of {} bytes", compressed.len(), csv.len());
+
+        // Every byte value, long runs up to the longest match, and no input at all
+        let mut bytes = (0..=255u8).collect::<Vec<_>>();
+        bytes.extend([b'a'; 1000]);
+        bytes.extend((0..=255u8).rev());
+        let mut encoder = GzipEncoder::new(Vec::new());
+        encoder.write_all(&bytes).unwrap();
+        encoder.finish().unwrap();
+        assert_eq!(gunzip(encoder.get_ref()), bytes);
+
+        let mut encoder = GzipEncoder::new(Vec::new());
+        encoder.finish().unwrap();
+        assert!(gunzip(encoder.get_ref()).is_empty());
+    }
+
+    /// Pseudo-random bytes, which don't compress.
+    fn noise(len: usize) -> Vec<u8> {
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                (state >> 32) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_gzip_stores_incompressible_blocks() {
+        // Falls back to stored blocks rather than growing the data by fixed Huffman codes
+        let bytes = noise(300_000);
+        let mut encoder = GzipEncoder::new(Vec::new());
+        encoder.write_all(&bytes).unwrap();
+        encoder.finish().unwrap();
+        assert!(encoder.get_ref().len() < bytes.len() + 100, "{} of {} bytes", encoder.get_ref().len(), bytes.len());
+        assert_eq!(gunzip(encoder.get_ref()), bytes);
+    }
+
+    #[test]
+    fn test_gzip_decodes_with_system_gzip() {
+        // Checked against a real inflater, so a format bug the encoder shares with `gunzip`
+        // can't pass unnoticed
+        let mut input = (0..20_000)
+            .map(|row| format!("{},42000.5,{}.25,12.{}\n", 1_704_067_200_000u64 + row * 60_000, 42000 + row % 97, row % 10))
+            .collect::<String>()
+            .into_bytes();
+        input.extend(noise(100_000));
+        input.extend([b'a'; 1000]);
+        let mut encoder = GzipEncoder::new(Vec::new());
+        for (index, piece) in input.chunks(30_011).enumerate() {
+            encoder.write_all(piece).unwrap();
+            if index % 2 == 0 {
+                encoder.flush().unwrap();
+            }
+        }
+        encoder.finish().unwrap();
+
+        let child = std::process::Command::new("gzip")
+            .arg("-dc")
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .spawn();
+        let Ok(mut child) = child else {
+            eprintln!("gzip isn't installed, skipping the system gzip check");
+            return;
+        };
+        let mut stdin = child.stdin.take().unwrap();
+        let compressed = encoder.get_ref().clone();
+        let feed = std::thread::spawn(move || stdin.write_all(&compressed));
+        let output = child.wait_with_output().unwrap();
+        feed.join().unwrap().unwrap();
+        assert!(output.status.success());
+        assert_eq!(output.stdout, input);
+    }
+}
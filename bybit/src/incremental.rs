@@ -0,0 +1,117 @@
+use crate::{checksum, kline::Kline, BybitError};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    ffi::OsString,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Bars emitted by the previous poll of a `--incremental-emit` run, so the next poll only
+/// emits bars that are new or whose values changed.
+///
+/// Bybit's kline endpoints don't support conditional requests (ETag or If-Modified-Since), so
+/// every poll still fetches its window; this only cuts the redundant output. Stored as a JSON
+/// array of each bar's canonical checksum row (time, series, prices and volumes at a fixed
+/// precision), so float formatting noise doesn't count as a change.
+#[derive(Debug)]
+pub struct EmitSnapshot {
+    path: PathBuf,
+    rows: BTreeSet<String>,
+}
+
+/// How the bars of a poll compare with the previous one.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct EmitCounts {
+    pub new: usize,
+    pub changed: usize,
+    pub unchanged: usize,
+}
+
+impl EmitSnapshot {
+    /// Load the snapshot, a missing file is treated as an empty first poll.
+    pub fn load(path: &Path) -> Result<Self, BybitError> {
+        let rows = match fs::read(path) {
+            Ok(contents) => serde_json::from_slice(&contents)?,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => BTreeSet::new(),
+            Err(error) => return Err(error.into()),
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            rows,
+        })
+    }
+
+    /// Keep only the klines which are new or changed since the previous poll, returning how
+    /// many of each were kept and how many unchanged ones were dropped.
+    pub fn retain_changed(&self, klines: &mut Vec<Kline>) -> EmitCounts {
+        // Start times of previously emitted bars per series, to tell changed bars from new ones
+        let mut emitted = BTreeMap::<(&str, &str), BTreeSet<u64>>::new();
+        for row in &self.rows {
+            let mut fields = row.splitn(4, ',');
+            if let (Some(start_time), Some(category), Some(kline_type)) = (fields.next(), fields.next(), fields.next()) {
+                if let Ok(start_time) = start_time.parse() {
+                    emitted.entry((category, kline_type)).or_default().insert(start_time);
+                }
+            }
+        }
+
+        let mut counts = EmitCounts::default();
+        klines.retain(|kline| {
+            if self.rows.contains(&row(kline)) {
+                counts.unchanged += 1;
+                return false;
+            }
+            let kline_type = kline.kline_type.to_string();
+            match emitted.get(&(kline.category.as_str(), kline_type.as_str())) {
+                Some(starts) if starts.contains(&kline.start_time) => counts.changed += 1,
+                _ => counts.new += 1,
+            }
+            true
+        });
+        counts
+    }
+
+    /// Replace the snapshot with this poll's complete set of bars and persist it, writing to a
+    /// temporary file first so a crash never leaves it partial.
+    pub fn save(&mut self, klines: &[Kline]) -> Result<(), BybitError> {
+        self.rows = klines.iter().map(row).collect();
+        let mut temp_path = OsString::from(self.path.as_os_str());
+        temp_path.push(format!(".{}.tmp", std::process::id()));
+        fs::write(&temp_path, serde_json::to_vec(&self.rows)?)?;
+        fs::rename(&temp_path, &self.path)?;
+        Ok(())
+    }
+}
+
+fn row(kline: &Kline) -> String {
+    checksum::canonical_row(kline).trim_end().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kline::test_kline;
+
+    #[test]
+    fn test_incremental_emit_skips_unchanged_bars() {
+        let path = std::env::temp_dir()
+            .join(format!("bybit-kline-incremental-{}.json", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        // The first poll emits everything
+        let mut snapshot = EmitSnapshot::load(&path).unwrap();
+        let first_poll = vec![test_kline(0, 1.0), test_kline(60_000, 1.5)];
+        let mut emitted = first_poll.clone();
+        assert_eq!(snapshot.retain_changed(&mut emitted), EmitCounts { new: 2, changed: 0, unchanged: 0 });
+        snapshot.save(&first_poll).unwrap();
+
+        // The next poll sees the forming bar revised and a new bar, the first bar unchanged
+        let snapshot = EmitSnapshot::load(&path).unwrap();
+        let mut emitted = vec![test_kline(0, 1.0), test_kline(60_000, 1.7), test_kline(120_000, 1.8)];
+        assert_eq!(snapshot.retain_changed(&mut emitted), EmitCounts { new: 1, changed: 1, unchanged: 1 });
+        let starts = emitted.iter().map(|kline| kline.start_time).collect::<Vec<_>>();
+        assert_eq!(starts, vec![60_000, 120_000]);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,359 @@
+use crate::{category::Category, BybitClient, BybitError};
+use chrono::NaiveDate;
+use serde::Deserialize;
+use std::str::FromStr;
+
+#[derive(Debug, Deserialize)]
+struct InstrumentsInfoResult {
+    list: Vec<InstrumentInfo>,
+}
+
+/// Subset of Bybit's `/v5/market/instruments-info` entry describing an instrument's coins.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct InstrumentInfo {
+    pub symbol: String,
+    #[serde(rename = "baseCoin")]
+    pub base_coin: String,
+    #[serde(rename = "quoteCoin")]
+    pub quote_coin: String,
+    /// Not reported for spot instruments.
+    #[serde(rename = "settleCoin", default)]
+    pub settle_coin: Option<String>,
+    #[serde(rename = "priceFilter", default)]
+    pub price_filter: Option<PriceFilter>,
+    #[serde(rename = "lotSizeFilter", default)]
+    pub lot_size_filter: Option<LotSizeFilter>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct PriceFilter {
+    #[serde(rename = "tickSize")]
+    pub tick_size: String,
+}
+
+/// Quantity increments, reported as `qtyStep` for derivatives and `basePrecision` for spot.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct LotSizeFilter {
+    #[serde(rename = "qtyStep", default)]
+    pub qty_step: Option<String>,
+    #[serde(rename = "basePrecision", default)]
+    pub base_precision: Option<String>,
+}
+
+/// Bybit symbols are uppercase ASCII across every category, including spot pairs such as
+/// `BTCUSDC` or leveraged tokens like `BTC3LUSDT`, so uppercasing never changes which
+/// instrument is meant. Lowercase input otherwise silently returns no klines.
+pub fn normalize_symbol(symbol: &str) -> String {
+    symbol.trim().to_ascii_uppercase()
+}
+
+/// Right an option grants.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OptionKind {
+    Call,
+    Put,
+}
+
+/// Bybit option symbol: `BASE-DMMMYY-STRIKE-C|P`, optionally followed by the settle coin for
+/// USDT settled options (eg/ `BTC-30AUG24-60000-C` or `ETH-27DEC24-4000-P-USDT`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct OptionSymbol {
+    pub base_coin: String,
+    pub expiry: NaiveDate,
+    pub strike: f64,
+    pub kind: OptionKind,
+    /// Only present on non-USDC settled options.
+    pub settle_coin: Option<String>,
+}
+
+impl FromStr for OptionSymbol {
+    type Err = BybitError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            BybitError::ArgumentError(format!(
+                "Invalid option symbol: {} (expected eg/ BTC-30AUG24-60000-C)",
+                s
+            ))
+        };
+
+        let parts = s.split('-').collect::<Vec<_>>();
+        let (base_coin, expiry, strike, kind, settle_coin) = match parts.as_slice() {
+            [base, expiry, strike, kind] => (base, expiry, strike, kind, None),
+            [base, expiry, strike, kind, settle] => (base, expiry, strike, kind, Some(settle)),
+            _ => return Err(invalid()),
+        };
+        if base_coin.is_empty() || settle_coin.is_some_and(|settle| settle.is_empty()) {
+            return Err(invalid());
+        }
+
+        Ok(Self {
+            base_coin: base_coin.to_string(),
+            expiry: NaiveDate::parse_from_str(expiry, "%d%b%y").map_err(|_| invalid())?,
+            strike: strike
+                .parse::<f64>()
+                .ok()
+                .filter(|strike| *strike > 0.0)
+                .ok_or_else(invalid)?,
+            kind: match *kind {
+                "C" => OptionKind::Call,
+                "P" => OptionKind::Put,
+                _ => return Err(invalid()),
+            },
+            settle_coin: settle_coin.map(|settle| settle.to_string()),
+        })
+    }
+}
+
+/// Number of decimal places in a step size such as "0.00010000" (4).
+fn step_decimals(step: &str) -> Option<usize> {
+    step.parse::<f64>().ok().filter(|step| *step > 0.0)?;
+    Some(
+        step.split_once('.')
+            .map(|(_, fraction)| fraction.trim_end_matches('0').len())
+            .unwrap_or(0),
+    )
+}
+
+impl InstrumentInfo {
+    /// Canonical multi-venue symbol: `BASE/QUOTE` for spot, `BASE/QUOTE:SETTLE` for
+    /// derivatives (eg/ `BTC/USDT:USDT` for the linear BTCUSDT perpetual).
+    pub fn canonical_symbol(&self) -> String {
+        match self.settle_coin.as_deref() {
+            Some(settle) if !settle.is_empty() => {
+                format!("{}/{}:{}", self.base_coin, self.quote_coin, settle)
+            }
+            _ => format!("{}/{}", self.base_coin, self.quote_coin),
+        }
+    }
+
+    /// Decimal places needed to display prices at the instrument's tick size.
+    pub fn price_decimals(&self) -> Option<usize> {
+        step_decimals(&self.price_filter.as_ref()?.tick_size)
+    }
+
+    /// Decimal places needed to display quantities at the instrument's lot size step.
+    pub fn volume_decimals(&self) -> Option<usize> {
+        let lot_size = self.lot_size_filter.as_ref()?;
+        step_decimals(
+            lot_size
+                .qty_step
+                .as_ref()
+                .or(lot_size.base_precision.as_ref())?,
+        )
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct TickersResult {
+    list: Vec<Ticker>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Ticker {
+    symbol: String,
+    #[serde(rename = "turnover24h")]
+    turnover_24h: String,
+    #[serde(rename = "volume24h")]
+    volume_24h: String,
+}
+
+impl Ticker {
+    /// 24h turnover in the quote coin, comparable across categories.
+    ///
+    /// Like kline volume, inverse tickers report `turnover24h` in the base coin and
+    /// `volume24h` in the quote coin.
+    fn quote_turnover(&self, category: Category) -> Option<f64> {
+        let turnover = if category == Category::Inverse {
+            &self.volume_24h
+        } else {
+            &self.turnover_24h
+        };
+        turnover.parse().ok()
+    }
+}
+
+/// Warning to print if `symbol` trades in several categories and `chosen` isn't the one with
+/// the highest 24h quote turnover.
+fn liquidity_warning(
+    symbol: &str,
+    chosen: Category,
+    turnovers: &[(Category, f64)],
+) -> Option<String> {
+    if turnovers.len() < 2 {
+        return None;
+    }
+    let (most_liquid, turnover) = turnovers
+        .iter()
+        .copied()
+        .max_by(|a, b| a.1.total_cmp(&b.1))?;
+    let chosen_turnover = turnovers
+        .iter()
+        .find(|(category, _)| *category == chosen)
+        .map(|(_, turnover)| *turnover)?;
+    if most_liquid == chosen {
+        return None;
+    }
+
+    let listed = turnovers
+        .iter()
+        .map(|(category, _)| category.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+    Some(format!(
+        "{} is listed in several categories ({}), fetching {} ({:.0} 24h turnover) but {} is the most liquid ({:.0}), pass --category {} if that's what you meant",
+        symbol, listed, chosen, chosen_turnover, most_liquid, turnover, most_liquid
+    ))
+}
+
+impl BybitClient {
+    /// Advisory check for the common spot vs perpetual mixup: warn if `symbol` is listed in
+    /// several categories and `category` isn't the most liquid one.
+    ///
+    /// Best effort, any request failure skips the check.
+    pub async fn category_mismatch_warning(
+        &self,
+        category: Category,
+        symbol: &str,
+    ) -> Option<String> {
+        let mut turnovers = Vec::new();
+        for &listed in Category::all() {
+            if self.get_instrument_info(listed, symbol).await.is_err() {
+                continue;
+            }
+            let tickers = self
+                .get::<TickersResult>(
+                    "/v5/market/tickers",
+                    &[("category", listed.as_str()), ("symbol", symbol)],
+                )
+                .await
+                .ok()?;
+            let turnover = tickers
+                .list
+                .iter()
+                .find(|ticker| ticker.symbol == symbol)
+                .and_then(|ticker| ticker.quote_turnover(listed))?;
+            turnovers.push((listed, turnover));
+        }
+
+        liquidity_warning(symbol, category, &turnovers)
+    }
+
+    pub async fn get_instrument_info(
+        &self,
+        category: Category,
+        symbol: &str,
+    ) -> Result<InstrumentInfo, BybitError> {
+        self.get::<InstrumentsInfoResult>(
+            "/v5/market/instruments-info",
+            &[("category", category.as_str()), ("symbol", symbol)],
+        )
+        .await?
+        .list
+        .into_iter()
+        .find(|instrument| instrument.symbol == symbol)
+        .ok_or_else(|| BybitError::ApiError {
+            msg: format!("Instrument {} not found in category {}", symbol, category),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_symbol() {
+        assert_eq!(normalize_symbol("btcusdt"), normalize_symbol("BTCUSDT"));
+        assert_eq!(normalize_symbol(" btcUSDT "), "BTCUSDT");
+        assert_eq!(normalize_symbol("BTC3LUSDT"), "BTC3LUSDT");
+    }
+
+    #[test]
+    fn test_canonical_symbol() {
+        let linear = serde_json::from_str::<InstrumentInfo>(
+            r#"{"symbol":"BTCUSDT","baseCoin":"BTC","quoteCoin":"USDT","settleCoin":"USDT"}"#,
+        )
+        .unwrap();
+        assert_eq!(linear.canonical_symbol(), "BTC/USDT:USDT");
+
+        let inverse = serde_json::from_str::<InstrumentInfo>(
+            r#"{"symbol":"BTCUSD","baseCoin":"BTC","quoteCoin":"USD","settleCoin":"BTC"}"#,
+        )
+        .unwrap();
+        assert_eq!(inverse.canonical_symbol(), "BTC/USD:BTC");
+
+        let spot = serde_json::from_str::<InstrumentInfo>(
+            r#"{"symbol":"BTCUSDT","baseCoin":"BTC","quoteCoin":"USDT"}"#,
+        )
+        .unwrap();
+        assert_eq!(spot.canonical_symbol(), "BTC/USDT");
+    }
+
+    #[test]
+    fn test_parse_option_symbol() {
+        let call = "BTC-30AUG24-60000-C".parse::<OptionSymbol>().unwrap();
+        assert_eq!(call.base_coin, "BTC");
+        assert_eq!(call.expiry, NaiveDate::from_ymd_opt(2024, 8, 30).unwrap());
+        assert_eq!(call.strike, 60000.0);
+        assert_eq!(call.kind, OptionKind::Call);
+        assert_eq!(call.settle_coin, None);
+
+        let put = "ETH-6SEP24-2250-P-USDT".parse::<OptionSymbol>().unwrap();
+        assert_eq!(put.expiry, NaiveDate::from_ymd_opt(2024, 9, 6).unwrap());
+        assert_eq!(put.kind, OptionKind::Put);
+        assert_eq!(put.settle_coin.as_deref(), Some("USDT"));
+
+        for invalid in ["BTCUSDT", "BTC-30AUG24-60000-X", "BTC-31FEB24-60000-C", "BTC-30AUG24-0-C"] {
+            assert!(invalid.parse::<OptionSymbol>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_instrument_decimals() {
+        let shib = serde_json::from_str::<InstrumentInfo>(
+            r#"{
+                "symbol":"SHIBUSDT","baseCoin":"SHIB","quoteCoin":"USDT",
+                "priceFilter":{"tickSize":"0.00000001"},
+                "lotSizeFilter":{"basePrecision":"0.01","quotePrecision":"0.000001"}
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(shib.price_decimals(), Some(8));
+        assert_eq!(shib.volume_decimals(), Some(2));
+
+        let btc = serde_json::from_str::<InstrumentInfo>(
+            r#"{
+                "symbol":"BTCUSDT","baseCoin":"BTC","quoteCoin":"USDT","settleCoin":"USDT",
+                "priceFilter":{"minPrice":"0.10","tickSize":"0.10"},
+                "lotSizeFilter":{"qtyStep":"0.001"}
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(btc.price_decimals(), Some(1));
+        assert_eq!(btc.volume_decimals(), Some(3));
+
+        assert_eq!(step_decimals("1"), Some(0));
+        assert_eq!(step_decimals("0"), None);
+    }
+
+    #[test]
+    fn test_liquidity_warning() {
+        let turnovers = [(Category::Spot, 1.2e9), (Category::Linear, 8.5e9)];
+        let warning = liquidity_warning("BTCUSDT", Category::Spot, &turnovers).unwrap();
+        assert!(warning.contains("--category linear"), "{}", warning);
+
+        assert!(liquidity_warning("BTCUSDT", Category::Linear, &turnovers).is_none());
+        assert!(
+            liquidity_warning("BTCUSD", Category::Inverse, &[(Category::Inverse, 1.0e9)]).is_none()
+        );
+
+        // Inverse tickers report quote turnover as volume24h
+        let inverse = serde_json::from_str::<Ticker>(
+            r#"{"symbol":"BTCUSD","turnover24h":"9123.5","volume24h":"612345678"}"#,
+        )
+        .unwrap();
+        assert_eq!(inverse.quote_turnover(Category::Inverse), Some(612345678.0));
+        assert_eq!(inverse.quote_turnover(Category::Linear), Some(9123.5));
+    }
+}
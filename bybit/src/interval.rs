@@ -0,0 +1,169 @@
+use crate::BybitError;
+use chrono::{DateTime, Datelike, Months};
+use clap::{builder::PossibleValue, ValueEnum};
+use std::{fmt, str::FromStr};
+
+/// Kline interval supported by Bybit's `/v5/market/kline` endpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Interval {
+    Min1,
+    Min3,
+    Min5,
+    Min15,
+    Min30,
+    Hour1,
+    Hour2,
+    Hour4,
+    Hour6,
+    Hour12,
+    Day,
+    Week,
+    Month,
+}
+
+impl Interval {
+    /// Every supported interval, ordered from finest to coarsest.
+    pub fn all() -> &'static [Interval] {
+        &[
+            Interval::Min1,
+            Interval::Min3,
+            Interval::Min5,
+            Interval::Min15,
+            Interval::Min30,
+            Interval::Hour1,
+            Interval::Hour2,
+            Interval::Hour4,
+            Interval::Hour6,
+            Interval::Hour12,
+            Interval::Day,
+            Interval::Week,
+            Interval::Month,
+        ]
+    }
+
+    /// Interval identifier as expected by the Bybit API (e.g. "15", "D").
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Interval::Min1 => "1",
+            Interval::Min3 => "3",
+            Interval::Min5 => "5",
+            Interval::Min15 => "15",
+            Interval::Min30 => "30",
+            Interval::Hour1 => "60",
+            Interval::Hour2 => "120",
+            Interval::Hour4 => "240",
+            Interval::Hour6 => "360",
+            Interval::Hour12 => "720",
+            Interval::Day => "D",
+            Interval::Week => "W",
+            Interval::Month => "M",
+        }
+    }
+
+    /// Interval duration in milliseconds.
+    ///
+    /// Note that a month is approximated as 30 days.
+    pub fn duration_ms(&self) -> u64 {
+        match self {
+            Interval::Min1 => 60_000,
+            Interval::Min3 => 180_000,
+            Interval::Min5 => 300_000,
+            Interval::Min15 => 900_000,
+            Interval::Min30 => 1_800_000,
+            Interval::Hour1 => 3_600_000,
+            Interval::Hour2 => 7_200_000,
+            Interval::Hour4 => 14_400_000,
+            Interval::Hour6 => 21_600_000,
+            Interval::Hour12 => 43_200_000,
+            Interval::Day => 86_400_000,
+            Interval::Week => 604_800_000,
+            Interval::Month => 2_592_000_000,
+        }
+    }
+
+    /// Start time of the bar following the one starting at `start_time` (milliseconds).
+    ///
+    /// Monthly bars follow calendar months rather than the fixed 30 day approximation used by
+    /// [`Interval::duration_ms`].
+    pub fn next_start(&self, start_time: u64) -> u64 {
+        match self {
+            Interval::Month => DateTime::from_timestamp_millis(start_time as i64)
+                .and_then(|start| start.checked_add_months(Months::new(1)))
+                .map(|next| next.timestamp_millis() as u64)
+                .unwrap_or(start_time + self.duration_ms()),
+            _ => start_time + self.duration_ms(),
+        }
+    }
+
+    /// Start time of the bar containing `millis` on Bybit's grid: multiples of the interval
+    /// from the epoch, except weekly bars which start on Mondays and monthly bars on the first
+    /// of each calendar month (all at 00:00 UTC).
+    pub fn bar_start(&self, millis: u64) -> u64 {
+        match self {
+            Interval::Week => {
+                // 1970-01-05, the first Monday
+                let monday = 4 * Interval::Day.duration_ms() as i64;
+                let week = self.duration_ms() as i64;
+                (monday + (millis as i64 - monday).div_euclid(week) * week).max(0) as u64
+            }
+            Interval::Month => DateTime::from_timestamp_millis(millis as i64)
+                .and_then(|time| time.date_naive().with_day(1))
+                .and_then(|first| first.and_hms_opt(0, 0, 0))
+                .map(|first| first.and_utc().timestamp_millis() as u64)
+                .unwrap_or(millis),
+            _ => millis - millis % self.duration_ms(),
+        }
+    }
+
+    /// Human readable description of the interval duration.
+    pub fn description(&self) -> &'static str {
+        match self {
+            Interval::Min1 => "1 minute",
+            Interval::Min3 => "3 minutes",
+            Interval::Min5 => "5 minutes",
+            Interval::Min15 => "15 minutes",
+            Interval::Min30 => "30 minutes",
+            Interval::Hour1 => "1 hour",
+            Interval::Hour2 => "2 hours",
+            Interval::Hour4 => "4 hours",
+            Interval::Hour6 => "6 hours",
+            Interval::Hour12 => "12 hours",
+            Interval::Day => "1 day",
+            Interval::Week => "1 week",
+            Interval::Month => "1 month (~30 days)",
+        }
+    }
+}
+
+impl fmt::Display for Interval {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.as_str())
+    }
+}
+
+impl FromStr for Interval {
+    type Err = BybitError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Interval::all()
+            .iter()
+            .copied()
+            .find(|interval| interval.as_str() == s)
+            .ok_or_else(|| BybitError::ApiError {
+                msg: format!(
+                    "Unsupported interval: {} (use --list-intervals to see supported intervals)",
+                    s
+                ),
+            })
+    }
+}
+
+impl ValueEnum for Interval {
+    fn value_variants<'a>() -> &'a [Self] {
+        Interval::all()
+    }
+
+    fn to_possible_value(&self) -> Option<PossibleValue> {
+        Some(PossibleValue::new(self.as_str()).help(self.description()))
+    }
+}
@@ -0,0 +1,160 @@
+use crate::{
+    category::Category,
+    instrument::normalize_symbol,
+    interval::Interval,
+    kline::{self, KlineType},
+    output, parse_date, BybitClient, BybitError, KlineQuery,
+};
+use futures_util::future::join_all;
+use serde::Deserialize;
+use std::{fs, io::BufWriter, path::Path};
+
+fn default_category() -> Category {
+    Category::Linear
+}
+
+fn default_kline_type() -> String {
+    "trade".to_string()
+}
+
+/// A single fetch in a `--jobs-file` manifest.
+#[derive(Debug, Clone, Deserialize)]
+pub struct JobSpec {
+    pub symbol: String,
+    pub interval: String,
+    #[serde(default = "default_category")]
+    pub category: Category,
+    /// Start date (YYYY/MM/DD).
+    pub start_date: String,
+    /// End date (YYYY/MM/DD).
+    pub end_date: String,
+    #[serde(default = "default_kline_type")]
+    pub kline_type: String,
+    /// Defaults to `--max-records`.
+    #[serde(default)]
+    pub max_records: Option<u32>,
+    /// Output path, overriding the `--jobs-output` template for this job.
+    #[serde(default)]
+    pub output: Option<String>,
+}
+
+impl JobSpec {
+    /// Output path for this job, substituting `{symbol}`, `{interval}`, `{category}` and
+    /// `{kline_type}` in `template` unless the job sets its own output.
+    pub fn output_path(&self, template: &str) -> String {
+        self.output.clone().unwrap_or_else(|| {
+            template
+                .replace("{symbol}", &self.symbol)
+                .replace("{interval}", &self.interval)
+                .replace("{category}", self.category.as_str())
+                .replace("{kline_type}", &self.kline_type)
+        })
+    }
+
+    fn query(
+        &self,
+        default_max_records: u32,
+        limit_per_request: u32,
+    ) -> Result<KlineQuery, BybitError> {
+        let start = parse_date(&self.start_date)?;
+        let end = parse_date(&self.end_date)?;
+        if start >= end {
+            return Err(BybitError::DateParseError(
+                "Start date must be before end date".to_string(),
+            ));
+        }
+
+        Ok(KlineQuery {
+            symbol: normalize_symbol(&self.symbol),
+            category: self.category,
+            interval: self.interval.parse::<Interval>()?,
+            kline_type: self.kline_type.parse::<KlineType>()?,
+            start,
+            end,
+            max_records: self.max_records.unwrap_or(default_max_records),
+            limit_per_request,
+        })
+    }
+}
+
+/// Read a JSON array of [`JobSpec`]s.
+pub fn load_jobs(path: &Path) -> Result<Vec<JobSpec>, BybitError> {
+    Ok(serde_json::from_slice(&fs::read(path)?)?)
+}
+
+/// Outcome of a single job.
+#[derive(Debug)]
+pub struct JobReport {
+    pub output: String,
+    pub result: Result<usize, BybitError>,
+}
+
+async fn run_job(
+    client: &BybitClient,
+    job: &JobSpec,
+    output: &str,
+    default_max_records: u32,
+    limit_per_request: u32,
+) -> Result<usize, BybitError> {
+    let query = job.query(default_max_records, limit_per_request)?;
+
+    // Per-chunk progress is ignored, it would interleave across jobs
+    let mut klines = client.get_kline(&query, |_| {}).await?;
+    kline::sort_and_dedup(&mut klines);
+
+    if let Some(parent) = Path::new(output).parent() {
+        fs::create_dir_all(parent)?;
+    }
+    output::write_csv(BufWriter::new(fs::File::create(output)?), &klines)?;
+    Ok(klines.len())
+}
+
+/// Run every job concurrently against the shared client, so they're bounded by its in-flight
+/// limit, writing each to its own CSV file.
+pub async fn run_jobs(
+    client: &BybitClient,
+    jobs: &[JobSpec],
+    template: &str,
+    default_max_records: u32,
+    limit_per_request: u32,
+) -> Vec<JobReport> {
+    join_all(jobs.iter().map(|job| async move {
+        let output = job.output_path(template);
+        let result = run_job(client, job, &output, default_max_records, limit_per_request).await;
+        JobReport { output, result }
+    }))
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_job_spec_defaults_and_output_path() {
+        let jobs = serde_json::from_str::<Vec<JobSpec>>(
+            r#"[
+                {"symbol":"BTCUSDT","interval":"60","start_date":"2024/01/01","end_date":"2024/01/02"},
+                {"symbol":"ETHUSDT","interval":"D","category":"spot","start_date":"2024/01/01","end_date":"2024/02/01","output":"eth.csv"}
+            ]"#,
+        )
+        .unwrap();
+
+        assert_eq!(jobs[0].category, Category::Linear);
+        assert_eq!(jobs[0].kline_type, "trade");
+        assert_eq!(
+            jobs[0].output_path("out/{category}/{symbol}_{interval}.csv"),
+            "out/linear/BTCUSDT_60.csv"
+        );
+        assert_eq!(jobs[1].output_path("{symbol}_{interval}.csv"), "eth.csv");
+
+        let query = jobs[0].query(500, 1000).unwrap();
+        assert_eq!(query.interval, Interval::Hour1);
+        assert_eq!(query.end - query.start, Interval::Day.duration_ms());
+        assert_eq!(query.max_records, 500);
+
+        let mut lowercase = jobs[0].clone();
+        lowercase.symbol = "btcusdt".to_string();
+        assert_eq!(lowercase.query(500, 1000).unwrap().symbol, query.symbol);
+    }
+}
@@ -0,0 +1,639 @@
+use crate::{category::Category, gaps::GapFill, interval::Interval, BybitError};
+use chrono::DateTime;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fmt, str::FromStr};
+
+/// Kind of kline series served by Bybit's market endpoints.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum KlineType {
+    /// Traded price klines (`/v5/market/kline`).
+    Trade,
+    /// Mark price klines (`/v5/market/mark-price-kline`).
+    Mark,
+    /// Index price klines (`/v5/market/index-price-kline`).
+    Index,
+    /// Premium index price klines (`/v5/market/premium-index-price-kline`).
+    PremiumIndex,
+}
+
+impl KlineType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            KlineType::Trade => "trade",
+            KlineType::Mark => "mark",
+            KlineType::Index => "index",
+            KlineType::PremiumIndex => "premium-index",
+        }
+    }
+
+    /// Endpoint path serving this kline type.
+    pub fn endpoint(&self) -> &'static str {
+        match self {
+            KlineType::Trade => "/v5/market/kline",
+            KlineType::Mark => "/v5/market/mark-price-kline",
+            KlineType::Index => "/v5/market/index-price-kline",
+            KlineType::PremiumIndex => "/v5/market/premium-index-price-kline",
+        }
+    }
+
+    /// Whether rows of this kline type carry volume and turnover.
+    ///
+    /// Mark, index and premium index klines only contain `[startTime, open, high, low, close]`.
+    pub fn has_volume(&self) -> bool {
+        matches!(self, KlineType::Trade)
+    }
+}
+
+impl fmt::Display for KlineType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.as_str())
+    }
+}
+
+impl FromStr for KlineType {
+    type Err = BybitError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "trade" => Ok(KlineType::Trade),
+            "mark" => Ok(KlineType::Mark),
+            "index" => Ok(KlineType::Index),
+            "premium-index" => Ok(KlineType::PremiumIndex),
+            _ => Err(BybitError::ArgumentError(format!(
+                "Unsupported kline type: {} (expected trade, mark, index or premium-index)",
+                s
+            ))),
+        }
+    }
+}
+
+/// A single Bybit kline, as returned by the API.
+///
+/// Serializes losslessly to JSON with these field names, eg/ for `--output-format kline-json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Kline {
+    /// Bar open time in unix millis.
+    pub start_time: u64,
+    pub open_price: f64,
+    pub high_price: f64,
+    pub low_price: f64,
+    pub close_price: f64,
+    /// Bybit's `volume`: base coin for spot and linear, contracts (USD) for inverse.
+    pub volume: f64,
+    /// Bybit's `turnover`: quote coin for spot and linear, base coin for inverse.
+    pub turnover: f64,
+    pub kline_type: KlineType,
+    /// Bybit category the kline was fetched from.
+    pub category: Category,
+    /// Set on synthetic klines inserted by `--fill-gaps`, to the fill strategy used.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub filled: Option<GapFill>,
+}
+
+/// Key identifying a unique bar across one or more merged kline series.
+///
+/// Bars from distinct series (eg/ mark price and trade klines) legitimately share a
+/// `start_time`, so the series identity is part of the key.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DedupKey<'a> {
+    pub start_time: u64,
+    pub kline_type: KlineType,
+    pub category: &'a str,
+}
+
+impl Kline {
+    /// Parse a raw kline row by its documented positions, `[startTime, open, high, low, close,
+    /// volume, turnover]`, ignoring any trailing fields Bybit adds.
+    ///
+    /// Spot rows have been served without `turnover`, so a 6 field spot row is accepted with a
+    /// zero turnover, while the other categories must carry both volume fields. Price only
+    /// kline types stop at `close`.
+    pub fn from_vec(
+        data: Vec<String>,
+        kline_type: KlineType,
+        category: Category,
+    ) -> Result<Self, BybitError> {
+        let expected_len = match (kline_type.has_volume(), category) {
+            (false, _) => 5,
+            (true, Category::Spot) => 6,
+            (true, _) => 7,
+        };
+        if data.len() < expected_len {
+            return Err(BybitError::ApiError {
+                msg: format!(
+                    "Invalid kline data format: {} fields, expected at least {} for {} {} klines",
+                    data.len(),
+                    expected_len,
+                    category,
+                    kline_type
+                ),
+            });
+        }
+
+        let (volume, turnover) = if kline_type.has_volume() {
+            (
+                data[5].parse().map_err(|_| BybitError::ApiError {
+                    msg: "Invalid volume".to_string(),
+                })?,
+                match data.get(6) {
+                    Some(turnover) => turnover.parse().map_err(|_| BybitError::ApiError {
+                        msg: "Invalid turnover".to_string(),
+                    })?,
+                    None => 0.0,
+                },
+            )
+        } else {
+            (0.0, 0.0)
+        };
+
+        Ok(Kline {
+            start_time: data[0].parse().map_err(|_| BybitError::ApiError {
+                msg: "Invalid start time".to_string(),
+            })?,
+            open_price: data[1].parse().map_err(|_| BybitError::ApiError {
+                msg: "Invalid open price".to_string(),
+            })?,
+            high_price: data[2].parse().map_err(|_| BybitError::ApiError {
+                msg: "Invalid high price".to_string(),
+            })?,
+            low_price: data[3].parse().map_err(|_| BybitError::ApiError {
+                msg: "Invalid low price".to_string(),
+            })?,
+            close_price: data[4].parse().map_err(|_| BybitError::ApiError {
+                msg: "Invalid close price".to_string(),
+            })?,
+            volume,
+            turnover,
+            kline_type,
+            category,
+            filled: None,
+        })
+    }
+
+    pub fn format_time(&self) -> String {
+        format_millis(self.start_time)
+    }
+
+    /// Close time of the bar, the start of the next bar of `interval` (following calendar
+    /// months for the M interval), as barter candles' `close_time`.
+    pub fn close_time(&self, interval: Interval) -> u64 {
+        interval.next_start(self.start_time)
+    }
+
+    /// [`Kline::close_time`] formatted like [`Kline::format_time`].
+    pub fn format_close_time(&self, interval: Interval) -> String {
+        format_millis(self.close_time(interval))
+    }
+
+    /// First OHLC invariant this bar breaks, if any: prices must be positive and finite, the
+    /// high at or above and the low at or below both the open and close, and volumes
+    /// non-negative.
+    pub fn ohlc_violation(&self) -> Option<&'static str> {
+        let prices = [self.open_price, self.high_price, self.low_price, self.close_price];
+        if prices.iter().any(|price| !price.is_finite() || *price <= 0.0) {
+            Some("non-positive or non-finite price")
+        } else if self.high_price < self.open_price.max(self.close_price) {
+            Some("high below open or close")
+        } else if self.low_price > self.open_price.min(self.close_price) {
+            Some("low above open or close")
+        } else if [self.volume, self.turnover].iter().any(|volume| !volume.is_finite() || *volume < 0.0) {
+            Some("negative or non-finite volume")
+        } else {
+            None
+        }
+    }
+
+    /// Volume denominated in the base coin (e.g. BTC for BTCUSDT).
+    ///
+    /// Bybit reports `volume` in the base coin and `turnover` in the quote coin for spot and
+    /// linear contracts, but the other way around for inverse contracts, see
+    /// [`Category::volume_units`].
+    pub fn base_volume(&self) -> f64 {
+        self.normalized_volumes().0
+    }
+
+    /// Volume denominated in the quote coin (e.g. USDT for BTCUSDT).
+    ///
+    /// See [`Kline::base_volume`] for how this maps onto Bybit's fields per category.
+    pub fn quote_volume(&self) -> f64 {
+        self.normalized_volumes().1
+    }
+
+    /// `(base_volume, quote_volume)`.
+    fn normalized_volumes(&self) -> (f64, f64) {
+        self.category.volume_units().normalize(self.volume, self.turnover)
+    }
+
+    pub fn dedup_key(&self) -> DedupKey<'_> {
+        DedupKey {
+            start_time: self.start_time,
+            kline_type: self.kline_type,
+            category: self.category.as_str(),
+        }
+    }
+}
+
+fn format_millis(millis: u64) -> String {
+    DateTime::from_timestamp_millis(millis as i64)
+        .unwrap_or_else(|| DateTime::from_timestamp(0, 0).unwrap())
+        .format("%Y-%m-%d %H:%M:%S UTC")
+        .to_string()
+}
+
+/// Parse a page of raw kline rows, either failing on the first malformed row or, with
+/// `best_effort`, logging and skipping malformed rows.
+///
+/// Returns the parsed klines and the number of rows skipped.
+pub fn parse_rows(
+    rows: Vec<Vec<String>>,
+    kline_type: KlineType,
+    category: Category,
+    best_effort: bool,
+) -> Result<(Vec<Kline>, usize), BybitError> {
+    let mut klines = Vec::with_capacity(rows.len());
+    let mut skipped = 0;
+    for row in rows {
+        match Kline::from_vec(row.clone(), kline_type, category) {
+            Ok(kline) => klines.push(kline),
+            Err(error) if best_effort => {
+                log::warn!("skipping malformed kline row {:?}: {}", row, error);
+                skipped += 1;
+            }
+            Err(error) => return Err(error),
+        }
+    }
+    Ok((klines, skipped))
+}
+
+/// Sort klines by time and remove duplicate bars, keeping distinct series which share a
+/// timestamp side by side.
+///
+/// `klines` are expected in fetch order. Where a bar appears more than once, eg/ a recent candle
+/// Bybit revised between two overlapping pages, the last occurrence wins, so the most recently
+/// fetched values are kept. The sort is stable, so the result depends only on the input order
+/// and output stays byte stable across runs.
+pub fn sort_and_dedup(klines: &mut Vec<Kline>) {
+    klines.sort_by(|a, b| a.dedup_key().cmp(&b.dedup_key()));
+    // `dedup_by` keeps the first of a run, so move each later duplicate into the kept slot
+    klines.dedup_by(|later, kept| {
+        let duplicate = later.dedup_key() == kept.dedup_key();
+        if duplicate {
+            std::mem::swap(later, kept);
+        }
+        duplicate
+    });
+}
+
+/// Single price of a bar, for `--price-field` output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum PriceField {
+    Open,
+    High,
+    Low,
+    Close,
+    /// Typical price, (high + low + close) / 3.
+    Hlc3,
+    /// Average price, (open + high + low + close) / 4.
+    Ohlc4,
+}
+
+impl PriceField {
+    /// Column name, eg/ `hlc3`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PriceField::Open => "open",
+            PriceField::High => "high",
+            PriceField::Low => "low",
+            PriceField::Close => "close",
+            PriceField::Hlc3 => "hlc3",
+            PriceField::Ohlc4 => "ohlc4",
+        }
+    }
+
+    pub fn value(&self, kline: &Kline) -> f64 {
+        match self {
+            PriceField::Open => kline.open_price,
+            PriceField::High => kline.high_price,
+            PriceField::Low => kline.low_price,
+            PriceField::Close => kline.close_price,
+            PriceField::Hlc3 => (kline.high_price + kline.low_price + kline.close_price) / 3.0,
+            PriceField::Ohlc4 => {
+                (kline.open_price + kline.high_price + kline.low_price + kline.close_price) / 4.0
+            }
+        }
+    }
+}
+
+/// Remove traded klines with zero volume, eg/ the many empty bars of an illiquid symbol,
+/// returning how many were dropped. Mark, index and premium index klines carry no volume and
+/// are kept.
+pub fn drop_zero_volume(klines: &mut Vec<Kline>) -> usize {
+    let before = klines.len();
+    klines.retain(|kline| !kline.kline_type.has_volume() || kline.volume != 0.0);
+    before - klines.len()
+}
+
+/// Keep every `every`th kline of each series, starting with its first, eg/ bars 0, 10, 20, ...
+/// for 10. A straight decimation for plotting, the bars kept are unchanged rather than
+/// aggregated over the ones dropped. Merged series (kline types and categories) are counted
+/// independently, so each keeps the same bars it would alone.
+pub fn decimate(klines: &mut Vec<Kline>, every: usize) {
+    let mut seen = HashMap::<(KlineType, Category), usize>::new();
+    klines.retain(|kline| {
+        let index = seen.entry((kline.kline_type, kline.category)).or_default();
+        let keep = index.is_multiple_of(every);
+        *index += 1;
+        keep
+    });
+}
+
+/// Order a series is emitted in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum SeriesOrder {
+    /// Oldest bar first
+    #[default]
+    Asc,
+    /// Newest bar first
+    Desc,
+}
+
+/// Put an ascending series in `order`, then keep its first `head` or last `tail` klines in that
+/// order, so with [`SeriesOrder::Desc`] `head` keeps the newest bars and `tail` the oldest.
+pub fn order_and_slice(klines: &mut Vec<Kline>, order: SeriesOrder, head: Option<usize>, tail: Option<usize>) {
+    if order == SeriesOrder::Desc {
+        klines.reverse();
+    }
+    if let Some(head) = head {
+        klines.truncate(head);
+    }
+    if let Some(tail) = tail {
+        klines.drain(..klines.len().saturating_sub(tail));
+    }
+}
+
+/// Check klines are strictly ascending by [`DedupKey`], ie/ ordered by time with no bar
+/// repeated within a series, failing with the start times of the first offending pair.
+pub fn validate_monotonic<'a>(
+    klines: impl IntoIterator<Item = &'a Kline>,
+) -> Result<(), BybitError> {
+    let mut previous: Option<&Kline> = None;
+    for kline in klines {
+        if let Some(previous) = previous.filter(|previous| previous.dedup_key() >= kline.dedup_key()) {
+            return Err(BybitError::NotMonotonic {
+                previous: previous.start_time,
+                next: kline.start_time,
+            });
+        }
+        previous = Some(kline);
+    }
+    Ok(())
+}
+
+/// Flat linear trade kline for tests, every price at `close_price` with 10 base coin traded
+/// at it; set other fields with struct update syntax.
+#[cfg(test)]
+pub(crate) fn test_kline(start_time: u64, close_price: f64) -> Kline {
+    Kline {
+        start_time,
+        open_price: close_price,
+        high_price: close_price,
+        low_price: close_price,
+        close_price,
+        volume: 10.0,
+        turnover: 10.0 * close_price,
+        kline_type: KlineType::Trade,
+        category: Category::Linear,
+        filled: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kline_json_round_trip() {
+        // Index style klines carry no volume
+        let kline = Kline {
+            volume: 0.0,
+            turnover: 0.0,
+            kline_type: KlineType::PremiumIndex,
+            ..test_kline(1704067200000, 0.0001)
+        };
+        let json = serde_json::to_string(&kline).unwrap();
+        assert_eq!(
+            json,
+            r#"{"start_time":1704067200000,"open_price":0.0001,"high_price":0.0001,"low_price":0.0001,"close_price":0.0001,"volume":0.0,"turnover":0.0,"kline_type":"premium-index","category":"linear"}"#
+        );
+
+        let parsed = serde_json::from_str::<Kline>(&json).unwrap();
+        assert_eq!(parsed.dedup_key(), kline.dedup_key());
+        assert_eq!(parsed.close_price, kline.close_price);
+        assert_eq!(parsed.filled, None);
+    }
+
+    #[test]
+    fn test_parse_rows_with_one_bad_row() {
+        let row = |start: &str, open: &str| {
+            [start, open, "2", "0.5", "1.5", "10", "15"]
+                .map(str::to_string)
+                .to_vec()
+        };
+        let page = || {
+            vec![
+                row("0", "1"),
+                row("60000", "not-a-price"),
+                row("120000", "1"),
+            ]
+        };
+
+        assert!(matches!(
+            parse_rows(page(), KlineType::Trade, Category::Linear, false),
+            Err(BybitError::ApiError { .. })
+        ));
+
+        let (klines, skipped) = parse_rows(page(), KlineType::Trade, Category::Linear, true).unwrap();
+        assert_eq!(skipped, 1);
+        let starts = klines.iter().map(|k| k.start_time).collect::<Vec<_>>();
+        assert_eq!(starts, vec![0, 120000]);
+    }
+
+    #[test]
+    fn test_base_and_quote_volume_per_category() {
+        let raw = |category, volume, turnover| Kline {
+            volume,
+            turnover,
+            category,
+            ..test_kline(0, 100.0)
+        };
+
+        // Spot and linear: volume is base coin, turnover quote coin
+        for category in [Category::Spot, Category::Linear] {
+            let kline = raw(category, 8.0, 800.0);
+            assert_eq!((kline.base_volume(), kline.quote_volume()), (8.0, 800.0));
+        }
+
+        // Inverse: volume is contracts (USD), turnover base coin
+        let kline = raw(Category::Inverse, 800.0, 8.0);
+        assert_eq!((kline.base_volume(), kline.quote_volume()), (8.0, 800.0));
+    }
+
+    #[test]
+    fn test_validate_monotonic() {
+        let mut klines = vec![
+            test_kline(1, 100.0),
+            Kline { kline_type: KlineType::Mark, ..test_kline(1, 100.5) },
+            test_kline(2, 102.0),
+        ];
+        sort_and_dedup(&mut klines);
+        assert!(validate_monotonic(&klines).is_ok());
+        assert!(validate_monotonic(&[]).is_ok());
+
+        let duplicate = [test_kline(1, 100.0), test_kline(1, 100.0)];
+        assert!(matches!(
+            validate_monotonic(&duplicate),
+            Err(BybitError::NotMonotonic { previous: 1, next: 1 })
+        ));
+
+        let reversed = [test_kline(3, 100.0), test_kline(2, 100.0)];
+        assert!(matches!(
+            validate_monotonic(&reversed),
+            Err(BybitError::NotMonotonic { previous: 3, next: 2 })
+        ));
+    }
+
+    #[test]
+    fn test_sort_and_dedup_keeps_later_fetched_revision() {
+        // Two overlapping pages, the second revising the close of the bar at 2
+        let first_page = vec![test_kline(3, 103.0), test_kline(2, 102.0), test_kline(1, 101.0)];
+        let second_page = vec![test_kline(4, 104.0), test_kline(2, 102.5)];
+        let mut klines = [first_page.clone(), second_page.clone()].concat();
+        sort_and_dedup(&mut klines);
+        let closes = klines.iter().map(|k| (k.start_time, k.close_price)).collect::<Vec<_>>();
+        assert_eq!(closes, vec![(1, 101.0), (2, 102.5), (3, 103.0), (4, 104.0)]);
+
+        // Fetched the other way round, the other revision is the later one
+        let mut klines = [second_page, first_page].concat();
+        sort_and_dedup(&mut klines);
+        assert_eq!(klines[1].close_price, 102.0);
+
+        // A bar repeated three times keeps its last value
+        let mut klines = vec![test_kline(1, 1.0), test_kline(1, 2.0), test_kline(1, 3.0)];
+        sort_and_dedup(&mut klines);
+        assert_eq!(klines.len(), 1);
+        assert_eq!(klines[0].close_price, 3.0);
+    }
+
+    #[test]
+    fn test_drop_zero_volume() {
+        let mut klines = vec![
+            test_kline(0, 1.0),
+            Kline { volume: 0.0, ..test_kline(1, 1.0) },
+            Kline { volume: 0.0, kline_type: KlineType::Mark, ..test_kline(1, 1.0) },
+        ];
+        assert_eq!(drop_zero_volume(&mut klines), 1);
+        let kept = klines.iter().map(|k| (k.start_time, k.kline_type)).collect::<Vec<_>>();
+        assert_eq!(kept, vec![(0, KlineType::Trade), (1, KlineType::Mark)]);
+    }
+
+    #[test]
+    fn test_order_and_slice() {
+        let sliced = |order, head, tail| {
+            let mut klines = (0..5).map(|i| test_kline(i, 1.0)).collect::<Vec<_>>();
+            order_and_slice(&mut klines, order, head, tail);
+            klines.iter().map(|k| k.start_time).collect::<Vec<_>>()
+        };
+        assert_eq!(sliced(SeriesOrder::Asc, None, None), vec![0, 1, 2, 3, 4]);
+        assert_eq!(sliced(SeriesOrder::Desc, None, None), vec![4, 3, 2, 1, 0]);
+
+        // Head and tail follow the output order
+        assert_eq!(sliced(SeriesOrder::Asc, Some(2), None), vec![0, 1]);
+        assert_eq!(sliced(SeriesOrder::Asc, None, Some(2)), vec![3, 4]);
+        assert_eq!(sliced(SeriesOrder::Desc, Some(2), None), vec![4, 3]);
+        assert_eq!(sliced(SeriesOrder::Desc, None, Some(2)), vec![1, 0]);
+        assert_eq!(sliced(SeriesOrder::Desc, Some(10), None), vec![4, 3, 2, 1, 0]);
+    }
+
+    #[test]
+    fn test_decimate_per_series() {
+        let mut klines = (0..25)
+            .flat_map(|i| [test_kline(i, i as f64), Kline { kline_type: KlineType::Mark, ..test_kline(i, i as f64) }])
+            .collect::<Vec<_>>();
+        decimate(&mut klines, 10);
+        let kept = klines.iter().map(|k| (k.start_time, k.kline_type)).collect::<Vec<_>>();
+        assert_eq!(
+            kept,
+            vec![
+                (0, KlineType::Trade),
+                (0, KlineType::Mark),
+                (10, KlineType::Trade),
+                (10, KlineType::Mark),
+                (20, KlineType::Trade),
+                (20, KlineType::Mark),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sort_and_dedup_keeps_distinct_kline_types_at_same_timestamp() {
+        let mut klines = vec![
+            Kline { kline_type: KlineType::Mark, ..test_kline(2, 101.0) },
+            test_kline(1, 100.0),
+            Kline { kline_type: KlineType::Mark, ..test_kline(1, 100.5) },
+            test_kline(2, 102.0),
+            test_kline(1, 100.0),
+        ];
+
+        sort_and_dedup(&mut klines);
+
+        let keys = klines
+            .iter()
+            .map(|k| (k.start_time, k.kline_type))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            keys,
+            vec![
+                (1, KlineType::Trade),
+                (1, KlineType::Mark),
+                (2, KlineType::Trade),
+                (2, KlineType::Mark),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_from_vec_price_only_kline_type() {
+        let row = ["1670608800000", "17071", "17073", "17027", "17055.5"]
+            .map(String::from)
+            .to_vec();
+
+        let kline = Kline::from_vec(row.clone(), KlineType::Mark, Category::Linear).unwrap();
+        assert_eq!(kline.close_price, 17055.5);
+        assert_eq!(kline.volume, 0.0);
+
+        assert!(Kline::from_vec(row, KlineType::Trade, Category::Linear).is_err());
+    }
+
+    #[test]
+    fn test_from_vec_row_lengths_per_category() {
+        let row = |fields: &[&str]| fields.iter().map(|field| field.to_string()).collect::<Vec<_>>();
+        let linear = row(&["1670608800000", "17071", "17073", "17027", "17055.5", "268611", "15.74"]);
+        let kline = Kline::from_vec(linear.clone(), KlineType::Trade, Category::Linear).unwrap();
+        assert_eq!((kline.volume, kline.turnover), (268611.0, 15.74));
+
+        // Trailing fields are ignored rather than shifting the documented positions
+        let mut extended = linear.clone();
+        extended.push("1".to_string());
+        let kline = Kline::from_vec(extended, KlineType::Trade, Category::Spot).unwrap();
+        assert_eq!((kline.close_price, kline.volume, kline.turnover), (17055.5, 268611.0, 15.74));
+
+        // Spot rows may omit turnover, other categories may not
+        let short = linear[..6].to_vec();
+        let kline = Kline::from_vec(short.clone(), KlineType::Trade, Category::Spot).unwrap();
+        assert_eq!((kline.volume, kline.turnover), (268611.0, 0.0));
+        assert!(Kline::from_vec(short, KlineType::Trade, Category::Linear).is_err());
+        assert!(Kline::from_vec(linear[..5].to_vec(), KlineType::Trade, Category::Spot).is_err());
+    }
+}
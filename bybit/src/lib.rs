@@ -0,0 +1,1582 @@
+//! Fetch historical and live kline (candlestick) data from Bybit's v5 API.
+//!
+//! The `bybit-kline` binary is a thin command line wrapper around [`BybitClient`].
+
+pub mod anomaly;
+pub mod arrow_stream;
+pub mod barter;
+pub mod benchmark;
+pub mod cache;
+pub mod categories;
+pub mod category;
+pub mod checksum;
+pub mod chunking;
+pub mod continuity;
+pub mod credentials;
+pub mod delivery;
+pub mod gaps;
+pub mod gzip;
+pub mod incremental;
+pub mod instrument;
+pub mod interval;
+pub mod jobs;
+pub mod kline;
+pub mod live;
+pub mod ohlcv_binary;
+pub mod output;
+pub mod parquet_writer;
+#[cfg(test)]
+mod mock_server;
+mod preflight;
+pub mod probe;
+pub mod progress;
+pub mod rate_limit;
+pub mod request_log;
+pub mod retry;
+pub mod server_time;
+pub mod size_estimate;
+pub mod spec_change;
+pub mod state;
+pub mod summary;
+pub mod table;
+pub mod trades;
+pub mod validate;
+pub mod window;
+pub mod writer;
+
+use benchmark::TransferStats;
+use cache::{CacheKey, KlineCache};
+use category::Category;
+use chunking::ChunkController;
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use chrono_tz::Tz;
+use credentials::Credentials;
+use futures_util::{stream, Stream, StreamExt, TryStreamExt};
+use interval::Interval;
+use kline::{Kline, KlineType};
+use rate_limit::RateLimiter;
+use request_log::{RequestLog, RequestLogEntry};
+use reqwest::{header::HeaderMap, Client, StatusCode};
+use retry::{Jitter, RetryPolicy};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    pin::pin,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+use thiserror::Error;
+use tokio::sync::{OnceCell, Semaphore};
+use window::{CompletedWindows, FetchEstimate, FetchPlan, Pagination, Window};
+
+/// Delay before refetching an empty page near the current time, see
+/// [`BybitClient::with_retry_on_empty`].
+const EMPTY_PAGE_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// `User-Agent` sent with every request unless overridden, see
+/// [`BybitClient::with_default_headers`].
+pub const DEFAULT_USER_AGENT: &str = concat!("bybit-kline/", env!("CARGO_PKG_VERSION"));
+
+#[derive(Error, Debug)]
+pub enum BybitError {
+    #[error("HTTP request failed: {0}")]
+    RequestError(#[from] reqwest::Error),
+    #[error("JSON parsing failed: {0}")]
+    JsonError(#[from] serde_json::Error),
+    #[error("Date parsing failed: {0}")]
+    DateParseError(String),
+    #[error("API error: {msg}")]
+    ApiError { msg: String },
+    #[error("Invalid argument: {0}")]
+    ArgumentError(String),
+    #[error("WebSocket error: {0}")]
+    WebSocketError(Box<tokio_tungstenite::tungstenite::Error>),
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("Parquet error: {0}")]
+    ParquetError(#[from] parquet::errors::ParquetError),
+    #[error("API error {code}: {msg}")]
+    ServerError { code: i32, msg: String },
+    #[error("Retry budget of {budget} exhausted, last error: {source}")]
+    RetryBudgetExhausted { budget: u32, source: Box<BybitError> },
+    #[error("Rate limited, retry after {retry_after:?}")]
+    RateLimited { retry_after: Duration },
+    #[error("Checksum mismatch: expected {expected}, got {actual}")]
+    ChecksumMismatch { expected: String, actual: String },
+    #[error("Klines not strictly increasing: {previous} followed by {next}")]
+    NotMonotonic { previous: u64, next: u64 },
+    #[error("Continuity check failed: {discrepancies} sampled bars differ from Bybit")]
+    ContinuityMismatch { discrepancies: usize },
+    #[error("Validation failed: {issues} issues found")]
+    ValidationFailed { issues: usize },
+}
+
+impl From<tokio_tungstenite::tungstenite::Error> for BybitError {
+    fn from(error: tokio_tungstenite::tungstenite::Error) -> Self {
+        BybitError::WebSocketError(Box::new(error))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct BybitResponse<T> {
+    #[serde(rename = "retCode")]
+    ret_code: i32,
+    #[serde(rename = "retMsg")]
+    ret_msg: String,
+    result: Option<T>,
+    time: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct KlineResult {
+    symbol: String,
+    category: String,
+    list: Vec<Vec<String>>,
+}
+
+/// Page of a cursor paginated endpoint, see [`BybitClient::get_all_pages`].
+#[derive(Debug, Deserialize)]
+struct CursorPage<T> {
+    list: Vec<T>,
+    #[serde(rename = "nextPageCursor", default)]
+    next_page_cursor: String,
+}
+
+/// Parameters identifying a single kline series and the window to fetch.
+#[derive(Debug, Clone)]
+pub struct KlineQuery {
+    pub symbol: String,
+    pub category: Category,
+    pub interval: Interval,
+    pub kline_type: KlineType,
+    pub start: u64,
+    pub end: u64,
+    /// Cap on the klines fetched, [`window::UNLIMITED_RECORDS`] (0) for none.
+    pub max_records: u32,
+    /// Klines requested per API call, at most Bybit's limit of 1000.
+    pub limit_per_request: u32,
+}
+
+/// Progress of a fetch, reported after each chunk completes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FetchProgress {
+    /// Klines fetched so far.
+    pub fetched: usize,
+    /// Klines expected for the whole query, an upper bound (see [`FetchEstimate`]).
+    pub expected: u64,
+    /// Start time of the latest kline fetched so far.
+    pub last_time: Option<u64>,
+}
+
+pub struct BybitClient {
+    client: Client,
+    /// Idle connections kept per host, matching the in-flight limit.
+    pool_max_idle: usize,
+    base_url: String,
+    /// Bounds the number of requests in flight at once, shared by every fetch using this client.
+    inflight: Arc<Semaphore>,
+    /// Whether to probe API health before the first fetch.
+    run_preflight: bool,
+    preflight_done: OnceCell<()>,
+    /// Optional on-disk cache of kline responses.
+    cache: Option<KlineCache>,
+    /// Retry limits shared by every request using this client.
+    retry: RetryPolicy,
+    jitter: Jitter,
+    /// Paces requests from Bybit's rate limit headers.
+    rate_limiter: RateLimiter,
+    /// Offset of Bybit's clock from the local clock, synced on first use.
+    server_time_offset: OnceCell<i64>,
+    /// `recvWindow` of signed requests.
+    recv_window_ms: u64,
+    /// API key pair for signed requests, `None` for public endpoints only.
+    credentials: Option<Credentials>,
+    /// Retries of an empty page near the current time before concluding the data ended.
+    retry_on_empty: u32,
+    /// Target latency of `--adaptive-chunking`, `None` to always request `limit_per_request`.
+    adaptive_chunking: Option<Duration>,
+    /// Optional JSON lines log of every kline request.
+    request_log: Option<RequestLog>,
+    /// Skip malformed kline rows instead of failing the fetch.
+    best_effort: bool,
+    /// Sort each page and the collected klines by start time.
+    sort: bool,
+    /// Drop klines overlapping the previous page and duplicates in the collected klines.
+    dedup: bool,
+    pagination: Pagination,
+    skipped_rows: AtomicUsize,
+    transfer: TransferStats,
+}
+
+impl BybitClient {
+    pub fn new(testnet: bool, max_inflight: usize) -> Result<Self, BybitError> {
+        let base_url = if testnet {
+            "https://api-testnet.bybit.com".to_string()
+        } else {
+            "https://api.bybit.com".to_string()
+        };
+
+        Ok(Self {
+            client: http_client(max_inflight, HeaderMap::new())?,
+            pool_max_idle: max_inflight,
+            base_url,
+            inflight: Arc::new(Semaphore::new(max_inflight)),
+            run_preflight: true,
+            preflight_done: OnceCell::new(),
+            cache: None,
+            retry: RetryPolicy::default(),
+            jitter: Jitter::default(),
+            rate_limiter: RateLimiter::default(),
+            server_time_offset: OnceCell::new(),
+            recv_window_ms: server_time::DEFAULT_RECV_WINDOW_MS,
+            credentials: None,
+            retry_on_empty: 0,
+            adaptive_chunking: None,
+            request_log: None,
+            best_effort: false,
+            sort: true,
+            dedup: true,
+            pagination: Pagination::Time,
+            skipped_rows: AtomicUsize::new(0),
+            transfer: TransferStats::default(),
+        })
+    }
+
+    /// Send requests to `base_url` instead of Bybit's mainnet or testnet API (eg/ a proxy).
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into();
+        self
+    }
+
+    /// Send `headers` with every REST request, eg/ a `User-Agent` identifying the caller in
+    /// place of [`DEFAULT_USER_AGENT`], or headers an intermediary proxy requires.
+    pub fn with_default_headers(mut self, headers: HeaderMap) -> Result<Self, BybitError> {
+        self.client = http_client(self.pool_max_idle, headers)?;
+        Ok(self)
+    }
+
+    pub fn with_preflight(mut self, enabled: bool) -> Self {
+        self.run_preflight = enabled;
+        self
+    }
+
+    pub fn with_cache(mut self, cache: Option<KlineCache>) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn with_jitter(mut self, jitter: Jitter) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// How long after its timestamp a signed request stays valid, see
+    /// [`BybitClient::signing_params`].
+    pub fn with_recv_window(mut self, recv_window_ms: u64) -> Self {
+        self.recv_window_ms = recv_window_ms;
+        self
+    }
+
+    /// API key pair to sign private requests with, see [`credentials::resolve`].
+    pub fn with_credentials(mut self, credentials: Option<Credentials>) -> Self {
+        self.credentials = credentials;
+        self
+    }
+
+    pub fn credentials(&self) -> Option<&Credentials> {
+        self.credentials.as_ref()
+    }
+
+    /// Retry an empty page up to `retries` times when its window reaches the current time,
+    /// since Bybit can return nothing for the latest bars for a few seconds before they
+    /// populate, instead of treating it as the end of the data.
+    pub fn with_retry_on_empty(mut self, retries: u32) -> Self {
+        self.retry_on_empty = retries;
+        self
+    }
+
+    /// Adapt the klines requested per chunk to observed latency and retries, see
+    /// [`ChunkController`], starting from each query's `limit_per_request`.
+    pub fn with_adaptive_chunking(mut self, target_latency: Option<Duration>) -> Self {
+        self.adaptive_chunking = target_latency;
+        self
+    }
+
+    /// Record the outcome of every kline request, see [`RequestLog`].
+    pub fn with_request_log(mut self, request_log: Option<RequestLog>) -> Self {
+        self.request_log = request_log;
+        self
+    }
+
+    /// Log and skip malformed kline rows rather than failing the fetch, see
+    /// [`BybitClient::skipped_rows`].
+    pub fn with_best_effort(mut self, best_effort: bool) -> Self {
+        self.best_effort = best_effort;
+        self
+    }
+
+    /// Keep klines exactly as Bybit returned them, including bars repeated across overlapping
+    /// pages, rather than deduplicating them. A diagnostic aid for studying the API's
+    /// pagination.
+    pub fn with_dedup(mut self, dedup: bool) -> Self {
+        self.dedup = dedup;
+        self
+    }
+
+    /// Keep each page in the order Bybit returned it (newest first) and pages in fetch order,
+    /// rather than sorting by start time. Deduplication then only trims each page against the
+    /// previous one.
+    pub fn with_sort(mut self, sort: bool) -> Self {
+        self.sort = sort;
+        self
+    }
+
+    /// Page through ranges by time windows (the default) or by bar count, see [`Pagination`].
+    pub fn with_pagination(mut self, pagination: Pagination) -> Self {
+        self.pagination = pagination;
+        self
+    }
+
+    /// Malformed kline rows skipped so far with [`BybitClient::with_best_effort`].
+    pub fn skipped_rows(&self) -> usize {
+        self.skipped_rows.load(Ordering::SeqCst)
+    }
+
+    /// Requests made so far, with the bytes received and their latency.
+    pub fn transfer_stats(&self) -> &TransferStats {
+        &self.transfer
+    }
+
+    /// Issue a GET request against a Bybit v5 endpoint, returning the `result` payload of a
+    /// successful response.
+    ///
+    /// Transient failures are retried with jittered exponential backoff, within the client's
+    /// [`RetryPolicy`]. Rate limited requests instead wait exactly as long as Bybit advertises.
+    pub(crate) async fn get<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &[(&str, &str)],
+    ) -> Result<T, BybitError> {
+        self.get_counting_retries(path, query, &mut 0).await
+    }
+
+    /// Every entry of a cursor paginated v5 endpoint, requesting pages with `query` until
+    /// Bybit returns an empty `nextPageCursor`.
+    pub(crate) async fn get_all_pages<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &[(&str, &str)],
+    ) -> Result<Vec<T>, BybitError> {
+        let mut entries = Vec::new();
+        let mut cursor = String::new();
+        loop {
+            let mut page_query = query.to_vec();
+            if !cursor.is_empty() {
+                page_query.push(("cursor", &cursor));
+            }
+            let page = self.get::<CursorPage<T>>(path, &page_query).await?;
+            entries.extend(page.list);
+            // A cursor pointing back at the same page would never end
+            if page.next_page_cursor.is_empty() || page.next_page_cursor == cursor {
+                return Ok(entries);
+            }
+            cursor = page.next_page_cursor;
+        }
+    }
+
+    /// [`BybitClient::get`], counting the retries it consumes into `attempt`.
+    async fn get_counting_retries<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &[(&str, &str)],
+        attempt: &mut u32,
+    ) -> Result<T, BybitError> {
+        loop {
+            match self.get_once(path, query).await {
+                Ok(result) => return Ok(result),
+                Err(error) => {
+                    // Waits advertised by Bybit are exact, only guessed backoff is jittered
+                    let advertised = error.retry_after().is_some();
+                    let backoff = self.retry.should_retry(*attempt, error)?;
+                    let backoff = if advertised {
+                        backoff
+                    } else {
+                        self.jitter.apply(backoff)
+                    };
+                    tokio::time::sleep(backoff).await;
+                    *attempt += 1;
+                }
+            }
+        }
+    }
+
+    async fn get_once<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &[(&str, &str)],
+    ) -> Result<T, BybitError> {
+        let url = format!("{}{}", self.base_url, path);
+
+        self.rate_limiter.wait().await;
+        let _permit = self
+            .inflight
+            .acquire()
+            .await
+            .expect("inflight semaphore is never closed");
+        let sent = Instant::now();
+        let response = self.client.get(&url).query(query).send().await?;
+        self.rate_limiter.observe(response.headers());
+        let retry_after = rate_limit::retry_after(response.headers());
+        let status = response.status();
+        if let (StatusCode::TOO_MANY_REQUESTS, Some(retry_after)) = (status, retry_after) {
+            self.rate_limiter.pause(retry_after);
+            return Err(BybitError::RateLimited { retry_after });
+        }
+        let body = response.error_for_status()?.bytes().await?;
+        self.transfer.record(body.len(), sent.elapsed());
+        let response = serde_json::from_slice::<BybitResponse<T>>(&body)?;
+
+        // Bybit also signals rate limits through the retCode of a 200 response
+        let ret_code = response.ret_code;
+        if let (retry::RATE_LIMITED_RET_CODE, Some(retry_after)) = (ret_code, retry_after) {
+            self.rate_limiter.pause(retry_after);
+            return Err(BybitError::RateLimited { retry_after });
+        }
+        if retry::TRANSIENT_RET_CODES.contains(&response.ret_code) {
+            return Err(BybitError::ServerError {
+                code: response.ret_code,
+                msg: response.ret_msg,
+            });
+        }
+        if response.ret_code != 0 {
+            return Err(BybitError::ApiError {
+                msg: response.ret_msg,
+            });
+        }
+
+        response.result.ok_or_else(|| BybitError::ApiError {
+            msg: "No result data".to_string(),
+        })
+    }
+
+    async fn get_kline_single(
+        &self,
+        query: &KlineQuery,
+        start: u64,
+        end: u64,
+        limit: u32,
+    ) -> Result<Vec<Kline>, BybitError> {
+        let requested = Instant::now();
+        let mut retries = 0;
+        let result = self
+            .fetch_kline_single(query, start, end, limit, &mut retries)
+            .await;
+
+        if let Some(request_log) = &self.request_log {
+            let (returned, status, error) = match &result {
+                Ok((klines, true)) => (klines.len(), "cached", None),
+                Ok((klines, false)) => (klines.len(), "ok", None),
+                Err(error) => (0, "error", Some(error.to_string())),
+            };
+            request_log.record(&RequestLogEntry {
+                ts: RequestLogEntry::now(),
+                symbol: &query.symbol,
+                category: query.category.as_str(),
+                interval: query.interval.as_str(),
+                start,
+                end,
+                limit,
+                returned,
+                status,
+                retries,
+                latency_ms: requested.elapsed().as_millis() as u64,
+                error,
+            });
+        }
+
+        result.map(|(klines, _)| klines)
+    }
+
+    /// Fetch a single page, from the cache when possible, returning the klines and whether
+    /// they were cached.
+    async fn fetch_kline_single(
+        &self,
+        query: &KlineQuery,
+        start: u64,
+        end: u64,
+        limit: u32,
+        retries: &mut u32,
+    ) -> Result<(Vec<Kline>, bool), BybitError> {
+        let cache_key = CacheKey {
+            base_url: &self.base_url,
+            endpoint: query.kline_type.endpoint(),
+            category: query.category.as_str(),
+            symbol: &query.symbol,
+            interval: query.interval.as_str(),
+            start,
+            end,
+            limit,
+        };
+        if let Some(klines) = self.cache.as_ref().and_then(|cache| cache.get(&cache_key)) {
+            return Ok((klines, true));
+        }
+
+        let result = self
+            .get_counting_retries::<KlineResult>(
+                query.kline_type.endpoint(),
+                &[
+                    ("category", query.category.as_str()),
+                    ("symbol", query.symbol.as_str()),
+                    ("interval", query.interval.as_str()),
+                    ("start", &start.to_string()),
+                    ("end", &end.to_string()),
+                    ("limit", &limit.to_string()),
+                ],
+                retries,
+            )
+            .await?;
+
+        let (klines, skipped) = kline::parse_rows(
+            result.list,
+            query.kline_type,
+            query.category,
+            self.best_effort,
+        )?;
+        self.skipped_rows.fetch_add(skipped, Ordering::SeqCst);
+
+        // A failed cache write only costs a refetch next time, so don't fail the pull over it.
+        // Empty pages aren't cached, the latest bars may only populate seconds later
+        if let Some(cache) = self.cache.as_ref().filter(|_| !klines.is_empty()) {
+            if let Err(error) = cache.put(&cache_key, &klines) {
+                log::warn!("failed to write kline cache: {}", error);
+            }
+        }
+
+        Ok((klines, false))
+    }
+
+    /// Retry limits and usage shared by every request using this client.
+    pub fn retry(&self) -> &RetryPolicy {
+        &self.retry
+    }
+
+    /// Fetch every kline of the query, paginating through the range.
+    ///
+    /// `progress` is invoked after each chunk, pass `|_| {}` to ignore it.
+    pub async fn get_kline(
+        &self,
+        query: &KlineQuery,
+        progress: impl FnMut(FetchProgress),
+    ) -> Result<Vec<Kline>, BybitError> {
+        let mut all_klines = Vec::new();
+        self.get_kline_streaming(query, progress, |klines| {
+            all_klines.extend(klines);
+            Ok(())
+        })
+        .await?;
+
+        // Final sort and deduplication
+        match (self.sort, self.dedup) {
+            (true, true) => kline::sort_and_dedup(&mut all_klines),
+            (true, false) => all_klines.sort_by_key(|k| k.start_time),
+            (false, _) => {}
+        }
+
+        Ok(all_klines)
+    }
+
+    /// Fetch klines chunk by chunk, passing each chunk to `on_chunk` as soon as it completes
+    /// rather than buffering the whole range.
+    ///
+    /// Chunks are fetched in time order and trimmed to start after the previous chunk's last
+    /// kline, so `on_chunk` observes a single ascending, duplicate free series, unless disabled
+    /// with [`BybitClient::with_sort`] or [`BybitClient::with_dedup`]. [`Pagination::Count`]
+    /// walks the range backward, so its pages are buffered and passed as a single chunk.
+    pub async fn get_kline_streaming<F>(
+        &self,
+        query: &KlineQuery,
+        mut progress: impl FnMut(FetchProgress),
+        mut on_chunk: F,
+    ) -> Result<(), BybitError>
+    where
+        F: FnMut(Vec<Kline>) -> Result<(), BybitError>,
+    {
+        let expected = FetchEstimate::new(
+            query.start,
+            query.end,
+            query.interval,
+            window::record_cap(query.max_records),
+            query.limit_per_request,
+        )
+        .records;
+
+        let mut fetched = 0;
+        let mut last_time = None;
+        let mut backward = Vec::new();
+        let mut pages = pin!(self.get_kline_paged(query));
+        while let Some(page) = pages.try_next().await? {
+            fetched += page.len();
+            last_time = page.iter().map(|kline| kline.start_time).max().max(last_time);
+            match self.pagination {
+                Pagination::Time => on_chunk(page)?,
+                Pagination::Count => backward.push(page),
+            }
+            progress(FetchProgress {
+                fetched,
+                expected,
+                last_time,
+            });
+        }
+
+        // Backward pages arrive newest first
+        if !backward.is_empty() {
+            if self.sort {
+                backward.reverse();
+            }
+            on_chunk(backward.concat())?;
+        }
+        Ok(())
+    }
+
+    /// Like [`BybitClient::get_kline_streaming`], but requesting up to `concurrency` windows
+    /// of the range at once.
+    ///
+    /// The range is split up front into the windows of a [`FetchPlan`]. Windows complete in
+    /// any order but are passed to `on_chunk` in time order: a window which finishes early is
+    /// held until every earlier one has been passed on, so at most `concurrency` chunks are
+    /// buffered. Each chunk is trimmed to start after the previous one's last kline, so
+    /// `on_chunk` observes the same ascending series, unless disabled with
+    /// [`BybitClient::with_sort`] or [`BybitClient::with_dedup`].
+    ///
+    /// Every planned window is requested even if an earlier one came back short, so with a
+    /// record cap and gaps in the data fewer klines may be passed on than a sequential fetch
+    /// would find. [`Pagination::Count`] and monthly bars, whose windows can't be planned
+    /// exactly, fall back to [`BybitClient::get_kline_streaming`].
+    pub async fn get_kline_concurrent<F>(
+        &self,
+        query: &KlineQuery,
+        concurrency: usize,
+        mut progress: impl FnMut(FetchProgress),
+        mut on_chunk: F,
+    ) -> Result<(), BybitError>
+    where
+        F: FnMut(Vec<Kline>) -> Result<(), BybitError>,
+    {
+        if self.pagination == Pagination::Count || query.interval == Interval::Month {
+            return self.get_kline_streaming(query, progress, on_chunk).await;
+        }
+        self.warn_preflight().await;
+
+        let plan = FetchPlan::new(
+            query.start,
+            query.end,
+            query.interval,
+            query.max_records,
+            query.limit_per_request,
+        );
+        let expected = plan.estimated_bars;
+        let cap = window::record_cap(query.max_records) as usize;
+        // Bybit's end is inclusive, so each request stops a millisecond short of its window's end
+        let mut chunks = pin!(stream::iter(plan.chunks)
+            .map(|chunk| async move {
+                let window = Window { start: chunk.start, end: chunk.end };
+                let klines = self.get_kline_single(query, chunk.start, chunk.end - 1, chunk.limit).await?;
+                Ok::<_, BybitError>((window, klines))
+            })
+            .buffered(concurrency.max(1)));
+
+        // Windows merge through the completed map, so one re-issued after it completed, eg/
+        // by a retry after a timeout Bybit actually served, isn't passed on twice
+        let mut completed = CompletedWindows::default();
+        while let Some((window, mut klines)) = chunks.try_next().await? {
+            if completed.is_complete(&window) {
+                log::warn!(
+                    "{} {}: window [{}, {}) was served twice, its klines are merged once",
+                    query.symbol,
+                    query.interval,
+                    window.start,
+                    window.end
+                );
+                continue;
+            }
+            if self.sort {
+                klines.sort_by_key(|k| k.start_time);
+            }
+            if let (true, Some(last_time)) = (self.dedup, completed.last().map(|k| k.start_time)) {
+                klines.retain(|k| k.start_time > last_time);
+            }
+            klines.truncate(cap - completed.len());
+            completed.complete(window, klines);
+            let klines = completed.drain();
+            progress(FetchProgress {
+                fetched: completed.len(),
+                expected,
+                last_time: completed.last().map(|kline| kline.start_time),
+            });
+            if !klines.is_empty() {
+                on_chunk(klines)?;
+            }
+            if completed.len() >= cap {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Warn once per client, before committing to a long pull, if the API appears degraded.
+    async fn warn_preflight(&self) {
+        if self.run_preflight {
+            self.preflight_done
+                .get_or_init(|| async {
+                    for warning in self.preflight().await {
+                        log::warn!("{}", warning);
+                    }
+                })
+                .await;
+        }
+    }
+
+    /// Fetch klines lazily as a stream of pages, one per API request which returned data,
+    /// so callers can process or time each page on its own.
+    ///
+    /// Pages follow the same ordering guarantees as [`BybitClient::get_kline_streaming`],
+    /// which along with [`BybitClient::get_kline`] is built on top of this, except that with
+    /// [`Pagination::Count`] pages arrive newest first.
+    pub fn get_kline_paged<'a>(
+        &'a self,
+        query: &'a KlineQuery,
+    ) -> impl Stream<Item = Result<Vec<Kline>, BybitError>> + 'a {
+        let pager = KlinePager {
+            client: self,
+            query,
+            fetched: 0,
+            last_time: None,
+            current_start: query.start,
+            current_end: query.end,
+            max_records: window::record_cap(query.max_records),
+            chunking: self
+                .adaptive_chunking
+                .map(|target| ChunkController::new(query.limit_per_request, target)),
+            started: false,
+        };
+        stream::try_unfold(pager, |mut pager| async move {
+            Ok(pager.next_page().await?.map(|page| (page, pager)))
+        })
+    }
+}
+
+/// HTTP client sending [`DEFAULT_USER_AGENT`] and `headers`, which take precedence, with every
+/// request.
+fn http_client(pool_max_idle: usize, headers: HeaderMap) -> Result<Client, BybitError> {
+    Ok(Client::builder()
+        .pool_max_idle_per_host(pool_max_idle)
+        .user_agent(DEFAULT_USER_AGENT)
+        .default_headers(headers)
+        .build()?)
+}
+
+/// Pagination state of a single query, see [`BybitClient::get_kline_paged`].
+struct KlinePager<'a> {
+    client: &'a BybitClient,
+    query: &'a KlineQuery,
+    /// Klines passed on so far, counted against the record cap.
+    fetched: usize,
+    /// Start of the latest kline received.
+    last_time: Option<u64>,
+    current_start: u64,
+    /// Exclusive end of the next [`Pagination::Count`] request.
+    current_end: u64,
+    max_records: u32,
+    chunking: Option<ChunkController>,
+    started: bool,
+}
+
+impl KlinePager<'_> {
+    /// Fetch the next page, `None` once the range or record cap is exhausted.
+    async fn next_page(&mut self) -> Result<Option<Vec<Kline>>, BybitError> {
+        let client = self.client;
+        if self.started {
+            // Add a small, jittered delay between pages to avoid rate limiting
+            tokio::time::sleep(client.jitter.apply(tokio::time::Duration::from_millis(100))).await;
+        } else {
+            self.started = true;
+            client.warn_preflight().await;
+        }
+
+        match client.pagination {
+            Pagination::Time => self.next_time_page().await,
+            Pagination::Count => self.next_count_page().await,
+        }
+    }
+
+    /// Next page walking forward, requesting the window of `limit` bars from the bar after the
+    /// last one received.
+    async fn next_time_page(&mut self) -> Result<Option<Vec<Kline>>, BybitError> {
+        let client = self.client;
+        let query = self.query;
+        let end = query.end;
+        let interval_ms = query.interval.duration_ms();
+        if self.current_start >= end || (self.fetched as u32) >= self.max_records {
+            return Ok(None);
+        }
+
+        // Calculate how many more records we need
+        let remaining_records = self.max_records - (self.fetched as u32);
+        let chunk_limit = self
+            .chunking
+            .map_or(query.limit_per_request, |chunking| chunking.limit());
+        let current_chunk_limit = std::cmp::min(chunk_limit, remaining_records);
+        log::debug!(
+            "{} {}: requesting {} klines from {}",
+            query.symbol,
+            query.interval,
+            current_chunk_limit,
+            self.current_start
+        );
+
+        // Calculate the end time for this chunk
+        let chunk_end = std::cmp::min(
+            self.current_start + (current_chunk_limit as u64 * interval_ms),
+            end,
+        );
+
+        // Bybit's end is inclusive, so a window ending on a bar's start would hold `limit + 1`
+        // bars and the newest `limit` served would skip the first one
+        let request_end = chunk_end - 1;
+        let requested = Instant::now();
+        let retries = client.retry.used();
+        let mut chunk_klines = client
+            .get_kline_single(query, self.current_start, request_end, current_chunk_limit)
+            .await?;
+        // Retries are counted client wide, so concurrent fetches also slow each other down
+        if let Some(chunking) = &mut self.chunking {
+            chunking.observe(requested.elapsed(), client.retry.used() - retries);
+        }
+
+        // Bybit can serve an empty page for the latest bars before they populate
+        let mut empty_retries = 0;
+        while chunk_klines.is_empty()
+            && empty_retries < client.retry_on_empty
+            && chunk_end + interval_ms >= Utc::now().timestamp_millis() as u64
+        {
+            empty_retries += 1;
+            tokio::time::sleep(client.jitter.apply(EMPTY_PAGE_RETRY_DELAY)).await;
+            chunk_klines = client
+                .get_kline_single(query, self.current_start, request_end, current_chunk_limit)
+                .await?;
+        }
+
+        if chunk_klines.is_empty() {
+            return Ok(None);
+        }
+
+        // Sort by start_time to ensure proper ordering
+        if client.sort {
+            chunk_klines.sort_by_key(|k| k.start_time);
+        }
+
+        // Remove duplicates if any (based on start_time)
+        if let (true, Some(last_time)) = (client.dedup, self.last_time) {
+            chunk_klines.retain(|k| k.start_time > last_time);
+        }
+
+        // Limit the chunk to not exceed max_records
+        let space_left = self.max_records as usize - self.fetched;
+        if chunk_klines.len() > space_left {
+            chunk_klines.truncate(space_left);
+        }
+        self.fetched += chunk_klines.len();
+        self.last_time = chunk_klines.iter().map(|k| k.start_time).max().max(self.last_time);
+
+        // Move to next chunk - start from the last kline's time + interval, stopping once
+        // the max_records limit is reached, or once the latest bar reaches the end of the range
+        // or the current time, since a short final page means no later bar exists yet
+        let now = Utc::now().timestamp_millis() as u64;
+        match self.last_time.map(|last_time| last_time + interval_ms) {
+            Some(next_start) if (self.fetched as u32) < self.max_records && next_start < end.min(now) => {
+                if next_start > self.current_start {
+                    self.current_start = next_start;
+                } else {
+                    // A page holding only bars already received would re-issue the same window
+                    log::warn!(
+                        "{} {}: the window from {} was answered with bars already received, ending the fetch early after {} klines",
+                        query.symbol,
+                        query.interval,
+                        self.current_start,
+                        self.fetched
+                    );
+                    self.current_start = end;
+                }
+            }
+            _ => self.current_start = end,
+        }
+
+        Ok(Some(chunk_klines))
+    }
+
+    /// Next page walking backward, requesting the `limit` bars before the oldest one received.
+    ///
+    /// With a record cap this keeps the latest bars of the range, where walking forward keeps
+    /// the earliest.
+    async fn next_count_page(&mut self) -> Result<Option<Vec<Kline>>, BybitError> {
+        let client = self.client;
+        let query = self.query;
+        if self.current_end <= query.start || (self.fetched as u32) >= self.max_records {
+            return Ok(None);
+        }
+
+        let remaining_records = self.max_records - (self.fetched as u32);
+        let chunk_limit = self
+            .chunking
+            .map_or(query.limit_per_request, |chunking| chunking.limit());
+        let current_chunk_limit = std::cmp::min(chunk_limit, remaining_records);
+        log::debug!(
+            "{} {}: requesting {} klines before {}",
+            query.symbol,
+            query.interval,
+            current_chunk_limit,
+            self.current_end
+        );
+
+        let requested = Instant::now();
+        let retries = client.retry.used();
+        // Bybit's end is inclusive, stop a millisecond short of the oldest bar received
+        let mut chunk_klines = client
+            .get_kline_single(query, query.start, self.current_end - 1, current_chunk_limit)
+            .await?;
+        if let Some(chunking) = &mut self.chunking {
+            chunking.observe(requested.elapsed(), client.retry.used() - retries);
+        }
+
+        if client.dedup {
+            chunk_klines.retain(|k| k.start_time >= query.start && k.start_time < self.current_end);
+        }
+        // A page exceeding the record cap keeps its latest bars
+        if chunk_klines.len() > remaining_records as usize {
+            chunk_klines.sort_by_key(|k| std::cmp::Reverse(k.start_time));
+            chunk_klines.truncate(remaining_records as usize);
+        }
+        if client.sort {
+            chunk_klines.sort_by_key(|k| k.start_time);
+        }
+
+        // Stop on an empty page, or one which doesn't reach further back
+        match chunk_klines.iter().map(|k| k.start_time).min() {
+            Some(oldest) if oldest < self.current_end => self.current_end = oldest,
+            Some(_) => self.current_end = query.start,
+            None => return Ok(None),
+        }
+
+        self.fetched += chunk_klines.len();
+        Ok(Some(chunk_klines))
+    }
+}
+
+pub fn parse_date(date_str: &str) -> Result<u64, BybitError> {
+    parse_date_in(date_str, &Utc)
+}
+
+/// Parse a YYYY/MM/DD date as midnight in the provided timezone, returning UTC millis.
+pub fn parse_date_in<Z: TimeZone>(date_str: &str, tz: &Z) -> Result<u64, BybitError> {
+    let date = NaiveDate::parse_from_str(date_str, "%Y/%m/%d")
+        .map_err(|e| BybitError::DateParseError(format!("Invalid date format '{}': {}", date_str, e)))?;
+    
+    let datetime = date.and_hms_opt(0, 0, 0)
+        .ok_or_else(|| BybitError::DateParseError("Invalid time".to_string()))?;
+    
+    let local_datetime = tz.from_local_datetime(&datetime).earliest().ok_or_else(|| {
+        BybitError::DateParseError(format!("Midnight does not exist on '{}' in the requested timezone", date_str))
+    })?;
+    Ok(local_datetime.timestamp_millis() as u64)
+}
+
+/// Interpret the requested dates as local midnights in `tz`, warning when they don't fall on
+/// the 00:00 UTC boundaries Bybit aligns daily bars to, and optionally snapping them back to
+/// the start of the enclosing UTC day.
+pub fn daily_range_in_tz(start_date: &str, end_date: &str, tz: Tz, snap: bool) -> Result<(u64, u64), BybitError> {
+    let day_ms = Interval::Day.duration_ms();
+    let mut range = [parse_date_in(start_date, &tz)?, parse_date_in(end_date, &tz)?];
+
+    for (label, timestamp) in ["start", "end"].into_iter().zip(range.iter_mut()) {
+        let misalignment = *timestamp % day_ms;
+        if misalignment == 0 {
+            continue;
+        }
+
+        let format_utc = |millis: u64| {
+            DateTime::from_timestamp_millis(millis as i64)
+                .unwrap_or_else(|| DateTime::from_timestamp(0, 0).unwrap())
+                .format("%Y-%m-%d %H:%M UTC")
+        };
+        log::warn!(
+            "{} date midnight in {} is {}, but Bybit daily bars are aligned to 00:00 UTC",
+            label, tz, format_utc(*timestamp)
+        );
+        if snap {
+            *timestamp -= misalignment;
+            log::info!("Snapped {} to {}", label, format_utc(*timestamp));
+        }
+    }
+
+    Ok((range[0], range[1]))
+}
+
+/// For `--strict-interval-alignment`: fail unless the `label` bound of the range falls on a
+/// bar start of `interval` (see [`Interval::bar_start`]), instead of letting Bybit snap it
+/// silently, suggesting the aligned times either side of it.
+pub fn check_alignment(label: &str, timestamp: u64, interval: Interval) -> Result<(), BybitError> {
+    let before = interval.bar_start(timestamp);
+    if before == timestamp {
+        return Ok(());
+    }
+    let after = interval.next_start(before);
+    let format_utc = |millis: u64| {
+        DateTime::from_timestamp_millis(millis as i64)
+            .unwrap_or_else(|| DateTime::from_timestamp(0, 0).unwrap())
+            .format("%Y-%m-%d %H:%M UTC")
+    };
+    Err(BybitError::ArgumentError(format!(
+        "{} {} ({}) isn't aligned to the {} interval, the nearest aligned times are {} ({}) and {} ({})",
+        label,
+        format_utc(timestamp),
+        timestamp,
+        interval,
+        format_utc(before),
+        before,
+        format_utc(after),
+        after
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mock_server::MockResponse;
+    use std::sync::atomic::AtomicUsize;
+
+    #[test]
+    fn test_check_alignment() {
+        // Midnight UTC is aligned up to daily bars
+        let monday = parse_date("2024/01/01").unwrap();
+        for interval in [Interval::Min15, Interval::Hour4, Interval::Day, Interval::Week, Interval::Month] {
+            assert!(check_alignment("start", monday, interval).is_ok());
+        }
+
+        // A 15 minute fetch from 09:07 suggests 09:00 and 09:15
+        let misaligned = monday + (9 * 60 + 7) * 60_000;
+        let error = check_alignment("start", misaligned, Interval::Min15).unwrap_err().to_string();
+        assert!(error.contains("2024-01-01 09:00 UTC (1704099600000) and 2024-01-01 09:15 UTC (1704100500000)"), "{}", error);
+
+        // Weekly bars start on Mondays and monthly ones on the first of the month
+        let wednesday = parse_date("2024/01/03").unwrap();
+        assert_eq!(Interval::Week.bar_start(wednesday), monday);
+        assert!(check_alignment("start", wednesday, Interval::Week).is_err());
+        assert_eq!(Interval::Month.bar_start(parse_date("2024/02/29").unwrap()), parse_date("2024/02/01").unwrap());
+        let error = check_alignment("end", wednesday, Interval::Month).unwrap_err().to_string();
+        assert!(error.contains("2024-01-01 00:00 UTC") && error.contains("2024-02-01 00:00 UTC"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn test_retry_on_empty_page_near_now() {
+        let interval_ms = Interval::Min1.duration_ms();
+        let now = Utc::now().timestamp_millis() as u64;
+        let start = now - now % interval_ms;
+
+        // The latest bar only appears on the second request
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        let base_url = mock_server::serve(move |_| {
+            match counter.fetch_add(1, Ordering::SeqCst) {
+                0 => MockResponse::klines("linear", &[]),
+                _ => MockResponse::klines("linear", &[start]),
+            }
+        })
+        .await;
+        let client = BybitClient::new(false, 1)
+            .unwrap()
+            .with_base_url(base_url)
+            .with_preflight(false)
+            .with_retry_on_empty(2);
+        let query = KlineQuery {
+            symbol: "BTCUSDT".to_string(),
+            category: Category::Linear,
+            interval: Interval::Min1,
+            kline_type: KlineType::Trade,
+            start,
+            end: now + 1,
+            max_records: 10,
+            limit_per_request: 10,
+        };
+
+        let klines = client.get_kline(&query, |_| {}).await.unwrap();
+        assert_eq!(klines.len(), 1);
+        assert_eq!(klines[0].start_time, start);
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_default_headers_sent_with_requests() {
+        let start = 1704067200000;
+        let base_url = mock_server::serve(move |request| {
+            let headers = request.to_ascii_lowercase();
+            match (
+                headers.contains("\r\nuser-agent: research-desk/1.0\r\n"),
+                headers.contains("\r\nx-proxy-token: abc\r\n"),
+            ) {
+                (true, true) => MockResponse::klines("linear", &[start]),
+                _ => MockResponse::status("403 Forbidden"),
+            }
+        })
+        .await;
+        let query = KlineQuery {
+            symbol: "BTCUSDT".to_string(),
+            category: Category::Linear,
+            interval: Interval::Min1,
+            kline_type: KlineType::Trade,
+            start,
+            end: start + Interval::Min1.duration_ms(),
+            max_records: 10,
+            limit_per_request: 10,
+        };
+        let client = |headers| {
+            BybitClient::new(false, 1)
+                .unwrap()
+                .with_base_url(base_url.clone())
+                .with_preflight(false)
+                .with_retry(RetryPolicy::new(0, None))
+                .with_default_headers(headers)
+                .unwrap()
+        };
+
+        assert!(client(HeaderMap::new()).get_kline(&query, |_| {}).await.is_err());
+
+        let mut headers = HeaderMap::new();
+        headers.insert(reqwest::header::USER_AGENT, "research-desk/1.0".parse().unwrap());
+        headers.insert("x-proxy-token", "abc".parse().unwrap());
+        let klines = client(headers).get_kline(&query, |_| {}).await.unwrap();
+        assert_eq!(klines.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_transfer_stats_count_requests() {
+        let start = 1704067200000;
+        let step = Interval::Min1.duration_ms();
+        let base_url = mock_server::serve(move |request| {
+            let page_start = mock_server::query_param(request, "start").unwrap().parse::<u64>().unwrap();
+            MockResponse::klines("linear", &[page_start, page_start + step])
+        })
+        .await;
+        let client = BybitClient::new(false, 1)
+            .unwrap()
+            .with_base_url(base_url)
+            .with_preflight(false);
+        let query = KlineQuery {
+            symbol: "BTCUSDT".to_string(),
+            category: Category::Linear,
+            interval: Interval::Min1,
+            kline_type: KlineType::Trade,
+            start,
+            end: start + 4 * step,
+            max_records: 100,
+            limit_per_request: 2,
+        };
+
+        let klines = client.get_kline(&query, |_| {}).await.unwrap();
+        assert_eq!(klines.len(), 4);
+        let stats = client.transfer_stats();
+        assert_eq!(stats.requests(), 2);
+        assert!(stats.bytes() > 0);
+    }
+
+    #[tokio::test]
+    async fn test_short_final_page_near_now_ends_fetch() {
+        let interval_ms = Interval::Min1.duration_ms();
+        let now = Utc::now().timestamp_millis() as u64;
+        let forming = now - now % interval_ms;
+        let start = forming - 7 * interval_ms;
+
+        // Bars exist up to the one still forming, so the second page is short
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        let base_url = mock_server::serve(move |request| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            let param = |name| {
+                mock_server::query_param(request, name)
+                    .and_then(|value| value.parse::<u64>().ok())
+                    .unwrap_or_default()
+            };
+            let bars = (param("start")..param("end").min(forming + 1))
+                .step_by(interval_ms as usize)
+                .take(param("limit") as usize)
+                .collect::<Vec<_>>();
+            MockResponse::klines("linear", &bars)
+        })
+        .await;
+        let client = BybitClient::new(false, 1)
+            .unwrap()
+            .with_base_url(base_url)
+            .with_preflight(false)
+            .with_retry_on_empty(2);
+        // An end date beyond now, eg/ tomorrow
+        let query = KlineQuery {
+            symbol: "BTCUSDT".to_string(),
+            category: Category::Linear,
+            interval: Interval::Min1,
+            kline_type: KlineType::Trade,
+            start,
+            end: forming + 60 * interval_ms,
+            max_records: window::UNLIMITED_RECORDS,
+            limit_per_request: 5,
+        };
+
+        let klines = client.get_kline(&query, |_| {}).await.unwrap();
+        assert_eq!(klines.len(), 8);
+        assert_eq!(klines.last().unwrap().start_time, forming);
+        // No request (or empty page retry) for bars which can't exist yet
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_get_option_klines() {
+        let start = 1724400000000;
+        let base_url = mock_server::serve(move |request| {
+            match (
+                mock_server::query_param(request, "category"),
+                mock_server::query_param(request, "symbol"),
+            ) {
+                (Some("option"), Some("BTC-30AUG24-60000-C")) => {
+                    MockResponse::klines("option", &[start])
+                }
+                _ => MockResponse::status("400 Bad Request"),
+            }
+        })
+        .await;
+        let client = BybitClient::new(false, 1)
+            .unwrap()
+            .with_base_url(base_url)
+            .with_preflight(false);
+        let query = KlineQuery {
+            symbol: "BTC-30AUG24-60000-C".to_string(),
+            category: Category::Option,
+            interval: Interval::Hour1,
+            kline_type: KlineType::Trade,
+            start,
+            end: start + Interval::Hour1.duration_ms(),
+            max_records: 10,
+            limit_per_request: 10,
+        };
+
+        let klines = client.get_kline(&query, |_| {}).await.unwrap();
+        assert_eq!(klines.len(), 1);
+        assert_eq!(klines[0].category, Category::Option);
+        assert_eq!((klines[0].base_volume(), klines[0].quote_volume()), (10.0, 15.0));
+        assert_eq!(barter::exchange_name(klines[0].category), "bybit_options");
+    }
+
+    #[tokio::test]
+    async fn test_get_kline_paged_yields_one_page_per_request() {
+        let interval_ms = Interval::Min1.duration_ms();
+        let start = 1704067200000;
+
+        // Serve every bar of the requested window, up to the limit
+        let base_url = mock_server::serve(move |request| {
+            let param = |name| {
+                mock_server::query_param(request, name)
+                    .and_then(|value| value.parse::<u64>().ok())
+                    .unwrap_or_default()
+            };
+            let bars = (param("start")..param("end"))
+                .step_by(interval_ms as usize)
+                .take(param("limit") as usize)
+                .collect::<Vec<_>>();
+            MockResponse::klines("linear", &bars)
+        })
+        .await;
+        let client = BybitClient::new(false, 1)
+            .unwrap()
+            .with_base_url(base_url)
+            .with_preflight(false);
+        let query = KlineQuery {
+            symbol: "BTCUSDT".to_string(),
+            category: Category::Linear,
+            interval: Interval::Min1,
+            kline_type: KlineType::Trade,
+            start,
+            end: start + 5 * interval_ms,
+            max_records: 10,
+            limit_per_request: 2,
+        };
+
+        let pages = client
+            .get_kline_paged(&query)
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        let sizes = pages.iter().map(Vec::len).collect::<Vec<_>>();
+        assert_eq!(sizes, vec![2, 2, 1]);
+
+        let paged = pages.concat().iter().map(|k| k.start_time).collect::<Vec<_>>();
+        let collected = client.get_kline(&query, |_| {}).await.unwrap();
+        let collected = collected.iter().map(|k| k.start_time).collect::<Vec<_>>();
+        assert_eq!(paged, collected);
+        assert_eq!(collected.len(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_count_pagination_matches_time_pagination() {
+        let interval_ms = Interval::Min1.duration_ms();
+        let start = 1704067200000;
+
+        // Like Bybit, serve the latest `limit` bars of the window newest first, with bars
+        // 10 and 11 missing
+        let base_url = mock_server::serve(move |request| {
+            let param = |name| {
+                mock_server::query_param(request, name)
+                    .and_then(|value| value.parse::<u64>().ok())
+                    .unwrap_or_default()
+            };
+            let bars = (param("start")..param("end"))
+                .filter(|time| time % interval_ms == 0)
+                .filter(|time| !(10..12).contains(&((time - start) / interval_ms)))
+                .rev()
+                .take(param("limit") as usize)
+                .collect::<Vec<_>>();
+            MockResponse::klines("linear", &bars)
+        })
+        .await;
+        let query = KlineQuery {
+            symbol: "BTCUSDT".to_string(),
+            category: Category::Linear,
+            interval: Interval::Min1,
+            kline_type: KlineType::Trade,
+            start,
+            end: start + 23 * interval_ms,
+            max_records: window::UNLIMITED_RECORDS,
+            limit_per_request: 5,
+        };
+        let client = |pagination| {
+            BybitClient::new(false, 1)
+                .unwrap()
+                .with_base_url(base_url.clone())
+                .with_preflight(false)
+                .with_pagination(pagination)
+        };
+        let bars = |klines: &[Kline]| {
+            klines
+                .iter()
+                .map(|k| (k.start_time - start) / interval_ms)
+                .collect::<Vec<_>>()
+        };
+
+        let by_time = client(Pagination::Time).get_kline(&query, |_| {}).await.unwrap();
+        let by_count = client(Pagination::Count).get_kline(&query, |_| {}).await.unwrap();
+        assert_eq!(bars(&by_count), bars(&by_time));
+        assert_eq!(by_count.len(), 21);
+
+        // Pages walk backward from the end of the range
+        let count = client(Pagination::Count);
+        let pages = count.get_kline_paged(&query).try_collect::<Vec<_>>().await.unwrap();
+        let pages = pages.iter().map(|page| bars(page)).collect::<Vec<_>>();
+        assert_eq!(pages[0], vec![18, 19, 20, 21, 22]);
+        assert_eq!(pages[2], vec![6, 7, 8, 9, 12]);
+        assert_eq!(pages.last().unwrap(), &vec![0]);
+
+        // A record cap keeps the latest bars
+        let capped = KlineQuery { max_records: 7, ..query.clone() };
+        let klines = count.get_kline(&capped, |_| {}).await.unwrap();
+        assert_eq!(bars(&klines), vec![16, 17, 18, 19, 20, 21, 22]);
+    }
+
+    #[tokio::test]
+    async fn test_record_cap_below_limit_keeps_first_bars() {
+        let interval_ms = Interval::Hour1.duration_ms();
+        let start = 1704067200000;
+
+        // Like Bybit, `end` is inclusive and the latest `limit` bars of the window are served
+        let base_url = mock_server::serve(move |request| {
+            let param = |name| {
+                mock_server::query_param(request, name)
+                    .and_then(|value| value.parse::<u64>().ok())
+                    .unwrap_or_default()
+            };
+            let bars = (param("start").div_ceil(interval_ms)..=param("end") / interval_ms)
+                .map(|bar| bar * interval_ms)
+                .rev()
+                .take(param("limit") as usize)
+                .collect::<Vec<_>>();
+            MockResponse::klines("linear", &bars)
+        })
+        .await;
+        let client = BybitClient::new(false, 1)
+            .unwrap()
+            .with_base_url(base_url)
+            .with_preflight(false);
+        let query = KlineQuery {
+            symbol: "BTCUSDT".to_string(),
+            category: Category::Linear,
+            interval: Interval::Hour1,
+            kline_type: KlineType::Trade,
+            start,
+            end: start + 365 * Interval::Day.duration_ms(),
+            max_records: 50,
+            limit_per_request: 1000,
+        };
+
+        // A 50 bar window of a year long range holds exactly the first 50 bars
+        let klines = client.get_kline(&query, |_| {}).await.unwrap();
+        let bars = klines
+            .iter()
+            .map(|k| (k.start_time - start) / interval_ms)
+            .collect::<Vec<_>>();
+        assert_eq!(bars, (0..50).collect::<Vec<_>>());
+
+        // Without a cap, consecutive pages neither skip nor repeat the bar at their boundary
+        let uncapped = KlineQuery { max_records: 120, limit_per_request: 50, ..query };
+        let klines = client.get_kline(&uncapped, |_| {}).await.unwrap();
+        let bars = klines
+            .iter()
+            .map(|k| (k.start_time - start) / interval_ms)
+            .collect::<Vec<_>>();
+        assert_eq!(bars, (0..120).collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_chunks_arrive_in_time_order() {
+        let interval_ms = Interval::Min1.duration_ms();
+        let start = 1704067200000;
+
+        // Newest first like Bybit, every bar of the requested window
+        let base_url = mock_server::serve(move |request| {
+            let param = |name| {
+                mock_server::query_param(request, name)
+                    .and_then(|value| value.parse::<u64>().ok())
+                    .unwrap_or_default()
+            };
+            let mut bars = (param("start")..=param("end")).step_by(interval_ms as usize).collect::<Vec<_>>();
+            bars.reverse();
+            MockResponse::klines("linear", &bars)
+        })
+        .await;
+        let client = BybitClient::new(false, 4)
+            .unwrap()
+            .with_base_url(base_url)
+            .with_preflight(false);
+        let query = KlineQuery {
+            symbol: "BTCUSDT".to_string(),
+            category: Category::Linear,
+            interval: Interval::Min1,
+            kline_type: KlineType::Trade,
+            start,
+            end: start + 9 * interval_ms,
+            max_records: 7,
+            limit_per_request: 2,
+        };
+
+        let mut chunks = Vec::new();
+        client
+            .get_kline_concurrent(&query, 4, |_| {}, |klines| {
+                chunks.push(klines.iter().map(|k| (k.start_time - start) / interval_ms).collect::<Vec<_>>());
+                Ok(())
+            })
+            .await
+            .unwrap();
+        assert_eq!(chunks, vec![vec![0, 1], vec![2, 3], vec![4, 5], vec![6]]);
+
+        let sequential = client.get_kline(&query, |_| {}).await.unwrap();
+        let sequential = sequential.iter().map(|k| (k.start_time - start) / interval_ms).collect::<Vec<_>>();
+        assert_eq!(chunks.concat(), sequential);
+    }
+
+    #[tokio::test]
+    async fn test_replayed_window_is_merged_once() {
+        let interval_ms = Interval::Min1.duration_ms();
+        let start = 1704067200000;
+
+        // The first window is served again in reply to the second request, eg/ by a stale cache
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        let base_url = mock_server::serve(move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            MockResponse::klines("linear", &[start + interval_ms, start])
+        })
+        .await;
+        let client = BybitClient::new(false, 1)
+            .unwrap()
+            .with_base_url(base_url)
+            .with_preflight(false);
+        let query = KlineQuery {
+            symbol: "BTCUSDT".to_string(),
+            category: Category::Linear,
+            interval: Interval::Min1,
+            kline_type: KlineType::Trade,
+            start,
+            end: start + 5 * interval_ms,
+            max_records: 10,
+            limit_per_request: 2,
+        };
+
+        // The replay adds nothing and would re-issue the same window, so the fetch ends there,
+        // with a warning that it ended early
+        let klines = client.get_kline(&query, |_| {}).await.unwrap();
+        let starts = klines.iter().map(|k| k.start_time).collect::<Vec<_>>();
+        assert_eq!(starts, vec![start, start + interval_ms]);
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_get_kline_without_sort_or_dedup_keeps_raw_pages() {
+        let interval_ms = Interval::Min1.duration_ms();
+        let start = 1704067200000;
+
+        // Newest first like Bybit, repeating the bar before each window after the first
+        let base_url = mock_server::serve(move |request| {
+            let param = |name| {
+                mock_server::query_param(request, name)
+                    .and_then(|value| value.parse::<u64>().ok())
+                    .unwrap_or_default()
+            };
+            let from = param("start").saturating_sub(interval_ms).max(start);
+            let mut bars = (from..param("end")).step_by(interval_ms as usize).collect::<Vec<_>>();
+            bars.reverse();
+            MockResponse::klines("linear", &bars)
+        })
+        .await;
+        let query = KlineQuery {
+            symbol: "BTCUSDT".to_string(),
+            category: Category::Linear,
+            interval: Interval::Min1,
+            kline_type: KlineType::Trade,
+            start,
+            end: start + 5 * interval_ms,
+            max_records: 10,
+            limit_per_request: 2,
+        };
+        let fetch = |sort, dedup| {
+            let client = BybitClient::new(false, 1)
+                .unwrap()
+                .with_base_url(base_url.clone())
+                .with_preflight(false)
+                .with_sort(sort)
+                .with_dedup(dedup);
+            let query = query.clone();
+            async move {
+                let klines = client.get_kline(&query, |_| {}).await.unwrap();
+                klines
+                    .iter()
+                    .map(|k| (k.start_time - start) / interval_ms)
+                    .collect::<Vec<_>>()
+            }
+        };
+
+        assert_eq!(fetch(true, true).await, vec![0, 1, 2, 3, 4]);
+        assert_eq!(fetch(true, false).await, vec![0, 1, 1, 2, 3, 3, 4]);
+        assert_eq!(fetch(false, false).await, vec![1, 0, 3, 2, 1, 4, 3]);
+    }
+}
@@ -0,0 +1,180 @@
+use crate::{
+    category::Category,
+    interval::Interval,
+    kline::{Kline, KlineType},
+    BybitError,
+};
+use futures_util::{SinkExt, StreamExt};
+use serde::Deserialize;
+use std::time::Duration;
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+
+/// Bybit drops public connections which haven't pinged for 30 seconds.
+const PING_INTERVAL: Duration = Duration::from_secs(20);
+
+/// Kline update pushed on a `kline.{interval}.{symbol}` topic.
+#[derive(Debug, Deserialize)]
+struct LiveKline {
+    start: u64,
+    open: String,
+    high: String,
+    low: String,
+    close: String,
+    volume: String,
+    turnover: String,
+    /// Whether the candle has closed, `false` while it's still forming.
+    confirm: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct LiveMessage {
+    topic: Option<String>,
+    #[serde(default)]
+    data: Vec<LiveKline>,
+    success: Option<bool>,
+    ret_msg: Option<String>,
+    op: Option<String>,
+}
+
+/// Public WebSocket url for a Bybit category.
+pub fn stream_url(testnet: bool, category: Category) -> String {
+    let host = if testnet {
+        "stream-testnet.bybit.com"
+    } else {
+        "stream.bybit.com"
+    };
+    format!("wss://{}/v5/public/{}", host, category)
+}
+
+/// Stream live klines until interrupted with Ctrl-C or the connection closes.
+///
+/// `on_kline` is invoked with each update and whether the candle is confirmed (closed). Only
+/// confirmed candles are passed on unless `include_unconfirmed` is set, in which case the
+/// forming candle is also passed on every tick.
+pub async fn stream_klines<F>(
+    url: &str,
+    symbol: &str,
+    interval: Interval,
+    category: Category,
+    include_unconfirmed: bool,
+    mut on_kline: F,
+) -> Result<(), BybitError>
+where
+    F: FnMut(&Kline, bool) -> Result<(), BybitError>,
+{
+    let (mut websocket, _) = connect_async(url).await?;
+
+    let topic = format!("kline.{}.{}", interval.as_str(), symbol);
+    let subscribe = serde_json::json!({ "op": "subscribe", "args": [topic] });
+    websocket.send(Message::text(subscribe.to_string())).await?;
+
+    let mut ping = tokio::time::interval(PING_INTERVAL);
+    loop {
+        let message = tokio::select! {
+            _ = tokio::signal::ctrl_c() => break,
+            _ = ping.tick() => {
+                websocket.send(Message::text(r#"{"op":"ping"}"#)).await?;
+                continue;
+            }
+            message = websocket.next() => match message {
+                Some(message) => message?,
+                None => break,
+            },
+        };
+
+        let text = match message {
+            Message::Text(text) => text,
+            Message::Close(_) => break,
+            _ => continue,
+        };
+
+        for (kline, confirmed) in
+            parse_message(&text, &topic, category, include_unconfirmed)?
+        {
+            on_kline(&kline, confirmed)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Parse a WebSocket text message into the klines (and their confirmed flag) to emit for
+/// `topic`, skipping forming candles unless `include_unconfirmed` is set.
+fn parse_message(
+    text: &str,
+    topic: &str,
+    category: Category,
+    include_unconfirmed: bool,
+) -> Result<Vec<(Kline, bool)>, BybitError> {
+    let message = serde_json::from_str::<LiveMessage>(text)?;
+    if message.op.as_deref() == Some("subscribe") && message.success == Some(false) {
+        return Err(BybitError::ApiError {
+            msg: format!(
+                "Failed to subscribe to {}: {}",
+                topic,
+                message.ret_msg.unwrap_or_default()
+            ),
+        });
+    }
+    if message.topic.as_deref() != Some(topic) {
+        return Ok(Vec::new());
+    }
+
+    message
+        .data
+        .into_iter()
+        .filter(|update| update.confirm || include_unconfirmed)
+        .map(|update| {
+            let row = vec![
+                update.start.to_string(),
+                update.open,
+                update.high,
+                update.low,
+                update.close,
+                update.volume,
+                update.turnover,
+            ];
+            let kline = Kline::from_vec(row, KlineType::Trade, category)?;
+            Ok((kline, update.confirm))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UPDATE: &str = r#"{
+        "topic": "kline.5.BTCUSDT",
+        "type": "snapshot",
+        "ts": 1672324988882,
+        "data": [
+            {"start": 1672324500000, "end": 1672324799999, "interval": "5", "open": "16640", "close": "16649.5", "high": "16650", "low": "16630", "volume": "3.2", "turnover": "53260.1", "confirm": true, "timestamp": 1672324800000},
+            {"start": 1672324800000, "end": 1672325099999, "interval": "5", "open": "16649.5", "close": "16677", "high": "16677", "low": "16608", "volume": "2.081", "turnover": "34666.4005", "confirm": false, "timestamp": 1672324988882}
+        ]
+    }"#;
+
+    #[test]
+    fn test_parse_message_confirmed_only_by_default() {
+        let klines = parse_message(UPDATE, "kline.5.BTCUSDT", Category::Linear, false).unwrap();
+        assert_eq!(klines.len(), 1);
+        assert_eq!(klines[0].0.start_time, 1672324500000);
+        assert!(klines[0].1);
+    }
+
+    #[test]
+    fn test_parse_message_include_unconfirmed() {
+        let klines = parse_message(UPDATE, "kline.5.BTCUSDT", Category::Linear, true).unwrap();
+        let flags = klines
+            .iter()
+            .map(|(kline, confirmed)| (kline.start_time, *confirmed))
+            .collect::<Vec<_>>();
+        assert_eq!(flags, vec![(1672324500000, true), (1672324800000, false)]);
+
+        // Pong and other topics are ignored
+        let pong = r#"{"success":true,"ret_msg":"pong","conn_id":"abc","op":"ping"}"#;
+        assert!(parse_message(pong, "kline.5.BTCUSDT", Category::Linear, true)
+            .unwrap()
+            .is_empty());
+    }
+}
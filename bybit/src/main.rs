@@ -1,496 +1,2611 @@
-use chrono::{DateTime, NaiveDate, Utc};
-use clap::Parser;
-use reqwest::Client;
-use serde::{Deserialize, Serialize};
-use std::error::Error;
-use thiserror::Error;
-
-#[derive(Error, Debug)]
-pub enum BybitError {
-    #[error("HTTP request failed: {0}")]
-    RequestError(#[from] reqwest::Error),
-    #[error("JSON parsing failed: {0}")]
-    JsonError(#[from] serde_json::Error),
-    #[error("Date parsing failed: {0}")]
-    DateParseError(String),
-    #[error("API error: {msg}")]
-    ApiError { msg: String },
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-struct BybitResponse {
-    #[serde(rename = "retCode")]
-    ret_code: i32,
-    #[serde(rename = "retMsg")]
-    ret_msg: String,
-    result: Option<KlineResult>,
-    time: u64,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-struct KlineResult {
+use bybit_kline::{
+    anomaly,
+    arrow_stream::ArrowStreamWriter,
+    benchmark::{self, BenchmarkReport},
+    categories::CategoryReport,
+    category::{self, Category},
+    checksum,
+    chunking,
+    continuity,
+    credentials::{self, Credentials},
+    delivery,
+    barter::{self, BarterOptions, CompactBarterEvent, ExchangeNames, MergeOrder, TimeReceived, TimestampConvention, TradeWalk},
+    cache::KlineCache,
+    check_alignment, daily_range_in_tz,
+    gaps::{self, GapFill, SegmentMarker},
+    incremental::EmitSnapshot,
+    instrument::{normalize_symbol, OptionSymbol},
+    interval::Interval,
+    jobs,
+    progress::MultiProgress,
+    kline::{self, Kline, KlineType, PriceField, SeriesOrder},
+    live,
+    ohlcv_binary::{self, OhlcvBinaryWriter},
+    output::{self, Compression, Delimited, HumanNumbers, TimestampUnit},
+    parse_date, parquet_writer,
+    request_log::RequestLog,
+    size_estimate::{self, SizeEstimate},
+    spec_change::{self, SpecChange},
+    retry::{Jitter, RetryPolicy},
+    state::StateFile,
+    summary::RangeSummary,
+    table::TableRenderer,
+    trades::{self, TradeSummaries},
+    validate,
+    window::{self, FetchEstimate, FetchPlan, Pagination},
+    writer::{self, BarterWriter, DelimitedWriter, InfluxLineWriter, JsonArrayItems, JsonArrayWriter, JsonlFlatWriter, KlineJsonWriter, OutputWriter, PriceFieldWriter, TableWriter},
+    BybitClient, BybitError, FetchProgress, KlineQuery,
+};
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use clap::{Parser, ValueEnum};
+use futures_util::future::join_all;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, USER_AGENT};
+use std::{error::Error, fs::File, io::{self, BufReader, IsTerminal, Write}, path::{Path, PathBuf}, slice, time::{Duration, Instant}};
+
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// Symbol to fetch (e.g., BTCUSDT), or a comma separated list (e.g., BTCUSDT,ETHUSDT) to fetch several concurrently into one barter stream
+    #[arg(short, long, default_value = "BTCUSDT")]
     symbol: String,
-    category: String,
-    list: Vec<Vec<String>>,
+
+    /// Kline interval in minutes (e.g., 15, 60, 240), or a comma separated list (e.g., 1,5,60) to fetch several timeframes
+    #[arg(short, long, default_value = "15", value_delimiter = ',')]
+    interval: Vec<Interval>,
+
+    /// Start date in YYYY/MM/DD format
+    #[arg(long, required_unless_present_any = ["list_intervals", "dump_schema", "live", "jobs_file", "state_file", "convert_from", "benchmark", "probe_listing_date", "validate_file", "only_final_bar", "since_last_bar_of"])]
+    start_date: Option<String>,
+
+    /// End date in YYYY/MM/DD format
+    #[arg(long, required_unless_present_any = ["list_intervals", "dump_schema", "live", "jobs_file", "state_file", "convert_from", "benchmark", "probe_listing_date", "validate_file", "only_final_bar", "since_last_bar_of"])]
+    end_date: Option<String>,
+
+    /// Category (spot, linear, inverse, option, or all to fetch spot, linear and inverse)
+    #[arg(short, long, default_value = "linear")]
+    category: CategoryArg,
+
+    /// Try these categories in order (e.g., linear,inverse,spot) and fetch from the first with data over the range instead of --category, reporting which was used
+    #[arg(long, value_delimiter = ',', conflicts_with_all = ["category", "state_file", "jobs_file", "live", "convert_from", "benchmark"])]
+    category_fallback: Vec<Category>,
+
+    /// Pick the finest interval whose bar count over the date range fits within --max-records instead of --interval
+    #[arg(long, conflicts_with_all = ["interval", "state_file", "jobs_file", "live", "convert_from"])]
+    interval_auto: bool,
+
+    /// Maximum number of records to fetch (program will automatically paginate), 0 for no cap
+    #[arg(short, long, default_value = "1000")]
+    max_records: u32,
+
+    /// Use testnet instead of mainnet
+    #[arg(long)]
+    testnet: bool,
+
+    /// Output format
+    #[arg(long, value_enum, default_value_t = OutputFormat::Table, requires_if("ohlcv-binary", "output"))]
+    output_format: OutputFormat,
+
+    /// What to fetch: kline (default) or delivery-price, the settlement history of an expiring futures or options contract
+    #[arg(long, value_enum, default_value_t = DataType::Kline)]
+    data_type: DataType,
+
+    /// Objects in --output-format json-array-file: native klines, or barter events
+    #[arg(long, value_enum, default_value_t = JsonArrayItems::Kline)]
+    json_array_items: JsonArrayItems,
+
+    /// Render start times in csv/tsv as 'rfc3339', 'millis' (default), 'seconds' or 'nanos', and influx-line timestamps as millis, seconds or nanos (default)
+    #[arg(long)]
+    timestamp_unit: Option<TimestampUnit>,
+
+    /// Instrument index for barter format (default: 0)
+    #[arg(long, default_value = "0")]
+    instrument_index: usize,
+
+    /// Print every supported interval with its duration and exit
+    #[arg(long)]
+    list_intervals: bool,
+
+    /// Print fully populated sample barter events (a candle, a trade and a trade summary) documenting the emitted JSON shape, and exit
+    #[arg(long)]
+    dump_schema: bool,
+
+    /// Kline type(s) to fetch, comma-separated to merge several
+    #[arg(long, value_enum, value_delimiter = ',', default_value = "trade")]
+    kline_type: Vec<KlineType>,
+
+    /// Emit synthetic barter public trades approximating each kline instead of candles (barter format only)
+    #[arg(long)]
+    synthesize_trades: bool,
+
+    /// Barter event kinds emitted per bar: candle, trade-summary (buy and sell volume, trade count and vwap aggregated from Bybit's recent trades, one extra request, covering only bars since the oldest of the latest 1000 trades, 60 for spot), or both comma separated
+    #[arg(long, value_enum, value_delimiter = ',', default_value = "candle", conflicts_with_all = ["synthesize_trades", "live", "jobs_file", "convert_from", "benchmark"])]
+    data_kind: Vec<DataKind>,
+
+    /// Barter exchange name of spot events, in place of bybit_spot
+    #[arg(long, value_name = "NAME")]
+    exchange_name_spot: Option<String>,
+
+    /// Barter exchange name of linear events, in place of bybit_perpetuals_usd
+    #[arg(long, value_name = "NAME")]
+    exchange_name_linear: Option<String>,
+
+    /// Barter exchange name of inverse events, in place of bybit_perpetuals_usd
+    #[arg(long, value_name = "NAME")]
+    exchange_name_inverse: Option<String>,
+
+    /// Barter exchange name of option events, in place of bybit_options
+    #[arg(long, value_name = "NAME")]
+    exchange_name_option: Option<String>,
+
+    /// Write barter events flattened with short keys (eg/ {t,o,h,l,c,v,i}) instead of the nested Item.Ok.kind.Candle form, see the README for the mapping
+    #[arg(long)]
+    compact_barter: bool,
+
+    /// OHLC path walked by --synthesize-trades
+    #[arg(long, value_enum, default_value_t = TradeWalk::Auto)]
+    trade_walk: TradeWalk,
+
+    /// Maximum number of HTTP requests in flight at once (also caps idle pooled connections per host)
+    #[arg(long, aliases = ["max-concurrency-per-host", "concurrency"], default_value = "4", value_parser = clap::value_parser!(u32).range(1..))]
+    max_inflight: u32,
+
+    /// For the D interval, interpret dates as midnight in this IANA timezone (e.g. Asia/Tokyo) and warn when they don't align with Bybit's 00:00 UTC daily bars
+    #[arg(long)]
+    day_boundary_tz: Option<String>,
+
+    /// With --day-boundary-tz, snap misaligned start/end times back to 00:00 UTC of the same UTC day
+    #[arg(long, requires = "day_boundary_tz")]
+    snap_to_utc_day: bool,
+
+    /// Fail when --start-date or --end-date isn't on a bar start of the interval (multiples of the interval from the epoch, Mondays for W and the first of the month for M), suggesting the nearest aligned times, rather than letting Bybit silently snap the range
+    #[arg(long)]
+    strict_interval_alignment: bool,
+
+    /// Print a single aggregate of the fetched range (OHLC, volume, turnover, VWAP) instead of every kline
+    #[arg(long)]
+    summary_only: bool,
+
+    /// Skip the API liveness and maintenance check performed before fetching
+    #[arg(long)]
+    no_preflight: bool,
+
+    /// Include the quote coin volume as `quote_volume` in barter candles
+    #[arg(long)]
+    barter_quote_volume: bool,
+
+    /// Add a canonical base/quote:settle `symbol` (from instruments-info) and Bybit's `raw_symbol` to barter events
+    #[arg(long)]
+    normalize_symbol: bool,
+
+    /// Stream live klines over Bybit's public WebSocket until interrupted (Ctrl-C) instead of fetching history, to stdout as a table or barter events
+    #[arg(long, conflicts_with = "output")]
+    live: bool,
+
+    /// In live mode, also emit the forming (unconfirmed) candle on every update, flagged as unconfirmed
+    #[arg(long, requires = "live")]
+    include_unconfirmed: bool,
+
+    /// Cache fetched kline ranges on disk under this directory, keyed on the request parameters
+    #[arg(long, env = "BYBIT_KLINE_CACHE_DIR")]
+    cache_dir: Option<PathBuf>,
+
+    /// Seconds a cached range remains valid before it is fetched again
+    #[arg(long, default_value = "3600")]
+    cache_ttl: u64,
+
+    /// Bypass the disk cache, even if a cache directory is configured
+    #[arg(long)]
+    no_cache: bool,
+
+    /// Synthesize klines for missing bars (synthetic bars are flagged in the output)
+    #[arg(long, value_enum)]
+    fill_gaps: Option<GapFill>,
+
+    /// With --fill-gaps, leave gaps of more than N missing bars unfilled (and report them) rather than synthesizing long stretches
+    #[arg(long, value_name = "N", requires = "fill_gaps")]
+    max_gap_fill: Option<u64>,
+
+    /// Decimal places for prices in table output (default: derived from the instrument's tick size, else 4)
+    #[arg(long)]
+    price_decimals: Option<usize>,
+
+    /// Decimal places for volumes in table output (default: derived from the instrument's lot size step, else 4)
+    #[arg(long)]
+    volume_decimals: Option<usize>,
+
+    /// Run every fetch spec in this JSON manifest concurrently, writing each to its own CSV file
+    #[arg(long)]
+    jobs_file: Option<PathBuf>,
+
+    /// Output path template for --jobs-file, supporting {symbol}, {interval}, {category} and {kline_type}
+    #[arg(long, default_value = "{symbol}_{interval}.csv")]
+    jobs_output: String,
+
+    /// Source of barter `time_received` [default: exchange for historical fetches, now for --live]
+    #[arg(long, value_enum)]
+    time_received: Option<TimeReceived>,
+
+    /// Order of a multi-symbol barter stream
+    #[arg(long, value_enum, default_value_t = MergeOrder::PerSymbol)]
+    merge_order: MergeOrder,
+
+    /// Fetch multiple symbols one after another, pausing this long between them, instead of concurrently
+    #[arg(long, default_value = "0", value_name = "MS")]
+    delay_between_symbols_ms: u64,
+
+    /// With several symbols and stderr not a terminal, write a consolidated progress line across all symbols at most this often (on a terminal, a bar per symbol in flight and an overall bar are redrawn in place)
+    #[arg(long, default_value_t = 10, value_name = "SECS")]
+    status_interval_secs: u64,
+
+    /// Merge a multi-symbol barter stream into one globally time ordered stream, shorthand for --merge-order time
+    #[arg(long, conflicts_with = "merge_order")]
+    merge_streams: bool,
+
+    /// Retry an empty page up to N times (about a second apart) when it reaches the current time, rather than concluding the data ended
+    #[arg(long, default_value = "0", value_name = "N")]
+    retry_on_empty: u32,
+
+    /// Adapt the klines requested per chunk, starting at --limit-per-request: grow while requests stay fast, halve on slow or retried ones
+    #[arg(long)]
+    adaptive_chunking: bool,
+
+    /// Write one JSON object per kline request to this file: ts, symbol, category, interval, start, end, limit, returned, status, retries and latency_ms
+    #[arg(long, value_name = "PATH")]
+    request_log: Option<PathBuf>,
+
+    /// Print debug diagnostics on stderr, eg/ the limit requested for each chunk
+    #[arg(long)]
+    verbose: bool,
+
+    /// recvWindow in millis for signed requests, whose timestamps follow Bybit's clock synced from /v5/market/time
+    #[arg(long, default_value = "5000")]
+    recv_window_ms: u64,
+
+    /// API key for signed requests, overriding BYBIT_API_KEY and the credentials file
+    #[arg(long, env = "BYBIT_API_KEY", hide_env_values = true)]
+    api_key: Option<String>,
+
+    /// API secret for signed requests, overriding BYBIT_API_SECRET and the credentials file
+    #[arg(long, env = "BYBIT_API_SECRET", hide_env_values = true)]
+    api_secret: Option<String>,
+
+    /// TOML file with api_key and api_secret (default: ~/.config/bybit-fetch/credentials.toml, if present)
+    #[arg(long)]
+    credentials_file: Option<PathBuf>,
+
+    /// User-Agent sent with every request (default: bybit-kline/<version>)
+    #[arg(long, value_parser = HeaderValue::from_str)]
+    user_agent: Option<HeaderValue>,
+
+    /// Extra header sent with every request as KEY=VALUE, eg/ one a proxy requires. Repeatable
+    #[arg(long = "header", value_name = "KEY=VALUE", value_parser = parse_header)]
+    headers: Vec<(HeaderName, HeaderValue)>,
+
+    /// Timestamp populating barter candles' close_time
+    #[arg(long, value_enum, default_value_t = TimestampConvention::Close)]
+    timestamp_convention: TimestampConvention,
+
+    /// Write the formatted output to this file instead of stdout (required for --output-format parquet).
+    /// Supports {symbol}, {category}, {interval}, {start} and {end} placeholders, parent directories are created
+    #[arg(long)]
+    output: Option<PathBuf>,
+
+    /// Klines per Parquet row group, bounding memory use while streaming a fetch to disk
+    #[arg(long, default_value_t = parquet_writer::DEFAULT_ROW_GROUP_SIZE, value_parser = clap::value_parser!(u64).range(1..))]
+    parquet_row_group_size: u64,
+
+    /// Klines requested per API call (Bybit allows at most 1000)
+    #[arg(long, default_value = "1000", value_parser = clap::value_parser!(u32).range(1..=1000))]
+    limit_per_request: u32,
+
+    /// Print the expected record and API call counts for the range and exit without fetching
+    #[arg(long)]
+    count_only: bool,
+
+    /// Print the estimated size of the --output-format output for the expected records, measured by writing sample klines in that format, and exit without fetching
+    #[arg(long)]
+    estimate_size: bool,
+
+    /// Remember the bars emitted in this JSON snapshot file and, on the next run, emit only bars which are new or whose values changed since, eg/ for dashboards polling the latest bars
+    #[arg(long, value_name = "SNAPSHOT_FILE", conflicts_with_all = ["live", "jobs_file", "convert_from", "split_on_gaps", "split_by_day", "summary_only"])]
+    incremental_emit: Option<PathBuf>,
+
+    /// Treat an empty range (start date at or after the end date) as a successful run emitting zero records instead of an error, eg/ for incremental jobs with nothing new yet
+    #[arg(long)]
+    empty_ok: bool,
+
+    /// Print the pagination plan as JSON ({chunks: [{start, end, limit}], total_chunks, estimated_bars, estimated_seconds}) and exit without fetching, eg/ to distribute chunks across workers
+    #[arg(long, conflicts_with_all = ["pagination", "live", "jobs_file", "convert_from", "count_only", "estimate_size", "benchmark"])]
+    plan_json: bool,
+
+    /// Fetch a fixed window (the date range, or the 10000 bars before --end-date or now) without writing it, and report wall time, bars/s, requests/s, average request latency and bytes transferred
+    #[arg(long, conflicts_with_all = ["live", "jobs_file", "convert_from", "state_file", "count_only", "estimate_size"])]
+    benchmark: bool,
+
+    /// With --output, also echo the formatted output to stdout (text formats only)
+    #[arg(long, requires = "output")]
+    tee: bool,
+
+    /// Retries per request on transient failures (network errors, HTTP 429/5xx, Bybit server and rate limit errors)
+    #[arg(long, default_value = "3")]
+    max_retries: u32,
+
+    /// Cap on the total retries across every request in the run, failing fast once exhausted
+    #[arg(long)]
+    retry_budget: Option<u32>,
+
+    /// Seed the backoff and request delay jitter, so timing driven sequences are reproducible (default: entropy)
+    #[arg(long)]
+    rng_seed: Option<u64>,
+
+    /// Write one file per UTC day under --output-dir as <symbol>/<YYYY-MM-DD>.csv (or .tsv with --output-format tsv)
+    #[arg(long, requires = "output_dir", conflicts_with_all = ["output", "summary_only"])]
+    split_by_day: bool,
+
+    /// When --split-by-day appends to existing day files (with --state-file), skip bars at or before the latest start time in the last N rows of each file, so a run resumed after writing but before saving its state never appends a bar twice (0 appends every bar)
+    #[arg(long, value_name = "N", default_value_t = 1000)]
+    retry_idempotency_window: usize,
+
+    /// Add each bar's close time (start + interval, as barter candles' close_time) to table output as a Close Time column, and to csv and tsv as a trailing close_time column
+    #[arg(long, conflicts_with_all = ["split_by_day", "summary_only", "live", "jobs_file", "convert_from"])]
+    show_close_time: bool,
+
+    /// Write csv or tsv with just two columns, start_time and this price: open, high, low, close, hlc3 ((high + low + close) / 3) or ohlc4 ((open + high + low + close) / 4)
+    #[arg(long, value_enum, conflicts_with_all = ["show_close_time", "split_by_day", "summary_only", "live", "jobs_file"])]
+    price_field: Option<PriceField>,
+
+    /// After fetching, re-request random windows of the fetched range from Bybit and fail if the sampled bars differ from the fetched ones (missing, duplicated, or OHLCV beyond float noise), reporting each mismatch on stderr
+    #[arg(long, conflicts_with_all = ["live", "jobs_file", "convert_from", "no_dedup"])]
+    verify_continuity_against_exchange: bool,
+
+    /// Windows of up to 50 bars re-requested by --verify-continuity-against-exchange (default: 5)
+    #[arg(long, value_name = "N", requires = "verify_continuity_against_exchange")]
+    verify_samples: Option<usize>,
+
+    /// Remove bars with zero volume after fetching, reporting how many were dropped on stderr
+    #[arg(long, conflicts_with_all = ["fill_gaps", "split_on_gaps", "live", "jobs_file"])]
+    drop_zero_volume: bool,
+
+    /// Restate bars before a contract redenomination, as DATE:scale=F (e.g., 2023-06-01:scale=100 multiplies earlier prices by 100 and divides their base volume by 100), repeatable
+    #[arg(long, value_name = "DATE:scale=F", conflicts_with_all = ["live", "jobs_file", "convert_from"])]
+    spec_change: Vec<SpecChange>,
+
+    /// Flag bars whose open, high, low or close deviates from the rolling median close of their neighbours by more than --anomaly-threshold, reporting them on stderr
+    #[arg(long, conflicts_with_all = ["no_sort", "live", "jobs_file", "convert_from"])]
+    detect_anomalies: bool,
+
+    /// Deviation from the rolling median, in percent, beyond which --detect-anomalies flags a bar
+    #[arg(long, default_value_t = anomaly::DEFAULT_THRESHOLD * 100.0, value_name = "PERCENT", requires = "detect_anomalies")]
+    anomaly_threshold: f64,
+
+    /// Drop the bars flagged by --detect-anomalies from the output
+    #[arg(long, requires = "detect_anomalies")]
+    drop_anomalies: bool,
+
+    /// Split the series at every gap into contiguous segments, each written to its own --output file (eg/ out.seg0.csv), or preceded by a segment marker line in kline-json and barter output on stdout
+    #[arg(long, conflicts_with_all = ["fill_gaps", "split_by_day", "summary_only", "tee"])]
+    split_on_gaps: bool,
+
+    /// Write --output as numbered parts (eg/ out.part0.csv, out.part1.csv), moving on to the next part at the first kline after the current one reaches SIZE, so a part overshoots by less than a kline and its footer; every part is a complete file with its own header. SIZE is in bytes, or with a unit: KB, MB, GB (decimal) or KiB, MiB, GiB (binary), eg/ 100MB. With --compress, SIZE counts compressed bytes, which trail the input by up to one 64KiB compression block
+    #[arg(long, value_name = "SIZE", value_parser = size_estimate::parse_bytes, requires = "output", conflicts_with_all = ["split_on_gaps", "split_by_day", "summary_only", "tee", "live", "jobs_file", "convert_from"])]
+    max_file_size: Option<u64>,
+
+    /// Compress --output files, each --max-file-size part or --split-on-gaps segment independently (eg/ --output out.csv.gz). Parquet output ignores it, it's compressed internally
+    #[arg(long, value_enum, default_value_t = Compression::None, requires = "output", conflicts_with = "jobs_file")]
+    compress: Compression,
+
+    /// Root directory for --split-by-day partitions
+    #[arg(long, requires = "split_by_day")]
+    output_dir: Option<PathBuf>,
+
+    /// Format table volumes for readability, with separators when given without a style
+    #[arg(long, value_enum, num_args = 0..=1, default_missing_value = "separators")]
+    human_numbers: Option<HumanNumbers>,
+
+    /// Log and skip malformed kline rows, counting them, instead of failing the fetch
+    #[arg(long, conflicts_with = "fail_fast")]
+    best_effort: bool,
+
+    /// Fail the fetch on the first malformed kline row (default)
+    #[arg(long)]
+    fail_fast: bool,
+
+    /// Page through the range by time windows walking forward (default), or by bar count walking backward from the end like the Bybit UI
+    #[arg(long, value_enum, default_value_t = Pagination::Time)]
+    pagination: Pagination,
+
+    /// Diagnostic: keep klines repeated across overlapping pages instead of deduplicating them, output may contain duplicates
+    #[arg(long, conflicts_with = "fill_gaps")]
+    no_dedup: bool,
+
+    /// Diagnostic: with --no-dedup, also keep each page in Bybit's order (newest first) and pages in fetch order instead of sorting by start time
+    #[arg(long, requires = "no_dedup")]
+    no_sort: bool,
+
+    /// Add the exchange's native timezone ("UTC" for Bybit) to barter events, for multi-exchange merges
+    #[arg(long)]
+    annotate_timezone: bool,
+
+    /// Print symbol,listing_date csv with the UTC date of each --symbol's first daily kline (bisected with a few single kline requests), then exit
+    #[arg(long, conflicts_with_all = ["live", "jobs_file", "convert_from", "benchmark", "probe_intervals"])]
+    probe_listing_date: bool,
+
+    /// Report which intervals have data for the symbol over the range, with their earliest and latest bars, using a few single kline requests per interval
+    #[arg(long)]
+    probe_intervals: bool,
+
+    /// Print a SHA-256 checksum of the fetched klines (canonical order, fixed precision), on stderr for machine readable formats
+    #[arg(long)]
+    print_checksum: bool,
+
+    /// Fail if the checksum of the fetched klines doesn't match this hex digest, detecting revised data
+    #[arg(long)]
+    verify_checksum: Option<String>,
+
+    /// JSON file tracking the last kline fetched per symbol: each run fetches from there up to now (or --end-date) and updates it
+    #[arg(long, conflicts_with_all = ["live", "jobs_file", "summary_only"])]
+    state_file: Option<PathBuf>,
+
+    /// Start the fetch one interval after the latest bar in this existing csv, tsv, kline-json or ohlcv-binary file, instead of --start-date; only read, so the output can go anywhere
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["start_date", "state_file", "live", "jobs_file", "convert_from"])]
+    since_last_bar_of: Option<PathBuf>,
+
+    /// Keep every Nth bar of the sorted, deduplicated series (e.g., 10 keeps bars 0, 10, 20, ...), a plain decimation for plotting which doesn't aggregate the bars in between
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..), conflicts_with_all = ["state_file", "live"])]
+    sample_rate: Option<u64>,
+
+    /// Keep only the first N records of the final sorted, deduplicated series, in --order
+    #[arg(long, value_name = "N", conflicts_with_all = ["tail", "state_file"])]
+    head: Option<usize>,
+
+    /// Keep only the last N records of the final sorted, deduplicated series, in --order
+    #[arg(long, value_name = "N", conflicts_with = "state_file")]
+    tail: Option<usize>,
+
+    /// Order records are written in, asc (oldest first) or desc (newest first), which --head and --tail count from
+    #[arg(long, value_enum, default_value_t = SeriesOrder::Asc, conflicts_with_all = ["state_file", "live", "jobs_file", "split_by_day", "split_on_gaps", "incremental_emit"])]
+    order: SeriesOrder,
+
+    /// Fail, naming the offending timestamps, unless the final series is strictly increasing by start time with no duplicate bars
+    #[arg(long)]
+    validate_monotonic: bool,
+
+    /// Fail a --category all run whose output keeps Bybit's raw volume fields (kline-json, influx-line), whose units differ between inverse and the other categories
+    #[arg(long)]
+    strict_units: bool,
+
+    /// Read klines from this ohlcv-binary file instead of fetching, writing them in --output-format (taken as --category klines)
+    #[arg(long, conflicts_with_all = ["live", "jobs_file", "state_file"])]
+    convert_from: Option<PathBuf>,
+
+    /// Validate a csv, tsv or kline-json file written earlier instead of fetching: OHLC invariants, ascending bars without duplicates and gaps at --interval, printing a report and failing on any issue
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["live", "jobs_file", "state_file", "convert_from", "benchmark", "probe_listing_date"])]
+    validate_file: Option<PathBuf>,
+
+    /// Print only the most recent closed bar of --symbol, as one compact line for table output (or a single csv, tsv, kline-json, jsonl-flat or influx-line record), fetched with a single request up to now
+    #[arg(long, conflicts_with_all = ["start_date", "end_date", "live", "jobs_file", "state_file", "convert_from", "benchmark", "probe_listing_date", "validate_file"])]
+    only_final_bar: bool,
+}
+
+/// `--output-format`: how klines are written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    /// Aligned columns for reading in a terminal
+    Table,
+    /// JSON events compatible with barter backtesting, one per line
+    Barter,
+    /// Native klines, one JSON object per line
+    KlineJson,
+    /// One flat JSON object per bar: time, OHLC, base volume, quote turnover, symbol and category
+    JsonlFlat,
+    /// A single JSON array of klines or barter events
+    JsonArrayFile,
+    /// InfluxDB line protocol
+    InfluxLine,
+    /// Comma separated values
+    Csv,
+    /// Tab separated values
+    Tsv,
+    /// Parquet file, requires --output
+    Parquet,
+    /// 56 byte little-endian records, requires --output
+    OhlcvBinary,
+    /// Arrow IPC stream of record batches, eg/ for pyarrow.ipc.open_stream
+    ArrowStream,
+}
+
+impl OutputFormat {
+    /// Delimiter of csv and tsv output.
+    fn delimited(self) -> Option<Delimited> {
+        match self {
+            OutputFormat::Csv => Some(Delimited::Csv),
+            OutputFormat::Tsv => Some(Delimited::Tsv),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let value = self.to_possible_value().expect("no output format is skipped");
+        f.write_str(value.get_name())
+    }
+}
+
+/// `--data-type`: what to fetch for the symbol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum DataType {
+    /// Klines of the --kline-type series
+    Kline,
+    /// Delivery (settlement) prices of an expiring futures or options contract
+    DeliveryPrice,
+}
+
+/// `--data-kind`: barter events emitted per bar.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum DataKind {
+    /// Candle of the kline
+    Candle,
+    /// Taker buy and sell volume, trade count and vwap of the bar's public trades
+    TradeSummary,
+}
+
+/// `--category`: a single Bybit category, or every category with `all`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum CategoryArg {
+    /// Spot pairs
+    Spot,
+    /// USDT and USDC settled perpetuals and futures
+    Linear,
+    /// Coin settled perpetuals and futures
+    Inverse,
+    /// USDC and USDT settled options, eg/ BTC-30AUG24-60000-C
+    Option,
+    /// Every category, merged into one series
+    All,
+}
+
+impl CategoryArg {
+    /// Categories fetched, in the order they're reported.
+    fn categories(self) -> &'static [Category] {
+        match self {
+            CategoryArg::Spot => &[Category::Spot],
+            CategoryArg::Linear => &[Category::Linear],
+            CategoryArg::Inverse => &[Category::Inverse],
+            CategoryArg::Option => &[Category::Option],
+            CategoryArg::All => Category::all(),
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            CategoryArg::All => "all",
+            category => category.categories()[0].as_str(),
+        }
+    }
+}
+
+impl From<Category> for CategoryArg {
+    fn from(category: Category) -> Self {
+        match category {
+            Category::Spot => CategoryArg::Spot,
+            Category::Linear => CategoryArg::Linear,
+            Category::Inverse => CategoryArg::Inverse,
+            Category::Option => CategoryArg::Option,
+        }
+    }
+}
+
+impl std::fmt::Display for CategoryArg {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Args {
+    /// Category for lookups which only apply to a single category (eg/ instrument precision),
+    /// the first one for `--category all`.
+    fn category(&self) -> Category {
+        self.category.categories()[0]
+    }
+
+    /// First `--interval`, the one used wherever a single interval applies.
+    fn interval(&self) -> Interval {
+        self.interval[0]
+    }
+
+    /// Interval of the close times added by `--show-close-time`.
+    fn close_time(&self) -> Option<Interval> {
+        self.show_close_time.then(|| self.interval())
+    }
+
+    /// `--timestamp-unit` of csv and tsv start times, unix millis by default.
+    fn delimited_timestamp_unit(&self) -> TimestampUnit {
+        self.timestamp_unit.unwrap_or(TimestampUnit::Millis)
+    }
+
+    /// Whether stdout carries machine readable output (barter, csv or tsv), so progress and
+    /// informational messages must be kept off it.
+    fn machine_readable(&self) -> bool {
+        match self.output_format {
+            OutputFormat::Table | OutputFormat::Parquet | OutputFormat::OhlcvBinary => self.plan_json,
+            _ => true,
+        }
+    }
+
+    /// Whether the output holds barter events, either as barter ndjson or a JSON array of them.
+    fn barter_objects(&self) -> bool {
+        match self.output_format {
+            OutputFormat::Barter => true,
+            OutputFormat::JsonArrayFile => self.json_array_items == JsonArrayItems::Barter,
+            _ => false,
+        }
+    }
+
+    /// Barter event settings shared by every historical run, `time_received` defaulting to a
+    /// reproducible one.
+    fn barter_options(&self) -> BarterOptions {
+        BarterOptions {
+            instrument_index: self.instrument_index,
+            quote_volume: self.barter_quote_volume,
+            annotate_timezone: self.annotate_timezone,
+            timestamp_convention: self.timestamp_convention,
+            time_received: self.time_received.unwrap_or(TimeReceived::Exchange),
+            exchange_names: self.exchange_names(),
+            ..Default::default()
+        }
+    }
+
+    /// `--merge-order`, or time order with `--merge-streams`.
+    fn merge_order(&self) -> MergeOrder {
+        match self.merge_streams {
+            true => MergeOrder::Time,
+            false => self.merge_order,
+        }
+    }
+
+    /// Barter exchange names given by the `--exchange-name-*` overrides.
+    fn exchange_names(&self) -> ExchangeNames {
+        ExchangeNames {
+            spot: self.exchange_name_spot.clone(),
+            linear: self.exchange_name_linear.clone(),
+            inverse: self.exchange_name_inverse.clone(),
+            option: self.exchange_name_option.clone(),
+        }
+    }
+
+    /// Whether `--data-kind` asks for trade summaries.
+    fn trade_summaries(&self) -> bool {
+        self.data_kind.contains(&DataKind::TradeSummary)
+    }
+}
+
+/// Stream live klines to stdout in the requested output format.
+async fn run_live(args: &Args) -> Result<(), Box<dyn Error>> {
+    if args.category == CategoryArg::All {
+        return Err(BybitError::ArgumentError(
+            "--live streams a single category, --category all isn't supported".to_string(),
+        ).into());
+    }
+    if args.symbol.contains(',') {
+        return Err(BybitError::ArgumentError(
+            "--live streams a single symbol, a comma separated --symbol isn't supported".to_string(),
+        ).into());
+    }
+    if !matches!(args.output_format, OutputFormat::Table | OutputFormat::Barter) {
+        return Err(BybitError::ArgumentError(format!(
+            "--live streams a table or barter events, --output-format {} isn't supported",
+            args.output_format
+        )).into());
+    }
+    let interval = args.interval();
+    let barter = args.output_format == OutputFormat::Barter;
+    let barter_options = BarterOptions {
+        time_received: args.time_received.unwrap_or(TimeReceived::Now),
+        ..args.barter_options()
+    };
+
+    if !barter {
+        println!("Streaming live {} {} klines ({}), press Ctrl-C to stop\n", args.symbol, interval, args.category);
+        println!(
+            "{:<20} {:<12} {:<12} {:<12} {:<12} {:<15} {:<15}",
+            "Time", "Open", "High", "Low", "Close", "base_volume", "quote_volume"
+        );
+        println!("{}", "-".repeat(110));
+    }
+
+    let url = live::stream_url(args.testnet, args.category());
+    live::stream_klines(&url, &args.symbol, interval, args.category(), args.include_unconfirmed, |kline, confirmed| {
+        if barter {
+            let mut barter_event = kline.to_barter_event(interval, &barter_options);
+            barter_event.item.ok.confirmed = args.include_unconfirmed.then_some(confirmed);
+            println!("{}", barter_event.to_json(args.compact_barter)?);
+        } else {
+            println!(
+                "{:<20} {:<12.4} {:<12.4} {:<12.4} {:<12.4} {:<15.4} {:<15.4}{}",
+                kline.format_time(),
+                kline.open_price,
+                kline.high_price,
+                kline.low_price,
+                kline.close_price,
+                kline.base_volume(),
+                kline.quote_volume(),
+                if confirmed { "" } else { " (unconfirmed)" }
+            );
+        }
+        Ok(())
+    })
+    .await?;
+
+    Ok(())
+}
+
+/// Print fetch progress after each chunk.
+fn print_progress(progress: FetchProgress) {
+    let last_time = progress
+        .last_time
+        .and_then(|time| DateTime::from_timestamp_millis(time as i64))
+        .map(|time| time.format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_default();
+    println!(
+        "Retrieved {} of up to {} records (through {})",
+        progress.fetched, progress.expected, last_time
+    );
+}
+
+/// Logger printing the library's warnings and notices on stderr, so machine readable stdout
+/// stays clean, and its debug diagnostics too with --verbose.
+struct StderrLogger;
+
+impl log::Log for StderrLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.target().starts_with("bybit_kline")
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            match record.level() {
+                log::Level::Error => eprintln!("Error: {}", record.args()),
+                log::Level::Warn => eprintln!("Warning: {}", record.args()),
+                log::Level::Info => eprintln!("{}", record.args()),
+                level => eprintln!("[{}] {}", level, record.args()),
+            }
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// Build the client for a historical fetch from the command line options.
+fn build_client(args: &Args) -> Result<BybitClient, BybitError> {
+    BybitClient::new(args.testnet, args.max_inflight as usize)?
+        .with_preflight(!args.no_preflight)
+        .with_cache(
+            args.cache_dir
+                .clone()
+                .filter(|_| !args.no_cache && !args.benchmark)
+                .map(|dir| KlineCache::new(dir, Duration::from_secs(args.cache_ttl))),
+        )
+        .with_retry(RetryPolicy::new(args.max_retries, args.retry_budget))
+        .with_jitter(Jitter::new(args.rng_seed))
+        .with_best_effort(args.best_effort)
+        .with_dedup(!args.no_dedup)
+        .with_sort(!args.no_sort)
+        .with_pagination(args.pagination)
+        .with_retry_on_empty(args.retry_on_empty)
+        .with_adaptive_chunking(args.adaptive_chunking.then_some(chunking::DEFAULT_TARGET_LATENCY))
+        .with_request_log(args.request_log.as_deref().map(RequestLog::create).transpose()?)
+        .with_recv_window(args.recv_window_ms)
+        .with_credentials(resolve_credentials(args)?)
+        .with_default_headers(default_headers(args))
+}
+
+/// Credentials from `--api-key`/`--api-secret`, their environment variables or the credentials
+/// file, reporting only the masked key.
+fn resolve_credentials(args: &Args) -> Result<Option<Credentials>, BybitError> {
+    let credentials = credentials::resolve(
+        args.api_key.clone(),
+        args.api_secret.clone(),
+        args.credentials_file.as_deref(),
+    )?;
+    if let Some(credentials) = &credentials {
+        log::debug!("Using API key {}", credentials.masked_key());
+    }
+    Ok(credentials)
+}
+
+/// Headers from `--user-agent` and `--header`, sent with every request.
+fn default_headers(args: &Args) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    if let Some(user_agent) = &args.user_agent {
+        headers.insert(USER_AGENT, user_agent.clone());
+    }
+    for (name, value) in &args.headers {
+        headers.append(name.clone(), value.clone());
+    }
+    headers
+}
+
+/// Parse a `--header` KEY=VALUE pair, rejecting names or values which aren't valid HTTP.
+fn parse_header(header: &str) -> Result<(HeaderName, HeaderValue), String> {
+    let (name, value) = header
+        .split_once('=')
+        .ok_or_else(|| format!("expected KEY=VALUE, got '{}'", header))?;
+    let name = HeaderName::from_bytes(name.trim().as_bytes())
+        .map_err(|_| format!("invalid header name '{}'", name.trim()))?;
+    let value = HeaderValue::from_str(value.trim())
+        .map_err(|_| format!("invalid value for header '{}'", name))?;
+    Ok((name, value))
+}
+
+/// Measure the size of `--output-format` output with the writer settings of the run, see
+/// [`SizeEstimate`].
+fn estimate_output_size(args: &Args, interval: Interval) -> Result<SizeEstimate, BybitError> {
+    let category = args.category();
+    if args.output_format == OutputFormat::Parquet {
+        return SizeEstimate::of_parquet(args.parquet_row_group_size as usize, interval, category);
+    }
+    let barter = BarterWriter {
+        options: BarterOptions {
+            // Stand-ins of about the same length for the instrument lookup
+            symbol: args.normalize_symbol.then(|| args.symbol.clone()),
+            raw_symbol: args.normalize_symbol.then(|| args.symbol.clone()),
+            ..args.barter_options()
+        },
+        interval,
+        synthetic_trades: args.synthesize_trades.then_some(args.trade_walk),
+        candles: true,
+        trade_summaries: None,
+        compact: args.compact_barter,
+    };
+    let table = TableWriter::new(
+        TableRenderer {
+            price_decimals: args.price_decimals.unwrap_or(4),
+            volume_decimals: args.volume_decimals.unwrap_or(4),
+            human_numbers: args.human_numbers,
+            close_time: args.close_time(),
+            ..Default::default()
+        },
+        None,
+    );
+    let mut writer = output_writer(args, Some(barter), Some(table)).expect("every format but parquet has a writer");
+    SizeEstimate::of_writer(writer.as_mut(), interval, category)
+}
+
+/// Bybit's recent trades of `--symbol` for `--data-kind trade-summary`, reporting on stderr
+/// how many of the fetched bars they cover.
+async fn trade_summaries(args: &Args, client: &BybitClient, klines: &[Kline]) -> Result<TradeSummaries, BybitError> {
+    let summaries = client.get_trade_summaries(&args.symbol, args.category()).await?;
+    let covered = klines.iter().filter(|kline| kline.start_time >= summaries.covered_from()).count();
+    if covered < klines.len() {
+        eprintln!(
+            "Warning: Bybit only serves the latest {} trades, trade summaries cover {} of {} bars",
+            trades::recent_trade_limit(args.category()),
+            covered,
+            klines.len()
+        );
+    }
+    Ok(summaries)
+}
+
+/// Merge klines fetched for several series into one ascending, duplicate free series, or only
+/// as far as `--no-dedup` and `--no-sort` allow.
+fn merge_series(args: &Args, klines: &mut Vec<Kline>) {
+    if !args.no_dedup {
+        kline::sort_and_dedup(klines);
+    } else if !args.no_sort {
+        klines.sort_by_key(|kline| kline.start_time);
+    }
+}
+
+/// `--fill-gaps` within `--max-gap-fill`, reporting the gaps too large to fill on stderr.
+fn fill_series(args: &Args, klines: Vec<Kline>, interval: Interval, gap_fill: GapFill) -> Vec<Kline> {
+    let (klines, unfilled) = gaps::fill_gaps(klines, interval, gap_fill, args.max_gap_fill);
+    let format_millis = |millis: u64| {
+        DateTime::<Utc>::from_timestamp_millis(millis as i64)
+            .map(|time| time.format("%Y-%m-%d %H:%M UTC").to_string())
+            .unwrap_or_else(|| millis.to_string())
+    };
+    for gap in &unfilled {
+        eprintln!(
+            "Gap not filled: {} bars from {} to {} exceed --max-gap-fill {}",
+            gap.bars(interval),
+            format_millis(gap.start),
+            format_millis(gap.end),
+            args.max_gap_fill.unwrap_or_default()
+        );
+    }
+    klines
+}
+
+/// `--verify-continuity-against-exchange`: re-request sampled windows between the first and last
+/// fetched bars and compare them with `klines`, reporting every discrepancy on stderr.
+async fn verify_continuity(
+    args: &Args,
+    client: &BybitClient,
+    query: KlineQuery,
+    klines: &[Kline],
+) -> Result<(), BybitError> {
+    let (Some(first), Some(last)) = (klines.first(), klines.last()) else {
+        return Ok(());
+    };
+    let query = KlineQuery {
+        start: first.start_time,
+        end: query.interval.next_start(last.start_time),
+        ..query
+    };
+    let samples = args.verify_samples.unwrap_or(continuity::DEFAULT_SAMPLES);
+    let report = client.verify_continuity(&query, klines, samples, args.rng_seed).await?;
+    for discrepancy in &report.discrepancies {
+        eprintln!("Continuity mismatch: {}", discrepancy);
+    }
+    eprintln!(
+        "Continuity check: {} bars in {} windows, {} discrepancies",
+        report.bars_checked,
+        report.windows,
+        report.discrepancies.len()
+    );
+    match report.discrepancies.len() {
+        0 => Ok(()),
+        discrepancies => Err(BybitError::ContinuityMismatch { discrepancies }),
+    }
+}
+
+/// `--drop-zero-volume`: remove empty bars, reporting how many were dropped on stderr.
+fn drop_zero_volume(args: &Args, klines: &mut Vec<Kline>) {
+    if args.drop_zero_volume {
+        let dropped = kline::drop_zero_volume(klines);
+        eprintln!("Dropped {} zero-volume bars", dropped);
+    }
+}
+
+/// `--detect-anomalies`: report bars straying from their neighbours on stderr, dropping them
+/// with `--drop-anomalies`.
+fn screen_anomalies(args: &Args, klines: &mut Vec<Kline>) {
+    if !args.detect_anomalies {
+        return;
+    }
+    let anomalies = anomaly::detect_anomalies(klines, args.anomaly_threshold / 100.0);
+    for anomaly in &anomalies {
+        eprintln!(
+            "Anomaly: {} {} {} {} {} is {:.1}% off the rolling median close {}",
+            DateTime::<Utc>::from_timestamp_millis(anomaly.start_time as i64)
+                .map(|time| time.format("%Y-%m-%d %H:%M UTC").to_string())
+                .unwrap_or_else(|| anomaly.start_time.to_string()),
+            anomaly.category,
+            anomaly.kline_type,
+            anomaly.field,
+            anomaly.price,
+            anomaly.deviation * 100.0,
+            anomaly.median
+        );
+    }
+    if args.drop_anomalies && !anomalies.is_empty() {
+        anomaly::drop_anomalies(klines, &anomalies);
+        eprintln!("Dropped {} anomalous bars", anomalies.len());
+    }
+}
+
+/// Write each contiguous segment of `klines` to its own file derived from `--output`, or to
+/// stdout with a [`SegmentMarker`] line ahead of each, reporting the segments on stderr.
+fn write_segments(
+    args: &Args,
+    writer: &mut dyn OutputWriter,
+    klines: &[Kline],
+    interval: Interval,
+) -> Result<(), BybitError> {
+    let segments = gaps::split_on_gaps(klines, interval);
+    let mut paths = Vec::new();
+    match &args.output {
+        Some(output) => {
+            for (segment, klines) in segments.iter().enumerate() {
+                let path = output::segment_path(output, segment);
+                let mut out = output::OutputSink::new(Some(&path), false)?.with_compression(args.compress);
+                writer::write_klines(writer, &mut out, klines)?;
+                out.flush()?;
+                paths.push(path);
+            }
+        }
+        None => {
+            let mut out = output::OutputSink::new(None, false)?;
+            writer.write_header(&mut out)?;
+            for (segment, klines) in segments.iter().enumerate() {
+                writeln!(out, "{}", serde_json::to_string(&SegmentMarker::new(segment, klines))?)?;
+                for kline in klines.iter() {
+                    writer.write_kline(&mut out, kline)?;
+                }
+            }
+            writer.finish(&mut out)?;
+            out.flush()?;
+        }
+    }
+
+    eprintln!("Segments: {} ({} records)", segments.len(), klines.len());
+    for (segment, klines) in segments.iter().enumerate() {
+        let (Some(first), Some(last)) = (klines.first(), klines.last()) else { continue };
+        let destination = paths
+            .get(segment)
+            .map(|path| format!(" -> {}", path.display()))
+            .unwrap_or_default();
+        eprintln!(
+            "Segment {}: {} records, {} to {}{}",
+            segment,
+            klines.len(),
+            first.format_time(),
+            last.format_time(),
+            destination
+        );
+    }
+    Ok(())
+}
+
+/// Advance the `--state-file` entry for `symbol` to the last kline written, once output succeeded.
+fn save_state(state: Option<&mut StateFile>, symbol: &str, klines: &[Kline]) -> Result<(), BybitError> {
+    let (Some(state), Some(last)) = (state, klines.iter().map(|kline| kline.start_time).max()) else {
+        return Ok(());
+    };
+    state.record(symbol, last);
+    state.save()
+}
+
+/// Table layout for a run, with precision following the instrument unless set explicitly.
+async fn table_renderer(args: &Args, client: &BybitClient, all_categories: bool) -> TableRenderer {
+    let (mut price_decimals, mut volume_decimals) = (args.price_decimals, args.volume_decimals);
+    if !all_categories && (price_decimals.is_none() || volume_decimals.is_none()) {
+        match client.get_instrument_info(args.category(), &args.symbol).await {
+            Ok(instrument) => {
+                price_decimals = price_decimals.or(instrument.price_decimals());
+                volume_decimals = volume_decimals.or(instrument.volume_decimals());
+            }
+            Err(error) => eprintln!(
+                "Warning: failed to fetch instrument precision, using defaults: {}",
+                error
+            ),
+        }
+    }
+    TableRenderer {
+        price_decimals: price_decimals.unwrap_or(4),
+        volume_decimals: volume_decimals.unwrap_or(4),
+        human_numbers: args.human_numbers,
+        close_time: args.close_time(),
+        ..Default::default()
+    }
+}
+
+/// Sink for `--output`, split into parts with `--max-file-size` and compressed with
+/// `--compress`.
+fn output_sink(args: &Args) -> Result<output::OutputSink, BybitError> {
+    match (args.output.as_deref(), args.max_file_size) {
+        (Some(output), Some(max_bytes)) => output::OutputSink::parted(output, max_bytes),
+        (output, _) => output::OutputSink::new(output, args.tee),
+    }
+    .map(|out| out.with_compression(args.compress))
+}
+
+/// Write table, csv or tsv output as each chunk arrives rather than after the whole range has
+/// been fetched, so output starts immediately and memory stays bounded by a single chunk.
+async fn stream_output(args: &Args, client: &BybitClient, query: &KlineQuery) -> Result<(), Box<dyn Error>> {
+    let table = match args.output_format {
+        OutputFormat::Table => Some(TableWriter::new(table_renderer(args, client, false).await, None)),
+        _ => None,
+    };
+    let mut writer = output_writer(args, None, table).expect("streamed formats have a row writer");
+
+    let mut out = output_sink(args)?;
+    writer.write_header(&mut out)?;
+
+    // Progress lines would interleave with rows streamed to stdout
+    let show_progress = !args.machine_readable() && args.output.is_some() && !args.tee;
+    let mut last: Option<Kline> = None;
+    client
+        .get_kline_streaming(
+            query,
+            |progress| if show_progress { print_progress(progress) },
+            |klines| {
+                if args.validate_monotonic {
+                    kline::validate_monotonic(last.iter().chain(&klines))?;
+                    last = klines.last().cloned().or(last.take());
+                }
+                for kline in &klines {
+                    writer::write_kline_parted(writer.as_mut(), &mut out, kline)?;
+                }
+                out.flush()?;
+                Ok(())
+            },
+        )
+        .await?;
+
+    writer.finish(&mut out)?;
+    out.flush()?;
+    report_retries(client, args.machine_readable());
+    Ok(())
+}
+
+/// Re-emit the klines of an ohlcv-binary file in `--output-format`, without fetching.
+fn run_convert(args: &Args, path: &Path) -> Result<(), Box<dyn Error>> {
+    if args.category == CategoryArg::All {
+        return Err(BybitError::ArgumentError(
+            "--convert-from reads a single category, --category all isn't supported".to_string(),
+        ).into());
+    }
+    let mut klines = ohlcv_binary::read_klines(BufReader::new(File::open(path)?), args.category())?;
+    if args.validate_monotonic {
+        kline::validate_monotonic(&klines)?;
+    }
+    slice_series(args, &mut klines);
+
+    let barter = BarterWriter {
+        options: args.barter_options(),
+        interval: args.interval(),
+        synthetic_trades: None,
+        candles: true,
+        trade_summaries: None,
+        compact: args.compact_barter,
+    };
+    let table = TableWriter::new(TableRenderer::default(), Some(klines.len()));
+    let Some(mut writer) = output_writer(args, Some(barter), Some(table)) else {
+        return Err(BybitError::ArgumentError(format!(
+            "--convert-from doesn't support {} output",
+            args.output_format
+        )).into());
+    };
+
+    let mut out = output::OutputSink::new(args.output.as_deref(), args.tee)?.with_compression(args.compress);
+    writer::write_klines(writer.as_mut(), &mut out, &klines)?;
+    out.flush()?;
+    Ok(())
+}
+
+/// Print the most recent closed bar of `--symbol`, compactly on one line for table output.
+async fn run_final_bar(args: &Args) -> Result<(), Box<dyn Error>> {
+    if args.category == CategoryArg::All || args.symbol.contains(',') || args.kline_type.len() > 1 {
+        return Err(BybitError::ArgumentError(
+            "--only-final-bar checks a single series: one symbol, kline type and category".to_string(),
+        ).into());
+    }
+    let mut writer = match (args.output_format, output_writer(args, None, None)) {
+        (OutputFormat::Table, _) => None,
+        (_, Some(writer)) => Some(writer),
+        (format, None) => {
+            return Err(BybitError::ArgumentError(format!(
+                "--only-final-bar prints table, csv, tsv, kline-json, jsonl-flat or influx-line output, not {}",
+                format
+            )).into());
+        }
+    };
+    let interval = args.interval();
+    let client = build_client(args)?;
+    let now = Utc::now().timestamp_millis() as u64;
+    let Some(kline) = client.final_bar(&args.symbol, args.category(), interval, args.kline_type[0], now).await? else {
+        return Err(BybitError::ApiError {
+            msg: format!("No closed {} bar for {}", interval, args.symbol),
+        }.into());
+    };
+
+    let mut out = output::OutputSink::new(args.output.as_deref(), args.tee)?.with_compression(args.compress);
+    match writer.as_mut() {
+        Some(writer) => writer::write_klines(writer.as_mut(), &mut out, slice::from_ref(&kline))?,
+        None => writeln!(
+            out,
+            "{} {} {}  O {}  H {}  L {}  C {}  V {}",
+            args.symbol,
+            interval,
+            kline.format_time(),
+            kline.open_price,
+            kline.high_price,
+            kline.low_price,
+            kline.close_price,
+            kline.base_volume()
+        )?,
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Validate a kline file offline, printing its report and failing if it found any issue.
+fn run_validate_file(args: &Args, path: &Path) -> Result<(), Box<dyn Error>> {
+    if args.category == CategoryArg::All {
+        return Err(BybitError::ArgumentError(
+            "--validate-file reads a single category, --category all isn't supported".to_string(),
+        ).into());
+    }
+    let report = validate::validate_file(BufReader::new(File::open(path)?), args.interval(), args.category())?;
+    println!("Validated {} as {} bars", path.display(), args.interval().description());
+    println!("{}", report);
+    match report.issues.len() {
+        0 => Ok(()),
+        issues => Err(BybitError::ValidationFailed { issues }.into()),
+    }
+}
+
+/// `--empty-ok` with an empty range: a successful run emitting zero records, still writing the
+/// header and footer of formats which have one (eg/ csv columns, or `[]` for json-array-file)
+/// so downstream readers see a well formed, empty output.
+fn write_empty(args: &Args) -> Result<(), Box<dyn Error>> {
+    match output_writer(args, None, None) {
+        Some(mut writer) => {
+            let mut out = output_sink(args)?;
+            writer::write_klines(writer.as_mut(), &mut out, &[])?;
+            out.flush()?;
+        }
+        None if !args.machine_readable() => println!("Empty range, no records to fetch"),
+        None => {}
+    }
+    Ok(())
+}
+
+/// Apply `--sample-rate`, then `--order` and `--head` or `--tail`, to a sorted, deduplicated
+/// series.
+fn slice_series(args: &Args, klines: &mut Vec<Kline>) {
+    if let Some(sample_rate) = args.sample_rate {
+        kline::decimate(klines, sample_rate as usize);
+    }
+    kline::order_and_slice(klines, args.order, args.head, args.tail);
+}
+
+/// Writer of `--output-format`, the one place formats are mapped to their writers.
+///
+/// Barter and table output go through the `barter` and `table` writers the caller set up for
+/// its run, every other format needs nothing beyond the arguments. `None` if the format needs a
+/// writer the caller didn't pass, or is parquet, which [`write_parquet`] writes by column.
+fn output_writer(args: &Args, barter: Option<BarterWriter>, table: Option<TableWriter>) -> Option<Box<dyn OutputWriter>> {
+    let writer: Box<dyn OutputWriter> = match args.output_format {
+        OutputFormat::Table => Box::new(table?),
+        OutputFormat::Barter => Box::new(barter?),
+        OutputFormat::KlineJson => Box::new(KlineJsonWriter),
+        OutputFormat::JsonlFlat => Box::new(JsonlFlatWriter { symbol: args.symbol.clone() }),
+        OutputFormat::JsonArrayFile => {
+            let items: Box<dyn OutputWriter> = match args.json_array_items {
+                JsonArrayItems::Kline => Box::new(KlineJsonWriter),
+                JsonArrayItems::Barter => Box::new(barter?),
+            };
+            Box::new(JsonArrayWriter::new(items))
+        }
+        OutputFormat::InfluxLine => Box::new(InfluxLineWriter {
+            symbol: args.symbol.clone(),
+            precision: args.timestamp_unit.unwrap_or(TimestampUnit::Nanos),
+        }),
+        OutputFormat::Csv | OutputFormat::Tsv => {
+            let format = args.output_format.delimited()?;
+            let timestamp_unit = args.delimited_timestamp_unit();
+            match args.price_field {
+                Some(field) => Box::new(PriceFieldWriter { format, timestamp_unit, field }),
+                None => Box::new(DelimitedWriter { format, timestamp_unit, close_time: args.close_time() }),
+            }
+        }
+        OutputFormat::OhlcvBinary => Box::new(OhlcvBinaryWriter),
+        OutputFormat::ArrowStream => Box::new(ArrowStreamWriter::default()),
+        OutputFormat::Parquet => return None,
+    };
+    Some(writer)
+}
+
+/// Report the outcome of each category fetched with `--category all`, tagging failures.
+fn report_categories(reports: &[(KlineType, CategoryReport)], show_kline_type: bool, to_stderr: bool) {
+    let mut lines = vec!["\nCategory results:".to_string()];
+    for (kline_type, report) in reports {
+        let series = if show_kline_type {
+            format!("{} {}", report.category, kline_type)
+        } else {
+            report.category.to_string()
+        };
+        lines.push(match &report.result {
+            Ok(records) => format!("OK     {} ({} records)", series, records),
+            Err(error) => format!("FAILED {}: {}", series, error),
+        });
+    }
+    for line in lines {
+        if to_stderr {
+            eprintln!("{}", line);
+        } else {
+            println!("{}", line);
+        }
+    }
+}
+
+/// Report the retries consumed by a run, and any rows skipped with --best-effort, alongside
+/// its final summary.
+fn report_retries(client: &BybitClient, to_stderr: bool) {
+    let used = client.retry().used();
+    let mut report = match client.retry().budget() {
+        Some(budget) => format!("Retries used: {} of {} budget", used, budget),
+        None => format!("Retries used: {}", used),
+    };
+    if client.skipped_rows() > 0 {
+        report.push_str(&format!("\nMalformed rows skipped: {}", client.skipped_rows()));
+    }
+    if to_stderr {
+        eprintln!("{}", report);
+    } else {
+        println!("{}", report);
+    }
+}
+
+/// Stream a fetch straight to a Parquet file, flushing a row group every
+/// `--parquet-row-group-size` klines so memory stays bounded on very large ranges.
+async fn write_parquet(
+    args: &Args,
+    client: &BybitClient,
+    interval: Interval,
+    start: u64,
+    end: u64,
+) -> Result<(), Box<dyn Error>> {
+    let Some(output) = &args.output else {
+        return Err(BybitError::ArgumentError("--output-format parquet requires --output".to_string()).into());
+    };
+    // Merging series or filling gaps would need the whole range in memory
+    let [kline_type] = args.kline_type.as_slice() else {
+        return Err(BybitError::ArgumentError("--output-format parquet requires a single --kline-type".to_string()).into());
+    };
+    if args.fill_gaps.is_some() || args.summary_only || args.drop_zero_volume || args.sample_rate.is_some() || args.head.is_some() || args.tail.is_some() {
+        return Err(BybitError::ArgumentError(
+            "--output-format parquet can't be combined with --fill-gaps, --summary-only, --drop-zero-volume, --sample-rate, --head or --tail".to_string(),
+        ).into());
+    }
+
+    let query = KlineQuery {
+        symbol: args.symbol.clone(),
+        category: args.category(),
+        interval,
+        kline_type: *kline_type,
+        start,
+        end,
+        max_records: args.max_records,
+        limit_per_request: args.limit_per_request,
+    };
+
+    println!("Streaming kline data to {}...", output.display());
+    let mut writer = parquet_writer::ParquetKlineWriter::create(output, args.parquet_row_group_size as usize)?;
+    let mut last: Option<Kline> = None;
+    client
+        .get_kline_concurrent(&query, args.max_inflight as usize, print_progress, |klines| {
+            if args.validate_monotonic {
+                kline::validate_monotonic(last.iter().chain(&klines))?;
+                last = klines.last().cloned().or(last.take());
+            }
+            writer.write(klines)
+        })
+        .await?;
+    let rows = writer.finish()?;
+
+    println!("\nWrote {} records to {}", rows, output.display());
+    report_retries(client, false);
+    Ok(())
+}
+
+/// Fetch every interval of a comma separated `--interval` concurrently, bounded by the
+/// client's in-flight limit, then write each to its own `{interval}` output file or, for
+/// barter output without a template, a single time ordered stream tagged with the interval.
+async fn run_multi_interval(
+    args: &Args,
+    client: &BybitClient,
+    intervals: &[Interval],
+    start: u64,
+    end: u64,
+) -> Result<(), Box<dyn Error>> {
+    let quiet = args.machine_readable();
+    if !quiet {
+        println!("Fetching kline data for {} intervals...", intervals.len());
+    }
+
+    let fetches = intervals.iter().map(|&interval| async move {
+        let mut klines = Vec::new();
+        for &kline_type in &args.kline_type {
+            let query = KlineQuery {
+                symbol: args.symbol.clone(),
+                category: args.category(),
+                interval,
+                kline_type,
+                start,
+                end,
+                max_records: args.max_records,
+                limit_per_request: args.limit_per_request,
+            };
+            // Per-chunk progress is ignored, it would interleave across intervals
+            klines.extend(client.get_kline(&query, |_| {}).await?);
+        }
+        merge_series(args, &mut klines);
+        spec_change::apply_spec_changes(&mut klines, &args.spec_change);
+        screen_anomalies(args, &mut klines);
+        drop_zero_volume(args, &mut klines);
+        if let Some(gap_fill) = args.fill_gaps {
+            klines = fill_series(args, klines, interval, gap_fill);
+        }
+        if args.validate_monotonic {
+            kline::validate_monotonic(&klines)?;
+        }
+        slice_series(args, &mut klines);
+        Ok::<_, BybitError>((interval, klines))
+    });
+    let series = join_all(fetches).await.into_iter().collect::<Result<Vec<_>, _>>()?;
+
+    let mut barter_options = args.barter_options();
+    if args.normalize_symbol {
+        let instrument = client.get_instrument_info(args.category(), &args.symbol).await?;
+        barter_options.symbol = Some(instrument.canonical_symbol());
+        barter_options.raw_symbol = Some(instrument.symbol);
+    }
+    let barter_events = |kline: &Kline, interval: Interval| {
+        let options = BarterOptions { interval: Some(interval), ..barter_options.clone() };
+        if args.synthesize_trades {
+            kline.to_synthetic_trades(interval, args.trade_walk, &options)
+        } else {
+            vec![kline.to_barter_event(interval, &options)]
+        }
+    };
+
+    let template = args.output.as_ref().map(|output| output.to_string_lossy().into_owned());
+    let mut reports = Vec::new();
+    match template.filter(|template| template.contains("{interval}")) {
+        Some(template) => {
+            for (interval, klines) in &series {
+                let path = PathBuf::from(template.replace("{interval}", interval.as_str()));
+                let mut out = output::OutputSink::new(Some(&path), args.tee)?.with_compression(args.compress);
+                match output_writer(args, None, None) {
+                    Some(mut writer) => writer::write_klines(writer.as_mut(), &mut out, klines)?,
+                    None => {
+                        for kline in klines {
+                            for event in barter_events(kline, *interval) {
+                                writeln!(out, "{}", event.to_json(args.compact_barter)?)?;
+                            }
+                        }
+                    }
+                }
+                out.flush()?;
+                reports.push(format!("Interval {}: {} records -> {}", interval, klines.len(), path.display()));
+            }
+        }
+        None => {
+            // Merge into a single stream, ordered by time then from the shortest interval
+            let mut merged = series
+                .iter()
+                .flat_map(|(interval, klines)| klines.iter().map(move |kline| (*interval, kline)))
+                .collect::<Vec<_>>();
+            merged.sort_by_key(|(interval, kline)| (kline.start_time, interval.duration_ms()));
+
+            let mut out = output::OutputSink::new(args.output.as_deref(), args.tee)?.with_compression(args.compress);
+            for (interval, kline) in merged {
+                for event in barter_events(kline, interval) {
+                    writeln!(out, "{}", event.to_json(args.compact_barter)?)?;
+                }
+            }
+            out.flush()?;
+            for (interval, klines) in &series {
+                reports.push(format!("Interval {}: {} records", interval, klines.len()));
+            }
+        }
+    }
+
+    for report in reports {
+        if quiet {
+            eprintln!("{}", report);
+        } else {
+            println!("{}", report);
+        }
+    }
+    report_retries(client, quiet);
+    Ok(())
 }
 
-#[derive(Debug)]
-struct Kline {
-    start_time: u64,
-    open_price: f64,
-    high_price: f64,
-    low_price: f64,
-    close_price: f64,
-    volume: f64,
-    turnover: f64,
+/// Fetch every symbol of a comma separated `--symbol` concurrently, bounded by the client's
+/// in-flight limit, or one after another with `--delay-between-symbols-ms`, then write a single
+/// barter stream in a deterministic `--merge-order`.
+///
+/// Each symbol's events carry their own instrument index, counting up from
+/// `--instrument-index` in the order the symbols were given.
+async fn run_multi_symbol(
+    args: &Args,
+    client: &BybitClient,
+    symbols: &[String],
+    interval: Interval,
+    start: u64,
+    end: u64,
+) -> Result<(), Box<dyn Error>> {
+    // One display shared by every symbol's fetch, on stderr as stdout carries the barter stream
+    let stderr = io::stderr();
+    let tty = stderr.is_terminal();
+    let progress = MultiProgress::new(stderr, tty, Duration::from_secs(args.status_interval_secs));
+    let progress = &progress;
+    let fetches = symbols.iter().enumerate().map(|(index, symbol)| async move {
+        let bar = progress.add(symbol);
+        let mut klines = Vec::new();
+        for &kline_type in &args.kline_type {
+            let query = KlineQuery {
+                symbol: symbol.clone(),
+                category: args.category(),
+                interval,
+                kline_type,
+                start,
+                end,
+                max_records: args.max_records,
+                limit_per_request: args.limit_per_request,
+            };
+            let completed = klines.len();
+            let series = args.kline_type.len();
+            klines.extend(client.get_kline(&query, |fetch| bar.set_fetch(fetch, completed, series)).await?);
+        }
+        bar.finish(klines.len());
+        merge_series(args, &mut klines);
+        spec_change::apply_spec_changes(&mut klines, &args.spec_change);
+        screen_anomalies(args, &mut klines);
+        drop_zero_volume(args, &mut klines);
+        if let Some(gap_fill) = args.fill_gaps {
+            klines = fill_series(args, klines, interval, gap_fill);
+        }
+        if args.validate_monotonic {
+            kline::validate_monotonic(&klines)?;
+        }
+        slice_series(args, &mut klines);
+
+        let mut barter_options = BarterOptions {
+            instrument_index: args.instrument_index + index,
+            ..args.barter_options()
+        };
+        if args.normalize_symbol {
+            let instrument = client.get_instrument_info(args.category(), symbol).await?;
+            barter_options.symbol = Some(instrument.canonical_symbol());
+            barter_options.raw_symbol = Some(instrument.symbol);
+        }
+        let events = klines
+            .iter()
+            .flat_map(|kline| {
+                if args.synthesize_trades {
+                    kline.to_synthetic_trades(interval, args.trade_walk, &barter_options)
+                } else {
+                    vec![kline.to_barter_event(interval, &barter_options)]
+                }
+            })
+            .collect::<Vec<_>>();
+        Ok::<_, BybitError>((klines.len(), events))
+    });
+    let series = match args.delay_between_symbols_ms {
+        0 => join_all(fetches).await,
+        // One symbol at a time, pausing in between, for limits applied per symbol series
+        delay => {
+            let mut series = Vec::with_capacity(symbols.len());
+            for (index, fetch) in fetches.enumerate() {
+                if index > 0 {
+                    tokio::time::sleep(Duration::from_millis(delay)).await;
+                }
+                series.push(fetch.await);
+            }
+            series
+        }
+    };
+    let mut series = series.into_iter().collect::<Result<Vec<_>, _>>()?;
+
+    let mut out = output::OutputSink::new(args.output.as_deref(), args.tee)?.with_compression(args.compress);
+    match args.merge_order() {
+        MergeOrder::PerSymbol => {
+            for (_, events) in &series {
+                for event in events {
+                    writeln!(out, "{}", event.to_json(args.compact_barter)?)?;
+                }
+            }
+        }
+        MergeOrder::Time => {
+            let streams = series.iter_mut().map(|(_, events)| std::mem::take(events));
+            for event in barter::merge_by_time(streams) {
+                writeln!(out, "{}", event.to_json(args.compact_barter)?)?;
+            }
+        }
+    }
+    out.flush()?;
+
+    for (symbol, (records, _)) in symbols.iter().zip(&series) {
+        eprintln!("Symbol {}: {} records", symbol, records);
+    }
+    report_retries(client, true);
+    Ok(())
 }
 
-// Barter-compatible data structures
-#[derive(Debug, Serialize, Deserialize)]
-struct BarterCandle {
-    pub close_time: DateTime<Utc>,
-    pub open: f64,
-    pub high: f64,
-    pub low: f64,
-    pub close: f64,
-    pub volume: f64,
-    pub trade_count: u64,
+/// `--interval-auto`: the finest interval fitting the date range within `--max-records`,
+/// reporting the choice on stderr.
+fn auto_interval(args: &Args, now: u64) -> Result<Interval, BybitError> {
+    let start = parse_date(args.start_date.as_deref().unwrap_or_default())?;
+    let end = args.end_date.as_deref().map(parse_date).transpose()?.unwrap_or(now);
+    let (interval, bars) = window::finest_interval_within(start, end, args.max_records).ok_or_else(|| {
+        BybitError::ArgumentError(format!(
+            "--interval-auto: even monthly bars over the range exceed --max-records {}",
+            args.max_records
+        ))
+    })?;
+    eprintln!(
+        "Auto-selected interval {} ({}): {} bars within --max-records {}",
+        interval.as_str(),
+        interval.description(),
+        bars,
+        match args.max_records {
+            window::UNLIMITED_RECORDS => "unlimited".to_string(),
+            max_records => max_records.to_string(),
+        }
+    );
+    Ok(interval)
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct BarterMarketEvent {
-    pub time_exchange: DateTime<Utc>,
-    pub time_received: DateTime<Utc>,
-    pub exchange: String,
-    pub instrument: usize,
-    pub kind: BarterDataKind,
+/// Resolve `--category-fallback` to the first of its categories with data for the symbol over
+/// the date range, reporting each category skipped and the one used on stderr.
+async fn fallback_category(args: &Args, now: u64) -> Result<CategoryArg, Box<dyn Error>> {
+    if args.symbol.contains(',') {
+        return Err(BybitError::ArgumentError(
+            "--category-fallback resolves a single symbol, a comma separated --symbol isn't supported".to_string(),
+        ).into());
+    }
+    let query = KlineQuery {
+        symbol: args.symbol.clone(),
+        category: args.category_fallback[0],
+        interval: args.interval(),
+        kline_type: args.kline_type[0],
+        start: parse_date(args.start_date.as_deref().unwrap_or_default())?,
+        end: args.end_date.as_deref().map(parse_date).transpose()?.unwrap_or(now),
+        max_records: 1,
+        limit_per_request: 1,
+    };
+    let client = build_client(args)?;
+    let (category, reports) = client.first_category_with_data(&query, &args.category_fallback).await;
+    for report in reports.iter().filter(|report| Some(report.category) != category) {
+        match &report.result {
+            Ok(_) => eprintln!("Category fallback: no {} data for {} in the range, skipping", report.category, args.symbol),
+            Err(error) => eprintln!("Category fallback: {} failed ({}), skipping", report.category, error),
+        }
+    }
+    let category = category.ok_or_else(|| {
+        let tried = args.category_fallback.iter().map(Category::as_str).collect::<Vec<_>>();
+        BybitError::ArgumentError(format!(
+            "--category-fallback: no data for {} in the range in any of {}",
+            args.symbol,
+            tried.join(", ")
+        ))
+    })?;
+    eprintln!("Category fallback: using {} for {}", category, args.symbol);
+    Ok(category.into())
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct BarterDataKind {
-    #[serde(rename = "Candle")]
-    pub Candle: BarterCandle,
+/// Fetch the `--benchmark` window for every symbol and interval concurrently, within the
+/// in-flight limit, and report the throughput without writing any klines. The disk cache is
+/// bypassed so every page is a real request.
+async fn run_benchmark(args: &Args) -> Result<(), Box<dyn Error>> {
+    let interval = args.interval();
+    let end = match &args.end_date {
+        Some(end_date) => parse_date(end_date)?,
+        None => Utc::now().timestamp_millis() as u64,
+    };
+    let start = match &args.start_date {
+        Some(start_date) => parse_date(start_date)?,
+        None => end.saturating_sub(benchmark::DEFAULT_BARS * interval.duration_ms()),
+    };
+    if start >= end {
+        return Err(BybitError::DateParseError("Start date must be before end date".to_string()).into());
+    }
+    let kline_type = args.kline_type[0];
+    let queries = args
+        .symbol
+        .split(',')
+        .map(normalize_symbol)
+        .flat_map(|symbol| {
+            args.interval.iter().map(move |&interval| KlineQuery {
+                symbol: symbol.clone(),
+                category: args.category(),
+                interval,
+                kline_type,
+                start,
+                end,
+                max_records: window::UNLIMITED_RECORDS,
+                limit_per_request: args.limit_per_request,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    println!(
+        "Benchmarking {} series from {} to {}, {} klines per request, up to {} requests in flight",
+        queries.len(),
+        DateTime::<Utc>::from_timestamp_millis(start as i64).unwrap_or_default().format("%Y-%m-%d %H:%M UTC"),
+        DateTime::<Utc>::from_timestamp_millis(end as i64).unwrap_or_default().format("%Y-%m-%d %H:%M UTC"),
+        args.limit_per_request,
+        args.max_inflight
+    );
+    let client = build_client(args)?;
+    let started = Instant::now();
+    let fetches = queries.iter().map(|query| client.get_kline(query, |_| {}));
+    let bars = join_all(fetches)
+        .await
+        .into_iter()
+        .map(|klines| klines.map(|klines| klines.len() as u64))
+        .sum::<Result<u64, _>>()?;
+    let report = BenchmarkReport::new(started.elapsed(), bars, client.transfer_stats());
+    println!("\n{}", report);
+    report_retries(&client, false);
+    Ok(())
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct BarterMarketStreamEvent {
-    #[serde(rename = "Item")]
-    pub item: BarterMarketEventResult,
+/// Print the listing date of every `--symbol` as `symbol,listing_date` csv, probing the symbols
+/// concurrently. Symbols without data get an empty date and a warning on stderr.
+async fn run_listing_dates(args: &Args) -> Result<(), Box<dyn Error>> {
+    if args.category == CategoryArg::All {
+        return Err(BybitError::ArgumentError(
+            "--probe-listing-date probes a single category, --category all isn't supported".to_string(),
+        ).into());
+    }
+    let client = build_client(args)?;
+    let end = Utc::now().timestamp_millis() as u64;
+    let symbols = args.symbol.split(',').map(normalize_symbol).collect::<Vec<_>>();
+    let listings = join_all(symbols.iter().map(|symbol| client.listing_date(symbol, args.category(), end))).await;
+
+    println!("{}", Delimited::Csv.format_row(&["symbol", "listing_date"]));
+    for (symbol, listing) in symbols.iter().zip(listings) {
+        let date = match listing? {
+            Some(listed) => DateTime::<Utc>::from_timestamp_millis(listed as i64)
+                .map(|listed| listed.format("%Y-%m-%d").to_string())
+                .unwrap_or_default(),
+            None => {
+                eprintln!("Warning: no {} klines for {}", args.category, symbol);
+                String::new()
+            }
+        };
+        println!("{}", Delimited::Csv.format_row(&[symbol.as_str(), date.as_str()]));
+    }
+    report_retries(&client, true);
+    Ok(())
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct BarterMarketEventResult {
-    #[serde(rename = "Ok")]
-    pub ok: BarterMarketEvent,
+/// Write the delivery prices of `--symbol` falling within the date range, as a table or csv or
+/// tsv.
+async fn run_delivery_prices(args: &Args) -> Result<(), Box<dyn Error>> {
+    let format = match (args.output_format, args.output_format.delimited()) {
+        (OutputFormat::Table, _) => None,
+        (_, Some(format)) => Some(format),
+        (format, None) => {
+            return Err(BybitError::ArgumentError(format!(
+                "--data-type delivery-price supports table, csv and tsv output, not {}",
+                format
+            )).into());
+        }
+    };
+    if args.category == CategoryArg::All || args.symbol.contains(',') {
+        return Err(BybitError::ArgumentError(
+            "--data-type delivery-price fetches a single symbol of a single category".to_string(),
+        ).into());
+    }
+    let start = args.start_date.as_deref().map(parse_date).transpose()?.unwrap_or(0);
+    let end = args.end_date.as_deref().map(parse_date).transpose()?.unwrap_or(u64::MAX);
+
+    let client = build_client(args)?;
+    let mut prices = client.get_delivery_price(&args.symbol, args.category()).await?;
+    prices.retain(|price| price.delivery_time >= start && price.delivery_time < end);
+    let mut out = output::OutputSink::new(args.output.as_deref(), args.tee)?.with_compression(args.compress);
+    delivery::write_delivery_prices(&mut out, &args.symbol, &prices, format)?;
+    out.flush()?;
+    Ok(())
 }
 
-impl Kline {
-    fn from_vec(data: Vec<String>) -> Result<Self, BybitError> {
-        if data.len() < 7 {
-            return Err(BybitError::ApiError {
-                msg: "Invalid kline data format".to_string(),
-            });
+/// Run a `--jobs-file` manifest, reporting the outcome of each job.
+async fn run_jobs(args: &Args, jobs_file: &Path) -> Result<(), Box<dyn Error>> {
+    let mut jobs = jobs::load_jobs(jobs_file)?;
+    for job in &mut jobs {
+        let symbol = normalize_symbol(&job.symbol);
+        if symbol != job.symbol {
+            eprintln!("Warning: normalized job symbol {:?} to {}", job.symbol, symbol);
+            job.symbol = symbol;
         }
+    }
+    let client = build_client(args)?;
 
-        Ok(Kline {
-            start_time: data[0].parse().map_err(|_| BybitError::ApiError {
-                msg: "Invalid start time".to_string(),
-            })?,
-            open_price: data[1].parse().map_err(|_| BybitError::ApiError {
-                msg: "Invalid open price".to_string(),
-            })?,
-            high_price: data[2].parse().map_err(|_| BybitError::ApiError {
-                msg: "Invalid high price".to_string(),
-            })?,
-            low_price: data[3].parse().map_err(|_| BybitError::ApiError {
-                msg: "Invalid low price".to_string(),
-            })?,
-            close_price: data[4].parse().map_err(|_| BybitError::ApiError {
-                msg: "Invalid close price".to_string(),
-            })?,
-            volume: data[5].parse().map_err(|_| BybitError::ApiError {
-                msg: "Invalid volume".to_string(),
-            })?,
-            turnover: data[6].parse().map_err(|_| BybitError::ApiError {
-                msg: "Invalid turnover".to_string(),
-            })?,
-        })
+    println!("Running {} jobs from {}", jobs.len(), jobs_file.display());
+    let reports = jobs::run_jobs(&client, &jobs, &args.jobs_output, args.max_records, args.limit_per_request).await;
+
+    let mut failed = 0;
+    for (job, report) in jobs.iter().zip(&reports) {
+        match &report.result {
+            Ok(records) => println!(
+                "OK     {} {} {} -> {} ({} records)",
+                job.category, job.symbol, job.interval, report.output, records
+            ),
+            Err(error) => {
+                failed += 1;
+                println!(
+                    "FAILED {} {} {} -> {}: {}",
+                    job.category, job.symbol, job.interval, report.output, error
+                );
+            }
+        }
     }
 
-    fn format_time(&self) -> String {
-        let dt = DateTime::from_timestamp_millis(self.start_time as i64)
-            .unwrap_or_else(|| DateTime::from_timestamp(0, 0).unwrap());
-        dt.format("%Y-%m-%d %H:%M:%S UTC").to_string()
-    }
-
-    fn to_barter_event(&self, instrument_index: usize, interval_minutes: u32, category: &str) -> BarterMarketStreamEvent {
-        let start_time = DateTime::from_timestamp_millis(self.start_time as i64)
-            .unwrap_or_else(|| DateTime::from_timestamp(0, 0).unwrap());
-        
-        // Calculate close time by adding interval duration
-        let close_time = start_time + chrono::Duration::minutes(interval_minutes as i64);
-        let now = Utc::now();
-        
-        // Map category to exchange name
-        let exchange_name = match category {
-            "spot" => "bybit_spot",
-            "linear" => "bybit_perpetuals_usd",
-            "inverse" => "bybit_perpetuals_usd", // Using same as linear for now
-            _ => "bybit_spot",
-        };
-        
-        let candle = BarterCandle {
-            close_time,
-            open: self.open_price,
-            high: self.high_price,
-            low: self.low_price,
-            close: self.close_price,
-            volume: self.volume,
-            trade_count: 0, // Bybit doesn't provide trade count in kline data
-        };
-        
-        let market_event = BarterMarketEvent {
-            time_exchange: start_time,
-            time_received: now,
-            exchange: exchange_name.to_string(),
-            instrument: instrument_index,
-            kind: BarterDataKind {
-                Candle: candle,
-            },
-        };
-        
-        BarterMarketStreamEvent {
-            item: BarterMarketEventResult {
-                ok: market_event,
-            },
+    report_retries(&client, false);
+
+    if failed > 0 {
+        return Err(BybitError::ApiError {
+            msg: format!("{} of {} jobs failed", failed, jobs.len()),
         }
+        .into());
     }
+    Ok(())
 }
 
-#[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
-struct Args {
-    /// Symbol to fetch (e.g., BTCUSDT)
-    #[arg(short, long, default_value = "BTCUSDT")]
-    symbol: String,
+/// Reject `--output-format` settings which apply to every mode, beyond the conflicts clap
+/// checks itself.
+fn check_output_args(args: &Args) -> Result<(), BybitError> {
+    match (args.timestamp_unit, args.output_format) {
+        (Some(TimestampUnit::Rfc3339), OutputFormat::InfluxLine) => {
+            return Err(BybitError::ArgumentError(
+                "influx-line timestamps can't be rfc3339, use --timestamp-unit millis, seconds or nanos".to_string(),
+            ));
+        }
+        (Some(_), format) if !args.split_by_day && !matches!(format, OutputFormat::Csv | OutputFormat::Tsv | OutputFormat::InfluxLine) => {
+            return Err(BybitError::ArgumentError(format!(
+                "--timestamp-unit applies to csv, tsv and influx-line output, not {}",
+                format
+            )));
+        }
+        _ => {}
+    }
+    if args.tee && matches!(args.output_format, OutputFormat::OhlcvBinary | OutputFormat::Parquet | OutputFormat::ArrowStream) {
+        return Err(BybitError::ArgumentError(
+            "--tee can't echo binary output, it's only supported for the table, barter, csv and tsv formats".to_string(),
+        ));
+    }
+    Ok(())
+}
 
-    /// Kline interval in minutes (e.g., 15, 60, 240)
-    #[arg(short, long, default_value = "15")]
-    interval: String,
+/// Reject combinations of fetch options which clap can't check, as they depend on the output
+/// format or on how many symbols, intervals, kline types and categories are fetched.
+fn check_fetch_args(args: &Args) -> Result<(), BybitError> {
+    let format = args.output_format;
+    let parquet = format == OutputFormat::Parquet;
+    let intervals = args.interval.len();
+    let symbols = args.symbol.split(',').count();
+    let kline_types = args.kline_type.len();
+    let all_categories = args.category == CategoryArg::All;
+    let checksums = args.print_checksum || args.verify_checksum.is_some();
 
-    /// Start date in YYYY/MM/DD format
-    #[arg(long)]
-    start_date: String,
+    if args.summary_only && kline_types > 1 {
+        return Err(BybitError::ArgumentError(
+            "--summary-only requires a single --kline-type".to_string(),
+        ));
+    }
+    if args.compact_barter && !args.barter_objects() {
+        return Err(BybitError::ArgumentError(
+            "--compact-barter requires --output-format barter, or json-array-file with --json-array-items barter".to_string(),
+        ));
+    }
+    if args.synthesize_trades && !args.barter_objects() {
+        return Err(BybitError::ArgumentError(
+            "--synthesize-trades requires --output-format barter, or json-array-file with --json-array-items barter".to_string(),
+        ));
+    }
 
-    /// End date in YYYY/MM/DD format
-    #[arg(long)]
-    end_date: String,
+    // Every other format normalises volumes to base and quote coin
+    let raw_volumes = matches!(format, OutputFormat::KlineJson | OutputFormat::InfluxLine);
+    if args.strict_units && raw_volumes && category::mixes_volume_units(args.category.categories()) {
+        return Err(BybitError::ArgumentError(format!(
+            "--strict-units: {} output keeps raw volume and turnover, which are base and quote coin for spot and linear but quote and base coin for inverse; use table or barter output, or fetch categories separately",
+            format
+        )));
+    }
+    if all_categories
+        && (!matches!(format, OutputFormat::Table | OutputFormat::Barter | OutputFormat::KlineJson | OutputFormat::JsonlFlat | OutputFormat::InfluxLine)
+            || args.split_by_day
+            || args.summary_only
+            || args.normalize_symbol
+            || args.state_file.is_some())
+    {
+        return Err(BybitError::ArgumentError(
+            "--category all is only supported for table, barter, kline-json, jsonl-flat and influx-line output, without --split-by-day, --summary-only, --normalize-symbol or --state-file".to_string(),
+        ));
+    }
 
-    /// Category (spot, linear, inverse)
-    #[arg(short, long, default_value = "linear")]
-    category: String,
+    let templated_output = args
+        .output
+        .as_ref()
+        .is_some_and(|output| output.to_string_lossy().contains("{interval}"));
+    if intervals > 1
+        && (!matches!(format, OutputFormat::Barter | OutputFormat::Csv | OutputFormat::Tsv)
+            || (!templated_output && format != OutputFormat::Barter)
+            || args.split_by_day
+            || args.summary_only
+            || args.state_file.is_some()
+            || all_categories
+            || args.day_boundary_tz.is_some()
+            || checksums)
+    {
+        return Err(BybitError::ArgumentError(
+            "Multiple intervals are supported for barter output, or csv and tsv written to an --output path containing {interval}, without --split-by-day, --summary-only, --state-file, --category all, --day-boundary-tz or checksums".to_string(),
+        ));
+    }
+    if symbols > 1
+        && (format != OutputFormat::Barter
+            || intervals > 1
+            || args.split_by_day
+            || args.summary_only
+            || args.state_file.is_some()
+            || all_categories
+            || args.probe_intervals
+            || checksums)
+    {
+        return Err(BybitError::ArgumentError(
+            "Multiple symbols are supported for barter output of a single interval, without --split-by-day, --summary-only, --state-file, --category all, --probe-intervals or checksums".to_string(),
+        ));
+    }
+    if args.since_last_bar_of.is_some() && intervals > 1 {
+        return Err(BybitError::ArgumentError(
+            "--since-last-bar-of resumes a single interval".to_string(),
+        ));
+    }
 
-    /// Maximum number of records to fetch (program will automatically paginate)
-    #[arg(short, long, default_value = "1000")]
-    max_records: u32,
+    if args.split_by_day && !matches!(format, OutputFormat::Table | OutputFormat::Csv | OutputFormat::Tsv) {
+        return Err(BybitError::ArgumentError(
+            "--split-by-day writes csv or tsv files, it can't be combined with barter or parquet output".to_string(),
+        ));
+    }
+    if args.show_close_time && (!matches!(format, OutputFormat::Table | OutputFormat::Csv | OutputFormat::Tsv) || intervals > 1) {
+        return Err(BybitError::ArgumentError(
+            "--show-close-time applies to table, csv and tsv output of a single interval".to_string(),
+        ));
+    }
+    if args.verify_continuity_against_exchange
+        && (kline_types > 1 || all_categories || intervals > 1 || symbols > 1 || parquet)
+    {
+        return Err(BybitError::ArgumentError(
+            "--verify-continuity-against-exchange checks a single series: one symbol, interval, kline type and category, not written as parquet".to_string(),
+        ));
+    }
+    if !args.spec_change.is_empty() && (symbols > 1 || parquet) {
+        return Err(BybitError::ArgumentError(
+            "--spec-change restates a single symbol, without parquet output".to_string(),
+        ));
+    }
+    if args.trade_summaries()
+        && (!args.barter_objects() || kline_types > 1 || all_categories || intervals > 1 || symbols > 1)
+    {
+        return Err(BybitError::ArgumentError(
+            "--data-kind trade-summary emits barter events of a single series: one symbol, interval, kline type and category".to_string(),
+        ));
+    }
+    if args.price_field.is_some() && (format.delimited().is_none() || kline_types > 1 || all_categories) {
+        return Err(BybitError::ArgumentError(
+            "--price-field writes csv or tsv of a single series: one kline type and category".to_string(),
+        ));
+    }
+    if args.split_on_gaps
+        && (matches!(format, OutputFormat::Table | OutputFormat::JsonArrayFile | OutputFormat::Parquet)
+            || (args.output.is_none() && !matches!(format, OutputFormat::Barter | OutputFormat::KlineJson))
+            || kline_types > 1
+            || all_categories
+            || intervals > 1
+            || symbols > 1)
+    {
+        return Err(BybitError::ArgumentError(
+            "--split-on-gaps splits a single series, into --output files for barter, kline-json, jsonl-flat, influx-line, csv, tsv, ohlcv-binary and arrow-stream output, or with segment markers for barter and kline-json on stdout".to_string(),
+        ));
+    }
+    if args.max_file_size.is_some()
+        && (matches!(format, OutputFormat::Table | OutputFormat::Parquet) || intervals > 1 || symbols > 1)
+    {
+        return Err(BybitError::ArgumentError(
+            "--max-file-size splits a single symbol and interval, and not table or parquet output".to_string(),
+        ));
+    }
+    if args.incremental_emit.is_some() && (parquet || intervals > 1 || symbols > 1) {
+        return Err(BybitError::ArgumentError(
+            "--incremental-emit supports a single symbol and interval, and not parquet output".to_string(),
+        ));
+    }
+    if args.order == SeriesOrder::Desc && (parquet || intervals > 1 || symbols > 1) {
+        return Err(BybitError::ArgumentError(
+            "--order desc is only supported for a single series, written by a format other than parquet".to_string(),
+        ));
+    }
 
-    /// Use testnet instead of mainnet
-    #[arg(long)]
-    testnet: bool,
+    if parquet && args.pagination == Pagination::Count {
+        return Err(BybitError::ArgumentError(
+            "--pagination count buffers the whole range before it can be written, use the default --pagination time with parquet output, which is streamed".to_string(),
+        ));
+    }
+    if parquet && args.detect_anomalies {
+        return Err(BybitError::ArgumentError(
+            "--detect-anomalies isn't supported with parquet output, which is streamed".to_string(),
+        ));
+    }
+    if parquet && checksums {
+        return Err(BybitError::ArgumentError(
+            "--print-checksum and --verify-checksum aren't supported with parquet output, which is streamed".to_string(),
+        ));
+    }
+    if parquet && args.state_file.is_some() {
+        return Err(BybitError::ArgumentError(
+            "--state-file isn't supported with parquet output, each run would overwrite the previous file".to_string(),
+        ));
+    }
 
-    /// Output format: 'table' (default) or 'barter' (JSON format compatible with barter backtesting)
-    #[arg(long, default_value = "table")]
-    output_format: String,
+    if args.probe_intervals && all_categories {
+        return Err(BybitError::ArgumentError(
+            "--probe-intervals probes a single category, --category all isn't supported".to_string(),
+        ));
+    }
+    if args.plan_json && (intervals > 1 || symbols > 1 || kline_types > 1 || all_categories) {
+        return Err(BybitError::ArgumentError(
+            "--plan-json plans a single series: one symbol, interval, kline type and category".to_string(),
+        ));
+    }
+    Ok(())
+}
 
-    /// Instrument index for barter format (default: 0)
-    #[arg(long, default_value = "0")]
-    instrument_index: usize,
+/// Render unix millis as a UTC minute, for run reports.
+fn format_millis(millis: u64) -> String {
+    DateTime::<Utc>::from_timestamp_millis(millis as i64)
+        .unwrap_or_else(|| DateTime::from_timestamp(0, 0).unwrap())
+        .format("%Y-%m-%d %H:%M UTC")
+        .to_string()
 }
 
-struct BybitClient {
-    client: Client,
-    base_url: String,
+/// Describe the run before fetching, for table output.
+fn print_header(args: &Args, start_date: &str, end_date: &str) {
+    println!("Fetching Bybit Kline Data");
+    println!("Symbol: {}", args.symbol);
+    let intervals = args.interval.iter().map(Interval::as_str).collect::<Vec<_>>();
+    println!("Interval: {} minutes", intervals.join(","));
+    println!("Category: {}", args.category);
+    println!("Start Date: {}", start_date);
+    println!("End Date: {}", end_date);
+    match args.max_records {
+        window::UNLIMITED_RECORDS => println!("Max Records: unlimited"),
+        max_records => println!("Max Records: {}", max_records),
+    }
+    println!("Using: {}", if args.testnet { "Testnet" } else { "Mainnet" });
+    println!();
+    println!("Note: Program will automatically paginate to fetch all data within the date range.");
+    println!();
 }
 
-impl BybitClient {
-    fn new(testnet: bool) -> Self {
-        let base_url = if testnet {
-            "https://api-testnet.bybit.com".to_string()
-        } else {
-            "https://api.bybit.com".to_string()
+/// `--probe-intervals`: print the earliest and latest bar Bybit serves over the range for
+/// every interval.
+async fn run_probe_intervals(args: &Args, start: u64, end: u64) -> Result<(), Box<dyn Error>> {
+    let client = build_client(args)?;
+    println!("{:<9} {:<5} {:<22} {:<22}", "Interval", "Data", "Earliest", "Latest");
+    println!("{}", "-".repeat(60));
+    for &interval in Interval::all() {
+        match client.probe_interval(&args.symbol, args.category(), interval, start, end).await {
+            Ok(probe) => match (probe.earliest, probe.latest) {
+                (Some(earliest), Some(latest)) => println!(
+                    "{:<9} {:<5} {:<22} {:<22}",
+                    interval, "yes", format_millis(earliest), format_millis(latest)
+                ),
+                _ => println!("{:<9} {:<5}", interval, "no"),
+            },
+            Err(error) => println!("{:<9} error: {}", interval, error),
+        }
+    }
+    report_retries(&client, false);
+    Ok(())
+}
+
+/// `--count-only` and `--estimate-size`: print the records, API calls and output size a run
+/// over the range would produce, without fetching.
+fn print_estimate(args: &Args, symbols: usize, start: u64, end: u64) -> Result<(), Box<dyn Error>> {
+    let estimate = args
+        .interval
+        .iter()
+        .map(|&interval| FetchEstimate::new(start, end, interval, args.max_records, args.limit_per_request))
+        .fold(FetchEstimate { records: 0, requests: 0 }, |total, estimate| FetchEstimate {
+            records: total.records + estimate.records,
+            requests: total.requests + estimate.requests,
+        });
+    let (kline_types, categories) = (args.kline_type.len(), args.category.categories().len());
+    let series_count = (kline_types * categories * symbols) as u64;
+    let records = estimate.records * series_count;
+    println!("Expected records: {}", records);
+    if args.count_only {
+        println!("API calls: {} (limit per request: {})", estimate.requests * series_count, args.limit_per_request);
+        if series_count > 1 {
+            println!(
+                "Series: {} ({} kline types x {} categories x {} symbols, {} records, {} API calls each)",
+                series_count, kline_types, categories, symbols, estimate.records, estimate.requests
+            );
+        }
+    }
+    if args.estimate_size {
+        let size = estimate_output_size(args, args.interval())?;
+        println!(
+            "Estimated {} size: {} ({:.1} bytes per record)",
+            args.output_format,
+            size_estimate::format_bytes(size.total_bytes(records)),
+            size.bytes_per_record
+        );
+    }
+    Ok(())
+}
+
+/// Fetch every `--kline-type` of every category over the range and merge them into a single
+/// time ordered series, with the outcome of each category.
+///
+/// With `--category all`, failed categories are reported and the rest are still returned.
+async fn fetch_merged(
+    args: &Args,
+    client: &BybitClient,
+    interval: Interval,
+    start: u64,
+    end: u64,
+) -> Result<(Vec<Kline>, Vec<(KlineType, CategoryReport)>), BybitError> {
+    let categories = args.category.categories();
+    let mut klines = Vec::new();
+    let mut reports = Vec::new();
+    for &kline_type in &args.kline_type {
+        let query = KlineQuery {
+            symbol: args.symbol.clone(),
+            category: args.category(),
+            interval,
+            kline_type,
+            start,
+            end,
+            max_records: args.max_records,
+            limit_per_request: args.limit_per_request,
         };
+        // Only show progress for table format, machine readable output stays clean
+        let quiet = args.machine_readable();
+        let (fetched, category_reports) = client
+            .get_kline_categories(&query, categories, |_, progress| if !quiet { print_progress(progress) })
+            .await;
+        klines.extend(fetched);
+        reports.extend(category_reports.into_iter().map(|report| (kline_type, report)));
+    }
+    let failed = reports.iter().filter(|(_, report)| report.result.is_err()).count();
+    if failed == reports.len() || (failed > 0 && categories.len() == 1) {
+        let error = reports
+            .into_iter()
+            .find_map(|(_, report)| report.result.err())
+            .expect("at least one series failed");
+        return Err(error);
+    }
+    merge_series(args, &mut klines);
+    Ok((klines, reports))
+}
 
-        Self {
-            client: Client::new(),
-            base_url,
-        }
-    }
-
-    async fn get_kline_single(
-        &self,
-        symbol: &str,
-        interval: &str,
-        start: u64,
-        end: u64,
-        category: &str,
-        limit: u32,
-    ) -> Result<Vec<Kline>, BybitError> {
-        let url = format!("{}/v5/market/kline", self.base_url);
-        
-        let response = self
-            .client
-            .get(&url)
-            .query(&[
-                ("category", category),
-                ("symbol", symbol),
-                ("interval", interval),
-                ("start", &start.to_string()),
-                ("end", &end.to_string()),
-                ("limit", &limit.to_string()),
-            ])
-            .send()
-            .await?
-            .json::<BybitResponse>()
-            .await?;
-
-        if response.ret_code != 0 {
-            return Err(BybitError::ApiError {
-                msg: response.ret_msg,
+/// `--print-checksum` and `--verify-checksum` of the series about to be written.
+fn check_checksum(args: &Args, klines: &[Kline]) -> Result<(), BybitError> {
+    if !args.print_checksum && args.verify_checksum.is_none() {
+        return Ok(());
+    }
+    let actual = checksum::checksum(klines);
+    if args.print_checksum {
+        if args.machine_readable() {
+            eprintln!("Checksum: {}", actual);
+        } else {
+            println!("Checksum: {}", actual);
+        }
+    }
+    if let Some(expected) = &args.verify_checksum {
+        if !expected.eq_ignore_ascii_case(&actual) {
+            return Err(BybitError::ChecksumMismatch {
+                expected: expected.clone(),
+                actual,
             });
         }
+    }
+    Ok(())
+}
 
-        let result = response.result.ok_or_else(|| BybitError::ApiError {
-            msg: "No result data".to_string(),
-        })?;
+/// Barter writer of the run, looking up the canonical symbol for `--normalize-symbol` and the
+/// trades behind `--data-kind trade-summary`.
+async fn barter_writer(
+    args: &Args,
+    client: &BybitClient,
+    klines: &[Kline],
+    interval: Interval,
+) -> Result<BarterWriter, BybitError> {
+    let mut options = args.barter_options();
+    if args.normalize_symbol {
+        let instrument = client.get_instrument_info(args.category(), &args.symbol).await?;
+        options.symbol = Some(instrument.canonical_symbol());
+        options.raw_symbol = Some(instrument.symbol);
+    }
+    let trade_summaries = match args.trade_summaries() {
+        true => Some(trade_summaries(args, client, klines).await?),
+        false => None,
+    };
+    Ok(BarterWriter {
+        options,
+        interval,
+        synthetic_trades: args.synthesize_trades.then_some(args.trade_walk),
+        candles: args.data_kind.contains(&DataKind::Candle),
+        trade_summaries,
+        compact: args.compact_barter,
+    })
+}
 
-        let mut klines = Vec::new();
-        for kline_data in result.list {
-            klines.push(Kline::from_vec(kline_data)?);
-        }
-
-        Ok(klines)
-    }
-
-    async fn get_kline(
-        &self,
-        symbol: &str,
-        interval: &str,
-        start: u64,
-        end: u64,
-        category: &str,
-        max_records: u32,
-        output_format: &str,
-    ) -> Result<Vec<Kline>, BybitError> {
-        let mut all_klines: Vec<Kline> = Vec::new();
-        let mut current_start = start;
-        let chunk_limit = 1000u32; // Bybit's max limit is 1000
-        
-        // Calculate interval duration in milliseconds
-        let interval_ms = self.parse_interval_to_ms(interval)?;
-        
-        while current_start < end && (all_klines.len() as u32) < max_records {
-            // Calculate how many more records we need
-            let remaining_records = max_records - (all_klines.len() as u32);
-            let current_chunk_limit = std::cmp::min(chunk_limit, remaining_records);
-            
-            // Calculate the end time for this chunk
-            let chunk_end = std::cmp::min(
-                current_start + (current_chunk_limit as u64 * interval_ms),
-                end
-            );
-            
-            // Only show progress for table format
-            if output_format != "barter" {
-                println!("Fetching data from {} to {} (chunk size: {})...", 
-                    DateTime::from_timestamp_millis(current_start as i64)
-                        .unwrap_or_else(|| DateTime::from_timestamp(0, 0).unwrap())
-                        .format("%Y-%m-%d %H:%M:%S"),
-                    DateTime::from_timestamp_millis(chunk_end as i64)
-                        .unwrap_or_else(|| DateTime::from_timestamp(0, 0).unwrap())
-                        .format("%Y-%m-%d %H:%M:%S"),
-                    current_chunk_limit
-                );
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    let mut args = Args::parse();
+    if log::set_logger(&StderrLogger).is_ok() {
+        log::set_max_level(match args.verbose {
+            true => log::LevelFilter::Debug,
+            false => log::LevelFilter::Info,
+        });
+    }
+    output::install_panic_hook();
+
+    let symbol = normalize_symbol(&args.symbol);
+    if symbol != args.symbol {
+        eprintln!("Warning: normalized symbol {:?} to {}", args.symbol, symbol);
+        args.symbol = symbol;
+    }
+
+    if args.list_intervals {
+        println!("Supported intervals:");
+        for interval in Interval::all() {
+            println!("  {:<5} {}", interval.as_str(), interval.description());
+        }
+        return Ok(());
+    }
+
+    if args.dump_schema {
+        let samples = barter::sample_events();
+        match args.compact_barter {
+            true => {
+                let compact = samples.iter().map(|event| CompactBarterEvent::from(&event.item.ok)).collect::<Vec<_>>();
+                println!("{}", serde_json::to_string_pretty(&compact)?);
             }
-            
-            let mut chunk_klines = self.get_kline_single(
-                symbol,
-                interval,
-                current_start,
-                chunk_end,
-                category,
-                current_chunk_limit,
-            ).await?;
-            
-            if chunk_klines.is_empty() {
-                if output_format != "barter" {
-                    println!("No more data available.");
+            false => println!("{}", serde_json::to_string_pretty(&samples)?),
+        }
+        return Ok(());
+    }
+
+    if args.live {
+        return run_live(&args).await;
+    }
+
+    if args.no_dedup {
+        eprintln!(
+            "Warning: --no-dedup emits klines as Bybit returned them{}, output may contain duplicate and overlapping bars",
+            if args.no_sort { " in fetch order" } else { "" }
+        );
+    }
+    check_output_args(&args)?;
+
+    if let Some(jobs_file) = &args.jobs_file {
+        return run_jobs(&args, jobs_file).await;
+    }
+
+    if let Some(convert_from) = &args.convert_from {
+        return run_convert(&args, convert_from);
+    }
+
+    if let Some(validate_file) = &args.validate_file {
+        return run_validate_file(&args, validate_file);
+    }
+
+    if args.only_final_bar {
+        return run_final_bar(&args).await;
+    }
+
+    if args.benchmark {
+        return run_benchmark(&args).await;
+    }
+
+    if args.data_type == DataType::DeliveryPrice {
+        return run_delivery_prices(&args).await;
+    }
+
+    if args.probe_listing_date {
+        return run_listing_dates(&args).await;
+    }
+
+    run_fetch(args).await
+}
+
+/// Fetch the date range of every requested series and write it in `--output-format`.
+async fn run_fetch(mut args: Args) -> Result<(), Box<dyn Error>> {
+    check_fetch_args(&args)?;
+
+    // With --state-file, resume from the bar after the last one fetched for the symbol
+    let state = args.state_file.as_deref().map(StateFile::lock).transpose()?;
+    let resume_from = match &args.since_last_bar_of {
+        Some(path) => Some(validate::last_bar_start(path)?),
+        None => state.as_ref().and_then(|state| state.last_fetched(&args.symbol)),
+    };
+    let now = Utc::now().timestamp_millis() as u64;
+    let start_date = match (resume_from, &args.start_date) {
+        (Some(last), _) => format!("{} (resuming after the last fetched bar)", format_millis(last)),
+        (None, Some(start_date)) => start_date.clone(),
+        (None, None) => {
+            return Err(BybitError::ArgumentError(format!(
+                "--state-file has no entry for {}, pass --start-date for the first run",
+                args.symbol
+            )).into());
+        }
+    };
+    let end_date = args.end_date.clone().unwrap_or_else(|| "now".to_string());
+
+    if args.interval_auto {
+        args.interval = vec![auto_interval(&args, now)?];
+    }
+    if !args.category_fallback.is_empty() {
+        args.category = fallback_category(&args, now).await?;
+    }
+
+    // Only show info for table format, machine readable output stays clean
+    if !args.machine_readable() {
+        print_header(&args, &start_date, &end_date);
+    }
+
+    let intervals = args.interval.clone();
+    let interval = args.interval();
+    let symbols = args.symbol.split(',').map(normalize_symbol).collect::<Vec<_>>();
+    if args.category == CategoryArg::Option {
+        for symbol in &symbols {
+            symbol.parse::<OptionSymbol>()?;
+        }
+        eprintln!("Warning: option klines only cover an option's life until expiry, and Bybit may not serve every interval for options");
+    }
+    let day_boundary_tz = args
+        .day_boundary_tz
+        .as_deref()
+        .map(|zone| {
+            zone.parse::<Tz>()
+                .map_err(|e| BybitError::ArgumentError(format!("Invalid --day-boundary-tz: {}", e)))
+        })
+        .transpose()?;
+
+    let (start_timestamp, end_timestamp) = match (resume_from, &args.start_date, &args.end_date) {
+        (Some(last), _, end_date) => (
+            interval.next_start(last),
+            end_date.as_deref().map(parse_date).transpose()?.unwrap_or(now),
+        ),
+        (None, Some(start_date), None) => (parse_date(start_date)?, now),
+        (None, Some(start_date), Some(end_date)) => match day_boundary_tz {
+            Some(tz) if interval == Interval::Day => {
+                if !args.machine_readable() {
+                    let utc_midnight = DateTime::<Utc>::from_timestamp_millis(parse_date(start_date)? as i64)
+                        .unwrap_or_else(|| DateTime::from_timestamp(0, 0).unwrap());
+                    println!(
+                        "Day Boundary: 00:00 UTC ({} {})",
+                        utc_midnight.with_timezone(&tz).format("%H:%M"),
+                        tz
+                    );
+                    println!();
                 }
-                break;
-            }
-            
-            // Sort by start_time to ensure proper ordering
-            chunk_klines.sort_by_key(|k| k.start_time);
-            
-            // Remove duplicates if any (based on start_time)
-            if !all_klines.is_empty() {
-                let last_time = all_klines.last().unwrap().start_time;
-                chunk_klines.retain(|k| k.start_time > last_time);
+                daily_range_in_tz(start_date, end_date, tz, args.snap_to_utc_day)?
             }
-            
-            // Limit the chunk to not exceed max_records
-            let space_left = max_records as usize - all_klines.len();
-            if chunk_klines.len() > space_left {
-                chunk_klines.truncate(space_left);
-            }
-            
-            if output_format != "barter" {
-                println!("Retrieved {} records in this chunk. Total so far: {}", 
-                    chunk_klines.len(), 
-                    all_klines.len() + chunk_klines.len()
-                );
+            Some(_) => {
+                eprintln!("Warning: --day-boundary-tz only applies to the D interval and will be ignored");
+                (parse_date(start_date)?, parse_date(end_date)?)
             }
-            
-            all_klines.extend(chunk_klines);
-            
-            // Check if we've reached the max_records limit
-            if (all_klines.len() as u32) >= max_records {
-                if output_format != "barter" {
-                    println!("Reached maximum record limit of {}.", max_records);
-                }
-                break;
+            None => (parse_date(start_date)?, parse_date(end_date)?),
+        },
+        (None, None, _) => unreachable!("clap requires --start-date unless --state-file has an entry for the symbol"),
+    };
+    if args.strict_interval_alignment {
+        for &interval in &intervals {
+            if resume_from.is_none() {
+                check_alignment("--start-date", start_timestamp, interval)?;
             }
-            
-            // Move to next chunk - start from the last kline's time + interval
-            if let Some(last_kline) = all_klines.last() {
-                current_start = last_kline.start_time + interval_ms;
-            } else {
-                break;
+            if args.end_date.is_some() {
+                check_alignment("--end-date", end_timestamp, interval)?;
             }
-            
-            // Add a small delay to avoid rate limiting
-            tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
-        }
-        
-        // Final sort and deduplication
-        all_klines.sort_by_key(|k| k.start_time);
-        all_klines.dedup_by_key(|k| k.start_time);
-        
-        Ok(all_klines)
-    }
-    
-    fn parse_interval_to_ms(&self, interval: &str) -> Result<u64, BybitError> {
-        match interval {
-            "1" => Ok(60_000),           // 1 minute
-            "3" => Ok(180_000),          // 3 minutes
-            "5" => Ok(300_000),          // 5 minutes
-            "15" => Ok(900_000),         // 15 minutes
-            "30" => Ok(1_800_000),       // 30 minutes
-            "60" => Ok(3_600_000),       // 1 hour
-            "120" => Ok(7_200_000),      // 2 hours
-            "240" => Ok(14_400_000),     // 4 hours
-            "360" => Ok(21_600_000),     // 6 hours
-            "720" => Ok(43_200_000),     // 12 hours
-            "D" => Ok(86_400_000),       // 1 day
-            "W" => Ok(604_800_000),      // 1 week
-            "M" => Ok(2_592_000_000),    // 30 days (approximate)
-            _ => Err(BybitError::ApiError {
-                msg: format!("Unsupported interval: {}", interval),
-            }),
-        }
-    }
-}
-
-fn parse_date(date_str: &str) -> Result<u64, BybitError> {
-    let date = NaiveDate::parse_from_str(date_str, "%Y/%m/%d")
-        .map_err(|e| BybitError::DateParseError(format!("Invalid date format '{}': {}", date_str, e)))?;
-    
-    let datetime = date.and_hms_opt(0, 0, 0)
-        .ok_or_else(|| BybitError::DateParseError("Invalid time".to_string()))?;
-    
-    let utc_datetime = DateTime::<Utc>::from_naive_utc_and_offset(datetime, Utc);
-    Ok(utc_datetime.timestamp_millis() as u64)
-}
-
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn Error>> {
-    let args = Args::parse();
-
-    // Only show info for table format
-    if args.output_format != "barter" {
-        println!("Fetching Bybit Kline Data");
-        println!("Symbol: {}", args.symbol);
-        println!("Interval: {} minutes", args.interval);
-        println!("Category: {}", args.category);
-        println!("Start Date: {}", args.start_date);
-        println!("End Date: {}", args.end_date);
-        println!("Max Records: {}", args.max_records);
-        println!("Using: {}", if args.testnet { "Testnet" } else { "Mainnet" });
-        println!();
-        println!("Note: Program will automatically paginate to fetch all data within the date range.");
-        println!();
-    }
-
-    let start_timestamp = parse_date(&args.start_date)?;
-    let end_timestamp = parse_date(&args.end_date)?;
+        }
+    }
 
-    if start_timestamp >= end_timestamp {
+    if resume_from.is_some() && start_timestamp >= end_timestamp {
+        if !args.machine_readable() {
+            println!("Already up to date, no new bars since {}", format_millis(resume_from.unwrap_or_default()));
+        }
+        return Ok(());
+    }
+    if start_timestamp >= end_timestamp && !args.empty_ok {
         return Err(BybitError::DateParseError(
             "Start date must be before end date".to_string(),
         ).into());
     }
 
-    let client = BybitClient::new(args.testnet);
-    
-    if args.output_format != "barter" {
-        println!("Fetching kline data...");
+    // With several intervals, {interval} is expanded per interval when writing each one
+    let interval_placeholder = if intervals.len() > 1 { "{interval}" } else { interval.as_str() };
+    let output_template = output::OutputTemplate {
+        symbol: &args.symbol,
+        category: args.category.as_str(),
+        interval: interval_placeholder,
+        start: start_timestamp,
+        end: end_timestamp,
+    };
+    args.output = args.output.as_deref().map(|output| output_template.expand(output));
+    if start_timestamp >= end_timestamp {
+        return write_empty(&args);
     }
-    let klines = client
-        .get_kline(
-            &args.symbol,
-            &args.interval,
-            start_timestamp,
-            end_timestamp,
-            &args.category,
-            args.max_records,
-            &args.output_format,
-        )
-        .await?;
 
-    match args.output_format.as_str() {
-        "barter" => {
-            // Parse interval to get minutes for close_time calculation
-            let interval_minutes: u32 = args.interval.parse().unwrap_or(15);
-            
-            // Output in barter-compatible JSON format
-            for kline in &klines {
-                let barter_event = kline.to_barter_event(args.instrument_index, interval_minutes, &args.category);
-                println!("{}", serde_json::to_string(&barter_event)?);
+    if args.probe_intervals {
+        return run_probe_intervals(&args, start_timestamp, end_timestamp).await;
+    }
+
+    if args.plan_json {
+        let plan = FetchPlan::new(start_timestamp, end_timestamp, interval, args.max_records, args.limit_per_request);
+        println!("{}", serde_json::to_string_pretty(&plan)?);
+        return Ok(());
+    }
+
+    if args.count_only || args.estimate_size {
+        return print_estimate(&args, symbols.len(), start_timestamp, end_timestamp);
+    }
+
+    let client = build_client(&args)?;
+    let all_categories = args.category == CategoryArg::All;
+
+    // Advisory only, catches eg/ --category spot for a symbol whose perpetual is far more liquid
+    if !args.no_preflight && !all_categories {
+        for symbol in &symbols {
+            if let Some(warning) = client.category_mismatch_warning(args.category(), symbol).await {
+                eprintln!("Warning: {}", warning);
             }
-        },
-        "table" | _ => {
-            // Default table format
-            println!("\nReceived {} kline records:\n", klines.len());
-            println!(
-                "{:<20} {:<12} {:<12} {:<12} {:<12} {:<15} {:<15}",
-                "Time", "Open", "High", "Low", "Close", "Volume", "Turnover"
-            );
-            println!("{}", "-".repeat(110));
+        }
+    }
 
-            for kline in &klines {
-                println!(
-                    "{:<20} {:<12.4} {:<12.4} {:<12.4} {:<12.4} {:<15.4} {:<15.4}",
-                    kline.format_time(),
-                    kline.open_price,
-                    kline.high_price,
-                    kline.low_price,
-                    kline.close_price,
-                    kline.volume,
-                    kline.turnover
-                );
+    if args.output_format == OutputFormat::Parquet {
+        return write_parquet(&args, &client, interval, start_timestamp, end_timestamp).await;
+    }
+    if intervals.len() > 1 {
+        return run_multi_interval(&args, &client, &intervals, start_timestamp, end_timestamp).await;
+    }
+    if symbols.len() > 1 {
+        return run_multi_symbol(&args, &client, &symbols, interval, start_timestamp, end_timestamp).await;
+    }
+
+    if !args.machine_readable() {
+        println!("Fetching kline data...");
+    }
+
+    // Plain single series runs render rows as chunks arrive, anything which needs the whole
+    // range first (merging, gap filling, summaries, checksums) is buffered
+    let streamable = !matches!(args.output_format, OutputFormat::Barter | OutputFormat::Parquet)
+        && !args.barter_objects()
+        && args.kline_type.len() == 1
+        && args.pagination == Pagination::Time
+        && !all_categories
+        && !args.split_on_gaps
+        && !args.detect_anomalies
+        && !args.verify_continuity_against_exchange
+        && args.spec_change.is_empty()
+        && !args.drop_zero_volume
+        && args.incremental_emit.is_none()
+        && args.fill_gaps.is_none()
+        && !args.summary_only
+        && !args.split_by_day
+        && state.is_none()
+        && !args.print_checksum
+        && args.verify_checksum.is_none()
+        && args.sample_rate.is_none()
+        && args.head.is_none()
+        && args.tail.is_none()
+        && args.order == SeriesOrder::Asc;
+    let query = KlineQuery {
+        symbol: args.symbol.clone(),
+        category: args.category(),
+        interval,
+        kline_type: args.kline_type[0],
+        start: start_timestamp,
+        end: end_timestamp,
+        max_records: args.max_records,
+        limit_per_request: args.limit_per_request,
+    };
+    if streamable {
+        return stream_output(&args, &client, &query).await;
+    }
+    write_buffered(&args, &client, state, query, now).await
+}
+
+/// Fetch a single symbol and interval as a whole, then restate, screen, fill and slice it before
+/// writing it in `--output-format`, for runs which can't be streamed.
+async fn write_buffered(
+    args: &Args,
+    client: &BybitClient,
+    mut state: Option<StateFile>,
+    query: KlineQuery,
+    now: u64,
+) -> Result<(), Box<dyn Error>> {
+    let interval = query.interval;
+    let all_categories = args.category == CategoryArg::All;
+    let (mut klines, reports) = fetch_merged(args, client, interval, query.start, query.end).await?;
+    if state.is_some() {
+        // Drop the still forming bar, so the next run picks it up once it has closed
+        klines.retain(|kline| interval.next_start(kline.start_time) <= now);
+    }
+    if args.verify_continuity_against_exchange {
+        verify_continuity(args, client, query, &klines).await?;
+    }
+    spec_change::apply_spec_changes(&mut klines, &args.spec_change);
+    screen_anomalies(args, &mut klines);
+    drop_zero_volume(args, &mut klines);
+    if let Some(gap_fill) = args.fill_gaps {
+        klines = fill_series(args, klines, interval, gap_fill);
+    }
+    if args.validate_monotonic {
+        kline::validate_monotonic(&klines)?;
+    }
+    slice_series(args, &mut klines);
+    check_checksum(args, &klines)?;
+
+    if let (true, Some(output_dir)) = (args.split_by_day, &args.output_dir) {
+        let written = output::write_split_by_day(
+            output_dir,
+            &args.symbol,
+            &klines,
+            args.output_format.delimited().unwrap_or(Delimited::Csv),
+            args.delimited_timestamp_unit(),
+            state.is_some(),
+            args.retry_idempotency_window,
+        )?;
+        save_state(state.as_mut(), &args.symbol, &klines)?;
+        if !args.machine_readable() {
+            for (path, rows) in &written {
+                println!("Wrote {} records to {}", rows, path.display());
             }
+            let total = written.iter().map(|(_, rows)| rows).sum::<usize>();
+            if total < klines.len() {
+                println!("Skipped {} records already in the day files", klines.len() - total);
+            }
+            println!("\nTotal records: {} across {} files", total, written.len());
+        }
+        report_retries(client, args.machine_readable());
+        return Ok(());
+    }
+
+    if args.summary_only {
+        let mut out = output::OutputSink::new(args.output.as_deref(), args.tee)?.with_compression(args.compress);
+        let summary = RangeSummary::from_klines(&klines, &args.symbol, args.category.as_str(), interval.as_str());
+        match (args.output_format, summary) {
+            (OutputFormat::Barter, Some(summary)) => writeln!(out, "{}", serde_json::to_string(&summary)?)?,
+            (_, Some(summary)) => summary.write_table(&mut out)?,
+            (OutputFormat::Barter, None) => {}
+            (_, None) => writeln!(out, "\nNo kline records received, nothing to summarise.")?,
+        }
+        out.flush()?;
+        report_retries(client, args.machine_readable());
+        return Ok(());
+    }
 
-            println!("\nTotal records: {}", klines.len());
+    // With --incremental-emit, only bars new or changed since the previous poll are written
+    let mut snapshot = args.incremental_emit.as_deref().map(EmitSnapshot::load).transpose()?;
+    let polled = snapshot.as_ref().map(|snapshot| {
+        let polled = klines.clone();
+        let counts = snapshot.retain_changed(&mut klines);
+        eprintln!(
+            "Incremental emit: {} new, {} changed, {} unchanged bars skipped",
+            counts.new, counts.changed, counts.unchanged
+        );
+        polled
+    });
+
+    let show_kline_type = args.kline_type.len() > 1;
+    let barter = match args.barter_objects() {
+        true => Some(barter_writer(args, client, &klines, interval).await?),
+        false => None,
+    };
+    let table = match args.output_format {
+        OutputFormat::Table => {
+            let renderer = TableRenderer {
+                show_category: all_categories,
+                show_kline_type,
+                show_filled: args.fill_gaps.is_some(),
+                ..table_renderer(args, client, all_categories).await
+            };
+            Some(TableWriter::new(renderer, Some(klines.len())))
         }
+        _ => None,
+    };
+    let mut writer = output_writer(args, barter, table).expect("parquet output is written by write_parquet");
+    if args.split_on_gaps {
+        write_segments(args, writer.as_mut(), &klines, interval)?;
+        save_state(state.as_mut(), &args.symbol, &klines)?;
+        report_retries(client, args.machine_readable());
+        return Ok(());
+    }
+    let mut out = output_sink(args)?;
+    writer::write_klines_parted(writer.as_mut(), &mut out, &klines)?;
+    out.flush()?;
+    // Only once written, so a failed run re-emits its bars on the next poll
+    if let (Some(snapshot), Some(polled)) = (snapshot.as_mut(), &polled) {
+        snapshot.save(polled)?;
     }
+    save_state(state.as_mut(), &args.symbol, &klines)?;
+    if all_categories {
+        report_categories(&reports, show_kline_type, args.machine_readable());
+    }
+    report_retries(client, args.machine_readable());
     Ok(())
-}
\ No newline at end of file
+}
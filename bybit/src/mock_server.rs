@@ -0,0 +1,92 @@
+//! Minimal HTTP server standing in for the Bybit API in tests.
+
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+};
+
+/// Response returned by a mock handler.
+pub struct MockResponse {
+    pub status: &'static str,
+    pub headers: Vec<(&'static str, String)>,
+    pub body: String,
+}
+
+impl MockResponse {
+    /// Successful Bybit response wrapping `result`.
+    pub fn ok(result: &str) -> Self {
+        Self {
+            status: "200 OK",
+            headers: Vec::new(),
+            body: format!(
+                r#"{{"retCode":0,"retMsg":"OK","time":0,"result":{}}}"#,
+                result
+            ),
+        }
+    }
+
+    /// Successful kline page for `category` containing `rows`, ordered newest first like Bybit.
+    pub fn klines(category: &str, rows: &[u64]) -> Self {
+        let list = rows
+            .iter()
+            .map(|start| format!(r#"["{}","1","2","0.5","1.5","10","15"]"#, start))
+            .collect::<Vec<_>>()
+            .join(",");
+        Self::ok(&format!(
+            r#"{{"symbol":"BTCUSDT","category":"{}","list":[{}]}}"#,
+            category, list
+        ))
+    }
+
+    pub fn status(status: &'static str) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: String::new(),
+        }
+    }
+}
+
+/// Value of the query parameter `name` in a raw HTTP request.
+pub fn query_param<'a>(request: &'a str, name: &str) -> Option<&'a str> {
+    let target = request.split_whitespace().nth(1)?;
+    let (_, query) = target.split_once('?')?;
+    query.split('&').find_map(|pair| {
+        let (key, value) = pair.split_once('=')?;
+        (key == name).then_some(value)
+    })
+}
+
+/// Serve every request with `handler`, which receives the raw request, returning the base URL.
+pub async fn serve<F>(handler: F) -> String
+where
+    F: Fn(&str) -> MockResponse + Send + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        loop {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = vec![0; 4096];
+            let read = socket.read(&mut request).await.unwrap();
+            let response = handler(&String::from_utf8_lossy(&request[..read]));
+
+            let headers = response
+                .headers
+                .iter()
+                .map(|(name, value)| format!("{}: {}\r\n", name, value))
+                .collect::<String>();
+            let raw = format!(
+                "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n{}Connection: close\r\n\r\n{}",
+                response.status,
+                response.body.len(),
+                headers,
+                response.body
+            );
+            socket.write_all(raw.as_bytes()).await.unwrap();
+        }
+    });
+
+    format!("http://{}", address)
+}
@@ -0,0 +1,195 @@
+use crate::{
+    category::Category,
+    kline::{Kline, KlineType},
+    writer::OutputWriter,
+    BybitError,
+};
+use std::io::{self, Read, Write};
+
+/// Magic bytes opening every `ohlcv-binary` file.
+pub const MAGIC: [u8; 8] = *b"BYBTOHLC";
+
+/// Format version following [`MAGIC`], bumped on any layout change.
+pub const VERSION: u32 = 1;
+
+/// Size of the file header: [`MAGIC`], then [`VERSION`] and [`RECORD_SIZE`] as little-endian
+/// `u32`s.
+pub const HEADER_SIZE: usize = 16;
+
+/// Size of each bar record: a little-endian `u64` start time (unix millis) followed by
+/// little-endian `f64` open, high, low, close, volume (base coin) and turnover (quote coin).
+pub const RECORD_SIZE: usize = 56;
+
+/// Write the `ohlcv-binary` file header.
+pub fn write_header<W: Write + ?Sized>(writer: &mut W) -> io::Result<()> {
+    writer.write_all(&MAGIC)?;
+    writer.write_all(&VERSION.to_le_bytes())?;
+    writer.write_all(&(RECORD_SIZE as u32).to_le_bytes())
+}
+
+/// Write a single bar record, with volume and turnover normalised to base and quote coin like
+/// the csv columns.
+pub fn write_record<W: Write + ?Sized>(writer: &mut W, kline: &Kline) -> io::Result<()> {
+    let mut record = [0u8; RECORD_SIZE];
+    record[..8].copy_from_slice(&kline.start_time.to_le_bytes());
+    let values = [
+        kline.open_price,
+        kline.high_price,
+        kline.low_price,
+        kline.close_price,
+        kline.base_volume(),
+        kline.quote_volume(),
+    ];
+    for (field, value) in record[8..].chunks_exact_mut(8).zip(values) {
+        field.copy_from_slice(&value.to_le_bytes());
+    }
+    writer.write_all(&record)
+}
+
+/// Read every bar of an `ohlcv-binary` file as trade klines of `category`, which the format
+/// doesn't record.
+pub fn read_klines<R: Read>(mut reader: R, category: Category) -> Result<Vec<Kline>, BybitError> {
+    read_header(&mut reader)?;
+    let mut body = Vec::new();
+    reader.read_to_end(&mut body)?;
+    if body.len() % RECORD_SIZE != 0 {
+        return Err(truncated(body.len() % RECORD_SIZE));
+    }
+
+    Ok(body
+        .chunks_exact(RECORD_SIZE)
+        .map(|record| {
+            let field = |n: usize| record[n * 8..(n + 1) * 8].try_into().expect("8 bytes");
+            let value = |n: usize| f64::from_le_bytes(field(n));
+            let (base_volume, quote_volume) = (value(5), value(6));
+            // Inverse klines carry quote volume in `volume` and base in `turnover`
+            let (volume, turnover) = match category {
+                Category::Inverse => (quote_volume, base_volume),
+                _ => (base_volume, quote_volume),
+            };
+            Kline {
+                start_time: u64::from_le_bytes(field(0)),
+                open_price: value(1),
+                high_price: value(2),
+                low_price: value(3),
+                close_price: value(4),
+                volume,
+                turnover,
+                kline_type: KlineType::Trade,
+                category,
+                filled: None,
+            }
+        })
+        .collect())
+}
+
+/// Latest start time of an `ohlcv-binary` file's bars, `None` if it holds none. Records are
+/// streamed one at a time, so the file is never held in memory.
+pub fn latest_start_time<R: Read>(mut reader: R) -> Result<Option<u64>, BybitError> {
+    read_header(&mut reader)?;
+    let mut latest = None;
+    let mut record = [0u8; RECORD_SIZE];
+    loop {
+        let mut filled = 0;
+        while filled < RECORD_SIZE {
+            match reader.read(&mut record[filled..])? {
+                0 => break,
+                read => filled += read,
+            }
+        }
+        match filled {
+            0 => return Ok(latest),
+            RECORD_SIZE => {}
+            partial => return Err(truncated(partial)),
+        }
+        let start_time = u64::from_le_bytes(record[..8].try_into().expect("8 bytes"));
+        latest = latest.max(Some(start_time));
+    }
+}
+
+/// Read and check the file header, leaving `reader` at the first record.
+fn read_header<R: Read>(reader: &mut R) -> Result<(), BybitError> {
+    let mut header = [0u8; HEADER_SIZE];
+    reader.read_exact(&mut header)?;
+    if header[..8] != MAGIC {
+        return Err(invalid("not an ohlcv-binary file, bad magic".to_string()));
+    }
+    let version = u32::from_le_bytes(header[8..12].try_into().expect("4 bytes"));
+    let record_size = u32::from_le_bytes(header[12..16].try_into().expect("4 bytes"));
+    if version != VERSION || record_size as usize != RECORD_SIZE {
+        return Err(invalid(format!(
+            "unsupported ohlcv-binary version {} with {} byte records",
+            version, record_size
+        )));
+    }
+    Ok(())
+}
+
+fn invalid(msg: String) -> BybitError {
+    BybitError::IoError(io::Error::new(io::ErrorKind::InvalidData, msg))
+}
+
+fn truncated(trailing: usize) -> BybitError {
+    invalid(format!("truncated ohlcv-binary file, {} trailing bytes", trailing))
+}
+
+/// Fixed width little-endian bar records, see [`RECORD_SIZE`].
+#[derive(Debug)]
+pub struct OhlcvBinaryWriter;
+
+impl OutputWriter for OhlcvBinaryWriter {
+    fn write_header(&mut self, out: &mut dyn Write) -> Result<(), BybitError> {
+        Ok(write_header(out)?)
+    }
+
+    fn write_kline(&mut self, out: &mut dyn Write, kline: &Kline) -> Result<(), BybitError> {
+        Ok(write_record(out, kline)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kline::test_kline;
+    use crate::writer::write_klines;
+
+    #[test]
+    fn test_ohlcv_binary_round_trip() {
+        for &category in Category::all() {
+            let klines = [1_704_067_200_000, 1_704_068_100_000]
+                .map(|start_time| Kline {
+                    category,
+                    ..test_kline(start_time, 105.5)
+                })
+                .to_vec();
+
+            let mut bytes = Vec::new();
+            write_klines(&mut OhlcvBinaryWriter, &mut bytes, &klines).unwrap();
+            assert_eq!(bytes.len(), HEADER_SIZE + 2 * RECORD_SIZE);
+            assert_eq!(&bytes[..8], b"BYBTOHLC");
+            assert_eq!(
+                &bytes[HEADER_SIZE..HEADER_SIZE + 8],
+                &1_704_067_200_000u64.to_le_bytes()
+            );
+
+            let read = read_klines(bytes.as_slice(), category).unwrap();
+            assert_eq!(
+                serde_json::to_value(&read).unwrap(),
+                serde_json::to_value(&klines).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_ohlcv_binary_rejects_bad_input() {
+        let mut bytes = Vec::new();
+        write_klines(&mut OhlcvBinaryWriter, &mut bytes, &[test_kline(0, 105.5)]).unwrap();
+
+        assert!(read_klines(&bytes[..bytes.len() - 1], Category::Linear).is_err());
+        assert!(read_klines(&b"not ohlcv binary data"[..], Category::Linear).is_err());
+
+        let mut future = bytes.clone();
+        future[8..12].copy_from_slice(&2u32.to_le_bytes());
+        assert!(read_klines(future.as_slice(), Category::Linear).is_err());
+    }
+}
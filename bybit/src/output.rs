@@ -0,0 +1,1246 @@
+use crate::{
+    category::Category,
+    gzip::GzipEncoder,
+    kline::{Kline, KlineType},
+    ohlcv_binary, BybitError,
+};
+use chrono::SecondsFormat;
+use clap::ValueEnum;
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard, PoisonError, Weak},
+    thread::ThreadId,
+};
+
+/// Destination for formatted output: stdout, a file, or both with `--tee`.
+///
+/// The file is flushed, and a compressed one finished, when the sink is dropped. If a panic
+/// interrupts writing, eg/ a writer bug mid-range, the file is closed with only its complete
+/// records, so a reader never sees a half written row: a plain file is cut back to its last
+/// complete record, and a compressed one, which only ever receives complete records, is
+/// finished without the partial one. That happens as the panic unwinds, or earlier from the
+/// hook of [`install_panic_hook`], which also covers a panic which aborts.
+pub struct OutputSink {
+    file: Option<Arc<Mutex<RecordFile>>>,
+    stdout: Option<io::Stdout>,
+    parts: Option<Parts>,
+    compression: Compression,
+}
+
+/// Files of every live [`OutputSink`], for the panic hook to close.
+static OPEN_FILES: Mutex<Vec<Weak<Mutex<RecordFile>>>> = Mutex::new(Vec::new());
+
+/// `--compress` encoding of file output. Parquet files ignore it, they compress internally.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Compression {
+    #[default]
+    None,
+    /// Gzip, see [`GzipEncoder`].
+    Gzip,
+}
+
+/// `--max-file-size` state of an [`OutputSink`] writing numbered parts of `output`.
+struct Parts {
+    output: PathBuf,
+    max_bytes: u64,
+    /// Index of the part being written.
+    part: usize,
+}
+
+/// Install a panic hook closing the files the panicking thread was writing with only their
+/// complete records (see [`OutputSink`]) before running the previous hook. Without it files
+/// are only closed as a panic unwinds, which a panic that aborts never does.
+pub fn install_panic_hook() {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        close_panicking_thread_files();
+        previous(info);
+    }));
+}
+
+/// Close every file last written by the current thread with only its complete records. Files
+/// locked at the time of the panic are left to their sink's drop.
+fn close_panicking_thread_files() {
+    let Ok(files) = OPEN_FILES.try_lock() else {
+        return;
+    };
+    let thread = std::thread::current().id();
+    for file in files.iter().filter_map(Weak::upgrade) {
+        if let Ok(mut file) = file.try_lock() {
+            if file.thread == Some(thread) {
+                // Nothing can be reported from a panic hook
+                let _ = file.close(true);
+            }
+        }
+    }
+}
+
+/// Share `file` with the panic hook.
+fn register(file: RecordFile) -> Arc<Mutex<RecordFile>> {
+    let file = Arc::new(Mutex::new(file));
+    let mut files = OPEN_FILES.lock().unwrap_or_else(PoisonError::into_inner);
+    files.retain(|file| file.strong_count() > 0);
+    files.push(Arc::downgrade(&file));
+    file
+}
+
+impl OutputSink {
+    /// Write to `output` if set, creating its parent directories, otherwise stdout. With `tee`,
+    /// output is written to both.
+    pub fn new(output: Option<&Path>, tee: bool) -> Result<Self, BybitError> {
+        let file = output
+            .map(|path| create_file(path).map(|file| register(RecordFile::new(file))))
+            .transpose()?;
+        let stdout = (file.is_none() || tee).then(io::stdout);
+        Ok(Self {
+            file,
+            stdout,
+            parts: None,
+            compression: Compression::None,
+        })
+    }
+
+    /// Write numbered parts of `output` (see [`part_path`]) of about `max_bytes` each, starting
+    /// with part 0. Writers move on to the next part with [`OutputSink::next_part`] once
+    /// [`OutputSink::part_full`], see [`crate::writer::write_kline_parted`].
+    pub fn parted(output: &Path, max_bytes: u64) -> Result<Self, BybitError> {
+        Ok(Self {
+            file: Some(register(RecordFile::new(create_file(&part_path(output, 0))?))),
+            stdout: None,
+            parts: Some(Parts {
+                output: output.to_path_buf(),
+                max_bytes,
+                part: 0,
+            }),
+            compression: Compression::None,
+        })
+    }
+
+    /// Compress the file output, and every later part, with `compression`. Must be set before
+    /// anything is written; stdout output is never compressed.
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        if let Some(file) = self.file.take() {
+            let file = Arc::into_inner(file)
+                .expect("only its sink holds a file")
+                .into_inner()
+                .unwrap_or_else(PoisonError::into_inner);
+            self.file = Some(register(RecordFile::with_compression(file.into_file(), compression)));
+        }
+        self.compression = compression;
+        self
+    }
+
+    /// Whether the current part has reached its size, counting the bytes handed to the file so
+    /// far, after any compression. Always false for a sink without parts.
+    pub fn part_full(&self) -> bool {
+        match (&self.parts, self.file.as_deref().map(lock)) {
+            (Some(parts), Some(file)) => file.size() >= parts.max_bytes,
+            _ => false,
+        }
+    }
+
+    /// Flush and close the current part, finishing it if compressed, and continue in the next
+    /// one.
+    pub fn next_part(&mut self) -> Result<(), BybitError> {
+        let Some(parts) = &mut self.parts else {
+            return Ok(());
+        };
+        if let Some(file) = &self.file {
+            lock(file).close(false)?;
+        }
+        parts.part += 1;
+        let file = create_file(&part_path(&parts.output, parts.part))?;
+        self.file = Some(register(RecordFile::with_compression(file, self.compression)));
+        Ok(())
+    }
+}
+
+/// Lock a sink's file. One poisoned by a panic mid write is still usable, as its record
+/// tracking is only updated once a write went through.
+fn lock(file: &Mutex<RecordFile>) -> MutexGuard<'_, RecordFile> {
+    file.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+impl Drop for OutputSink {
+    fn drop(&mut self) {
+        if let Some(file) = &self.file {
+            // Errors can't be reported from here, runs which exit normally flush explicitly
+            let _ = lock(file).close(std::thread::panicking());
+        }
+    }
+}
+
+/// Buffered output file, tracking where its last complete record ends: the last newline of
+/// text output, or the last whole record of `ohlcv-binary` output (recognised by its magic).
+struct RecordFile {
+    writer: FileWriter,
+    /// Bytes written, before any compression.
+    written: u64,
+    line_end: u64,
+    binary: bool,
+    /// Bytes following the last complete record, held back from a compressed stream.
+    held: Vec<u8>,
+    /// Thread which last wrote to the file.
+    thread: Option<ThreadId>,
+    closed: bool,
+}
+
+enum FileWriter {
+    Plain(BufWriter<File>),
+    Gzip(GzipEncoder<Counted<BufWriter<File>>>),
+}
+
+/// Writer counting the bytes passing through it, eg/ the compressed bytes of a file.
+struct Counted<W> {
+    inner: W,
+    bytes: u64,
+}
+
+impl<W: Write> Write for Counted<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.bytes += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl RecordFile {
+    fn new(file: File) -> Self {
+        Self::with_compression(file, Compression::None)
+    }
+
+    fn with_compression(file: File, compression: Compression) -> Self {
+        let writer = match compression {
+            Compression::None => FileWriter::Plain(BufWriter::new(file)),
+            Compression::Gzip => FileWriter::Gzip(GzipEncoder::new(Counted {
+                inner: BufWriter::new(file),
+                bytes: 0,
+            })),
+        };
+        Self {
+            writer,
+            written: 0,
+            line_end: 0,
+            binary: false,
+            held: Vec::new(),
+            thread: None,
+            closed: false,
+        }
+    }
+
+    /// The file of a record file nothing has been written to yet.
+    fn into_file(self) -> File {
+        match self.writer {
+            FileWriter::Plain(writer) => writer.into_parts().0,
+            FileWriter::Gzip(encoder) => encoder.into_inner().inner.into_parts().0,
+        }
+    }
+
+    /// Bytes handed to the file so far, compressed if it is. A compressed file lags behind what
+    /// was written by the input its encoder still buffers.
+    fn size(&self) -> u64 {
+        match &self.writer {
+            FileWriter::Plain(_) => self.written,
+            FileWriter::Gzip(encoder) => encoder.get_ref().bytes,
+        }
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.thread = Some(std::thread::current().id());
+        match &mut self.writer {
+            FileWriter::Plain(writer) => writer.write_all(buf)?,
+            FileWriter::Gzip(_) => self.held.extend_from_slice(buf),
+        }
+        if self.written == 0 {
+            self.binary = buf.starts_with(&ohlcv_binary::MAGIC);
+        }
+        if let Some(newline) = buf.iter().rposition(|&byte| byte == b'\n') {
+            self.line_end = self.written + newline as u64 + 1;
+        }
+        self.written += buf.len() as u64;
+        // Only complete records enter a compressed stream, so it can be finished without a
+        // partial one
+        let partial = (self.written - self.complete()) as usize;
+        if let FileWriter::Gzip(encoder) = &mut self.writer {
+            let ready = self.held.len() - partial;
+            encoder.write_all(&self.held[..ready])?;
+            self.held.drain(..ready);
+        }
+        Ok(())
+    }
+
+    fn complete(&self) -> u64 {
+        let (header, record) = (ohlcv_binary::HEADER_SIZE as u64, ohlcv_binary::RECORD_SIZE as u64);
+        match self.binary {
+            true if self.written < header => 0,
+            true => header + (self.written - header) / record * record,
+            false => self.line_end,
+        }
+    }
+
+    /// Flush and close the file, finishing a compressed stream. With `complete_only`, eg/ on a
+    /// panic, a trailing partial record is dropped. Nothing can be written afterwards, and
+    /// further calls do nothing.
+    fn close(&mut self, complete_only: bool) -> io::Result<()> {
+        if std::mem::replace(&mut self.closed, true) {
+            return Ok(());
+        }
+        let complete = self.complete();
+        match &mut self.writer {
+            FileWriter::Plain(writer) => {
+                writer.flush()?;
+                if complete_only {
+                    writer.get_ref().set_len(complete)?;
+                }
+                Ok(())
+            }
+            FileWriter::Gzip(encoder) => {
+                if !complete_only {
+                    encoder.write_all(&self.held)?;
+                }
+                encoder.finish()
+            }
+        }
+    }
+}
+
+impl Write for FileWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            FileWriter::Plain(writer) => writer.write(buf),
+            FileWriter::Gzip(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            FileWriter::Plain(writer) => writer.flush(),
+            FileWriter::Gzip(encoder) => encoder.flush(),
+        }
+    }
+}
+
+/// Create `path`, along with any missing parent directories.
+pub fn create_file(path: &Path) -> io::Result<File> {
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        fs::create_dir_all(parent)?;
+    }
+    File::create(path)
+}
+
+/// Path of the `segment`th `--split-on-gaps` file of `output`, eg/ `out.seg0.csv` for
+/// `out.csv`.
+pub fn segment_path(output: &Path, segment: usize) -> PathBuf {
+    numbered_path(output, "seg", segment)
+}
+
+/// Path of the `part`th `--max-file-size` file of `output`, eg/ `out.part0.csv` for `out.csv`.
+pub fn part_path(output: &Path, part: usize) -> PathBuf {
+    numbered_path(output, "part", part)
+}
+
+fn numbered_path(output: &Path, label: &str, index: usize) -> PathBuf {
+    // A compressed file keeps its format's extension too, eg/ `out.part0.csv.gz`
+    if output.extension().is_some_and(|extension| extension == "gz") {
+        let inner = numbered_path(&output.with_extension(""), label, index);
+        return inner.with_file_name(format!("{}.gz", inner.file_name().unwrap_or_default().to_string_lossy()));
+    }
+    let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match output.extension() {
+        Some(extension) => format!("{}.{}{}.{}", stem, label, index, extension.to_string_lossy()),
+        None => format!("{}.{}{}", stem, label, index),
+    };
+    output.with_file_name(name)
+}
+
+/// Values substituted into an `--output` path template.
+#[derive(Debug, Clone, Copy)]
+pub struct OutputTemplate<'a> {
+    pub symbol: &'a str,
+    pub category: &'a str,
+    pub interval: &'a str,
+    /// Range start and end in unix millis, substituted as UTC dates (YYYY-MM-DD).
+    pub start: u64,
+    pub end: u64,
+}
+
+impl OutputTemplate<'_> {
+    /// Expand `{symbol}`, `{category}`, `{interval}`, `{start}` and `{end}` in `template`.
+    pub fn expand(&self, template: &Path) -> PathBuf {
+        let format_date = |millis: u64| {
+            chrono::DateTime::from_timestamp_millis(millis as i64)
+                .unwrap_or_default()
+                .format("%Y-%m-%d")
+                .to_string()
+        };
+        PathBuf::from(
+            template
+                .to_string_lossy()
+                .replace("{symbol}", self.symbol)
+                .replace("{category}", self.category)
+                .replace("{interval}", self.interval)
+                .replace("{start}", &format_date(self.start))
+                .replace("{end}", &format_date(self.end)),
+        )
+    }
+}
+
+impl Write for OutputSink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if let Some(file) = &self.file {
+            lock(file).write_all(buf)?;
+        }
+        if let Some(stdout) = &mut self.stdout {
+            stdout.write_all(buf)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if let Some(file) = &self.file {
+            lock(file).writer.flush()?;
+        }
+        if let Some(stdout) = &mut self.stdout {
+            stdout.flush()?;
+        }
+        Ok(())
+    }
+}
+
+/// Readable rendering of large table volumes, machine formats always stay exact.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum HumanNumbers {
+    /// Thousands separators, eg/ `12,345,678,901.23`.
+    Separators,
+    /// SI suffixes (K/M/B/T) with two decimals, eg/ `12.35B`.
+    Si,
+}
+
+
+impl HumanNumbers {
+    /// Format `value`, with `decimals` decimal places unless abbreviated with a suffix.
+    pub fn format(&self, value: f64, decimals: usize) -> String {
+        match self {
+            HumanNumbers::Separators => {
+                let formatted = format!("{:.*}", decimals, value.abs());
+                let (integer, fraction) = formatted
+                    .split_once('.')
+                    .map_or((formatted.as_str(), None), |(integer, fraction)| {
+                        (integer, Some(fraction))
+                    });
+
+                let mut grouped = String::new();
+                for (index, digit) in integer.chars().enumerate() {
+                    if index > 0 && (integer.len() - index) % 3 == 0 {
+                        grouped.push(',');
+                    }
+                    grouped.push(digit);
+                }
+                let sign = if value < 0.0 { "-" } else { "" };
+                match fraction {
+                    Some(fraction) => format!("{}{}.{}", sign, grouped, fraction),
+                    None => format!("{}{}", sign, grouped),
+                }
+            }
+            HumanNumbers::Si => {
+                let suffixes = [(1e12, "T"), (1e9, "B"), (1e6, "M"), (1e3, "K")];
+                match suffixes
+                    .into_iter()
+                    .find(|(scale, _)| value.abs() >= *scale)
+                {
+                    Some((scale, suffix)) => format!("{:.2}{}", value / scale, suffix),
+                    None => format!("{:.*}", decimals, value),
+                }
+            }
+        }
+    }
+}
+
+/// Rendering of kline timestamps in the `start_time` column of csv and tsv output, and of the
+/// influx-line timestamp.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum TimestampUnit {
+    /// RFC 3339 UTC with milliseconds, eg/ `2024-01-01T00:00:00.000Z`.
+    Rfc3339,
+    /// Unix milliseconds, the default for csv and tsv.
+    Millis,
+    /// Unix seconds.
+    Seconds,
+    /// Unix nanoseconds, the default for influx-line.
+    Nanos,
+}
+
+impl TimestampUnit {
+    /// Render a unix millis timestamp in this unit.
+    pub fn format(&self, millis: u64) -> String {
+        match self {
+            TimestampUnit::Rfc3339 => chrono::DateTime::from_timestamp_millis(millis as i64)
+                .unwrap_or_default()
+                .to_rfc3339_opts(SecondsFormat::Millis, true),
+            TimestampUnit::Millis => millis.to_string(),
+            TimestampUnit::Seconds => (millis / 1000).to_string(),
+            TimestampUnit::Nanos => (millis as u128 * 1_000_000).to_string(),
+        }
+    }
+
+    /// Unix millis of a timestamp rendered by [`TimestampUnit::format`].
+    pub fn parse(&self, timestamp: &str) -> Option<u64> {
+        match self {
+            TimestampUnit::Rfc3339 => chrono::DateTime::parse_from_rfc3339(timestamp)
+                .ok()
+                .map(|time| time.timestamp_millis() as u64),
+            TimestampUnit::Millis => timestamp.parse().ok(),
+            TimestampUnit::Seconds => timestamp.parse::<u64>().ok().map(|seconds| seconds * 1000),
+            TimestampUnit::Nanos => timestamp.parse::<u128>().ok().map(|nanos| (nanos / 1_000_000) as u64),
+        }
+    }
+
+    /// InfluxDB write precision of this unit, `None` for RFC 3339 which line protocol doesn't
+    /// accept.
+    pub fn influx_precision(&self) -> Option<&'static str> {
+        match self {
+            TimestampUnit::Rfc3339 => None,
+            TimestampUnit::Millis => Some("ms"),
+            TimestampUnit::Seconds => Some("s"),
+            TimestampUnit::Nanos => Some("ns"),
+        }
+    }
+}
+
+/// Columns written by [`write_delimited`].
+pub(crate) const COLUMNS: [&str; 8] = [
+    "start_time",
+    "time",
+    "open",
+    "high",
+    "low",
+    "close",
+    "base_volume",
+    "quote_volume",
+];
+
+/// Delimited text format, sharing a single row layout between CSV and TSV.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Delimited {
+    /// Comma separated, fields containing commas, quotes or newlines are quoted (RFC 4180).
+    Csv,
+    /// Tab separated, embedded tabs, newlines and backslashes are backslash escaped.
+    Tsv,
+}
+
+impl Delimited {
+    pub fn separator(&self) -> char {
+        match self {
+            Delimited::Csv => ',',
+            Delimited::Tsv => '\t',
+        }
+    }
+
+    /// Escape a single field for this format.
+    pub fn escape(&self, field: &str) -> String {
+        match self {
+            Delimited::Csv => {
+                if field.contains([',', '"', '\n', '\r']) {
+                    format!("\"{}\"", field.replace('"', "\"\""))
+                } else {
+                    field.to_string()
+                }
+            }
+            Delimited::Tsv => field
+                .replace('\\', "\\\\")
+                .replace('\t', "\\t")
+                .replace('\n', "\\n")
+                .replace('\r', "\\r"),
+        }
+    }
+
+    /// Join escaped fields into a single row, without the trailing newline.
+    pub fn format_row<S: AsRef<str>>(&self, fields: &[S]) -> String {
+        fields
+            .iter()
+            .map(|field| self.escape(field.as_ref()))
+            .collect::<Vec<_>>()
+            .join(&self.separator().to_string())
+    }
+
+    /// Split a row written by [`Delimited::format_row`] back into its unescaped fields.
+    pub fn parse_row(&self, row: &str) -> Vec<String> {
+        let mut fields = vec![String::new()];
+        let mut chars = row.chars().peekable();
+        let mut quoted = false;
+
+        while let Some(c) = chars.next() {
+            let field = fields.last_mut().expect("fields is never empty");
+            match (self, c) {
+                (Delimited::Csv, '"') if quoted && chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                (Delimited::Csv, '"') => quoted = !quoted,
+                (Delimited::Tsv, '\\') => match chars.next() {
+                    Some('t') => field.push('\t'),
+                    Some('n') => field.push('\n'),
+                    Some('r') => field.push('\r'),
+                    Some(other) => field.push(other),
+                    None => field.push('\\'),
+                },
+                (_, c) if c == self.separator() && !quoted => fields.push(String::new()),
+                (_, c) => field.push(c),
+            }
+        }
+        fields
+    }
+}
+
+pub(crate) fn kline_fields(kline: &Kline, unit: TimestampUnit) -> [String; 8] {
+    [
+        unit.format(kline.start_time),
+        kline.format_time(),
+        kline.open_price.to_string(),
+        kline.high_price.to_string(),
+        kline.low_price.to_string(),
+        kline.close_price.to_string(),
+        kline.base_volume().to_string(),
+        kline.quote_volume().to_string(),
+    ]
+}
+
+/// Write klines as delimited text, one row per kline with the start time in both `unit` and
+/// UTC.
+pub fn write_delimited<W: Write>(
+    mut writer: W,
+    klines: &[Kline],
+    format: Delimited,
+    unit: TimestampUnit,
+) -> Result<(), BybitError> {
+    write_delimited_header(&mut writer, format)?;
+    write_delimited_rows(&mut writer, klines, format, unit)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Write the header row of [`write_delimited`], for output streamed chunk by chunk.
+pub fn write_delimited_header<W: Write>(mut writer: W, format: Delimited) -> io::Result<()> {
+    writeln!(writer, "{}", format.format_row(&COLUMNS))
+}
+
+/// Write one row per kline, without a header.
+pub fn write_delimited_rows<W: Write>(
+    mut writer: W,
+    klines: &[Kline],
+    format: Delimited,
+    unit: TimestampUnit,
+) -> io::Result<()> {
+    for kline in klines {
+        writeln!(writer, "{}", format.format_row(&kline_fields(kline, unit)))?;
+    }
+    Ok(())
+}
+
+/// Write klines as CSV with unix millis start times, see [`write_delimited`].
+pub fn write_csv<W: Write>(writer: W, klines: &[Kline]) -> Result<(), BybitError> {
+    write_delimited(writer, klines, Delimited::Csv, TimestampUnit::Millis)
+}
+
+/// Write klines as newline delimited JSON in their native [`Kline`] structure, losslessly
+/// including turnover and the raw `start_time` millis.
+pub fn write_kline_json<W: Write>(mut writer: W, klines: &[Kline]) -> Result<(), BybitError> {
+    for kline in klines {
+        writeln!(writer, "{}", serde_json::to_string(kline)?)?;
+    }
+    Ok(())
+}
+
+/// Escape an InfluxDB line protocol tag value, where commas, equals signs and spaces are
+/// significant.
+fn escape_influx_tag(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, ',' | '=' | ' ' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Write klines as InfluxDB line protocol, eg/
+/// `kline,symbol=BTCUSDT,category=linear open=1,high=2,low=0.5,close=1.5,volume=10,turnover=15 1704067200000000000`,
+/// ready for `influx write`.
+///
+/// Timestamps are `start_time` in `precision`, nanoseconds unless written with a matching
+/// `influx write --precision`; RFC 3339 is rejected. Series other than trade klines get a
+/// `kline_type` tag, and synthetic bars inserted by `--fill-gaps` a `filled` tag, so they
+/// don't overwrite trade klines sharing a timestamp.
+pub fn write_influx_line<W: Write>(
+    mut writer: W,
+    symbol: &str,
+    klines: &[Kline],
+    precision: TimestampUnit,
+) -> Result<(), BybitError> {
+    if precision.influx_precision().is_none() {
+        return Err(BybitError::ArgumentError(
+            "influx-line timestamps must be millis, seconds or nanos".to_string(),
+        ));
+    }
+    let symbol = escape_influx_tag(symbol);
+    for kline in klines {
+        write!(
+            writer,
+            "kline,symbol={},category={}",
+            symbol,
+            kline.category
+        )?;
+        if kline.kline_type != KlineType::Trade {
+            write!(writer, ",kline_type={}", kline.kline_type)?;
+        }
+        if let Some(filled) = kline.filled {
+            write!(writer, ",filled={}", filled)?;
+        }
+        writeln!(
+            writer,
+            " open={},high={},low={},close={},volume={},turnover={} {}",
+            kline.open_price,
+            kline.high_price,
+            kline.low_price,
+            kline.close_price,
+            kline.volume,
+            kline.turnover,
+            precision.format(kline.start_time)
+        )?;
+    }
+    Ok(())
+}
+
+/// Bar of `jsonl-flat` output, see [`write_jsonl_flat`].
+#[derive(Debug, Serialize)]
+struct FlatKline<'a> {
+    time: u64,
+    open: f64,
+    high: f64,
+    low: f64,
+    close: f64,
+    volume: f64,
+    turnover: f64,
+    symbol: &'a str,
+    category: Category,
+}
+
+/// Write klines as flat JSON objects, one per line, for generic loaders, eg/
+/// `{"time":1704067200000,"open":1.0,"high":2.0,"low":0.5,"close":1.5,"volume":10.0,"turnover":15.0,"symbol":"BTCUSDT","category":"linear"}`.
+///
+/// `time` is the start time in unix millis. Unlike kline-json's raw fields, `volume` is in the
+/// base coin and `turnover` in the quote coin for every category, so bars of different
+/// categories merge consistently.
+pub fn write_jsonl_flat<W: Write>(mut writer: W, symbol: &str, klines: &[Kline]) -> Result<(), BybitError> {
+    for kline in klines {
+        let flat = FlatKline {
+            time: kline.start_time,
+            open: kline.open_price,
+            high: kline.high_price,
+            low: kline.low_price,
+            close: kline.close_price,
+            volume: kline.base_volume(),
+            turnover: kline.quote_volume(),
+            symbol,
+            category: kline.category,
+        };
+        writeln!(writer, "{}", serde_json::to_string(&flat)?)?;
+    }
+    Ok(())
+}
+
+/// Partition klines by the UTC day of their start time and write each day to its own file,
+/// `<dir>/<symbol>/<YYYY-MM-DD>.<csv|tsv>`, creating directories as needed.
+///
+/// The first and last days of a range may be partial, their files simply hold the bars
+/// received for that day. With `append`, bars are appended to existing day files (without a
+/// second header) so incremental runs can complete a partial day. Returns each file written
+/// with its row count, in day order.
+///
+/// A run which appended its bars but died before recording its progress (eg/ a request
+/// timed out locally after Bybit served it, then the run crashed) is resumed from an earlier
+/// bar, so appending is reconciled with what's already written: bars at or before the latest
+/// start time among the last `idempotency_window` rows of a day file are skipped. A window of
+/// 0 appends every bar.
+pub fn write_split_by_day(
+    dir: &Path,
+    symbol: &str,
+    klines: &[Kline],
+    format: Delimited,
+    unit: TimestampUnit,
+    append: bool,
+    idempotency_window: usize,
+) -> Result<Vec<(PathBuf, usize)>, BybitError> {
+    let mut days = BTreeMap::<String, Vec<Kline>>::new();
+    for kline in klines {
+        let day = chrono::DateTime::from_timestamp_millis(kline.start_time as i64)
+            .unwrap_or_default()
+            .format("%Y-%m-%d")
+            .to_string();
+        days.entry(day).or_default().push(kline.clone());
+    }
+
+    let symbol_dir = dir.join(symbol);
+    fs::create_dir_all(&symbol_dir)?;
+
+    let extension = match format {
+        Delimited::Csv => "csv",
+        Delimited::Tsv => "tsv",
+    };
+    days.into_iter()
+        .map(|(day, klines)| {
+            let path = symbol_dir.join(format!("{}.{}", day, extension));
+            let written = match append && path.exists() {
+                true => complete_rows(&path)?,
+                false => String::new(),
+            };
+            if !written.is_empty() {
+                let klines = match last_written(&written, format, unit, idempotency_window) {
+                    Some(last) => klines.into_iter().filter(|kline| kline.start_time > last).collect(),
+                    None => klines,
+                };
+                let mut writer = BufWriter::new(File::options().append(true).open(&path)?);
+                write_delimited_rows(&mut writer, &klines, format, unit)?;
+                writer.flush()?;
+                Ok((path, klines.len()))
+            } else {
+                write_delimited(BufWriter::new(File::create(&path)?), &klines, format, unit)?;
+                Ok((path, klines.len()))
+            }
+        })
+        .collect()
+}
+
+/// Cut a day file back to its last complete row, dropping a row cut short by a crash so
+/// appended rows don't run into it, and return what's left. Without a complete row, not even
+/// the header, the file is rewritten from scratch.
+fn complete_rows(path: &Path) -> Result<String, BybitError> {
+    let mut contents = fs::read_to_string(path)?;
+    let complete = contents.rfind('\n').map_or(0, |newline| newline + 1);
+    if complete < contents.len() {
+        File::options().write(true).open(path)?.set_len(complete as u64)?;
+        contents.truncate(complete);
+    }
+    Ok(contents)
+}
+
+/// Latest start time among the last `window` complete rows of a delimited file, skipping the
+/// header.
+fn last_written(contents: &str, format: Delimited, unit: TimestampUnit, window: usize) -> Option<u64> {
+    contents
+        .lines()
+        .rev()
+        .take(window)
+        .filter_map(|row| unit.parse(row.split(format.separator()).next()?))
+        .max()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gaps::GapFill;
+    use crate::kline::test_kline;
+    use std::slice;
+
+    #[test]
+    fn test_write_csv() {
+        let kline = Kline {
+            open_price: 42000.5,
+            high_price: 42100.0,
+            low_price: 41900.0,
+            volume: 12.5,
+            turnover: 525000.0,
+            ..test_kline(1704067200000, 42050.0)
+        };
+
+        let mut csv = Vec::new();
+        write_csv(&mut csv, &[kline]).unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "start_time,time,open,high,low,close,base_volume,quote_volume\n\
+             1704067200000,2024-01-01 00:00:00 UTC,42000.5,42100,41900,42050,12.5,525000\n"
+        );
+    }
+
+    #[test]
+    fn test_write_influx_line() {
+        let kline = Kline {
+            open_price: 42000.5,
+            high_price: 42100.0,
+            low_price: 41900.0,
+            volume: 12.5,
+            turnover: 525000.0,
+            ..test_kline(1704067200000, 42050.0)
+        };
+        let filled_mark = Kline {
+            kline_type: KlineType::Mark,
+            filled: Some(GapFill::Forward),
+            ..kline.clone()
+        };
+
+        let mut lines = Vec::new();
+        write_influx_line(&mut lines, "BTC USDT", &[kline, filled_mark], TimestampUnit::Nanos).unwrap();
+        assert_eq!(
+            String::from_utf8(lines).unwrap(),
+            "kline,symbol=BTC\\ USDT,category=linear open=42000.5,high=42100,low=41900,close=42050,volume=12.5,turnover=525000 1704067200000000000\n\
+             kline,symbol=BTC\\ USDT,category=linear,kline_type=mark,filled=forward open=42000.5,high=42100,low=41900,close=42050,volume=12.5,turnover=525000 1704067200000000000\n"
+        );
+    }
+
+    #[test]
+    fn test_write_jsonl_flat() {
+        let kline = Kline {
+            open_price: 42000.5,
+            high_price: 42100.0,
+            low_price: 41900.0,
+            volume: 12.5,
+            turnover: 525000.0,
+            ..test_kline(1704067200000, 42050.0)
+        };
+        // Inverse klines swap Bybit's raw fields, flat bars are base and quote volume throughout
+        let inverse = Kline {
+            volume: 525000.0,
+            turnover: 12.5,
+            category: Category::Inverse,
+            ..kline.clone()
+        };
+
+        let mut lines = Vec::new();
+        write_jsonl_flat(&mut lines, "BTCUSDT", &[kline, inverse]).unwrap();
+        let lines = String::from_utf8(lines).unwrap();
+        let lines = lines.lines().collect::<Vec<_>>();
+        assert_eq!(
+            lines[0],
+            r#"{"time":1704067200000,"open":42000.5,"high":42100.0,"low":41900.0,"close":42050.0,"volume":12.5,"turnover":525000.0,"symbol":"BTCUSDT","category":"linear"}"#
+        );
+        assert!(lines[1].contains(r#""volume":12.5,"turnover":525000.0,"symbol":"BTCUSDT","category":"inverse""#));
+    }
+
+    #[test]
+    fn test_timestamp_units() {
+        let kline = Kline {
+            open_price: 1.0,
+            high_price: 2.0,
+            low_price: 0.5,
+            ..test_kline(1704067200123, 1.5)
+        };
+        let start_time = |unit: TimestampUnit| {
+            let mut rows = Vec::new();
+            write_delimited_rows(&mut rows, slice::from_ref(&kline), Delimited::Tsv, unit).unwrap();
+            String::from_utf8(rows).unwrap().split('\t').next().unwrap().to_string()
+        };
+        let influx_time = |unit: TimestampUnit| {
+            let mut lines = Vec::new();
+            write_influx_line(&mut lines, "BTCUSDT", slice::from_ref(&kline), unit)?;
+            Ok::<_, BybitError>(String::from_utf8(lines).unwrap().trim_end().rsplit(' ').next().unwrap().to_string())
+        };
+
+        assert_eq!(start_time(TimestampUnit::Rfc3339), "2024-01-01T00:00:00.123Z");
+        assert!(influx_time(TimestampUnit::Rfc3339).is_err());
+
+        assert_eq!(start_time(TimestampUnit::Millis), "1704067200123");
+        assert_eq!(influx_time(TimestampUnit::Millis).unwrap(), "1704067200123");
+
+        assert_eq!(start_time(TimestampUnit::Seconds), "1704067200");
+        assert_eq!(influx_time(TimestampUnit::Seconds).unwrap(), "1704067200");
+
+        assert_eq!(start_time(TimestampUnit::Nanos), "1704067200123000000");
+        assert_eq!(influx_time(TimestampUnit::Nanos).unwrap(), "1704067200123000000");
+    }
+
+    #[test]
+    fn test_delimited_row_round_trip() {
+        let fields = [
+            "1704067200000",
+            "a,b \"quoted\"",
+            "tab\there",
+            "new\nline",
+            "back\\slash",
+        ];
+
+        for format in [Delimited::Csv, Delimited::Tsv] {
+            let row = format.format_row(&fields);
+            assert_eq!(format.parse_row(&row), fields, "{:?} row: {}", format, row);
+        }
+
+        // TSV rows never span lines
+        assert_eq!(
+            Delimited::Tsv.format_row(&fields),
+            "1704067200000\ta,b \"quoted\"\ttab\\there\tnew\\nline\tback\\\\slash"
+        );
+    }
+
+    #[test]
+    fn test_human_numbers() {
+        let separators = HumanNumbers::Separators;
+        assert_eq!(separators.format(12345678901.23, 2), "12,345,678,901.23");
+        assert_eq!(separators.format(999.5, 1), "999.5");
+        assert_eq!(separators.format(1000.0, 0), "1,000");
+        assert_eq!(separators.format(-1234.5, 2), "-1,234.50");
+
+        let si = HumanNumbers::Si;
+        assert_eq!(si.format(12345678901.23, 4), "12.35B");
+        assert_eq!(si.format(1500.0, 4), "1.50K");
+        assert_eq!(si.format(2_500_000.0, 4), "2.50M");
+        assert_eq!(si.format(12.5, 4), "12.5000");
+    }
+
+    #[test]
+    fn test_write_split_by_day_partial_days() {
+        let dir = std::env::temp_dir().join(format!("bybit-kline-split-{}", std::process::id()));
+        let hour = 3_600_000;
+        // 2024-01-01 22:00 UTC through 2024-01-03 01:00 UTC
+        let start = 1704067200000 + 22 * hour;
+        let klines = (0..28)
+            .map(|n| Kline {
+                volume: 1.0,
+                turnover: 1.0,
+                ..test_kline(start + n * hour, 1.0)
+            })
+            .collect::<Vec<_>>();
+
+        let written = write_split_by_day(&dir, "BTCUSDT", &klines, Delimited::Csv, TimestampUnit::Millis, false, 0).unwrap();
+        let files = written
+            .iter()
+            .map(|(path, rows)| {
+                let name = path
+                    .strip_prefix(&dir)
+                    .unwrap()
+                    .to_string_lossy()
+                    .into_owned();
+                (name, *rows)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            files,
+            vec![
+                ("BTCUSDT/2024-01-01.csv".to_string(), 2),
+                ("BTCUSDT/2024-01-02.csv".to_string(), 24),
+                ("BTCUSDT/2024-01-03.csv".to_string(), 2),
+            ]
+        );
+
+        // Each file has its own header
+        let last_day = std::fs::read_to_string(&written[2].0).unwrap();
+        assert_eq!(last_day.lines().count(), 3);
+        assert!(last_day.starts_with("start_time,"));
+
+        // Appending completes the partial last day without repeating the header
+        write_split_by_day(&dir, "BTCUSDT", &klines[27..], Delimited::Csv, TimestampUnit::Millis, true, 0).unwrap();
+        let last_day = std::fs::read_to_string(&written[2].0).unwrap();
+        assert_eq!(last_day.lines().count(), 4);
+        assert_eq!(last_day.matches("start_time,").count(), 1);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_split_by_day_append_skips_bars_already_written() {
+        let dir = std::env::temp_dir().join(format!("bybit-kline-idempotent-{}", std::process::id()));
+        let minute = 60_000;
+        let klines = (0..10)
+            .map(|n| Kline {
+                volume: 1.0,
+                turnover: 1.0,
+                ..test_kline(1704067200000 + n * minute, 1.0)
+            })
+            .collect::<Vec<_>>();
+        let unit = TimestampUnit::Rfc3339;
+        write_split_by_day(&dir, "BTCUSDT", &klines[..4], Delimited::Csv, unit, true, 1000).unwrap();
+
+        // A request timed out locally after Bybit served it, the retry succeeded and its bars
+        // were appended, then the run died before recording them in its state
+        write_split_by_day(&dir, "BTCUSDT", &klines[4..7], Delimited::Csv, unit, true, 1000).unwrap();
+
+        // The crash also cut the last row short, leaving its start time intact but no newline
+        let path = dir.join("BTCUSDT").join("2024-01-01.csv");
+        let day = std::fs::read_to_string(&path).unwrap();
+        std::fs::write(&path, &day[..day.len() - 10]).unwrap();
+
+        // The resumed run re-fetches the window from its last recorded bar, the partial row is
+        // dropped and its bar written again
+        let written = write_split_by_day(&dir, "BTCUSDT", &klines[4..], Delimited::Csv, unit, true, 1000).unwrap();
+        assert_eq!(written[0].1, 4);
+
+        let day = std::fs::read_to_string(&written[0].0).unwrap();
+        let starts = day
+            .lines()
+            .skip(1)
+            .map(|row| unit.parse(row.split(',').next().unwrap()).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(starts, klines.iter().map(|kline| kline.start_time).collect::<Vec<_>>());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_output_template_expand() {
+        let template = OutputTemplate {
+            symbol: "BTCUSDT",
+            category: "linear",
+            interval: "60",
+            start: 1704067200000,
+            end: 1704153600000,
+        };
+        assert_eq!(
+            template.expand(Path::new("data/{category}/{symbol}/{interval}.parquet")),
+            PathBuf::from("data/linear/BTCUSDT/60.parquet")
+        );
+        assert_eq!(
+            template.expand(Path::new("{symbol}_{start}_{end}.csv")),
+            PathBuf::from("BTCUSDT_2024-01-01_2024-01-02.csv")
+        );
+        assert_eq!(
+            template.expand(Path::new("out.csv")),
+            PathBuf::from("out.csv")
+        );
+    }
+
+    #[test]
+    fn test_segment_path() {
+        assert_eq!(segment_path(Path::new("data/out.csv"), 0), PathBuf::from("data/out.seg0.csv"));
+        assert_eq!(segment_path(Path::new("btc.tar.bin"), 12), PathBuf::from("btc.tar.seg12.bin"));
+        assert_eq!(segment_path(Path::new("out"), 3), PathBuf::from("out.seg3"));
+        assert_eq!(part_path(Path::new("data/out.csv"), 1), PathBuf::from("data/out.part1.csv"));
+        assert_eq!(part_path(Path::new("out.csv.gz"), 2), PathBuf::from("out.part2.csv.gz"));
+    }
+
+    #[test]
+    fn test_output_sink_writes_file() {
+        let path =
+            std::env::temp_dir().join(format!("bybit-kline-sink-{}.txt", std::process::id()));
+
+        let mut sink = OutputSink::new(Some(&path), false).unwrap();
+        assert!(sink.stdout.is_none());
+        writeln!(sink, "row").unwrap();
+        sink.flush().unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "row\n");
+
+        assert!(OutputSink::new(Some(&path), true).unwrap().stdout.is_some());
+        assert!(OutputSink::new(None, false).unwrap().stdout.is_some());
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_output_sink_compresses_each_part() {
+        let output =
+            std::env::temp_dir().join(format!("bybit-kline-sink-gzip-{}.csv.gz", std::process::id()));
+
+        // Parts fill up by compressed size, far more csv than the cap fits in the first part
+        let mut sink = OutputSink::parted(&output, 2000).unwrap().with_compression(Compression::Gzip);
+        let mut first = "start_time,close\n".to_string();
+        write!(sink, "{}", first).unwrap();
+        let mut start = 0;
+        while !sink.part_full() {
+            let row = format!("{},{}.5\n", start, start % 9973);
+            write!(sink, "{}", row).unwrap();
+            first.push_str(&row);
+            start += 60000;
+        }
+        assert!(first.len() > 20_000, "{} bytes", first.len());
+        sink.next_part().unwrap();
+        writeln!(sink, "start_time,close").unwrap();
+        writeln!(sink, "{},1.5", start).unwrap();
+        sink.next_part().unwrap();
+        drop(sink);
+        let size = std::fs::metadata(part_path(&output, 0)).unwrap().len();
+        assert!((2000..64 * 1024).contains(&size), "{} bytes", size);
+
+        // Every part decompresses on its own, the last one, started but never written, to nothing
+        let read_part = |part| crate::gzip::gunzip(&std::fs::read(part_path(&output, part)).unwrap());
+        assert_eq!(read_part(0), first.as_bytes());
+        assert_eq!(read_part(1), format!("start_time,close\n{},1.5\n", start).as_bytes());
+        assert!(read_part(2).is_empty());
+
+        for part in 0..3 {
+            std::fs::remove_file(part_path(&output, part)).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_output_sink_keeps_complete_records_on_panic() {
+        let path =
+            std::env::temp_dir().join(format!("bybit-kline-sink-panic-{}.csv", std::process::id()));
+
+        // The writer is dropped mid row by a panic, before anything was flushed
+        let thread_path = path.clone();
+        let result = std::thread::spawn(move || {
+            let mut sink = OutputSink::new(Some(&thread_path), false).unwrap();
+            writeln!(sink, "start_time,close").unwrap();
+            writeln!(sink, "0,1.5").unwrap();
+            write!(sink, "60000,").unwrap();
+            panic!("writer failed mid row");
+        })
+        .join();
+        assert!(result.is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "start_time,close\n0,1.5\n");
+
+        // Binary records are cut at a record boundary, newline bytes inside them don't count
+        let thread_path = path.clone();
+        let result = std::thread::spawn(move || {
+            let mut sink = OutputSink::new(Some(&thread_path), false).unwrap();
+            ohlcv_binary::write_header(&mut sink).unwrap();
+            sink.write_all(&[b'\n'; ohlcv_binary::RECORD_SIZE + 10]).unwrap();
+            panic!("writer failed mid record");
+        })
+        .join();
+        assert!(result.is_err());
+        let written = std::fs::metadata(&path).unwrap().len() as usize;
+        assert_eq!(written, ohlcv_binary::HEADER_SIZE + ohlcv_binary::RECORD_SIZE);
+
+        // A compressed file is finished with its complete rows, the partial one held back
+        let thread_path = path.clone();
+        let result = std::thread::spawn(move || {
+            let mut sink = OutputSink::new(Some(&thread_path), false).unwrap().with_compression(Compression::Gzip);
+            writeln!(sink, "start_time,close").unwrap();
+            write!(sink, "0,1.5\n60000,").unwrap();
+            panic!("writer failed mid row");
+        })
+        .join();
+        assert!(result.is_err());
+        assert_eq!(crate::gzip::gunzip(&std::fs::read(&path).unwrap()), b"start_time,close\n0,1.5\n");
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_panic_hook_closes_files_without_unwinding() {
+        let path =
+            std::env::temp_dir().join(format!("bybit-kline-sink-hook-{}.csv", std::process::id()));
+
+        // The hook runs before unwinding, if any: a panic which aborts never drops the sink
+        let thread_path = path.clone();
+        std::thread::spawn(move || {
+            let mut sink = OutputSink::new(Some(&thread_path), false).unwrap();
+            writeln!(sink, "start_time,close").unwrap();
+            write!(sink, "0,1.5\n60000,").unwrap();
+            close_panicking_thread_files();
+            std::mem::forget(sink);
+        })
+        .join()
+        .unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "start_time,close\n0,1.5\n");
+
+        // Files written by other threads are left alone
+        let mut sink = OutputSink::new(Some(&path), false).unwrap();
+        write!(sink, "0,").unwrap();
+        std::thread::spawn(close_panicking_thread_files).join().unwrap();
+        drop(sink);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "0,");
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,168 @@
+use crate::{kline::Kline, output, BybitError};
+use parquet::{
+    basic::Compression,
+    data_type::{ByteArray, ByteArrayType, DoubleType, Int64Type},
+    file::{properties::WriterProperties, writer::SerializedFileWriter},
+    schema::parser::parse_message_type,
+};
+use std::{fs::File, path::Path, sync::Arc};
+
+const SCHEMA: &str = "
+    message kline {
+        REQUIRED INT64 start_time (TIMESTAMP(MILLIS, true));
+        REQUIRED DOUBLE open;
+        REQUIRED DOUBLE high;
+        REQUIRED DOUBLE low;
+        REQUIRED DOUBLE close;
+        REQUIRED DOUBLE base_volume;
+        REQUIRED DOUBLE quote_volume;
+        REQUIRED BYTE_ARRAY kline_type (UTF8);
+    }
+";
+
+/// Default number of klines buffered per Parquet row group.
+pub const DEFAULT_ROW_GROUP_SIZE: u64 = 100_000;
+
+/// Parquet writer which flushes a row group every `row_group_size` klines, so memory use is
+/// bounded by the row group size rather than the size of the fetched range.
+///
+/// Klines must be pushed in the order they should appear in the file, no reordering is done
+/// across row groups.
+pub struct ParquetKlineWriter {
+    writer: SerializedFileWriter<File>,
+    buffer: Vec<Kline>,
+    row_group_size: usize,
+    rows: usize,
+}
+
+impl ParquetKlineWriter {
+    pub fn create(path: &Path, row_group_size: usize) -> Result<Self, BybitError> {
+        let schema = Arc::new(parse_message_type(SCHEMA)?);
+        let properties = Arc::new(
+            WriterProperties::builder()
+                .set_compression(Compression::SNAPPY)
+                .build(),
+        );
+
+        Ok(Self {
+            writer: SerializedFileWriter::new(output::create_file(path)?, schema, properties)?,
+            buffer: Vec::with_capacity(row_group_size),
+            row_group_size: row_group_size.max(1),
+            rows: 0,
+        })
+    }
+
+    /// Buffer klines, flushing a row group each time the buffer fills.
+    pub fn write(&mut self, klines: Vec<Kline>) -> Result<(), BybitError> {
+        for kline in klines {
+            self.buffer.push(kline);
+            if self.buffer.len() >= self.row_group_size {
+                self.flush_row_group()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn flush_row_group(&mut self) -> Result<(), BybitError> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+
+        let start_times = self
+            .buffer
+            .iter()
+            .map(|kline| kline.start_time as i64)
+            .collect::<Vec<_>>();
+        let prices: [fn(&Kline) -> f64; 6] = [
+            |kline| kline.open_price,
+            |kline| kline.high_price,
+            |kline| kline.low_price,
+            |kline| kline.close_price,
+            Kline::base_volume,
+            Kline::quote_volume,
+        ];
+        let kline_types = self
+            .buffer
+            .iter()
+            .map(|kline| ByteArray::from(kline.kline_type.as_str()))
+            .collect::<Vec<_>>();
+
+        let mut row_group = self.writer.next_row_group()?;
+
+        let mut column = row_group.next_column()?.expect("start_time column");
+        column
+            .typed::<Int64Type>()
+            .write_batch(&start_times, None, None)?;
+        column.close()?;
+
+        for price in prices {
+            let values = self.buffer.iter().map(price).collect::<Vec<_>>();
+            let mut column = row_group.next_column()?.expect("price column");
+            column
+                .typed::<DoubleType>()
+                .write_batch(&values, None, None)?;
+            column.close()?;
+        }
+
+        let mut column = row_group.next_column()?.expect("kline_type column");
+        column
+            .typed::<ByteArrayType>()
+            .write_batch(&kline_types, None, None)?;
+        column.close()?;
+
+        row_group.close()?;
+        self.rows += self.buffer.len();
+        self.buffer.clear();
+        Ok(())
+    }
+
+    /// Flush any buffered klines and write the file footer, returning the number of rows.
+    pub fn finish(mut self) -> Result<usize, BybitError> {
+        self.flush_row_group()?;
+        self.writer.close()?;
+        Ok(self.rows)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kline::test_kline;
+    use parquet::{
+        file::reader::{FileReader, SerializedFileReader},
+        record::RowAccessor,
+    };
+
+    #[test]
+    fn test_parquet_writer_flushes_row_groups_in_order() {
+        let path =
+            std::env::temp_dir().join(format!("bybit-kline-test-{}.parquet", std::process::id()));
+        let klines = (0..5u64)
+            .map(|n| Kline {
+                open_price: n as f64,
+                high_price: n as f64 + 1.0,
+                low_price: n as f64 - 1.0,
+                volume: 1.0,
+                turnover: n as f64,
+                ..test_kline(n * 60_000, n as f64 + 0.5)
+            })
+            .collect::<Vec<_>>();
+
+        let mut writer = ParquetKlineWriter::create(&path, 2).unwrap();
+        writer.write(klines[..3].to_vec()).unwrap();
+        writer.write(klines[3..].to_vec()).unwrap();
+        assert_eq!(writer.finish().unwrap(), 5);
+
+        let reader = SerializedFileReader::new(File::open(&path).unwrap()).unwrap();
+        assert_eq!(reader.metadata().num_row_groups(), 3);
+
+        let start_times = reader
+            .get_row_iter(None)
+            .unwrap()
+            .map(|row| row.unwrap().get_timestamp_millis(0).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(start_times, vec![0, 60_000, 120_000, 180_000, 240_000]);
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,101 @@
+use crate::{server_time::ServerTime, BybitClient};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::time::{Duration, Instant};
+
+/// Liveness probe latency above which the API is considered degraded.
+const DEGRADED_LATENCY: Duration = Duration::from_secs(2);
+
+/// Local clock drift from Bybit server time worth warning about.
+const MAX_CLOCK_SKEW_MS: i64 = 5_000;
+
+/// How far ahead scheduled maintenance is reported.
+const MAINTENANCE_LOOKAHEAD_MS: i64 = 24 * 60 * 60 * 1000;
+
+#[derive(Debug, Deserialize)]
+struct AnnouncementResult {
+    list: Vec<Announcement>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Announcement {
+    title: String,
+    #[serde(rename = "startDateTimestamp", default)]
+    start_date_timestamp: i64,
+    #[serde(rename = "endDateTimestamp", default)]
+    end_date_timestamp: i64,
+}
+
+impl BybitClient {
+    /// Probe Bybit's availability ahead of a fetch, returning human readable warnings if the
+    /// API appears degraded.
+    ///
+    /// Uses `/v5/market/time` as a liveness probe, then checks the maintenance announcements
+    /// (if reachable) for windows that are active or start within the next 24 hours. Never
+    /// fails: an unreachable liveness probe is itself reported as a warning.
+    pub async fn preflight(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+
+        let started = Instant::now();
+        match self.get::<ServerTime>("/v5/market/time", &[]).await {
+            Ok(server_time) => {
+                let latency = started.elapsed();
+                if latency > DEGRADED_LATENCY {
+                    warnings.push(format!(
+                        "Bybit liveness probe took {}ms, the API may be degraded",
+                        latency.as_millis()
+                    ));
+                }
+
+                if let Ok(server_nanos) = server_time.time_nano.parse::<i64>() {
+                    let skew_ms = Utc::now().timestamp_millis() - server_nanos / 1_000_000;
+                    if skew_ms.abs() > MAX_CLOCK_SKEW_MS {
+                        warnings.push(format!(
+                            "Local clock is {}ms off Bybit server time",
+                            skew_ms
+                        ));
+                    }
+                }
+            }
+            Err(error) => warnings.push(format!(
+                "Bybit liveness probe (/v5/market/time) failed, the API may be unavailable: {}",
+                error
+            )),
+        }
+
+        // Announcements are best effort, older deployments and testnet may not serve them
+        let announcements = self
+            .get::<AnnouncementResult>(
+                "/v5/announcements/index",
+                &[
+                    ("locale", "en-US"),
+                    ("type", "maintenance_updates"),
+                    ("limit", "20"),
+                ],
+            )
+            .await;
+        if let Ok(announcements) = announcements {
+            let now = Utc::now().timestamp_millis();
+            let format_time = |millis: i64| {
+                DateTime::from_timestamp_millis(millis)
+                    .unwrap_or_else(|| DateTime::from_timestamp(0, 0).unwrap())
+                    .format("%Y-%m-%d %H:%M UTC")
+            };
+
+            for announcement in announcements.list {
+                let upcoming = announcement.start_date_timestamp <= now + MAINTENANCE_LOOKAHEAD_MS;
+                let not_finished = announcement.end_date_timestamp >= now;
+                if upcoming && not_finished {
+                    warnings.push(format!(
+                        "Scheduled maintenance: {} ({} - {})",
+                        announcement.title,
+                        format_time(announcement.start_date_timestamp),
+                        format_time(announcement.end_date_timestamp)
+                    ));
+                }
+            }
+        }
+
+        warnings
+    }
+}
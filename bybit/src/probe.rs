@@ -0,0 +1,260 @@
+use crate::{
+    category::Category,
+    interval::Interval,
+    kline::{Kline, KlineType},
+    BybitClient, BybitError, KlineQuery,
+};
+
+/// Lower bound of the window searched by [`BybitClient::listing_date`], 2018-01-01 UTC, before
+/// Bybit's earliest listings.
+pub const LISTING_SEARCH_START: u64 = 1514764800000;
+
+/// Bars before now requested by [`BybitClient::final_bar`]: the forming bar and the two before
+/// it, enough to span two calendar months with the 30 day approximation.
+const FINAL_BAR_WINDOW: u64 = 3;
+
+/// Availability of a single interval over a window, found by `--probe-intervals`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IntervalProbe {
+    pub interval: Interval,
+    /// Start time of the earliest bar in the window, `None` if the window has no data.
+    pub earliest: Option<u64>,
+    /// Start time of the latest bar in the window.
+    pub latest: Option<u64>,
+}
+
+impl BybitClient {
+    /// Latest bar starting in `[start, end)`, requesting a single kline.
+    async fn latest_bar(
+        &self,
+        query: &KlineQuery,
+        start: u64,
+        end: u64,
+    ) -> Result<Option<u64>, BybitError> {
+        let klines = self
+            .get_kline_single(query, start, end.saturating_sub(1), 1)
+            .await?;
+        Ok(klines.iter().map(|kline| kline.start_time).max())
+    }
+
+    /// Probe whether `interval` has data for `symbol` over `[start, end)`, and find its earliest
+    /// and latest bars using single kline requests.
+    ///
+    /// Bybit returns the newest bars of a window first, so the latest bar takes one request and
+    /// the earliest is found by bisecting the window, assuming data is contiguous once listed.
+    pub async fn probe_interval(
+        &self,
+        symbol: &str,
+        category: Category,
+        interval: Interval,
+        start: u64,
+        end: u64,
+    ) -> Result<IntervalProbe, BybitError> {
+        let query = KlineQuery {
+            symbol: symbol.to_string(),
+            category,
+            interval,
+            kline_type: KlineType::Trade,
+            start,
+            end,
+            max_records: 1,
+            limit_per_request: 1,
+        };
+
+        let latest = self.latest_bar(&query, start, end).await?;
+        let Some(latest_time) = latest else {
+            return Ok(IntervalProbe {
+                interval,
+                earliest: None,
+                latest: None,
+            });
+        };
+
+        // Smallest window end which still contains a bar, its latest bar is the earliest overall
+        let step = interval.duration_ms();
+        let (mut low, mut high) = (0, (latest_time.saturating_sub(start)) / step);
+        let mut earliest = latest_time;
+        while low <= high {
+            let mid = low + (high - low) / 2;
+            match self
+                .latest_bar(&query, start, start + (mid + 1) * step)
+                .await?
+            {
+                Some(bar) => {
+                    earliest = bar;
+                    if mid == 0 {
+                        break;
+                    }
+                    high = mid - 1;
+                }
+                None => low = mid + 1,
+            }
+        }
+
+        Ok(IntervalProbe {
+            interval,
+            earliest: Some(earliest),
+            latest,
+        })
+    }
+
+    /// Start of the first daily bar of `symbol`, ie/ the UTC day it was listed in `category`,
+    /// bisecting daily klines from [`LISTING_SEARCH_START`] to `end`. `None` if it has no data.
+    pub async fn listing_date(
+        &self,
+        symbol: &str,
+        category: Category,
+        end: u64,
+    ) -> Result<Option<u64>, BybitError> {
+        let probe = self
+            .probe_interval(symbol, category, Interval::Day, LISTING_SEARCH_START, end)
+            .await?;
+        Ok(probe.earliest)
+    }
+
+    /// The most recent bar closed by `now`, in a single request for the last few bars. `None`
+    /// if none has closed yet, eg/ for a symbol listed moments ago.
+    pub async fn final_bar(
+        &self,
+        symbol: &str,
+        category: Category,
+        interval: Interval,
+        kline_type: KlineType,
+        now: u64,
+    ) -> Result<Option<Kline>, BybitError> {
+        let start = now.saturating_sub(FINAL_BAR_WINDOW * interval.duration_ms());
+        let query = KlineQuery {
+            symbol: symbol.to_string(),
+            category,
+            interval,
+            kline_type,
+            start,
+            end: now,
+            max_records: FINAL_BAR_WINDOW as u32,
+            limit_per_request: FINAL_BAR_WINDOW as u32,
+        };
+        let klines = self
+            .get_kline_single(&query, start, now, FINAL_BAR_WINDOW as u32)
+            .await?;
+        Ok(klines
+            .into_iter()
+            .filter(|kline| interval.next_start(kline.start_time) <= now)
+            .max_by_key(|kline| kline.start_time))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_server::{self, MockResponse};
+
+    #[tokio::test]
+    async fn test_probe_interval_finds_listing() {
+        let step = Interval::Hour1.duration_ms();
+        let start = 1704067200000;
+        let listed = start + 37 * step;
+        let last = start + 99 * step;
+
+        // Hourly bars from `listed` to `last`, newest first
+        let base_url = mock_server::serve(move |request| {
+            let param = |name| {
+                mock_server::query_param(request, name)
+                    .and_then(|value| value.parse::<u64>().ok())
+                    .unwrap()
+            };
+            let (from, to) = (param("start").max(listed), param("end").min(last));
+            let bar = to / step * step;
+            let bars = if bar >= from { vec![bar] } else { vec![] };
+            MockResponse::klines("linear", &bars)
+        })
+        .await;
+        let client = BybitClient::new(false, 1)
+            .unwrap()
+            .with_base_url(base_url)
+            .with_preflight(false);
+
+        let probe = client
+            .probe_interval(
+                "BTCUSDT",
+                Category::Linear,
+                Interval::Hour1,
+                start,
+                start + 200 * step,
+            )
+            .await
+            .unwrap();
+        assert_eq!(probe.earliest, Some(listed));
+        assert_eq!(probe.latest, Some(last));
+
+        let empty = client
+            .probe_interval(
+                "BTCUSDT",
+                Category::Linear,
+                Interval::Hour1,
+                start,
+                start + 10 * step,
+            )
+            .await
+            .unwrap();
+        assert_eq!(empty.earliest, None);
+        assert_eq!(empty.latest, None);
+    }
+
+    #[tokio::test]
+    async fn test_listing_date() {
+        let step = Interval::Day.duration_ms();
+        // Daily bars since 2021-03-15
+        let listed = 1615766400000;
+        let base_url = mock_server::serve(move |request| {
+            let param = |name| {
+                mock_server::query_param(request, name)
+                    .and_then(|value| value.parse::<u64>().ok())
+                    .unwrap()
+            };
+            let bar = param("end") / step * step;
+            let bars = if bar >= param("start").max(listed) { vec![bar] } else { vec![] };
+            MockResponse::klines("linear", &bars)
+        })
+        .await;
+        let client = BybitClient::new(false, 1)
+            .unwrap()
+            .with_base_url(base_url)
+            .with_preflight(false);
+
+        let end = 1704067200000;
+        let listing = client.listing_date("BTCUSDT", Category::Linear, end).await.unwrap();
+        assert_eq!(listing, Some(listed));
+        let unlisted = client.listing_date("BTCUSDT", Category::Linear, listed).await.unwrap();
+        assert_eq!(unlisted, None);
+    }
+
+    #[tokio::test]
+    async fn test_final_bar_skips_forming_bar() {
+        let step = Interval::Hour1.duration_ms();
+        // Hourly bars up to the one forming at `end`, newest first
+        let base_url = mock_server::serve(move |request| {
+            let param = |name| {
+                mock_server::query_param(request, name)
+                    .and_then(|value| value.parse::<u64>().ok())
+                    .unwrap()
+            };
+            let bars = (param("start").div_ceil(step)..=param("end") / step)
+                .rev()
+                .map(|bar| bar * step)
+                .collect::<Vec<_>>();
+            MockResponse::klines("linear", &bars)
+        })
+        .await;
+        let client = BybitClient::new(false, 1)
+            .unwrap()
+            .with_base_url(base_url)
+            .with_preflight(false);
+
+        let now = 1704067200000 + 90 * 60_000;
+        let bar = client
+            .final_bar("BTCUSDT", Category::Linear, Interval::Hour1, KlineType::Trade, now)
+            .await
+            .unwrap();
+        assert_eq!(bar.map(|bar| bar.start_time), Some(1704067200000));
+    }
+}
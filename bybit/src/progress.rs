@@ -0,0 +1,254 @@
+use crate::FetchProgress;
+use std::{
+    io::Write,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Default interval between the consolidated status lines written when the destination isn't a
+/// terminal.
+pub const STATUS_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Width of the bars drawn on a terminal, in characters.
+const BAR_WIDTH: usize = 24;
+
+/// Progress of symbols fetched concurrently, shared by their fetches so they report to a
+/// single display instead of interleaving their own lines.
+///
+/// On a terminal there's a bar per symbol still in flight plus an overall bar, redrawn in place
+/// on every update. Elsewhere, eg/ a log file, a single consolidated status line is written at
+/// most every `status_interval`, plus a final one once every symbol has finished. Updates take
+/// a lock, so the display can be shared across tasks and threads.
+pub struct MultiProgress<W: Write> {
+    state: Mutex<State<W>>,
+}
+
+struct State<W> {
+    out: W,
+    tty: bool,
+    status_interval: Duration,
+    bars: Vec<Bar>,
+    /// Lines of the previous terminal redraw, to move back over.
+    drawn: usize,
+    last_status: Option<Instant>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Bar {
+    symbol: String,
+    fetched: usize,
+    expected: u64,
+    done: bool,
+}
+
+/// Handle updating one symbol's bar of a [`MultiProgress`].
+pub struct ProgressBar<'a, W: Write> {
+    progress: &'a MultiProgress<W>,
+    index: usize,
+}
+
+impl<W: Write> MultiProgress<W> {
+    /// Display on `out`, redrawn in place if `tty`, or as status lines at most every
+    /// `status_interval` otherwise.
+    pub fn new(out: W, tty: bool, status_interval: Duration) -> Self {
+        Self {
+            state: Mutex::new(State {
+                out,
+                tty,
+                status_interval,
+                bars: Vec::new(),
+                drawn: 0,
+                last_status: None,
+            }),
+        }
+    }
+
+    /// Add a bar for `symbol`, shown until [`ProgressBar::finish`].
+    pub fn add(&self, symbol: &str) -> ProgressBar<'_, W> {
+        let mut state = self.lock();
+        state.bars.push(Bar {
+            symbol: symbol.to_string(),
+            ..Default::default()
+        });
+        ProgressBar {
+            progress: self,
+            index: state.bars.len() - 1,
+        }
+    }
+
+    /// Give back the destination, eg/ to inspect what was written.
+    pub fn into_inner(self) -> W {
+        self.state
+            .into_inner()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .out
+    }
+
+    fn update(&self, index: usize, update: impl FnOnce(&mut Bar)) {
+        let mut state = self.lock();
+        update(&mut state.bars[index]);
+        // Display errors are ignored, progress must never fail a fetch
+        let _ = state.draw();
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State<W>> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl<W: Write> ProgressBar<'_, W> {
+    /// Record the progress of the symbol's fetch, `fetched` and `expected` covering all of its
+    /// series so far.
+    pub fn set(&self, fetched: usize, expected: u64) {
+        self.progress.update(self.index, |bar| {
+            bar.fetched = fetched;
+            bar.expected = expected;
+        });
+    }
+
+    /// Record a [`FetchProgress`] of the symbol's fetch, following `completed` klines of series
+    /// already fetched, out of `series` similar series in total.
+    pub fn set_fetch(&self, progress: FetchProgress, completed: usize, series: usize) {
+        self.set(completed + progress.fetched, progress.expected * series as u64);
+    }
+
+    /// Mark the symbol done with `records` klines, removing its bar from a terminal display.
+    pub fn finish(&self, records: usize) {
+        self.progress.update(self.index, |bar| {
+            bar.fetched = records;
+            bar.expected = bar.expected.max(records as u64);
+            bar.done = true;
+        });
+    }
+}
+
+impl<W: Write> State<W> {
+    fn draw(&mut self) -> std::io::Result<()> {
+        let finished = self.bars.iter().all(|bar| bar.done);
+        if self.tty {
+            let mut lines = self
+                .bars
+                .iter()
+                .filter(|bar| !bar.done)
+                .map(|bar| bar_line(&bar.symbol, bar.fetched as u64, bar.expected))
+                .collect::<Vec<_>>();
+            lines.push(self.overall_line());
+            // Back to the first line of the previous redraw, clearing lines it no longer needs
+            if self.drawn > 0 {
+                write!(self.out, "\x1b[{}A", self.drawn)?;
+            }
+            for line in &lines {
+                writeln!(self.out, "\x1b[2K{}", line)?;
+            }
+            for _ in lines.len()..self.drawn {
+                writeln!(self.out, "\x1b[2K")?;
+            }
+            self.drawn = lines.len().max(self.drawn);
+        } else {
+            let due = self
+                .last_status
+                .is_none_or(|last| last.elapsed() >= self.status_interval);
+            if !due && !finished {
+                return Ok(());
+            }
+            self.last_status = Some(Instant::now());
+            writeln!(self.out, "{}", self.status_line())?;
+        }
+        self.out.flush()
+    }
+
+    fn totals(&self) -> (usize, u64, u64) {
+        let done = self.bars.iter().filter(|bar| bar.done).count();
+        let fetched = self.bars.iter().map(|bar| bar.fetched as u64).sum();
+        let expected = self.bars.iter().map(|bar| bar.expected).sum();
+        (done, fetched, expected)
+    }
+
+    fn overall_line(&self) -> String {
+        let (done, fetched, expected) = self.totals();
+        let label = format!("{}/{} symbols", done, self.bars.len());
+        bar_line(&label, fetched, expected)
+    }
+
+    fn status_line(&self) -> String {
+        let (done, fetched, expected) = self.totals();
+        let in_flight = self
+            .bars
+            .iter()
+            .filter(|bar| !bar.done)
+            .map(|bar| format!("{} {}%", bar.symbol, percent(bar.fetched as u64, bar.expected)))
+            .collect::<Vec<_>>();
+        let mut line = format!(
+            "Progress: {}/{} symbols done, {} of up to {} records",
+            done,
+            self.bars.len(),
+            fetched,
+            expected
+        );
+        if !in_flight.is_empty() {
+            line.push_str(&format!(" (in flight: {})", in_flight.join(", ")));
+        }
+        line
+    }
+}
+
+fn percent(fetched: u64, expected: u64) -> u64 {
+    match expected {
+        0 => 0,
+        expected => (fetched * 100 / expected).min(100),
+    }
+}
+
+fn bar_line(label: &str, fetched: u64, expected: u64) -> String {
+    let filled = BAR_WIDTH * percent(fetched, expected) as usize / 100;
+    format!(
+        "{:<16} [{}{}] {:>3}% {}/{}",
+        label,
+        "#".repeat(filled),
+        "-".repeat(BAR_WIDTH - filled),
+        percent(fetched, expected),
+        fetched,
+        expected
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_multi_progress() {
+        // Non-terminal output is consolidated into status lines, the first right away, then
+        // at most one per interval and a final one once every symbol is done
+        let progress = MultiProgress::new(Vec::new(), false, Duration::from_secs(3600));
+        let btc = progress.add("BTCUSDT");
+        let eth = progress.add("ETHUSDT");
+        btc.set(500, 1000);
+        eth.set(100, 1000);
+        btc.finish(1000);
+        eth.set_fetch(FetchProgress { fetched: 300, expected: 500, last_time: None }, 500, 2);
+        eth.finish(800);
+        let written = String::from_utf8(progress.into_inner()).unwrap();
+        assert_eq!(
+            written.lines().collect::<Vec<_>>(),
+            vec![
+                "Progress: 0/2 symbols done, 500 of up to 1000 records (in flight: BTCUSDT 50%, ETHUSDT 0%)",
+                "Progress: 2/2 symbols done, 1800 of up to 2000 records",
+            ]
+        );
+
+        // A terminal gets a bar per symbol in flight and an overall bar, redrawn in place
+        let progress = MultiProgress::new(Vec::new(), true, STATUS_INTERVAL);
+        let btc = progress.add("BTCUSDT");
+        btc.set(500, 1000);
+        btc.finish(1000);
+        let written = String::from_utf8(progress.into_inner()).unwrap();
+        let redraws = written.split("\x1b[2A").collect::<Vec<_>>();
+        assert_eq!(redraws.len(), 2);
+        assert!(redraws[0].contains("BTCUSDT          [############------------]  50% 500/1000"));
+        assert!(redraws[0].ends_with("0/1 symbols      [############------------]  50% 500/1000\n"));
+        // Finished symbols drop off, the line they used is cleared
+        assert!(!redraws[1].contains("BTCUSDT"));
+        assert!(redraws[1].contains("1/1 symbols      [########################] 100% 1000/1000\n\x1b[2K\n"));
+    }
+}
@@ -0,0 +1,156 @@
+use reqwest::header::HeaderMap;
+use std::{
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::time::Instant;
+
+/// Seconds to wait before retrying, sent with HTTP 429 responses.
+pub const RETRY_AFTER: &str = "Retry-After";
+/// Request limit of the current window.
+pub const LIMIT: &str = "X-Bapi-Limit";
+/// Requests remaining in the current window.
+pub const LIMIT_STATUS: &str = "X-Bapi-Limit-Status";
+/// Unix millis at which the current window resets.
+pub const LIMIT_RESET_TIMESTAMP: &str = "X-Bapi-Limit-Reset-Timestamp";
+
+/// Longest wait honoured from a rate limit header, so a bogus reset time can't stall a run.
+pub const MAX_RATE_LIMIT_WAIT: Duration = Duration::from_secs(60);
+
+fn header<T: std::str::FromStr>(headers: &HeaderMap, name: &str) -> Option<T> {
+    headers.get(name)?.to_str().ok()?.trim().parse().ok()
+}
+
+/// Time until the window resets, from `X-Bapi-Limit-Reset-Timestamp`.
+fn reset_in(headers: &HeaderMap) -> Option<Duration> {
+    let reset = Duration::from_millis(header(headers, LIMIT_RESET_TIMESTAMP)?);
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?;
+    Some(reset.saturating_sub(now).min(MAX_RATE_LIMIT_WAIT))
+}
+
+/// Wait advertised by a rate limited response: `Retry-After` (in seconds, HTTP dates aren't
+/// supported) or else the time until `X-Bapi-Limit-Reset-Timestamp`.
+pub fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    match header::<u64>(headers, RETRY_AFTER) {
+        Some(seconds) => Some(Duration::from_secs(seconds).min(MAX_RATE_LIMIT_WAIT)),
+        None => reset_in(headers),
+    }
+}
+
+/// Paces requests from the rate limit headers of previous responses, shared by every request
+/// using a client.
+///
+/// While more than a tenth of the window's limit remains requests are sent freely. Below that,
+/// requests are spread evenly over the time left until the window resets, and once a request
+/// has been rate limited every request waits for the advertised reset.
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+    resume_at: Mutex<Option<Instant>>,
+}
+
+impl RateLimiter {
+    /// Hold every request until at least `wait` from now.
+    pub fn pause(&self, wait: Duration) {
+        let until = Instant::now() + wait;
+        let mut resume_at = self.resume_at.lock().expect("rate limiter lock poisoned");
+        *resume_at = Some(resume_at.map_or(until, |resume_at| resume_at.max(until)));
+    }
+
+    /// Update the pacing from a response's rate limit headers, ignoring responses without them.
+    pub fn observe(&self, headers: &HeaderMap) {
+        let (Some(limit), Some(remaining), Some(reset_in)) = (
+            header::<u32>(headers, LIMIT),
+            header::<u32>(headers, LIMIT_STATUS),
+            reset_in(headers),
+        ) else {
+            return;
+        };
+
+        if remaining.saturating_mul(10) <= limit {
+            self.pause(reset_in / (remaining + 1));
+        }
+    }
+
+    /// Wait until requests may be sent again.
+    pub async fn wait(&self) {
+        let resume_at = *self.resume_at.lock().expect("rate limiter lock poisoned");
+        if let Some(resume_at) = resume_at {
+            tokio::time::sleep_until(resume_at).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        category::Category,
+        interval::Interval,
+        kline::KlineType,
+        mock_server::{self, MockResponse},
+        retry::RetryPolicy,
+        BybitClient, KlineQuery,
+    };
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_retry_after_headers() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(retry_after(&headers), None);
+
+        let in_two_seconds =
+            SystemTime::now().duration_since(UNIX_EPOCH).unwrap() + Duration::from_secs(2);
+        headers.insert(
+            LIMIT_RESET_TIMESTAMP,
+            in_two_seconds.as_millis().to_string().parse().unwrap(),
+        );
+        let wait = retry_after(&headers).unwrap();
+        assert!(wait > Duration::from_millis(1500) && wait <= Duration::from_secs(2));
+
+        // Retry-After takes precedence, and absurd waits are capped
+        headers.insert(RETRY_AFTER, "3".parse().unwrap());
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(3)));
+        headers.insert(RETRY_AFTER, "86400".parse().unwrap());
+        assert_eq!(retry_after(&headers), Some(MAX_RATE_LIMIT_WAIT));
+    }
+
+    #[tokio::test]
+    async fn test_rate_limited_request_waits_for_retry_after() {
+        let requests = AtomicUsize::new(0);
+        let base_url = mock_server::serve(move |_| {
+            if requests.fetch_add(1, Ordering::SeqCst) == 0 {
+                MockResponse {
+                    headers: vec![(RETRY_AFTER, "1".to_string())],
+                    ..MockResponse::status("429 Too Many Requests")
+                }
+            } else {
+                MockResponse::klines("linear", &[1704067200000])
+            }
+        })
+        .await;
+        let client = BybitClient::new(false, 1)
+            .unwrap()
+            .with_base_url(base_url)
+            .with_preflight(false)
+            .with_retry(RetryPolicy::new(1, None));
+        let query = KlineQuery {
+            symbol: "BTCUSDT".to_string(),
+            category: Category::Linear,
+            interval: Interval::Min15,
+            kline_type: KlineType::Trade,
+            start: 1704067200000,
+            end: 1704067200000 + Interval::Min15.duration_ms(),
+            max_records: 1,
+            limit_per_request: 1,
+        };
+
+        let started = std::time::Instant::now();
+        let klines = client.get_kline(&query, |_| {}).await.unwrap();
+
+        assert_eq!(klines.len(), 1);
+        assert_eq!(client.retry().used(), 1);
+        // Exactly the advertised wait, rather than the jittered 500ms first backoff
+        assert!(started.elapsed() >= Duration::from_secs(1));
+        assert!(started.elapsed() < Duration::from_secs(3));
+    }
+}
@@ -0,0 +1,112 @@
+use crate::{output, BybitError};
+use chrono::{SecondsFormat, Utc};
+use serde::Serialize;
+use std::{
+    io::{LineWriter, Write},
+    fs::File,
+    path::Path,
+    sync::Mutex,
+};
+
+/// Outcome of a single kline request, one line of `--request-log`.
+#[derive(Debug, Clone, Serialize)]
+pub struct RequestLogEntry<'a> {
+    /// UTC time the request completed, RFC 3339 with milliseconds.
+    pub ts: String,
+    pub symbol: &'a str,
+    pub category: &'a str,
+    pub interval: &'a str,
+    pub start: u64,
+    pub end: u64,
+    pub limit: u32,
+    /// Klines returned, 0 on failure.
+    pub returned: usize,
+    /// `ok`, `cached` when served from `--cache-dir`, or `error`.
+    pub status: &'static str,
+    /// Retries consumed by the request before it succeeded or gave up.
+    pub retries: u32,
+    pub latency_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl RequestLogEntry<'_> {
+    /// Current time in the format of [`RequestLogEntry::ts`].
+    pub fn now() -> String {
+        Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true)
+    }
+}
+
+/// JSON lines log of every kline request, shared by concurrent fetches and flushed per line
+/// so it can be tailed while a run is in progress.
+#[derive(Debug)]
+pub struct RequestLog {
+    writer: Mutex<LineWriter<File>>,
+}
+
+impl RequestLog {
+    /// Create (or truncate) the log at `path`, creating parent directories as needed.
+    pub fn create(path: &Path) -> Result<Self, BybitError> {
+        Ok(Self {
+            writer: Mutex::new(LineWriter::new(output::create_file(path)?)),
+        })
+    }
+
+    /// Append an entry. A failed write only loses observability, so it's reported rather than
+    /// failing the fetch.
+    pub fn record(&self, entry: &RequestLogEntry<'_>) {
+        let line = serde_json::to_string(entry).expect("RequestLogEntry is always serialisable");
+        let mut writer = self.writer.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Err(error) = writeln!(writer, "{}", line) {
+            log::warn!("failed to write request log: {}", error);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_log_writes_json_lines() {
+        let path = std::env::temp_dir()
+            .join(format!("bybit-kline-request-log-{}", std::process::id()))
+            .join("requests.jsonl");
+        let log = RequestLog::create(&path).unwrap();
+        let entry = RequestLogEntry {
+            ts: RequestLogEntry::now(),
+            symbol: "BTCUSDT",
+            category: "linear",
+            interval: "15",
+            start: 1704067200000,
+            end: 1704153600000,
+            limit: 1000,
+            returned: 96,
+            status: "ok",
+            retries: 1,
+            latency_ms: 230,
+            error: None,
+        };
+        log.record(&entry);
+        log.record(&RequestLogEntry {
+            returned: 0,
+            status: "error",
+            error: Some("HTTP request failed".to_string()),
+            ..entry
+        });
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let lines = contents
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["returned"], 96);
+        assert_eq!(lines[0]["retries"], 1);
+        assert!(lines[0].get("error").is_none());
+        assert_eq!(lines[1]["status"], "error");
+        assert!(lines[1]["ts"].as_str().unwrap().ends_with('Z'));
+
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,218 @@
+use crate::BybitError;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::{
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+/// Bybit `retCode`s signalling a transient server side failure worth retrying.
+///
+/// 10000: server timeout, 10006: too many visits (rate limited), 10016: internal server error.
+pub const TRANSIENT_RET_CODES: &[i32] = &[10000, RATE_LIMITED_RET_CODE, 10016];
+
+/// Bybit `retCode` of a rate limited request.
+pub const RATE_LIMITED_RET_CODE: i32 = 10006;
+
+/// Delay before the first retry, doubled for each subsequent retry of the same request.
+const BASE_BACKOFF: Duration = Duration::from_millis(500);
+
+/// Upper bound on the delay between two attempts of the same request.
+const MAX_BACKOFF: Duration = Duration::from_secs(10);
+
+impl BybitError {
+    /// Whether the request which failed with this error may succeed if retried.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            BybitError::RequestError(error) => {
+                error.is_timeout()
+                    || error.is_connect()
+                    || error
+                        .status()
+                        .is_some_and(|status| status.is_server_error() || status.as_u16() == 429)
+            }
+            BybitError::ServerError { .. } | BybitError::RateLimited { .. } => true,
+            _ => false,
+        }
+    }
+
+    /// Wait Bybit advertised before the failed request may be retried, for rate limited requests.
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            BybitError::RateLimited { retry_after } => Some(*retry_after),
+            _ => None,
+        }
+    }
+}
+
+/// Retry limits for a run: `max_retries` per request, and an optional `budget` capping the
+/// total number of retries across every request sharing the policy.
+#[derive(Debug, Default)]
+pub struct RetryPolicy {
+    max_retries: u32,
+    budget: Option<u32>,
+    used: AtomicU32,
+}
+
+impl RetryPolicy {
+    pub fn new(max_retries: u32, budget: Option<u32>) -> Self {
+        Self {
+            max_retries,
+            budget,
+            used: AtomicU32::new(0),
+        }
+    }
+
+    /// Decide whether a request which failed with `error` on its `attempt`th try (zero based)
+    /// should be retried, returning the delay to wait first: the wait advertised by a rate
+    /// limited response, or else exponential backoff.
+    ///
+    /// Retrying consumes one unit of the run's retry budget. Once the budget is exhausted, the
+    /// error is wrapped in [`BybitError::RetryBudgetExhausted`] so the run fails fast.
+    pub fn should_retry(&self, attempt: u32, error: BybitError) -> Result<Duration, BybitError> {
+        if !error.is_transient() || attempt >= self.max_retries {
+            return Err(error);
+        }
+
+        if let Some(budget) = self.budget {
+            let reserved = self
+                .used
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |used| {
+                    (used < budget).then_some(used + 1)
+                });
+            if reserved.is_err() {
+                return Err(BybitError::RetryBudgetExhausted {
+                    budget,
+                    source: Box::new(error),
+                });
+            }
+        } else {
+            self.used.fetch_add(1, Ordering::SeqCst);
+        }
+
+        Ok(error.retry_after().unwrap_or_else(|| {
+            BASE_BACKOFF
+                .saturating_mul(2u32.saturating_pow(attempt))
+                .min(MAX_BACKOFF)
+        }))
+    }
+
+    /// Total retries consumed so far.
+    pub fn used(&self) -> u32 {
+        self.used.load(Ordering::SeqCst)
+    }
+
+    pub fn budget(&self) -> Option<u32> {
+        self.budget
+    }
+}
+
+/// Random jitter applied to backoff and inter-request delays, so concurrent requests don't
+/// retry in lockstep.
+///
+/// Seeding makes the sequence of delays reproducible, for stable tests of the retry and
+/// concurrent fetch paths.
+#[derive(Debug)]
+pub struct Jitter {
+    rng: Mutex<StdRng>,
+}
+
+impl Default for Jitter {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+impl Jitter {
+    /// Seed the jitter deterministically, or from entropy if `seed` is `None`.
+    pub fn new(seed: Option<u64>) -> Self {
+        let rng = match seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_os_rng(),
+        };
+        Self {
+            rng: Mutex::new(rng),
+        }
+    }
+
+    /// Scale `delay` by a random factor in `[0.5, 1.5)`.
+    pub fn apply(&self, delay: Duration) -> Duration {
+        let factor = self
+            .rng
+            .lock()
+            .expect("jitter rng lock poisoned")
+            .random_range(0.5..1.5);
+        delay.mul_f64(factor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn server_error() -> BybitError {
+        BybitError::ServerError {
+            code: 10006,
+            msg: "Too many visits".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_retry_budget_shared_across_requests() {
+        let policy = RetryPolicy::new(3, Some(4));
+
+        // First request retries up to max_retries
+        assert_eq!(
+            policy.should_retry(0, server_error()).unwrap(),
+            Duration::from_millis(500)
+        );
+        assert_eq!(
+            policy.should_retry(1, server_error()).unwrap(),
+            Duration::from_secs(1)
+        );
+        assert_eq!(
+            policy.should_retry(2, server_error()).unwrap(),
+            Duration::from_secs(2)
+        );
+        assert!(matches!(
+            policy.should_retry(3, server_error()),
+            Err(BybitError::ServerError { .. })
+        ));
+
+        // Second request gets one retry before the run's budget is exhausted
+        assert!(policy.should_retry(0, server_error()).is_ok());
+        assert!(matches!(
+            policy.should_retry(1, server_error()),
+            Err(BybitError::RetryBudgetExhausted { budget: 4, .. })
+        ));
+        assert_eq!(policy.used(), 4);
+    }
+
+    #[test]
+    fn test_non_transient_errors_are_not_retried() {
+        let policy = RetryPolicy::new(3, None);
+        let error = BybitError::ApiError {
+            msg: "Invalid symbol".to_string(),
+        };
+        assert!(matches!(
+            policy.should_retry(0, error),
+            Err(BybitError::ApiError { .. })
+        ));
+        assert_eq!(policy.used(), 0);
+    }
+
+    #[test]
+    fn test_seeded_jitter_is_reproducible() {
+        let delay = Duration::from_millis(1000);
+        let sequence = |jitter: Jitter| (0..8).map(|_| jitter.apply(delay)).collect::<Vec<_>>();
+
+        let first = sequence(Jitter::new(Some(42)));
+        assert_eq!(first, sequence(Jitter::new(Some(42))));
+        assert_ne!(first, sequence(Jitter::new(Some(43))));
+        assert!(first
+            .iter()
+            .all(|jittered| (500..1500).contains(&jittered.as_millis())));
+    }
+}
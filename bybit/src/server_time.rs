@@ -0,0 +1,111 @@
+use crate::{BybitClient, BybitError};
+use chrono::Utc;
+use serde::Deserialize;
+
+/// Bybit's default `recvWindow`: how long after its `timestamp` a signed request stays valid.
+pub const DEFAULT_RECV_WINDOW_MS: u64 = 5_000;
+
+/// Result of `/v5/market/time`.
+#[derive(Debug, Deserialize)]
+pub(crate) struct ServerTime {
+    #[serde(rename = "timeNano")]
+    pub(crate) time_nano: String,
+}
+
+/// `timestamp` and `recvWindow` of a signed request, taken from Bybit's clock rather than the
+/// local one so clock skew doesn't get requests rejected with retCode 10002.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SigningParams {
+    /// Server time in unix millis.
+    pub timestamp: u64,
+    pub recv_window_ms: u64,
+}
+
+impl SigningParams {
+    /// Headers carrying these parameters on a signed v5 request, alongside the API key and
+    /// signature.
+    pub fn headers(&self) -> [(&'static str, String); 2] {
+        [
+            ("X-BAPI-TIMESTAMP", self.timestamp.to_string()),
+            ("X-BAPI-RECV-WINDOW", self.recv_window_ms.to_string()),
+        ]
+    }
+}
+
+impl BybitClient {
+    /// Offset of Bybit's clock from the local clock in millis, measured from `/v5/market/time`
+    /// on first use and cached for the life of the client.
+    ///
+    /// The local time is taken halfway through the request, so network latency doesn't skew
+    /// the offset.
+    pub async fn server_time_offset(&self) -> Result<i64, BybitError> {
+        self.server_time_offset
+            .get_or_try_init(|| async {
+                let sent = Utc::now().timestamp_millis();
+                let server_time = self.get::<ServerTime>("/v5/market/time", &[]).await?;
+                let received = Utc::now().timestamp_millis();
+
+                let time_nano = server_time.time_nano;
+                let server_nanos = time_nano.parse::<i64>().map_err(|_| BybitError::ApiError {
+                    msg: format!("Invalid server time: {}", time_nano),
+                })?;
+                Ok(server_nanos / 1_000_000 - (sent + received) / 2)
+            })
+            .await
+            .copied()
+    }
+
+    /// Timestamp and `recvWindow` for a signed request, from the synced server clock.
+    pub async fn signing_params(&self) -> Result<SigningParams, BybitError> {
+        let offset = self.server_time_offset().await?;
+        Ok(SigningParams {
+            timestamp: (Utc::now().timestamp_millis() + offset) as u64,
+            recv_window_ms: self.recv_window_ms,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_server::{self, MockResponse};
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    #[tokio::test]
+    async fn test_signing_params_follow_server_clock() {
+        // Bybit's clock runs a minute ahead of the local one
+        const SKEW_MS: i64 = 60_000;
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        let base_url = mock_server::serve(move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            let server_millis = Utc::now().timestamp_millis() + SKEW_MS;
+            MockResponse::ok(&format!(
+                r#"{{"timeSecond":"{}","timeNano":"{}"}}"#,
+                server_millis / 1000,
+                server_millis * 1_000_000
+            ))
+        })
+        .await;
+        let client = BybitClient::new(false, 1)
+            .unwrap()
+            .with_base_url(base_url)
+            .with_recv_window(3_000);
+
+        let params = client.signing_params().await.unwrap();
+        let server_now = Utc::now().timestamp_millis() + SKEW_MS;
+        assert!((params.timestamp as i64 - server_now).abs() < params.recv_window_ms as i64);
+        assert_eq!(params.recv_window_ms, 3_000);
+        assert_eq!(
+            params.headers()[1],
+            ("X-BAPI-RECV-WINDOW", "3000".to_string())
+        );
+
+        // The offset is cached after the first sync
+        client.signing_params().await.unwrap();
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+}
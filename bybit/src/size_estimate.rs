@@ -0,0 +1,211 @@
+use crate::{
+    category::Category,
+    interval::Interval,
+    kline::{Kline, KlineType},
+    parquet_writer::ParquetKlineWriter,
+    writer::{self, OutputWriter},
+    BybitError,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::fs;
+
+/// Klines in the smaller of the two samples written to measure a format.
+const SAMPLE_KLINES: usize = 2_000;
+
+/// Output size of a format as a fixed overhead plus a per-record cost, measured by writing
+/// sample klines in that format, for `--estimate-size`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SizeEstimate {
+    /// Bytes independent of the record count, eg/ headers and the parquet footer.
+    pub fixed_bytes: f64,
+    pub bytes_per_record: f64,
+}
+
+impl SizeEstimate {
+    /// Measure a format from the bytes `write` produces for two sample series of different
+    /// lengths, separating the per-record cost from the fixed overhead.
+    pub fn measure(
+        interval: Interval,
+        category: Category,
+        mut write: impl FnMut(&[Kline]) -> Result<u64, BybitError>,
+    ) -> Result<Self, BybitError> {
+        let sample = sample_klines(2 * SAMPLE_KLINES, interval, category);
+        let small = write(&sample[..SAMPLE_KLINES])? as f64;
+        let large = write(&sample)? as f64;
+        let bytes_per_record = (large - small) / SAMPLE_KLINES as f64;
+        Ok(Self {
+            fixed_bytes: (small - bytes_per_record * SAMPLE_KLINES as f64).max(0.0),
+            bytes_per_record,
+        })
+    }
+
+    /// Measure a text or binary format written with `writer`.
+    pub fn of_writer(
+        writer: &mut dyn OutputWriter,
+        interval: Interval,
+        category: Category,
+    ) -> Result<Self, BybitError> {
+        Self::measure(interval, category, |klines| {
+            let mut bytes = Vec::new();
+            writer::write_klines(writer, &mut bytes, klines)?;
+            Ok(bytes.len() as u64)
+        })
+    }
+
+    /// Measure Snappy compressed parquet written in row groups of `row_group_size`, through a
+    /// temporary file.
+    pub fn of_parquet(
+        row_group_size: usize,
+        interval: Interval,
+        category: Category,
+    ) -> Result<Self, BybitError> {
+        let path = std::env::temp_dir()
+            .join(format!("bybit-kline-size-estimate-{}.parquet", std::process::id()));
+        let estimate = Self::measure(interval, category, |klines| {
+            let mut writer = ParquetKlineWriter::create(&path, row_group_size)?;
+            writer.write(klines.to_vec())?;
+            writer.finish()?;
+            Ok(fs::metadata(&path)?.len())
+        });
+        let _ = fs::remove_file(&path);
+        estimate
+    }
+
+    /// Estimated bytes for `records` klines.
+    pub fn total_bytes(&self, records: u64) -> u64 {
+        (self.fixed_bytes + self.bytes_per_record * records as f64).round() as u64
+    }
+}
+
+/// Deterministic series shaped like BTCUSDT klines: a random walk around 42,000 with a 0.1
+/// tick, base volumes to 3 decimals and quote volumes their product to 4 decimals like Bybit's
+/// turnover, so numbers render with realistic widths.
+pub fn sample_klines(count: usize, interval: Interval, category: Category) -> Vec<Kline> {
+    let mut rng = StdRng::seed_from_u64(count as u64);
+    let tick = |price: f64| (price * 10.0).round() / 10.0;
+    let mut start_time = 1_704_067_200_000;
+    let mut close = 42_000.0;
+    (0..count)
+        .map(|_| {
+            let open = close;
+            close = tick(open * (1.0 + rng.random_range(-0.002..0.002)));
+            let high = tick(open.max(close) * (1.0 + rng.random_range(0.0..0.001)));
+            let low = tick(open.min(close) * (1.0 - rng.random_range(0.0..0.001)));
+            let base_volume = (rng.random_range(0.5..200.0) * 1000.0_f64).round() / 1000.0;
+            let quote_volume = (base_volume * (open + close) / 2.0 * 10_000.0).round() / 10_000.0;
+            let (volume, turnover) = match category {
+                Category::Inverse => (quote_volume.round(), base_volume),
+                _ => (base_volume, quote_volume),
+            };
+            let kline = Kline {
+                start_time,
+                open_price: open,
+                high_price: high,
+                low_price: low,
+                close_price: close,
+                volume,
+                turnover,
+                kline_type: KlineType::Trade,
+                category,
+                filled: None,
+            };
+            start_time = interval.next_start(start_time);
+            kline
+        })
+        .collect()
+}
+
+/// Format a byte count with binary units, eg/ `1.50 GiB`.
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["KiB", "MiB", "GiB", "TiB", "PiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64;
+    let mut unit = "B";
+    for next in UNITS {
+        if value < 1024.0 {
+            break;
+        }
+        value /= 1024.0;
+        unit = next;
+    }
+    format!("{:.2} {}", value, unit)
+}
+
+/// Parse a byte count with an optional unit, case insensitive: decimal `KB`, `MB`, `GB` and
+/// `TB`, binary `KiB`, `MiB`, `GiB` and `TiB`, or `B`. Eg/ `100MB` or `1.5GiB`.
+pub fn parse_bytes(s: &str) -> Result<u64, BybitError> {
+    let invalid = || BybitError::ArgumentError(format!("Invalid size: {} (expected eg/ 500000, 100MB or 1GiB)", s));
+    let s = s.trim();
+    let split = s
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let scale = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1u64,
+        "kb" => 1_000,
+        "mb" => 1_000_000,
+        "gb" => 1_000_000_000,
+        "tb" => 1_000_000_000_000,
+        "kib" => 1 << 10,
+        "mib" => 1 << 20,
+        "gib" => 1 << 30,
+        "tib" => 1 << 40,
+        _ => return Err(invalid()),
+    };
+    let bytes = number.parse::<f64>().map_err(|_| invalid())? * scale as f64;
+    match bytes >= 1.0 && bytes < u64::MAX as f64 {
+        true => Ok(bytes as u64),
+        false => Err(invalid()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ohlcv_binary::{self, OhlcvBinaryWriter},
+        output::{Delimited, TimestampUnit},
+        writer::DelimitedWriter,
+    };
+
+    #[test]
+    fn test_size_estimate_matches_written_output() {
+        // Fixed width records are measured exactly
+        let binary =
+            SizeEstimate::of_writer(&mut OhlcvBinaryWriter, Interval::Min1, Category::Linear).unwrap();
+        assert_eq!(binary.bytes_per_record, ohlcv_binary::RECORD_SIZE as f64);
+        assert_eq!(binary.total_bytes(10), (ohlcv_binary::HEADER_SIZE + 10 * ohlcv_binary::RECORD_SIZE) as u64);
+
+        // Variable width text is close to what a longer series actually takes
+        let mut csv = DelimitedWriter { format: Delimited::Csv, timestamp_unit: TimestampUnit::Millis, close_time: None };
+        let estimate = SizeEstimate::of_writer(&mut csv, Interval::Min1, Category::Linear).unwrap();
+        let mut written = Vec::new();
+        let klines = sample_klines(20_000, Interval::Min1, Category::Linear);
+        writer::write_klines(&mut csv, &mut written, &klines).unwrap();
+        let error = estimate.total_bytes(20_000) as f64 / written.len() as f64 - 1.0;
+        assert!(error.abs() < 0.05, "estimate off by {:.1}%", error * 100.0);
+
+        let parquet = SizeEstimate::of_parquet(100_000, Interval::Min1, Category::Linear).unwrap();
+        assert!(parquet.bytes_per_record > 0.0 && parquet.bytes_per_record < estimate.bytes_per_record);
+    }
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(1536), "1.50 KiB");
+        assert_eq!(format_bytes(3 * 1024 * 1024 * 1024), "3.00 GiB");
+    }
+
+    #[test]
+    fn test_parse_bytes() {
+        assert_eq!(parse_bytes("500000").unwrap(), 500_000);
+        assert_eq!(parse_bytes("100MB").unwrap(), 100_000_000);
+        assert_eq!(parse_bytes("1.5gib").unwrap(), 3 << 29);
+        assert_eq!(parse_bytes("64 KiB").unwrap(), 65_536);
+        assert!(parse_bytes("0").is_err());
+        assert!(parse_bytes("10PB").is_err());
+        assert!(parse_bytes("MB").is_err());
+    }
+}
@@ -0,0 +1,107 @@
+use crate::{
+    category::VolumeUnit,
+    kline::Kline,
+    parse_date, BybitError,
+};
+use std::str::FromStr;
+
+/// Contract redenomination at a cutoff, eg/ a perpetual relisted as 1000 units per contract,
+/// given as `YYYY-MM-DD:scale=F` (or `YYYY/MM/DD:scale=F`).
+///
+/// Bars starting before the cutoff (midnight UTC) are restated in the new contract: prices are
+/// multiplied by `scale` and base coin volume divided by it, leaving quote volume unchanged.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpecChange {
+    /// Unix millis of the first bar in the new contract.
+    pub cutoff: u64,
+    pub scale: f64,
+}
+
+impl FromStr for SpecChange {
+    type Err = BybitError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            BybitError::ArgumentError(format!(
+                "Invalid spec change: {} (expected YYYY-MM-DD:scale=F, eg/ 2023-06-01:scale=100)",
+                s
+            ))
+        };
+        let (date, change) = s.split_once(':').ok_or_else(invalid)?;
+        let scale = match change.split_once('=') {
+            Some(("scale", scale)) => scale.trim().parse::<f64>().map_err(|_| invalid())?,
+            _ => return Err(invalid()),
+        };
+        if !scale.is_finite() || scale <= 0.0 {
+            return Err(invalid());
+        }
+        Ok(Self {
+            cutoff: parse_date(&date.trim().replace('-', "/"))?,
+            scale,
+        })
+    }
+}
+
+/// Restate every kline starting before a change's cutoff by its scale, compounding changes
+/// which all precede the kline, so the series is continuous across redenominations.
+pub fn apply_spec_changes(klines: &mut [Kline], changes: &[SpecChange]) {
+    for kline in klines {
+        let scale = changes
+            .iter()
+            .filter(|change| kline.start_time < change.cutoff)
+            .map(|change| change.scale)
+            .product::<f64>();
+        if scale == 1.0 {
+            continue;
+        }
+        kline.open_price *= scale;
+        kline.high_price *= scale;
+        kline.low_price *= scale;
+        kline.close_price *= scale;
+
+        // The raw field holding base coin volume depends on the category
+        let units = kline.category.volume_units();
+        match (units.volume, units.turnover) {
+            (VolumeUnit::Base, _) => kline.volume /= scale,
+            (_, VolumeUnit::Base) => kline.turnover /= scale,
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{category::Category, kline::test_kline};
+
+    #[test]
+    fn test_apply_spec_changes() {
+        let change = "2023-06-01:scale=1000".parse::<SpecChange>().unwrap();
+        assert_eq!(change.cutoff, 1685577600000);
+        assert!("2023-06-01:scale=0".parse::<SpecChange>().is_err());
+        assert!("2023-06-01:size=10".parse::<SpecChange>().is_err());
+
+        let mut klines = [
+            (change.cutoff - 60_000, Category::Linear),
+            (change.cutoff, Category::Linear),
+            (change.cutoff - 60_000, Category::Inverse),
+        ]
+        .map(|(start_time, category)| Kline {
+            volume: 1_000_000.0,
+            turnover: 1_500.0,
+            category,
+            ..test_kline(start_time, 0.0015)
+        })
+        .to_vec();
+        apply_spec_changes(&mut klines, &[change]);
+
+        // Before the cutoff prices scale up and base volume down, quote volume is unchanged
+        assert!((klines[0].close_price - 1.5).abs() < 1e-9);
+        assert!((klines[0].base_volume() - 1_000.0).abs() < 1e-9);
+        assert_eq!(klines[0].quote_volume(), 1_500.0);
+        assert_eq!(klines[1].close_price, 0.0015);
+        // Inverse klines hold base volume in turnover
+        assert!((klines[2].base_volume() - 1.5).abs() < 1e-9);
+        assert_eq!(klines[2].quote_volume(), 1_000_000.0);
+    }
+}
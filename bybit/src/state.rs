@@ -0,0 +1,118 @@
+use crate::BybitError;
+use std::{
+    collections::BTreeMap,
+    ffi::OsString,
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+/// Incremental fetch state, tracking the start time of the last kline fetched per symbol so
+/// a recurring job can resume where the previous run left off.
+///
+/// Stored as a JSON object (eg/ `{"BTCUSDT": 1704153600000}`). The state file is locked for as
+/// long as this is alive, by creating a sibling `<state file>.lock`, so concurrent runs can't
+/// interleave their updates. The lock is released on drop.
+#[derive(Debug)]
+pub struct StateFile {
+    path: PathBuf,
+    lock_path: PathBuf,
+    last_fetched: BTreeMap<String, u64>,
+}
+
+impl StateFile {
+    /// Lock and load the state file, a missing file is treated as empty state.
+    pub fn lock(path: &Path) -> Result<Self, BybitError> {
+        let mut lock_path = OsString::from(path.as_os_str());
+        lock_path.push(".lock");
+        let lock_path = PathBuf::from(lock_path);
+
+        match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&lock_path)
+        {
+            Ok(mut lock) => writeln!(lock, "{}", std::process::id())?,
+            Err(error) if error.kind() == io::ErrorKind::AlreadyExists => {
+                return Err(BybitError::ArgumentError(format!(
+                    "State file {} is locked by another run (remove {} if that run is no longer alive)",
+                    path.display(),
+                    lock_path.display()
+                )))
+            }
+            Err(error) => return Err(error.into()),
+        }
+
+        // Constructed before loading, so the lock is released if the state is unreadable
+        let mut state = Self {
+            path: path.to_path_buf(),
+            lock_path,
+            last_fetched: BTreeMap::new(),
+        };
+        match fs::read(path) {
+            Ok(contents) => state.last_fetched = serde_json::from_slice(&contents)?,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {}
+            Err(error) => return Err(error.into()),
+        }
+        Ok(state)
+    }
+
+    /// Start time of the last kline fetched for `symbol`, if it's been fetched before.
+    pub fn last_fetched(&self, symbol: &str) -> Option<u64> {
+        self.last_fetched.get(symbol).copied()
+    }
+
+    /// Record the start time of the last kline fetched for `symbol`, never moving it backwards.
+    pub fn record(&mut self, symbol: &str, last_fetched_time: u64) {
+        let last = self.last_fetched.entry(symbol.to_string()).or_default();
+        *last = (*last).max(last_fetched_time);
+    }
+
+    /// Persist the state, writing to a temporary file first so a crash never leaves it partial.
+    pub fn save(&self) -> Result<(), BybitError> {
+        let mut temp_path = OsString::from(self.path.as_os_str());
+        temp_path.push(format!(".{}.tmp", std::process::id()));
+
+        fs::write(&temp_path, serde_json::to_vec_pretty(&self.last_fetched)?)?;
+        fs::rename(&temp_path, &self.path)?;
+        Ok(())
+    }
+}
+
+impl Drop for StateFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.lock_path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_state_file_round_trip_and_lock() {
+        let path =
+            std::env::temp_dir().join(format!("bybit-kline-state-{}.json", std::process::id()));
+
+        let mut state = StateFile::lock(&path).unwrap();
+        assert_eq!(state.last_fetched("BTCUSDT"), None);
+
+        // Concurrent runs are rejected while the lock is held
+        assert!(matches!(
+            StateFile::lock(&path),
+            Err(BybitError::ArgumentError(_))
+        ));
+
+        state.record("BTCUSDT", 1704153600000);
+        state.record("BTCUSDT", 1704067200000);
+        state.save().unwrap();
+        drop(state);
+
+        let state = StateFile::lock(&path).unwrap();
+        assert_eq!(state.last_fetched("BTCUSDT"), Some(1704153600000));
+        assert_eq!(state.last_fetched("ETHUSDT"), None);
+        drop(state);
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
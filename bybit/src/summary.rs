@@ -0,0 +1,172 @@
+use crate::kline::Kline;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::io::{self, Write};
+
+/// Aggregate of a kline series over the whole fetched range.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RangeSummary {
+    pub symbol: String,
+    pub category: String,
+    pub interval: String,
+    pub bars: usize,
+    /// Open time of the first bar.
+    pub start_time: DateTime<Utc>,
+    /// Open time of the last bar.
+    pub end_time: DateTime<Utc>,
+    pub open: f64,
+    pub high: f64,
+    pub high_time: DateTime<Utc>,
+    pub low: f64,
+    pub low_time: DateTime<Utc>,
+    pub close: f64,
+    pub base_volume: f64,
+    pub quote_volume: f64,
+    /// Volume weighted average price, `None` if there was no volume.
+    pub vwap: Option<f64>,
+}
+
+fn to_datetime(millis: u64) -> DateTime<Utc> {
+    DateTime::from_timestamp_millis(millis as i64)
+        .unwrap_or_else(|| DateTime::from_timestamp(0, 0).unwrap())
+}
+
+impl RangeSummary {
+    /// Summarise a time ordered kline series, returning `None` if it's empty.
+    pub fn from_klines(
+        klines: &[Kline],
+        symbol: &str,
+        category: &str,
+        interval: &str,
+    ) -> Option<Self> {
+        let first = klines.first()?;
+        let last = klines.last()?;
+
+        let high = klines.iter().fold(first, |high, k| {
+            if k.high_price > high.high_price {
+                k
+            } else {
+                high
+            }
+        });
+        let low = klines.iter().fold(
+            first,
+            |low, k| if k.low_price < low.low_price { k } else { low },
+        );
+        let base_volume = klines.iter().map(Kline::base_volume).sum::<f64>();
+        let quote_volume = klines.iter().map(Kline::quote_volume).sum::<f64>();
+        let vwap = (base_volume > 0.0).then(|| quote_volume / base_volume);
+
+        Some(Self {
+            symbol: symbol.to_string(),
+            category: category.to_string(),
+            interval: interval.to_string(),
+            bars: klines.len(),
+            start_time: to_datetime(first.start_time),
+            end_time: to_datetime(last.start_time),
+            open: first.open_price,
+            high: high.high_price,
+            high_time: to_datetime(high.start_time),
+            low: low.low_price,
+            low_time: to_datetime(low.start_time),
+            close: last.close_price,
+            base_volume,
+            quote_volume,
+            vwap,
+        })
+    }
+
+    pub fn write_table<W: Write>(&self, mut out: W) -> io::Result<()> {
+        let format_time = |time: &DateTime<Utc>| time.format("%Y-%m-%d %H:%M:%S UTC").to_string();
+
+        writeln!(out, "\nSummary of {} {} bars:\n", self.bars, self.symbol)?;
+        writeln!(
+            out,
+            "{:<12} {} -> {}",
+            "Range",
+            format_time(&self.start_time),
+            format_time(&self.end_time)
+        )?;
+        writeln!(out, "{:<12} {:.4}", "Open", self.open)?;
+        writeln!(
+            out,
+            "{:<12} {:.4} at {}",
+            "High",
+            self.high,
+            format_time(&self.high_time)
+        )?;
+        writeln!(
+            out,
+            "{:<12} {:.4} at {}",
+            "Low",
+            self.low,
+            format_time(&self.low_time)
+        )?;
+        writeln!(out, "{:<12} {:.4}", "Close", self.close)?;
+        writeln!(out, "{:<12} {:.4}", "base_volume", self.base_volume)?;
+        writeln!(out, "{:<12} {:.4}", "quote_volume", self.quote_volume)?;
+        match self.vwap {
+            Some(vwap) => writeln!(out, "{:<12} {:.4}", "VWAP", vwap)?,
+            None => writeln!(out, "{:<12} n/a", "VWAP")?,
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kline::test_kline;
+
+    #[test]
+    fn test_range_summary_from_klines() {
+        let klines = vec![
+            Kline {
+                open_price: 100.0,
+                high_price: 105.0,
+                low_price: 99.0,
+                volume: 1.0,
+                turnover: 104.0,
+                ..test_kline(0, 104.0)
+            },
+            Kline {
+                open_price: 104.0,
+                high_price: 110.0,
+                low_price: 101.0,
+                volume: 2.0,
+                turnover: 204.0,
+                ..test_kline(60_000, 102.0)
+            },
+            Kline {
+                open_price: 102.0,
+                high_price: 103.0,
+                low_price: 95.0,
+                volume: 1.0,
+                turnover: 96.0,
+                ..test_kline(120_000, 96.0)
+            },
+        ];
+
+        let summary = RangeSummary::from_klines(&klines, "BTCUSDT", "linear", "1").unwrap();
+
+        assert_eq!(summary.bars, 3);
+        assert_eq!(summary.open, 100.0);
+        assert_eq!(summary.close, 96.0);
+        assert_eq!(
+            (summary.high, summary.high_time.timestamp_millis()),
+            (110.0, 60_000)
+        );
+        assert_eq!(
+            (summary.low, summary.low_time.timestamp_millis()),
+            (95.0, 120_000)
+        );
+        assert_eq!(summary.base_volume, 4.0);
+        assert_eq!(summary.quote_volume, 104.0 + 204.0 + 96.0);
+        assert_eq!(summary.vwap, Some(404.0 / 4.0));
+
+        assert_eq!(
+            RangeSummary::from_klines(&[], "BTCUSDT", "linear", "1"),
+            None
+        );
+    }
+}
@@ -0,0 +1,184 @@
+use crate::{interval::Interval, kline::Kline, output::HumanNumbers};
+use std::io::{self, Write};
+
+/// Column layout of the kline table, shared by buffered and streamed rendering so rows written
+/// as chunks arrive line up with a header written before the first chunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TableRenderer {
+    pub price_decimals: usize,
+    pub volume_decimals: usize,
+    pub human_numbers: Option<HumanNumbers>,
+    /// Prefix rows with their category, for merged categories.
+    pub show_category: bool,
+    /// Prefix rows with their kline type, for merged kline types.
+    pub show_kline_type: bool,
+    /// Append the fill strategy of synthetic bars.
+    pub show_filled: bool,
+    /// Follow the open time with the close time of bars of this interval.
+    pub close_time: Option<Interval>,
+}
+
+impl Default for TableRenderer {
+    fn default() -> Self {
+        Self {
+            price_decimals: 4,
+            volume_decimals: 4,
+            human_numbers: None,
+            show_category: false,
+            show_kline_type: false,
+            show_filled: false,
+            close_time: None,
+        }
+    }
+}
+
+impl TableRenderer {
+    fn price_width(&self) -> usize {
+        12.max(self.price_decimals + 8)
+    }
+
+    fn volume_width(&self) -> usize {
+        match self.human_numbers {
+            // Room for a separator every three digits of a 1e12 volume
+            Some(HumanNumbers::Separators) => 19.max(self.volume_decimals + 15),
+            _ => 15.max(self.volume_decimals + 10),
+        }
+    }
+
+    fn format_volume(&self, volume: f64) -> String {
+        match self.human_numbers {
+            Some(human_numbers) => human_numbers.format(volume, self.volume_decimals),
+            None => format!("{:.*}", self.volume_decimals, volume),
+        }
+    }
+
+    /// Write the column titles and the rule beneath them.
+    pub fn write_header<W: Write>(&self, mut out: W) -> io::Result<()> {
+        let (price_width, volume_width) = (self.price_width(), self.volume_width());
+
+        if self.show_category {
+            write!(out, "{:<9} ", "Category")?;
+        }
+        if self.show_kline_type {
+            write!(out, "{:<14} ", "Type")?;
+        }
+        write!(out, "{:<23} ", "Time")?;
+        if self.close_time.is_some() {
+            write!(out, "{:<23} ", "Close Time")?;
+        }
+        write!(
+            out,
+            "{:<pw$} {:<pw$} {:<pw$} {:<pw$} {:<vw$} {:<vw$}",
+            "Open",
+            "High",
+            "Low",
+            "Close",
+            "base_volume",
+            "quote_volume",
+            pw = price_width,
+            vw = volume_width
+        )?;
+        if self.show_filled {
+            write!(out, " {:<12}", "Filled")?;
+        }
+        writeln!(out)?;
+
+        let width = 29
+            + 4 * (price_width + 1)
+            + 2 * (volume_width + 1)
+            + if self.show_category { 10 } else { 0 }
+            + if self.show_kline_type { 15 } else { 0 }
+            + if self.close_time.is_some() { 24 } else { 0 }
+            + if self.show_filled { 13 } else { 0 };
+        writeln!(out, "{}", "-".repeat(width))
+    }
+
+    pub fn write_row<W: Write>(&self, mut out: W, kline: &Kline) -> io::Result<()> {
+        if self.show_category {
+            write!(out, "{:<9} ", kline.category)?;
+        }
+        if self.show_kline_type {
+            write!(out, "{:<14} ", kline.kline_type)?;
+        }
+        write!(out, "{:<23} ", kline.format_time())?;
+        if let Some(interval) = self.close_time {
+            write!(out, "{:<23} ", kline.format_close_time(interval))?;
+        }
+        write!(
+            out,
+            "{:<pw$.pp$} {:<pw$.pp$} {:<pw$.pp$} {:<pw$.pp$} {:<vw$} {:<vw$}",
+            kline.open_price,
+            kline.high_price,
+            kline.low_price,
+            kline.close_price,
+            self.format_volume(kline.base_volume()),
+            self.format_volume(kline.quote_volume()),
+            pw = self.price_width(),
+            pp = self.price_decimals,
+            vw = self.volume_width()
+        )?;
+        if self.show_filled {
+            let filled = kline
+                .filled
+                .map(|fill| fill.to_string())
+                .unwrap_or_default();
+            write!(out, " {:<12}", filled)?;
+        }
+        writeln!(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kline::test_kline;
+
+    #[test]
+    fn test_table_rows_align_with_header() {
+        let kline = Kline {
+            open_price: 42000.5,
+            high_price: 42100.0,
+            low_price: 41900.0,
+            volume: 12.5,
+            turnover: 525000.0,
+            ..test_kline(1704067200000, 42050.0)
+        };
+        let renderer = TableRenderer {
+            price_decimals: 1,
+            volume_decimals: 2,
+            human_numbers: Some(HumanNumbers::Separators),
+            show_kline_type: true,
+            ..Default::default()
+        };
+
+        let mut header = Vec::new();
+        renderer.write_header(&mut header).unwrap();
+        let header = String::from_utf8(header).unwrap();
+        let mut row = Vec::new();
+        renderer.write_row(&mut row, &kline).unwrap();
+        let row = String::from_utf8(row).unwrap();
+
+        let titles = header.lines().next().unwrap();
+        let rule = header.lines().nth(1).unwrap().len();
+        assert!(row.starts_with("trade "));
+        assert_eq!(titles.find("Time"), row.find("2024-01-01"));
+        assert_eq!(titles.find("Open"), row.find("42000.5"));
+        assert_eq!(titles.find("quote_volume"), row.find("525,000.00"));
+
+        // The close time follows the open time, a bar later
+        let renderer = TableRenderer {
+            close_time: Some(Interval::Min15),
+            ..renderer
+        };
+        let mut header = Vec::new();
+        renderer.write_header(&mut header).unwrap();
+        let header = String::from_utf8(header).unwrap();
+        let mut row = Vec::new();
+        renderer.write_row(&mut row, &kline).unwrap();
+        let row = String::from_utf8(row).unwrap();
+        let titles = header.lines().next().unwrap();
+        assert_eq!(titles.find("Close Time"), row.find("2024-01-01 00:15:00 UTC"));
+        assert_eq!(header.lines().nth(1).unwrap().len(), rule + 24);
+        assert_eq!(titles.find("Open"), row.find("42000.5"));
+    }
+}
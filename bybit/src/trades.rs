@@ -0,0 +1,211 @@
+use crate::{barter::BarterSide, category::Category, BybitClient, BybitError};
+use serde::Deserialize;
+
+/// Entry of `/v5/market/recent-trade`, prices and sizes as strings.
+#[derive(Debug, Deserialize)]
+struct RawTrade {
+    #[serde(rename = "execId")]
+    exec_id: String,
+    price: String,
+    size: String,
+    side: String,
+    time: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct RecentTrades {
+    list: Vec<RawTrade>,
+}
+
+/// Public trade as reported by Bybit.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PublicTrade {
+    pub id: String,
+    /// Unix millis.
+    pub time: u64,
+    pub price: f64,
+    /// Base coin amount, contracts for inverse.
+    pub size: f64,
+    /// Taker side.
+    pub side: BarterSide,
+}
+
+impl TryFrom<RawTrade> for PublicTrade {
+    type Error = BybitError;
+
+    fn try_from(raw: RawTrade) -> Result<Self, Self::Error> {
+        let invalid = || BybitError::ApiError {
+            msg: format!("Invalid trade {}: {} {} at {}", raw.exec_id, raw.size, raw.price, raw.time),
+        };
+        Ok(Self {
+            time: raw.time.parse().map_err(|_| invalid())?,
+            price: raw.price.parse().map_err(|_| invalid())?,
+            size: raw.size.parse().map_err(|_| invalid())?,
+            side: match raw.side.as_str() {
+                "Buy" => BarterSide::Buy,
+                "Sell" => BarterSide::Sell,
+                _ => return Err(invalid()),
+            },
+            id: raw.exec_id,
+        })
+    }
+}
+
+/// Most trades `/v5/market/recent-trade` returns for a category, which is also all of them:
+/// the endpoint has no time range or cursor.
+pub fn recent_trade_limit(category: Category) -> u32 {
+    match category {
+        Category::Spot => 60,
+        _ => 1000,
+    }
+}
+
+/// Order flow of a bar aggregated from its public trades.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TradeSummary {
+    /// Volume of trades with a buying taker.
+    pub buy_volume: f64,
+    /// Volume of trades with a selling taker.
+    pub sell_volume: f64,
+    pub trade_count: u64,
+    /// Volume weighted average price, `None` for a bar without trades.
+    pub vwap: Option<f64>,
+}
+
+/// Recent trades of a symbol, aggregated into [`TradeSummary`]s for the bars they cover.
+#[derive(Debug, Clone, Default)]
+pub struct TradeSummaries {
+    /// Ascending by time.
+    trades: Vec<PublicTrade>,
+    /// Earliest bar start fully covered by the trades, later bars without any are summarised
+    /// as empty.
+    covered_from: u64,
+}
+
+impl TradeSummaries {
+    /// `complete` if `trades` holds every trade Bybit has, ie/ fewer than the endpoint's limit
+    /// were returned. Otherwise older trades were cut off, and the bar of the oldest trade is
+    /// partial so only bars starting after it are covered.
+    pub fn new(mut trades: Vec<PublicTrade>, complete: bool) -> Self {
+        trades.sort_by_key(|trade| trade.time);
+        let covered_from = match (complete, trades.first()) {
+            (false, Some(oldest)) => oldest.time + 1,
+            (false, None) => u64::MAX,
+            (true, _) => 0,
+        };
+        Self {
+            trades,
+            covered_from,
+        }
+    }
+
+    /// Earliest bar start a summary is available for.
+    pub fn covered_from(&self) -> u64 {
+        self.covered_from
+    }
+
+    /// Summary of the trades from `start` (inclusive) to `end` (exclusive), `None` if the range
+    /// isn't fully covered.
+    pub fn summary(&self, start: u64, end: u64) -> Option<TradeSummary> {
+        if start < self.covered_from {
+            return None;
+        }
+        let from = self.trades.partition_point(|trade| trade.time < start);
+        let to = self.trades.partition_point(|trade| trade.time < end);
+        let mut summary = TradeSummary::default();
+        let mut notional = 0.0;
+        for trade in &self.trades[from..to] {
+            match trade.side {
+                BarterSide::Buy => summary.buy_volume += trade.size,
+                BarterSide::Sell => summary.sell_volume += trade.size,
+            }
+            summary.trade_count += 1;
+            notional += trade.price * trade.size;
+        }
+        let volume = summary.buy_volume + summary.sell_volume;
+        summary.vwap = (volume > 0.0).then(|| notional / volume);
+        Some(summary)
+    }
+}
+
+impl BybitClient {
+    /// The most recent public trades of a symbol, newest first, in a single request of
+    /// [`recent_trade_limit`] trades. Bybit's REST API serves no older trades.
+    pub async fn get_recent_trades(
+        &self,
+        symbol: &str,
+        category: Category,
+    ) -> Result<Vec<PublicTrade>, BybitError> {
+        let limit = recent_trade_limit(category).to_string();
+        self.get::<RecentTrades>(
+            "/v5/market/recent-trade",
+            &[
+                ("category", category.as_str()),
+                ("symbol", symbol),
+                ("limit", limit.as_str()),
+            ],
+        )
+        .await?
+        .list
+        .into_iter()
+        .map(PublicTrade::try_from)
+        .collect()
+    }
+
+    /// Recent trades of a symbol as [`TradeSummaries`].
+    pub async fn get_trade_summaries(
+        &self,
+        symbol: &str,
+        category: Category,
+    ) -> Result<TradeSummaries, BybitError> {
+        let trades = self.get_recent_trades(symbol, category).await?;
+        let complete = trades.len() < recent_trade_limit(category) as usize;
+        Ok(TradeSummaries::new(trades, complete))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_server::{self, MockResponse};
+
+    #[tokio::test]
+    async fn test_trade_summaries() {
+        let base_url = mock_server::serve(|request| {
+            assert_eq!(mock_server::query_param(request, "limit"), Some("60"));
+            let trade = |id: u32, time: u64, price: &str, size: &str, side: &str| {
+                format!(
+                    r#"{{"execId":"{}","symbol":"BTCUSDT","price":"{}","size":"{}","side":"{}","time":"{}","isBlockTrade":false}}"#,
+                    id, price, size, side, time
+                )
+            };
+            // Newest first, like Bybit
+            MockResponse::ok(&format!(
+                r#"{{"category":"spot","list":[{},{},{}]}}"#,
+                trade(3, 125_000, "103", "1", "Sell"),
+                trade(2, 70_000, "101", "3", "Buy"),
+                trade(1, 65_000, "100", "1", "Buy")
+            ))
+        })
+        .await;
+        let client = BybitClient::new(false, 1)
+            .unwrap()
+            .with_base_url(base_url)
+            .with_preflight(false);
+
+        let summaries = client.get_trade_summaries("BTCUSDT", Category::Spot).await.unwrap();
+        assert_eq!(summaries.covered_from(), 0);
+        assert_eq!(
+            summaries.summary(60_000, 120_000),
+            Some(TradeSummary { buy_volume: 4.0, sell_volume: 0.0, trade_count: 2, vwap: Some(100.75) })
+        );
+        assert_eq!(summaries.summary(120_000, 180_000).unwrap().sell_volume, 1.0);
+        assert_eq!(summaries.summary(0, 60_000), Some(TradeSummary::default()));
+
+        // With older trades cut off, the bar of the oldest trade returned is partial
+        let trades = client.get_recent_trades("BTCUSDT", Category::Spot).await.unwrap();
+        let partial = TradeSummaries::new(trades, false);
+        assert_eq!(partial.summary(60_000, 120_000), None);
+        assert!(partial.summary(120_000, 180_000).is_some());
+    }
+}
@@ -0,0 +1,400 @@
+use crate::{
+    category::Category,
+    gaps::{self, Gap},
+    interval::Interval,
+    kline::{self, Kline, KlineType},
+    ohlcv_binary,
+    output::{Delimited, TimestampUnit},
+    BybitError,
+};
+use std::{
+    fmt,
+    fs::File,
+    io::{BufRead, BufReader},
+    path::Path,
+};
+
+/// Issues listed in a [`FileReport`], the rest are only counted.
+const MAX_LISTED: usize = 20;
+
+/// Problem found in a kline file by [`validate_file`], with its 1 based line number.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Issue {
+    /// A row which doesn't parse as a kline.
+    Malformed { line: usize, message: String },
+    /// A bar breaking an OHLC invariant, see [`Kline::ohlc_violation`].
+    Ohlc {
+        line: usize,
+        start_time: u64,
+        problem: &'static str,
+    },
+    /// A bar at or before the one preceding it.
+    NotMonotonic {
+        line: usize,
+        previous: u64,
+        next: u64,
+    },
+    /// Bars missing before the one on `line`.
+    Gap { line: usize, gap: Gap, bars: u64 },
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Issue::Malformed { line, message } => write!(f, "line {}: malformed row, {}", line, message),
+            Issue::Ohlc { line, start_time, problem } => write!(f, "line {}: {}: {}", line, start_time, problem),
+            Issue::NotMonotonic { line, previous, next } => {
+                write!(f, "line {}: {} follows {}, out of order or duplicate", line, next, previous)
+            }
+            Issue::Gap { line, gap, bars } => {
+                write!(f, "line {}: {} missing bars from {} to {}", line, bars, gap.start, gap.end)
+            }
+        }
+    }
+}
+
+/// Outcome of [`validate_file`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FileReport {
+    pub rows: usize,
+    /// Start times of the first and last bars read.
+    pub first: Option<u64>,
+    pub last: Option<u64>,
+    pub issues: Vec<Issue>,
+}
+
+impl FileReport {
+    pub fn passed(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    fn count(&self, matches: impl Fn(&Issue) -> bool) -> usize {
+        self.issues.iter().filter(|issue| matches(issue)).count()
+    }
+}
+
+impl fmt::Display for FileReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Rows: {}", self.rows)?;
+        if let (Some(first), Some(last)) = (self.first, self.last) {
+            write!(f, " ({} to {})", first, last)?;
+        }
+        let missing = self
+            .issues
+            .iter()
+            .map(|issue| match issue {
+                Issue::Gap { bars, .. } => *bars,
+                _ => 0,
+            })
+            .sum::<u64>();
+        writeln!(f)?;
+        writeln!(f, "Malformed rows: {}", self.count(|issue| matches!(issue, Issue::Malformed { .. })))?;
+        writeln!(f, "OHLC violations: {}", self.count(|issue| matches!(issue, Issue::Ohlc { .. })))?;
+        writeln!(f, "Out of order or duplicate bars: {}", self.count(|issue| matches!(issue, Issue::NotMonotonic { .. })))?;
+        write!(f, "Gaps: {} ({} missing bars)", self.count(|issue| matches!(issue, Issue::Gap { .. })), missing)?;
+        for issue in self.issues.iter().take(MAX_LISTED) {
+            write!(f, "\n  {}", issue)?;
+        }
+        if self.issues.len() > MAX_LISTED {
+            write!(f, "\n  ... and {} more", self.issues.len() - MAX_LISTED)?;
+        }
+        Ok(())
+    }
+}
+
+/// Columns of a csv or tsv file, located by their header names.
+struct Columns {
+    format: Delimited,
+    start_time: usize,
+    /// Open, high, low and close.
+    prices: [usize; 4],
+    base_volume: Option<usize>,
+    quote_volume: Option<usize>,
+    /// Rendering of `start_time`, detected from the first row.
+    unit: Option<TimestampUnit>,
+}
+
+impl Columns {
+    fn from_header(header: &str) -> Result<Self, BybitError> {
+        let format = match header.contains('\t') {
+            true => Delimited::Tsv,
+            false => Delimited::Csv,
+        };
+        let names = format.parse_row(header);
+        let column = |name: &str| names.iter().position(|column| column == name);
+        let required = |name: &str| {
+            column(name).ok_or_else(|| {
+                BybitError::ArgumentError(format!("Not a kline csv or tsv file, no {} column", name))
+            })
+        };
+        Ok(Self {
+            format,
+            start_time: required("start_time")?,
+            prices: [required("open")?, required("high")?, required("low")?, required("close")?],
+            base_volume: column("base_volume"),
+            quote_volume: column("quote_volume"),
+            unit: None,
+        })
+    }
+
+    fn parse(&mut self, row: &str, category: Category) -> Result<Kline, String> {
+        let fields = self.format.parse_row(row);
+        let field = |index: usize| fields.get(index).map(String::as_str).ok_or("missing fields");
+        let number = |index: usize| {
+            field(index)?
+                .parse::<f64>()
+                .map_err(|_| format!("invalid number {:?}", fields[index]))
+        };
+
+        let start_time = self.start_time(field(self.start_time)?)?;
+        let volume = |index: Option<usize>| index.map(number).unwrap_or(Ok(0.0));
+        let (base_volume, quote_volume) = (volume(self.base_volume)?, volume(self.quote_volume)?);
+        // Inverse klines carry quote volume in `volume` and base in `turnover`
+        let (volume, turnover) = match category {
+            Category::Inverse => (quote_volume, base_volume),
+            _ => (base_volume, quote_volume),
+        };
+        Ok(Kline {
+            start_time,
+            open_price: number(self.prices[0])?,
+            high_price: number(self.prices[1])?,
+            low_price: number(self.prices[2])?,
+            close_price: number(self.prices[3])?,
+            volume,
+            turnover,
+            kline_type: KlineType::Trade,
+            category,
+            filled: None,
+        })
+    }
+
+    /// Parse a `start_time` field, in the unit detected from the first one.
+    fn start_time(&mut self, start_time: &str) -> Result<u64, String> {
+        let unit = *self.unit.get_or_insert_with(|| detect_unit(start_time));
+        unit.parse(start_time)
+            .ok_or_else(|| format!("invalid start_time {:?}", start_time))
+    }
+
+    /// Parse only the `start_time` of a row.
+    fn row_start_time(&mut self, row: &str) -> Result<u64, String> {
+        let fields = self.format.parse_row(row);
+        let start_time = fields.get(self.start_time).ok_or("missing fields")?;
+        self.start_time(start_time)
+    }
+}
+
+/// `--timestamp-unit` a `start_time` was written in: RFC 3339, or unix seconds, millis or
+/// nanos told apart by magnitude.
+fn detect_unit(start_time: &str) -> TimestampUnit {
+    match start_time.len() {
+        _ if start_time.contains('T') => TimestampUnit::Rfc3339,
+        0..=11 => TimestampUnit::Seconds,
+        12..=14 => TimestampUnit::Millis,
+        _ => TimestampUnit::Nanos,
+    }
+}
+
+/// Validate a kline file written by this tool, streaming it row by row: kline-json, or csv or
+/// tsv with any `--timestamp-unit`. Every bar is checked against the OHLC invariants, for
+/// ascending start times without duplicates and for gaps at `interval`.
+///
+/// Csv and tsv files don't record their category, `category` only decides how their base and
+/// quote volumes map onto the kline's raw fields. Read errors fail, problems with the content
+/// are collected in the report.
+pub fn validate_file<R: BufRead>(
+    reader: R,
+    interval: Interval,
+    category: Category,
+) -> Result<FileReport, BybitError> {
+    let mut report = FileReport::default();
+    let mut columns: Option<Columns> = None;
+    let mut json = false;
+    // The previous bar and the current one, checked as a pair
+    let mut pair = Vec::with_capacity(2);
+
+    for (index, row) in reader.lines().enumerate() {
+        let row = row?;
+        let line = index + 1;
+        if row.trim().is_empty() {
+            continue;
+        }
+        if report.rows == 0 && columns.is_none() && !json {
+            json = row.starts_with('{');
+            if !json {
+                columns = Some(Columns::from_header(&row)?);
+                continue;
+            }
+        }
+
+        let parsed = match columns.as_mut() {
+            Some(columns) => columns.parse(&row, category),
+            None => serde_json::from_str::<Kline>(&row).map_err(|error| error.to_string()),
+        };
+        report.rows += 1;
+        let kline = match parsed {
+            Ok(kline) => kline,
+            Err(message) => {
+                report.issues.push(Issue::Malformed { line, message });
+                continue;
+            }
+        };
+
+        if let Some(problem) = kline.ohlc_violation() {
+            report.issues.push(Issue::Ohlc {
+                line,
+                start_time: kline.start_time,
+                problem,
+            });
+        }
+        report.first.get_or_insert(kline.start_time);
+        report.last = Some(kline.start_time);
+
+        pair.push(kline);
+        if pair.len() == 2 {
+            if let Err(BybitError::NotMonotonic { previous, next }) = kline::validate_monotonic(&pair) {
+                report.issues.push(Issue::NotMonotonic { line, previous, next });
+            }
+            for gap in gaps::detect_gaps(&pair, interval) {
+                let bars = gap.bars(interval);
+                report.issues.push(Issue::Gap { line, gap, bars });
+            }
+            pair.remove(0);
+        }
+    }
+    Ok(report)
+}
+
+/// Start time of the latest bar in a kline file written by this tool, for
+/// `--since-last-bar-of`: csv or tsv with any `--timestamp-unit`, kline-json or ohlcv-binary,
+/// told apart by their first bytes. The file is streamed, and bars needn't be in order.
+///
+/// Fails on a file which can't be read or has a row that doesn't parse, naming the line, and
+/// on a file without any bars, since there would be nothing to resume after.
+pub fn last_bar_start(path: &Path) -> Result<u64, BybitError> {
+    let context = |error: BybitError| {
+        let detail = match error {
+            BybitError::ArgumentError(detail) => detail,
+            error => error.to_string(),
+        };
+        BybitError::ArgumentError(format!("Can't read the last bar of {}: {}", path.display(), detail))
+    };
+    let mut reader = BufReader::new(File::open(path).map_err(|error| context(error.into()))?);
+    let binary = reader
+        .fill_buf()
+        .map_err(|error| context(error.into()))?
+        .starts_with(&ohlcv_binary::MAGIC);
+    let latest = match binary {
+        true => ohlcv_binary::latest_start_time(reader),
+        false => latest_start_time(reader),
+    };
+    latest.map_err(context)?.ok_or_else(|| context(BybitError::ArgumentError("the file holds no bars".to_string())))
+}
+
+/// Latest `start_time` of a csv, tsv or kline-json file, `None` if it has no rows.
+fn latest_start_time<R: BufRead>(reader: R) -> Result<Option<u64>, BybitError> {
+    let mut columns: Option<Columns> = None;
+    let mut json = false;
+    let mut latest = None;
+    for (index, row) in reader.lines().enumerate() {
+        let row = row?;
+        if row.trim().is_empty() {
+            continue;
+        }
+        if latest.is_none() && columns.is_none() && !json {
+            json = row.starts_with('{');
+            if !json {
+                columns = Some(Columns::from_header(&row)?);
+                continue;
+            }
+        }
+        let start_time = match columns.as_mut() {
+            Some(columns) => columns.row_start_time(&row),
+            None => serde_json::from_str::<Kline>(&row)
+                .map(|kline| kline.start_time)
+                .map_err(|error| error.to_string()),
+        };
+        let start_time = start_time.map_err(|message| {
+            BybitError::ArgumentError(format!("line {}: malformed row, {}", index + 1, message))
+        })?;
+        latest = latest.max(Some(start_time));
+    }
+    Ok(latest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_file() {
+        let csv = "start_time,time,open,high,low,close,base_volume,quote_volume\n\
+                   2024-01-01T00:00:00.000Z,2024-01-01 00:00:00 UTC,1,2,0.5,1.5,10,15\n\
+                   2024-01-01T00:01:00.000Z,2024-01-01 00:01:00 UTC,1.5,1.4,1,1.2,10,15\n\
+                   2024-01-01T00:04:00.000Z,2024-01-01 00:04:00 UTC,1.2,1.3,1.1,1.2,10,15\n\
+                   2024-01-01T00:04:00.000Z,2024-01-01 00:04:00 UTC,1.2,1.3,1.1,1.2,10,15\n\
+                   2024-01-01T00:05:00.000Z,2024-01-01 00:05:00 UTC,1.2,oops,1.1,1.2,10,15\n";
+        let report = validate_file(csv.as_bytes(), Interval::Min1, Category::Linear).unwrap();
+        assert_eq!(report.rows, 5);
+        assert_eq!((report.first, report.last), (Some(1704067200000), Some(1704067440000)));
+        assert_eq!(
+            report.issues,
+            vec![
+                Issue::Ohlc { line: 3, start_time: 1704067260000, problem: "high below open or close" },
+                Issue::Gap { line: 4, gap: Gap { start: 1704067320000, end: 1704067440000 }, bars: 2 },
+                Issue::NotMonotonic { line: 5, previous: 1704067440000, next: 1704067440000 },
+                Issue::Malformed { line: 6, message: "invalid number \"oops\"".to_string() },
+            ]
+        );
+        assert!(report.to_string().contains("Gaps: 1 (2 missing bars)"));
+
+        // kline-json output validates the same way
+        let ndjson = r#"{"start_time":0,"open_price":1.0,"high_price":2.0,"low_price":0.5,"close_price":1.5,"volume":10.0,"turnover":15.0,"kline_type":"trade","category":"linear"}
+{"start_time":60000,"open_price":1.5,"high_price":2.0,"low_price":0.5,"close_price":1.0,"volume":10.0,"turnover":15.0,"kline_type":"trade","category":"linear"}
+"#;
+        let report = validate_file(ndjson.as_bytes(), Interval::Min1, Category::Linear).unwrap();
+        assert_eq!(report.rows, 2);
+        assert!(report.passed());
+    }
+
+    #[test]
+    fn test_last_bar_start_of_each_format() {
+        let dir = std::env::temp_dir().join(format!("bybit-kline-last-bar-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let last_bar_start = |name: &str, contents: &[u8]| {
+            let path = dir.join(name);
+            std::fs::write(&path, contents).unwrap();
+            super::last_bar_start(&path)
+        };
+
+        // Bars needn't be in order, the latest start time wins
+        let csv = "start_time,time,open,high,low,close,base_volume,quote_volume\n\
+                   1704067260000,2024-01-01 00:01:00 UTC,1,2,0.5,1.5,10,15\n\
+                   1704067200000,2024-01-01 00:00:00 UTC,1,2,0.5,1.5,10,15\n";
+        assert_eq!(last_bar_start("out.csv", csv.as_bytes()).unwrap(), 1704067260000);
+        let tsv = "start_time\ttime\topen\thigh\tlow\tclose\n\
+                   2024-01-01T00:00:00.000Z\t2024-01-01 00:00:00 UTC\t1\t2\t0.5\t1.5\n\
+                   2024-01-01T00:15:00.000Z\t2024-01-01 00:15:00 UTC\t1\t2\t0.5\t1.5\n";
+        assert_eq!(last_bar_start("out.tsv", tsv.as_bytes()).unwrap(), 1704068100000);
+        let row = r#"{"start_time":60000,"open_price":1.0,"high_price":2.0,"low_price":0.5,"close_price":1.5,"volume":10.0,"turnover":15.0,"kline_type":"trade","category":"linear"}"#;
+        let ndjson = format!("{}\n{}\n", row, row.replace("60000", "120000"));
+        assert_eq!(last_bar_start("out.jsonl", ndjson.as_bytes()).unwrap(), 120000);
+        let mut binary = Vec::new();
+        ohlcv_binary::write_header(&mut binary).unwrap();
+        for start_time in [180_000, 240_000] {
+            let kline = Kline { start_time, ..serde_json::from_str(row).unwrap() };
+            ohlcv_binary::write_record(&mut binary, &kline).unwrap();
+        }
+        assert_eq!(last_bar_start("out.bin", &binary).unwrap(), 240_000);
+
+        // Missing, empty and unparseable files fail, naming the problem
+        let error = |result: Result<u64, BybitError>| result.unwrap_err().to_string();
+        assert!(error(super::last_bar_start(&dir.join("missing.csv"))).contains("missing.csv"));
+        assert!(error(last_bar_start("empty.csv", b"")).contains("holds no bars"));
+        assert!(error(last_bar_start("header.csv", csv.lines().next().unwrap().as_bytes())).contains("holds no bars"));
+        assert!(error(last_bar_start("bad.csv", b"start_time,open,high,low,close\noops,1,2,0.5,1.5\n")).contains("line 2"));
+        assert!(error(last_bar_start("bad.jsonl", b"{\"start_time\":\n")).contains("line 1"));
+        assert!(error(last_bar_start("bad.bin", &binary[..binary.len() - 1])).contains("truncated"));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
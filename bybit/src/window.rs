@@ -0,0 +1,300 @@
+use crate::{interval::Interval, kline::Kline};
+use clap::ValueEnum;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// How a fetch pages through its range.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Pagination {
+    /// Walk forward in windows of `limit` bars' duration from the start of the range.
+    #[default]
+    Time,
+    /// Walk backward from the end of the range, requesting `limit` bars ending before the
+    /// oldest bar received so far, like the Bybit UI. Doesn't depend on interval durations,
+    /// so isn't thrown off by calendar aligned intervals or very large windows.
+    Count,
+}
+
+/// Half-open `[start, end)` request window, in milliseconds since the epoch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Window {
+    pub start: u64,
+    pub end: u64,
+}
+
+/// Completed request windows mapped to the klines each one contributed.
+///
+/// Merging through this map makes window completion idempotent: if a request is re-issued for
+/// a window that already completed (eg/ a retry after a timeout that actually succeeded
+/// server-side), its klines are not merged a second time.
+#[derive(Debug, Default)]
+pub struct CompletedWindows {
+    windows: BTreeMap<Window, Vec<Kline>>,
+    len: usize,
+    /// Latest kline of the latest window which returned any data, kept when windows are drained.
+    last: Option<(Window, Kline)>,
+}
+
+impl CompletedWindows {
+    /// Record the klines fetched for a window.
+    ///
+    /// Returns `false` and discards the klines if the window was already completed.
+    pub fn complete(&mut self, window: Window, klines: Vec<Kline>) -> bool {
+        if self.windows.contains_key(&window) {
+            return false;
+        }
+        self.len += klines.len();
+        // Pages kept in Bybit's order are newest first, so find the latest rather than the last
+        if let Some(kline) = klines.iter().max_by_key(|kline| kline.start_time) {
+            if self.last.as_ref().is_none_or(|(last, _)| window > *last) {
+                self.last = Some((window, kline.clone()));
+            }
+        }
+        self.windows.insert(window, klines);
+        true
+    }
+
+    pub fn is_complete(&self, window: &Window) -> bool {
+        self.windows.contains_key(window)
+    }
+
+    /// Total number of klines across every completed window.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Latest kline of the latest window which returned any data.
+    pub fn last(&self) -> Option<&Kline> {
+        self.last.as_ref().map(|(_, kline)| kline)
+    }
+
+    /// Take the klines of every completed window, ordered by window, while still remembering
+    /// which windows completed so they aren't merged again.
+    pub fn drain(&mut self) -> Vec<Kline> {
+        self.windows.values_mut().flat_map(std::mem::take).collect()
+    }
+}
+
+/// `max_records` value removing the cap, so a fetch runs to the end of its range.
+pub const UNLIMITED_RECORDS: u32 = 0;
+
+/// Effective record cap of a `max_records` setting, see [`UNLIMITED_RECORDS`].
+pub fn record_cap(max_records: u32) -> u32 {
+    match max_records {
+        UNLIMITED_RECORDS => u32::MAX,
+        max_records => max_records,
+    }
+}
+
+/// Expected size of a fetch, computed from the range alone without calling the API.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FetchEstimate {
+    /// Bars in the range, capped at the record limit.
+    pub records: u64,
+    /// API calls needed to page through those bars.
+    pub requests: u64,
+}
+
+impl FetchEstimate {
+    /// Estimate a fetch of `[start, end)`.
+    ///
+    /// This is an upper bound: ranges before the listing date or with exchange downtime return
+    /// fewer bars, and monthly bars are approximated as 30 days.
+    pub fn new(
+        start: u64,
+        end: u64,
+        interval: Interval,
+        max_records: u32,
+        limit_per_request: u32,
+    ) -> Self {
+        let records = bar_count(start, end, interval).min(record_cap(max_records) as u64);
+        Self {
+            records,
+            requests: records.div_ceil(limit_per_request.max(1) as u64),
+        }
+    }
+}
+
+/// Seconds a planned request is expected to take: the 100ms pause between pages plus a typical
+/// round trip to Bybit. `--benchmark` measures the real figure under current conditions.
+pub const ESTIMATED_SECONDS_PER_REQUEST: f64 = 0.35;
+
+/// A single request of a [`FetchPlan`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct PlannedChunk {
+    /// Window of the request in unix millis, half-open `[start, end)`.
+    pub start: u64,
+    pub end: u64,
+    pub limit: u32,
+}
+
+/// The requests [`Pagination::Time`] makes for a fetch, computed from the range alone, for
+/// `--plan-json`. Each chunk is an independent window, so a scheduler can spread them across
+/// workers.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FetchPlan {
+    pub chunks: Vec<PlannedChunk>,
+    pub total_chunks: usize,
+    pub estimated_bars: u64,
+    pub estimated_seconds: f64,
+}
+
+impl FetchPlan {
+    /// Plan a fetch of `[start, end)` assuming every window returns all its bars, so like
+    /// [`FetchEstimate`] it's an upper bound, and monthly windows are approximated as 30 days.
+    pub fn new(start: u64, end: u64, interval: Interval, max_records: u32, limit_per_request: u32) -> Self {
+        let mut chunks = Vec::new();
+        let mut remaining = bar_count(start, end, interval).min(record_cap(max_records) as u64);
+        let estimated_bars = remaining;
+        let mut chunk_start = start;
+        while remaining > 0 {
+            let limit = (limit_per_request.max(1) as u64).min(remaining);
+            let chunk_end = (chunk_start + limit * interval.duration_ms()).min(end);
+            chunks.push(PlannedChunk {
+                start: chunk_start,
+                end: chunk_end,
+                limit: limit as u32,
+            });
+            remaining -= limit;
+            chunk_start = chunk_end;
+        }
+        Self {
+            total_chunks: chunks.len(),
+            estimated_seconds: (chunks.len() as f64 * ESTIMATED_SECONDS_PER_REQUEST * 100.0).round() / 100.0,
+            chunks,
+            estimated_bars,
+        }
+    }
+}
+
+/// Bars of `interval` starting in `[start, end)`, counting a partial trailing bar and
+/// approximating months as 30 days.
+pub fn bar_count(start: u64, end: u64, interval: Interval) -> u64 {
+    end.saturating_sub(start).div_ceil(interval.duration_ms())
+}
+
+/// Finest interval whose bar count over `[start, end)` fits within `max_records`, for
+/// `--interval-auto`, with that bar count. `None` if even monthly bars exceed the cap.
+pub fn finest_interval_within(start: u64, end: u64, max_records: u32) -> Option<(Interval, u64)> {
+    Interval::all()
+        .iter()
+        .map(|&interval| (interval, bar_count(start, end, interval)))
+        .find(|&(_, bars)| bars <= record_cap(max_records) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kline::test_kline;
+
+    #[test]
+    fn test_completed_windows_same_window_included_once() {
+        let mut completed = CompletedWindows::default();
+        let window = Window { start: 0, end: 3 };
+
+        assert!(completed.complete(window, vec![test_kline(0, 1.0), test_kline(1, 1.0), test_kline(2, 1.0)]));
+
+        // Retry of the same window after a timeout that succeeded server-side
+        assert!(!completed.complete(window, vec![test_kline(0, 1.0), test_kline(1, 1.0), test_kline(2, 1.0)]));
+
+        assert!(completed.complete(Window { start: 3, end: 5 }, vec![test_kline(3, 1.0), test_kline(4, 1.0)]));
+
+        assert_eq!(completed.len(), 5);
+        assert_eq!(completed.last().map(|k| k.start_time), Some(4));
+        assert_eq!(
+            completed
+                .drain()
+                .iter()
+                .map(|k| k.start_time)
+                .collect::<Vec<_>>(),
+            vec![0, 1, 2, 3, 4]
+        );
+    }
+
+    #[test]
+    fn test_completed_windows_drain_keeps_completion() {
+        let mut completed = CompletedWindows::default();
+        let window = Window { start: 0, end: 2 };
+
+        assert!(completed.complete(window, vec![test_kline(0, 1.0), test_kline(1, 1.0)]));
+        assert_eq!(completed.drain().len(), 2);
+        assert!(completed.drain().is_empty());
+
+        // Drained windows are still complete, and the latest kline is remembered
+        assert!(!completed.complete(window, vec![test_kline(0, 1.0), test_kline(1, 1.0)]));
+        assert_eq!(completed.last().map(|k| k.start_time), Some(1));
+        assert_eq!(completed.len(), 2);
+    }
+
+    #[test]
+    fn test_fetch_plan_matches_estimate() {
+        let day = Interval::Day.duration_ms();
+        let plan = FetchPlan::new(0, day, Interval::Min1, UNLIMITED_RECORDS, 1000);
+        assert_eq!(
+            plan.chunks,
+            vec![
+                PlannedChunk { start: 0, end: 60_000_000, limit: 1000 },
+                PlannedChunk { start: 60_000_000, end: day, limit: 440 },
+            ]
+        );
+        let estimate = FetchEstimate::new(0, day, Interval::Min1, UNLIMITED_RECORDS, 1000);
+        assert_eq!((plan.estimated_bars, plan.total_chunks as u64), (estimate.records, estimate.requests));
+
+        // A record cap ends the plan early, with a short final chunk
+        let plan = FetchPlan::new(0, day, Interval::Min1, 250, 100);
+        let limits = plan.chunks.iter().map(|chunk| chunk.limit).collect::<Vec<_>>();
+        assert_eq!(limits, vec![100, 100, 50]);
+        assert_eq!(plan.chunks[2].end, 250 * 60_000);
+        assert!(FetchPlan::new(day, day, Interval::Min1, 1000, 1000).chunks.is_empty());
+    }
+
+    #[test]
+    fn test_finest_interval_within() {
+        let day = Interval::Day.duration_ms();
+
+        // 30 days is 8640 5 minute bars, 2880 15 minute bars
+        assert_eq!(finest_interval_within(0, 30 * day, 5000), Some((Interval::Min15, 2880)));
+        // A bar count exactly at the cap fits
+        assert_eq!(finest_interval_within(0, 30 * day, 2880), Some((Interval::Min15, 2880)));
+        assert_eq!(finest_interval_within(0, 30 * day, 2879), Some((Interval::Min30, 1440)));
+        assert_eq!(finest_interval_within(0, 30 * day, UNLIMITED_RECORDS), Some((Interval::Min1, 43_200)));
+        assert_eq!(finest_interval_within(0, 3650 * day, 100), None);
+    }
+
+    #[test]
+    fn test_fetch_estimate() {
+        let day = Interval::Day.duration_ms();
+
+        // 1 day of 1 minute bars
+        let estimate = FetchEstimate::new(0, day, Interval::Min1, u32::MAX, 1000);
+        assert_eq!(
+            estimate,
+            FetchEstimate {
+                records: 1440,
+                requests: 2
+            }
+        );
+
+        // Capped by max records, with a smaller page size
+        let estimate = FetchEstimate::new(0, day, Interval::Min1, 1000, 200);
+        assert_eq!(
+            estimate,
+            FetchEstimate {
+                records: 1000,
+                requests: 5
+            }
+        );
+
+        // No cap with unlimited records
+        let estimate = FetchEstimate::new(0, day, Interval::Min1, UNLIMITED_RECORDS, 1000);
+        assert_eq!(estimate.records, 1440);
+
+        // A partial trailing bar still costs a record
+        let estimate = FetchEstimate::new(0, day + 1, Interval::Hour1, 1000, 1000);
+        assert_eq!(estimate.records, 25);
+    }
+}
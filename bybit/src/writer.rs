@@ -0,0 +1,442 @@
+use crate::{
+    barter::{BarterMarketStreamEvent, BarterOptions, TradeWalk},
+    interval::Interval,
+    kline::{Kline, PriceField},
+    output::{self, Delimited, OutputSink, TimestampUnit},
+    table::TableRenderer,
+    trades::TradeSummaries,
+    BybitError,
+};
+use clap::ValueEnum;
+use std::{io::Write, slice};
+
+/// A text output format, driven the same way whether klines arrive all at once or chunk by
+/// chunk: [`OutputWriter::write_header`] once, [`OutputWriter::write_kline`] per kline in
+/// order, then [`OutputWriter::finish`].
+pub trait OutputWriter {
+    /// Write anything preceding the first kline, eg/ column titles.
+    fn write_header(&mut self, _out: &mut dyn Write) -> Result<(), BybitError> {
+        Ok(())
+    }
+
+    fn write_kline(&mut self, out: &mut dyn Write, kline: &Kline) -> Result<(), BybitError>;
+
+    /// Write anything following the last kline, eg/ totals.
+    fn finish(&mut self, _out: &mut dyn Write) -> Result<(), BybitError> {
+        Ok(())
+    }
+}
+
+/// Write a complete set of klines with `writer`.
+pub fn write_klines(
+    writer: &mut dyn OutputWriter,
+    out: &mut dyn Write,
+    klines: &[Kline],
+) -> Result<(), BybitError> {
+    writer.write_header(out)?;
+    for kline in klines {
+        writer.write_kline(out, kline)?;
+    }
+    writer.finish(out)
+}
+
+/// Write a complete set of klines with `writer` to `out`, split into `--max-file-size` parts
+/// if the sink has them, see [`write_kline_parted`].
+pub fn write_klines_parted(
+    writer: &mut dyn OutputWriter,
+    out: &mut OutputSink,
+    klines: &[Kline],
+) -> Result<(), BybitError> {
+    writer.write_header(out)?;
+    for kline in klines {
+        write_kline_parted(writer, out, kline)?;
+    }
+    writer.finish(out)
+}
+
+/// Write a kline with `writer`, first moving `out` on to its next part if the current one is
+/// full. The full part is finished and the next one gets a header, so every part is a
+/// complete file of its own, overshooting the size by less than a kline and the footer (or a
+/// compression block of a compressed part).
+pub fn write_kline_parted(
+    writer: &mut dyn OutputWriter,
+    out: &mut OutputSink,
+    kline: &Kline,
+) -> Result<(), BybitError> {
+    if out.part_full() {
+        writer.finish(out)?;
+        out.next_part()?;
+        writer.write_header(out)?;
+    }
+    writer.write_kline(out, kline)
+}
+
+/// Human readable table, see [`TableRenderer`].
+#[derive(Debug)]
+pub struct TableWriter {
+    renderer: TableRenderer,
+    /// Number of klines announced in the title, when known up front.
+    expected: Option<usize>,
+    written: usize,
+}
+
+impl TableWriter {
+    pub fn new(renderer: TableRenderer, expected: Option<usize>) -> Self {
+        Self {
+            renderer,
+            expected,
+            written: 0,
+        }
+    }
+}
+
+impl OutputWriter for TableWriter {
+    fn write_header(&mut self, out: &mut dyn Write) -> Result<(), BybitError> {
+        match self.expected {
+            Some(expected) => writeln!(out, "\nReceived {} kline records:\n", expected)?,
+            None => writeln!(out, "\nReceived kline records:\n")?,
+        }
+        Ok(self.renderer.write_header(out)?)
+    }
+
+    fn write_kline(&mut self, out: &mut dyn Write, kline: &Kline) -> Result<(), BybitError> {
+        self.written += 1;
+        Ok(self.renderer.write_row(out, kline)?)
+    }
+
+    fn finish(&mut self, out: &mut dyn Write) -> Result<(), BybitError> {
+        Ok(writeln!(out, "\nTotal records: {}", self.written)?)
+    }
+}
+
+/// CSV or TSV rows, see [`output::write_delimited`].
+#[derive(Debug)]
+pub struct DelimitedWriter {
+    pub format: Delimited,
+    pub timestamp_unit: TimestampUnit,
+    /// Append a `close_time` column, in `timestamp_unit`, for bars of this interval.
+    pub close_time: Option<Interval>,
+}
+
+impl OutputWriter for DelimitedWriter {
+    fn write_header(&mut self, out: &mut dyn Write) -> Result<(), BybitError> {
+        match self.close_time {
+            Some(_) => {
+                let mut columns = output::COLUMNS.to_vec();
+                columns.push("close_time");
+                writeln!(out, "{}", self.format.format_row(&columns))?;
+            }
+            None => output::write_delimited_header(out, self.format)?,
+        }
+        Ok(())
+    }
+
+    fn write_kline(&mut self, out: &mut dyn Write, kline: &Kline) -> Result<(), BybitError> {
+        match self.close_time {
+            Some(interval) => {
+                let mut fields = output::kline_fields(kline, self.timestamp_unit).to_vec();
+                fields.push(self.timestamp_unit.format(kline.close_time(interval)));
+                writeln!(out, "{}", self.format.format_row(&fields))?;
+            }
+            None => output::write_delimited_rows(out, slice::from_ref(kline), self.format, self.timestamp_unit)?,
+        }
+        Ok(())
+    }
+}
+
+/// Two column CSV or TSV of each bar's start time and a single price, for `--price-field`.
+#[derive(Debug)]
+pub struct PriceFieldWriter {
+    pub format: Delimited,
+    pub timestamp_unit: TimestampUnit,
+    pub field: PriceField,
+}
+
+impl OutputWriter for PriceFieldWriter {
+    fn write_header(&mut self, out: &mut dyn Write) -> Result<(), BybitError> {
+        Ok(writeln!(out, "{}", self.format.format_row(&["start_time", self.field.as_str()]))?)
+    }
+
+    fn write_kline(&mut self, out: &mut dyn Write, kline: &Kline) -> Result<(), BybitError> {
+        let fields = [
+            self.timestamp_unit.format(kline.start_time),
+            self.field.value(kline).to_string(),
+        ];
+        Ok(writeln!(out, "{}", self.format.format_row(&fields))?)
+    }
+}
+
+/// Native [`Kline`] ndjson, see [`output::write_kline_json`].
+#[derive(Debug)]
+pub struct KlineJsonWriter;
+
+impl OutputWriter for KlineJsonWriter {
+    fn write_kline(&mut self, out: &mut dyn Write, kline: &Kline) -> Result<(), BybitError> {
+        output::write_kline_json(out, slice::from_ref(kline))
+    }
+}
+
+/// InfluxDB line protocol, see [`output::write_influx_line`].
+#[derive(Debug)]
+pub struct InfluxLineWriter {
+    pub symbol: String,
+    pub precision: TimestampUnit,
+}
+
+impl OutputWriter for InfluxLineWriter {
+    fn write_kline(&mut self, out: &mut dyn Write, kline: &Kline) -> Result<(), BybitError> {
+        output::write_influx_line(out, &self.symbol, slice::from_ref(kline), self.precision)
+    }
+}
+
+/// Flat per-bar JSON lines, see [`output::write_jsonl_flat`].
+#[derive(Debug)]
+pub struct JsonlFlatWriter {
+    pub symbol: String,
+}
+
+impl OutputWriter for JsonlFlatWriter {
+    fn write_kline(&mut self, out: &mut dyn Write, kline: &Kline) -> Result<(), BybitError> {
+        output::write_jsonl_flat(out, &self.symbol, slice::from_ref(kline))
+    }
+}
+
+/// Barter market events as ndjson: a candle per kline, or synthetic trades walking each
+/// kline's OHLC path with `synthetic_trades`.
+#[derive(Debug)]
+pub struct BarterWriter {
+    pub options: BarterOptions,
+    pub interval: Interval,
+    pub synthetic_trades: Option<TradeWalk>,
+    /// Write a candle per kline, `false` to only write its trade summary.
+    pub candles: bool,
+    /// Follow each kline's candle with a trade summary, for the bars the summaries cover.
+    pub trade_summaries: Option<TradeSummaries>,
+    /// Write events in their [`CompactBarterEvent`](crate::barter::CompactBarterEvent) form.
+    pub compact: bool,
+}
+
+impl BarterWriter {
+    /// Write an already built event, eg/ from a merge of several streams.
+    pub fn write_event(
+        &mut self,
+        out: &mut dyn Write,
+        event: &BarterMarketStreamEvent,
+    ) -> Result<(), BybitError> {
+        Ok(writeln!(out, "{}", event.to_json(self.compact)?)?)
+    }
+}
+
+impl OutputWriter for BarterWriter {
+    fn write_kline(&mut self, out: &mut dyn Write, kline: &Kline) -> Result<(), BybitError> {
+        match self.synthetic_trades {
+            Some(walk) => {
+                for event in kline.to_synthetic_trades(self.interval, walk, &self.options) {
+                    self.write_event(out, &event)?;
+                }
+                Ok(())
+            }
+            None => {
+                if self.candles {
+                    let event = kline.to_barter_event(self.interval, &self.options);
+                    self.write_event(out, &event)?;
+                }
+                let end = self.interval.next_start(kline.start_time);
+                let summary = self
+                    .trade_summaries
+                    .as_ref()
+                    .and_then(|summaries| summaries.summary(kline.start_time, end));
+                if let Some(summary) = summary {
+                    let event = kline.to_trade_summary_event(self.interval, &summary, &self.options);
+                    self.write_event(out, &event)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Objects held by `--output-format json-array-file`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum JsonArrayItems {
+    /// Native [`Kline`]s, as in kline-json output.
+    #[default]
+    Kline,
+    /// Barter market events, as in barter output.
+    Barter,
+}
+
+/// A single JSON array of the objects an ndjson writer emits, eg/ [`KlineJsonWriter`] or
+/// [`BarterWriter`], so the whole output loads with `serde_json::from_str::<Vec<_>>`.
+///
+/// Each object is serialized as its kline arrives and written out as an element, one per line,
+/// so memory stays bounded by a single kline however long the array grows.
+pub struct JsonArrayWriter {
+    inner: Box<dyn OutputWriter>,
+    /// The inner writer's ndjson for the current kline.
+    lines: Vec<u8>,
+    written: usize,
+}
+
+impl JsonArrayWriter {
+    pub fn new(inner: Box<dyn OutputWriter>) -> Self {
+        Self {
+            inner,
+            lines: Vec::new(),
+            written: 0,
+        }
+    }
+}
+
+impl OutputWriter for JsonArrayWriter {
+    fn write_header(&mut self, out: &mut dyn Write) -> Result<(), BybitError> {
+        // Each --max-file-size part is an array of its own
+        self.written = 0;
+        Ok(write!(out, "[")?)
+    }
+
+    fn write_kline(&mut self, out: &mut dyn Write, kline: &Kline) -> Result<(), BybitError> {
+        self.lines.clear();
+        self.inner.write_kline(&mut self.lines, kline)?;
+        // A kline can yield several objects, eg/ barter synthetic trades
+        for element in self.lines.split(|&byte| byte == b'\n').filter(|line| !line.is_empty()) {
+            out.write_all(if self.written == 0 { b"\n" } else { b",\n" })?;
+            out.write_all(element)?;
+            self.written += 1;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self, out: &mut dyn Write) -> Result<(), BybitError> {
+        Ok(writeln!(out, "\n]")?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kline::test_kline;
+
+    #[test]
+    fn test_price_field_writer() {
+        let bar = |start_time| Kline {
+            open_price: 1.0,
+            high_price: 2.0,
+            low_price: 0.5,
+            ..test_kline(start_time, 1.5)
+        };
+        let klines = vec![bar(0), bar(60_000)];
+        let mut written = Vec::new();
+        let mut writer = PriceFieldWriter {
+            format: Delimited::Csv,
+            timestamp_unit: TimestampUnit::Seconds,
+            field: PriceField::Ohlc4,
+        };
+        write_klines(&mut writer, &mut written, &klines).unwrap();
+        assert_eq!(String::from_utf8(written).unwrap(), "start_time,ohlc4\n0,1.25\n60,1.25\n");
+        assert!((PriceField::Hlc3.value(&klines[0]) - 4.0 / 3.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_writers_match_buffered_output() {
+        let klines = vec![test_kline(0, 1.5), test_kline(60_000, 1.5)];
+
+        // Driving a writer kline by kline matches the whole-slice helpers
+        let mut written = Vec::new();
+        let mut writer = DelimitedWriter { format: Delimited::Tsv, timestamp_unit: TimestampUnit::Millis, close_time: None };
+        write_klines(&mut writer, &mut written, &klines).unwrap();
+        let mut expected = Vec::new();
+        output::write_delimited(&mut expected, &klines, Delimited::Tsv, TimestampUnit::Millis).unwrap();
+        assert_eq!(written, expected);
+
+        // The close time column matches barter candles' close_time
+        let mut written = Vec::new();
+        let mut writer = DelimitedWriter { format: Delimited::Csv, timestamp_unit: TimestampUnit::Millis, close_time: Some(Interval::Min1) };
+        write_klines(&mut writer, &mut written, &klines).unwrap();
+        let written = String::from_utf8(written).unwrap();
+        let lines = written.lines().collect::<Vec<_>>();
+        assert!(lines[0].ends_with(",quote_volume,close_time"));
+        let close_time = klines[1].to_barter_event(Interval::Min1, &BarterOptions::default()).to_json(false).unwrap();
+        assert!(close_time.contains("\"close_time\":\"1970-01-01T00:02:00Z\""), "{}", close_time);
+        assert!(lines[2].ends_with(",120000"));
+
+        let mut table = Vec::new();
+        let mut writer = TableWriter::new(TableRenderer::default(), None);
+        write_klines(&mut writer, &mut table, &klines).unwrap();
+        let table = String::from_utf8(table).unwrap();
+        assert!(table.starts_with("\nReceived kline records:\n"));
+        assert!(table.ends_with("\nTotal records: 2\n"));
+
+        let mut events = Vec::new();
+        let mut writer = BarterWriter {
+            options: BarterOptions::default(),
+            interval: Interval::Min1,
+            synthetic_trades: Some(TradeWalk::Ohlc),
+            candles: true,
+            trade_summaries: None,
+            compact: false,
+        };
+        write_klines(&mut writer, &mut events, &klines).unwrap();
+        assert_eq!(String::from_utf8(events).unwrap().lines().count(), 8);
+    }
+
+    #[test]
+    fn test_json_array_file_parses_as_vec() {
+        let klines = vec![test_kline(0, 1.5), test_kline(60_000, 1.5)];
+
+        let mut written = Vec::new();
+        let mut writer = JsonArrayWriter::new(Box::new(KlineJsonWriter));
+        write_klines(&mut writer, &mut written, &klines).unwrap();
+        let parsed = serde_json::from_slice::<Vec<Kline>>(&written).unwrap();
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[1].start_time, 60_000);
+
+        // Every synthetic trade of a kline is its own element
+        let mut written = Vec::new();
+        let mut writer = JsonArrayWriter::new(Box::new(BarterWriter {
+            options: BarterOptions::default(),
+            interval: Interval::Min1,
+            synthetic_trades: Some(TradeWalk::Ohlc),
+            candles: true,
+            trade_summaries: None,
+            compact: false,
+        }));
+        write_klines(&mut writer, &mut written, &klines).unwrap();
+        let parsed = serde_json::from_slice::<Vec<serde_json::Value>>(&written).unwrap();
+        assert_eq!(parsed.len(), 8);
+
+        // An empty fetch is still a valid, empty array
+        let mut written = Vec::new();
+        let mut writer = JsonArrayWriter::new(Box::new(KlineJsonWriter));
+        write_klines(&mut writer, &mut written, &[]).unwrap();
+        assert!(serde_json::from_slice::<Vec<Kline>>(&written).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_parted_output_parts_are_complete_files() {
+        let klines = (0..5).map(|i| test_kline(i * 60_000, 1.5)).collect::<Vec<_>>();
+        let output = std::env::temp_dir()
+            .join(format!("bybit-kline-parts-{}", std::process::id()))
+            .join("out.json");
+
+        // A kline takes over half of the 300 bytes, so parts close after their second kline
+        let mut out = OutputSink::parted(&output, 300).unwrap();
+        let mut writer = JsonArrayWriter::new(Box::new(KlineJsonWriter));
+        write_klines_parted(&mut writer, &mut out, &klines).unwrap();
+        drop(out);
+
+        let parts = (0..3)
+            .map(|part| std::fs::read(output::part_path(&output, part)).unwrap())
+            .map(|written| serde_json::from_slice::<Vec<Kline>>(&written).unwrap())
+            .collect::<Vec<_>>();
+        let starts = parts
+            .iter()
+            .map(|part| part.iter().map(|kline| kline.start_time).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        assert_eq!(starts, vec![vec![0, 60_000], vec![120_000, 180_000], vec![240_000]]);
+        assert!(!output::part_path(&output, 3).exists());
+        assert!(!output.exists());
+
+        std::fs::remove_dir_all(output.parent().unwrap()).unwrap();
+    }
+}